@@ -12,7 +12,6 @@ import { useAgentNames } from "@/contexts/AgentNamesContext";
 import { GlassCard, Button, Input, useWalletReady, ConnectWalletPrompt, IconPicker, DemoTipbox } from "@/components";
 import { formatSol, solToLamports, lamportsToSol } from "@/lib/cloaked";
 import { AgentIconType, DEFAULT_AGENT_ICON } from "@/lib/agentIcons";
-import { CLOAKED_PROGRAM_ID } from "@/lib/constants";
 
 type CreateMode = "quick" | "guided" | "bulk";
 type Step = "name" | "constraints" | "funding" | "review" | "success";
@@ -212,15 +211,8 @@ function CreateAgentContent() {
       }
     );
 
-    // Derive vault address and save name to localStorage
-    const [agentStatePda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("cloaked_agent_state"), agent.publicKey.toBuffer()],
-      CLOAKED_PROGRAM_ID
-    );
-    const [vaultPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("vault"), agentStatePda.toBuffer()],
-      CLOAKED_PROGRAM_ID
-    );
+    // Save name to localStorage (keyed by vault address)
+    const vaultPda = agent.vaultPda;
     if (agentName) {
       setAgentData(vaultPda.toBase58(), agentName, selectedIcon);
     }
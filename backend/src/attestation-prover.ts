@@ -110,14 +110,14 @@ export async function generateAttestationProof(
   const randomBytes = crypto.randomBytes(8);
   const nonce = BigInt("0x" + randomBytes.toString("hex")) % BigInt(1e18) + 1n;
 
-  // Step 3: Get commitment from public inputs
-  const commitment = input.publicInputs[0];
-  if (!commitment) {
-    throw new Error("No commitment in public inputs");
+  // Step 3: Get commitment and action binding from public inputs
+  const [commitment, proofNonce, actionId, actionData] = input.publicInputs;
+  if (!commitment || !proofNonce || !actionId || !actionData) {
+    throw new Error("Expected commitment, proof_nonce, action_id and action_data in public inputs");
   }
 
   // Step 4: Generate Groth16 attestation proof
-  return generateGroth16Attestation(nonce, commitment);
+  return generateGroth16Attestation(nonce, [commitment, proofNonce, actionId, actionData]);
 }
 
 /**
@@ -125,8 +125,10 @@ export async function generateAttestationProof(
  */
 async function generateGroth16Attestation(
   nonce: bigint,
-  commitment: string
+  publicInputs: [string, string, string, string]
 ): Promise<AttestationOutput> {
+  const [commitment, proofNonce, actionId, actionData] = publicInputs;
+
   // Create temp directory
   const tempDir = await fs.mkdtemp(path.join(os.tmpdir(), "cloak-attestation-"));
 
@@ -157,7 +159,9 @@ async function generateGroth16Attestation(
     );
 
     // Write Prover.toml
-    const proverToml = `nonce = "${nonce}"\ncommitment = "${commitment}"\n`;
+    const proverToml =
+      `nonce = "${nonce}"\ncommitment = "${commitment}"\n` +
+      `proof_nonce = "${proofNonce}"\naction_id = "${actionId}"\naction_data = "${actionData}"\n`;
     await fs.writeFile(path.join(tempDir, "Prover.toml"), proverToml);
 
     // Generate witness with nargo
@@ -22,6 +22,7 @@ import {
   RelayerService,
   CreatePrivateAgentParams,
   PrivateOperationParams,
+  FreezePrivateParams,
   UpdateConstraintsPrivateParams,
   WithdrawPrivateParams,
   ClosePrivateParams,
@@ -375,6 +376,11 @@ app.get("/api/prover/recursive/status", async (req: Request, res: Response): Pro
   res.json(status);
 });
 
+/** A circuit public input as the provers emit it: 32-byte hex with 0x prefix */
+function isPublicInputHex(value: unknown): value is string {
+  return typeof value === "string" && value.startsWith("0x") && value.length === 66;
+}
+
 /**
  * POST /api/prove/recursive
  * Generate recursive Groth16 proof from UltraHonk proof artifacts
@@ -388,13 +394,17 @@ app.get("/api/prover/recursive/status", async (req: Request, res: Response): Pro
  *   proof: string[] - UltraHonk proof as fields (457 fields)
  *   vkHash: string - Hash of VK (hex with 0x prefix)
  *   commitment: string - The public commitment (hex with 0x prefix)
+ *   proofNonce: string - Agent's proof_nonce the proof is bound to (hex with 0x prefix)
+ *   actionId: string - Action the proof authorizes (hex with 0x prefix)
+ *   actionData: string - Hash of the action's parameters (hex with 0x prefix)
  *
  * Returns:
  *   proofBytes: number[] - 324 bytes (Groth16)
  *   witnessBytes: number[] - Witness bytes
  */
 app.post("/api/prove/recursive", async (req: Request, res: Response): Promise<void> => {
-  const { verificationKey, proof, vkHash, commitment } = req.body;
+  const { verificationKey, proof, vkHash, commitment, proofNonce, actionId, actionData } =
+    req.body;
 
   // Validate verificationKey
   if (!verificationKey || !Array.isArray(verificationKey)) {
@@ -442,12 +452,29 @@ app.post("/api/prove/recursive", async (req: Request, res: Response): Promise<vo
     return;
   }
 
+  // Validate the action binding (remaining public inputs)
+  for (const [name, value] of [
+    ["proofNonce", proofNonce],
+    ["actionId", actionId],
+    ["actionData", actionData],
+  ] as const) {
+    if (!isPublicInputHex(value)) {
+      res.status(400).json({
+        error: `${name} must be 32-byte hex string with 0x prefix (66 chars)`,
+      });
+      return;
+    }
+  }
+
   try {
     const input: RecursiveProofInput = {
       verificationKey,
       proof,
       vkHash,
       commitment,
+      proofNonce,
+      actionId,
+      actionData,
     };
 
     console.log("[prove-recursive] Starting recursive proof generation...");
@@ -509,18 +536,17 @@ app.post(
       res.status(400).json({ error: "Missing or invalid publicInputs array" });
       return;
     }
-    if (publicInputs.length !== 1) {
+    if (publicInputs.length !== 4) {
       res.status(400).json({
-        error: `Expected 1 public input (commitment), got ${publicInputs.length}`,
+        error: `Expected 4 public inputs (commitment, proof_nonce, action_id, action_data), got ${publicInputs.length}`,
       });
       return;
     }
 
-    // Validate commitment format
-    const commitment = publicInputs[0];
-    if (!commitment.startsWith("0x") || commitment.length !== 66) {
+    // Validate public input format
+    if (!publicInputs.every(isPublicInputHex)) {
       res.status(400).json({
-        error: "Commitment must be 32-byte hex string with 0x prefix (66 chars)",
+        error: "Public inputs must be 32-byte hex strings with 0x prefix (66 chars)",
       });
       return;
     }
@@ -856,9 +882,25 @@ app.post("/api/relayer/freeze-private", async (req: Request, res: Response): Pro
   if (!validated) return;
 
   const { relayer, agentStatePda, proofBytes, witnessBytes, clientIp } = validated;
+  const { reason } = req.body;
+
+  if (
+    reason !== undefined &&
+    (!Array.isArray(reason) ||
+      reason.length !== 32 ||
+      !reason.every((b) => Number.isInteger(b) && b >= 0 && b <= 255))
+  ) {
+    res.status(400).json({ error: "reason must be an array of 32 bytes" });
+    return;
+  }
 
   try {
-    const params: PrivateOperationParams = { agentStatePda, proofBytes, witnessBytes };
+    const params: FreezePrivateParams = {
+      agentStatePda,
+      proofBytes,
+      witnessBytes,
+      reason: reason ?? Array(32).fill(0),
+    };
     const signature = await relayer.freezePrivate(params, clientIp);
     res.json({ signature });
   } catch (error) {
@@ -922,7 +964,7 @@ app.post("/api/relayer/withdraw-private", async (req: Request, res: Response): P
   if (!validated) return;
 
   const { relayer, agentStatePda, proofBytes, witnessBytes, clientIp } = validated;
-  const { amount, destination } = req.body;
+  const { amount, destination, bypassExpiry } = req.body;
 
   if (typeof amount !== "number" || amount <= 0) {
     res.status(400).json({ error: "Missing or invalid amount" });
@@ -932,6 +974,10 @@ app.post("/api/relayer/withdraw-private", async (req: Request, res: Response): P
     res.status(400).json({ error: "Missing or invalid destination" });
     return;
   }
+  if (bypassExpiry !== undefined && typeof bypassExpiry !== "boolean") {
+    res.status(400).json({ error: "Invalid bypassExpiry" });
+    return;
+  }
 
   try {
     const params: WithdrawPrivateParams = {
@@ -940,6 +986,7 @@ app.post("/api/relayer/withdraw-private", async (req: Request, res: Response): P
       witnessBytes,
       amount,
       destination,
+      bypassExpiry: bypassExpiry ?? false,
     };
     const signature = await relayer.withdrawPrivate(params, clientIp);
     res.json({ signature });
@@ -40,6 +40,12 @@ export interface RecursiveProofInput {
   vkHash: string;
   /** Public commitment (hex with 0x prefix) */
   commitment: string;
+  /** Agent's proof_nonce the inner proof is bound to (hex with 0x prefix) */
+  proofNonce: string;
+  /** Action the inner proof authorizes (hex with 0x prefix) */
+  actionId: string;
+  /** Hash of the action's parameters (hex with 0x prefix) */
+  actionData: string;
 }
 
 /** Recursive proof output for Solana program */
@@ -152,6 +158,9 @@ export async function generateRecursiveProof(
  * - proof: [Field; 457]
  * - vk_hash: Field
  * - commitment: pub Field
+ * - proof_nonce: pub Field
+ * - action_id: pub Field
+ * - action_data: pub Field
  */
 function generateProverToml(input: RecursiveProofInput): string {
   const lines: string[] = [];
@@ -168,6 +177,11 @@ function generateProverToml(input: RecursiveProofInput): string {
   // Commitment (public input)
   lines.push(`commitment = "${input.commitment}"`);
 
+  // Action binding (public inputs, same order as the inner circuit)
+  lines.push(`proof_nonce = "${input.proofNonce}"`);
+  lines.push(`action_id = "${input.actionId}"`);
+  lines.push(`action_data = "${input.actionData}"`);
+
   return lines.join("\n") + "\n";
 }
 
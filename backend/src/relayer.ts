@@ -9,7 +9,8 @@
  * - User sends total (0.01 SOL fee + funding) to relayer via Privacy Cash
  * - Relayer keeps 0.01 SOL fee, forwards rest to vault
  * - On close_cloaked_agent_private, rent goes to fee_recipient (relayer) - recovers rent
- * - Private operations (freeze/unfreeze/etc) charge 50k lamports from vault to fee recipient
+ * - Private operations (freeze/unfreeze/etc) charge 50k lamports from vault to fee recipient,
+ *   plus the nullifier rent the vault funds (returned to the vault by close_nullifier)
  *
  * Limits:
  * - IP-based rate limit: 50 ops/hour/IP (basic DoS protection)
@@ -30,6 +31,7 @@ import {
 import { Program, AnchorProvider, BN, Wallet } from "@coral-xyz/anchor";
 import bs58 from "bs58";
 import nacl from "tweetnacl";
+import { createHash } from "crypto";
 import IDL from "./idl.json";
 import { hasSignature, addSignature, removeSignature } from "./persistence";
 
@@ -95,6 +97,19 @@ export function parseCloakedError(error: unknown): { code: string; message: stri
 const CLOAKED_PROGRAM_ID = new PublicKey("3yMjzAeXXc5FZRUrJ1YqP4YMPhPd5bBxHQ6npNSPCUwB");
 // Attestation verifier for hybrid client-side proving (must match on-chain program)
 const ZK_VERIFIER_PROGRAM_ID = new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F");
+// Owner commitment version the program accepts by default (Poseidon)
+const COMMITMENT_VERSION = 1;
+// Witness layout the attestation prover emits (commitment, proof_nonce, action_id, action_data)
+const VERIFIER_VERSION = 4;
+
+/** Nullifier PDA a private instruction creates for its proof */
+function nullifierPda(proofBytes: number[]): PublicKey {
+  const proofHash = createHash("sha256").update(Buffer.from(proofBytes)).digest();
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("nullifier"), proofHash],
+    CLOAKED_PROGRAM_ID
+  )[0];
+}
 
 /** Rate limit tracking for operations (per hour, per IP) */
 interface RateLimitEntry {
@@ -137,6 +152,11 @@ export interface PrivateOperationParams {
   witnessBytes: number[];
 }
 
+/** Freeze private request parameters */
+export interface FreezePrivateParams extends PrivateOperationParams {
+  reason: number[]; // 32 bytes, recorded in the agent's freeze_reason
+}
+
 /** Update constraints private request parameters */
 export interface UpdateConstraintsPrivateParams extends PrivateOperationParams {
   maxPerTx: number | null;
@@ -149,6 +169,7 @@ export interface UpdateConstraintsPrivateParams extends PrivateOperationParams {
 export interface WithdrawPrivateParams extends PrivateOperationParams {
   amount: number;
   destination: string;
+  bypassExpiry: boolean;
 }
 
 /** Close private request parameters */
@@ -357,8 +378,14 @@ export class RelayerService {
       const delegate = Keypair.generate();
       const agentKey = bs58.encode(delegate.secretKey);
 
+      // Agent state is seeded by the payer (relayer) and a random creation nonce
+      const creationNonce = new BN(nacl.randomBytes(8));
       const [agentStatePda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegate.publicKey.toBuffer()],
+        [
+          Buffer.from("cloaked_agent_state"),
+          this.keypair.publicKey.toBuffer(),
+          creationNonce.toArrayLike(Buffer, "le", 8),
+        ],
         CLOAKED_PROGRAM_ID
       );
       const [vaultPda] = PublicKey.findProgramAddressSync(
@@ -369,11 +396,18 @@ export class RelayerService {
       // Call create_cloaked_agent_private instruction
       const createSignature = await this.program.methods
         .createCloakedAgentPrivate(
+          creationNonce,
           params.ownerCommitment,
           new BN(params.maxPerTx),
           new BN(params.dailyLimit),
           new BN(params.totalLimit),
-          new BN(params.expiresAt)
+          new BN(params.expiresAt),
+          new BN(0),
+          null,
+          null,
+          false,
+          { zk: {} },
+          COMMITMENT_VERSION
         )
         .accounts({
           cloakedAgentState: agentStatePda,
@@ -425,7 +459,7 @@ export class RelayerService {
    * Freeze a private agent via relayer
    */
   async freezePrivate(
-    params: PrivateOperationParams,
+    params: FreezePrivateParams,
     clientIp: string
   ): Promise<string> {
     const rateLimit = this.checkRateLimit(clientIp);
@@ -442,13 +476,16 @@ export class RelayerService {
     const signature = await this.program.methods
       .freezePrivate(
         Buffer.from(params.proofBytes),
-        Buffer.from(params.witnessBytes)
+        Buffer.from(params.witnessBytes),
+        VERIFIER_VERSION,
+        params.reason
       )
       .accounts({
         cloakedAgentState: agentStatePda,
         vault: vaultPda,
         feeRecipient: this.keypair.publicKey,
         zkVerifier: ZK_VERIFIER_PROGRAM_ID,
+        nullifier: nullifierPda(params.proofBytes),
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -478,13 +515,15 @@ export class RelayerService {
     const signature = await this.program.methods
       .unfreezePrivate(
         Buffer.from(params.proofBytes),
-        Buffer.from(params.witnessBytes)
+        Buffer.from(params.witnessBytes),
+        VERIFIER_VERSION
       )
       .accounts({
         cloakedAgentState: agentStatePda,
         vault: vaultPda,
         feeRecipient: this.keypair.publicKey,
         zkVerifier: ZK_VERIFIER_PROGRAM_ID,
+        nullifier: nullifierPda(params.proofBytes),
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -515,16 +554,29 @@ export class RelayerService {
       .updateConstraintsPrivate(
         Buffer.from(params.proofBytes),
         Buffer.from(params.witnessBytes),
+        VERIFIER_VERSION,
         params.maxPerTx !== null ? new BN(params.maxPerTx) : null,
+        null,
         params.dailyLimit !== null ? new BN(params.dailyLimit) : null,
         params.totalLimit !== null ? new BN(params.totalLimit) : null,
-        params.expiresAt !== null ? new BN(params.expiresAt) : null
+        params.expiresAt !== null ? new BN(params.expiresAt) : null,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null
       )
       .accounts({
         cloakedAgentState: agentStatePda,
         vault: vaultPda,
         feeRecipient: this.keypair.publicKey,
         zkVerifier: ZK_VERIFIER_PROGRAM_ID,
+        nullifier: nullifierPda(params.proofBytes),
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -556,7 +608,9 @@ export class RelayerService {
       .withdrawPrivate(
         Buffer.from(params.proofBytes),
         Buffer.from(params.witnessBytes),
-        new BN(params.amount)
+        VERIFIER_VERSION,
+        new BN(params.amount),
+        params.bypassExpiry
       )
       .accounts({
         cloakedAgentState: agentStatePda,
@@ -564,6 +618,7 @@ export class RelayerService {
         destination,
         feeRecipient: this.keypair.publicKey,
         zkVerifier: ZK_VERIFIER_PROGRAM_ID,
+        nullifier: nullifierPda(params.proofBytes),
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -594,7 +649,8 @@ export class RelayerService {
     const signature = await this.program.methods
       .closeCloakedAgentPrivate(
         Buffer.from(params.proofBytes),
-        Buffer.from(params.witnessBytes)
+        Buffer.from(params.witnessBytes),
+        VERIFIER_VERSION
       )
       .accounts({
         cloakedAgentState: agentStatePda,
@@ -602,6 +658,7 @@ export class RelayerService {
         destination,
         feeRecipient: this.keypair.publicKey,
         zkVerifier: ZK_VERIFIER_PROGRAM_ID,
+        nullifier: nullifierPda(params.proofBytes),
         systemProgram: SystemProgram.programId,
       })
       .rpc();
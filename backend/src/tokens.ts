@@ -93,16 +93,44 @@ export class TokenService {
   }
 
   /**
-   * Derive CloakedAgentState PDA from delegate public key
+   * Derive CloakedAgentState PDA from its creator (the payer) and creation nonce
    */
-  deriveAgentStatePda(delegate: PublicKey): PublicKey {
+  deriveAgentStatePda(createdBy: PublicKey, creationNonce: BN): PublicKey {
     const [pda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("cloaked_agent_state"), delegate.toBuffer()],
+      [
+        Buffer.from("cloaked_agent_state"),
+        createdBy.toBuffer(),
+        creationNonce.toArrayLike(Buffer, "le", 8),
+      ],
       CLOAKED_PROGRAM_ID
     );
     return pda;
   }
 
+  /**
+   * Find the CloakedAgentState PDA for a delegate
+   *
+   * The state address is not derivable from the delegate, so look it up.
+   * owner is an Option<Pubkey>: the delegate sits at offset 41 when None
+   * (private agents) and at offset 73 when Some.
+   */
+  async findAgentStatePda(delegate: PublicKey): Promise<PublicKey | null> {
+    const layouts: [number, number][] = [
+      [0, 41],
+      [1, 73],
+    ];
+    for (const [ownerTag, delegateOffset] of layouts) {
+      const accounts = await (this.program.account as any).cloakedAgentState.all([
+        { memcmp: { offset: 8, bytes: bs58.encode([ownerTag]) } },
+        { memcmp: { offset: delegateOffset, bytes: delegate.toBase58() } },
+      ]);
+      if (accounts.length > 0) {
+        return accounts[0].publicKey;
+      }
+    }
+    return null;
+  }
+
   /**
    * Derive Vault PDA from CloakedAgentState PDA
    */
@@ -170,8 +198,8 @@ export class TokenService {
    * Get agent by delegate public key
    */
   async getAgentByDelegate(delegate: PublicKey): Promise<TokenInfo | null> {
-    const agentStatePda = this.deriveAgentStatePda(delegate);
-    return this.getAgent(agentStatePda);
+    const agentStatePda = await this.findAgentStatePda(delegate);
+    return agentStatePda ? this.getAgent(agentStatePda) : null;
   }
 
   /**
@@ -0,0 +1,20 @@
+use std::env;
+
+/// Set to build `skip-zk-verification` with the release profile (anchor/cargo-build-sbf
+/// always build release, so localnet test builds need this explicitly)
+const ALLOW_SKIP_ZK_RELEASE: &str = "CLOAKED_ALLOW_SKIP_ZK_RELEASE";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed={}", ALLOW_SKIP_ZK_RELEASE);
+
+    let skip_zk = env::var_os("CARGO_FEATURE_SKIP_ZK_VERIFICATION").is_some();
+    let release = env::var("PROFILE").map(|p| p == "release").unwrap_or(false);
+    let allowed = env::var_os(ALLOW_SKIP_ZK_RELEASE).is_some();
+
+    if skip_zk && release && !allowed {
+        panic!(
+            "`skip-zk-verification` is enabled in a release build; set {}=1 to confirm this is a test deployment",
+            ALLOW_SKIP_ZK_RELEASE
+        );
+    }
+}
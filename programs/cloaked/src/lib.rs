@@ -1,9 +1,24 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::{program::invoke, program::invoke_signed, system_instruction, instruction::Instruction};
+use anchor_lang::solana_program::{program::invoke, program::invoke_signed, system_instruction, instruction::{AccountMeta, Instruction}};
+use anchor_lang::solana_program::sysvar::instructions::{
+    self as sysvar_instructions, load_current_index_checked, load_instruction_at_checked,
+};
+use solana_keccak_hasher as keccak;
+use solana_sdk_ids::ed25519_program;
+use solana_secp256k1_recover::secp256k1_recover;
+use solana_sha256_hasher::hashv;
+use solana_stake_interface::{
+    self as stake,
+    instruction as stake_instruction,
+    state::{Authorized, Lockup, StakeStateV2},
+};
 
 #[cfg(not(feature = "no-entrypoint"))]
 use solana_security_txt::security_txt;
 
+#[cfg(all(feature = "skip-zk-verification", feature = "mainnet-deployment"))]
+compile_error!("features `skip-zk-verification` and `mainnet-deployment` are mutually exclusive");
+
 declare_id!("3yMjzAeXXc5FZRUrJ1YqP4YMPhPd5bBxHQ6npNSPCUwB");
 
 #[cfg(not(feature = "no-entrypoint"))]
@@ -18,45 +33,605 @@ security_txt! {
 /// ZK Verifier program ID (Attestation verifier for hybrid client-side proving)
 pub const ZK_VERIFIER_PROGRAM_ID: Pubkey = pubkey!("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F");
 
-/// Fixed fee for private operations (covers tx fee + margin)
+/// Default fee for private operations (covers tx fee + margin); ProgramConfig can override it
+/// Not the full cost: each private operation also pays its Nullifier's rent from the vault,
+/// which close_nullifier returns to the vault after NULLIFIER_RETENTION
+#[constant]
 pub const PRIVATE_OPERATION_FEE: u64 = 50_000;
 
-/// Fee reimbursement for spend operations (~0.00001 SOL, covers tx fee + margin)
-/// Fee payer fronts transaction fee, gets reimbursed from vault
+/// Default fee reimbursement for spend operations (~0.00001 SOL, covers tx fee + margin)
+/// Fee payer fronts transaction fee, gets reimbursed from vault; ProgramConfig can override it
+#[constant]
 pub const SPEND_FEE_REIMBURSEMENT: u64 = 10_000;
 
-/// Seconds in a day (for daily limit reset calculation)
+/// Ceilings for set_fees (10x the defaults), so the config admin cannot drain vaults through fees
+pub const MAX_PRIVATE_OPERATION_FEE: u64 = 10 * PRIVATE_OPERATION_FEE;
+pub const MAX_SPEND_FEE_REIMBURSEMENT: u64 = 10 * SPEND_FEE_REIMBURSEMENT;
+
+/// Share of the private operation fee charged when the owner relays the instruction
+/// themselves (fee_recipient signs); 0 = waived. ProgramConfig can override it
+pub const SELF_RELAY_FEE_BPS: u16 = 0;
+
+/// Lamports locked by every deposit_note and credited by its claim (0.1 SOL)
+/// A single denomination keeps deposits and claims from being matched by amount
+pub const NOTE_DENOMINATION: u64 = 100_000_000;
+/// Depth of a NoteSet's Merkle tree; each set holds 2^depth notes
+pub const NOTE_TREE_DEPTH: usize = 8;
+/// Recent NoteSet roots a claim may be proven against
+pub const NOTE_ROOT_HISTORY: usize = 16;
+
+/// Basis points denominator (10_000 bps = 100%)
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Seconds in a day (default spending window length)
+#[constant]
 pub const SECONDS_PER_DAY: i64 = 86_400;
 
+/// Default spending window length - emulates the original daily limit
+pub const DEFAULT_WINDOW_SECONDS: u64 = SECONDS_PER_DAY as u64;
+
+/// Spends remembered by the sliding window ring buffer
+pub const SLIDING_WINDOW_SLOTS: usize = 8;
+
+/// Fee-charging private operations allowed per UTC day when max_private_ops_per_day is 0
+pub const DEFAULT_MAX_PRIVATE_OPS_PER_DAY: u16 = 20;
+
+/// Delay before a changed withdraw_destination_lock takes effect (7 days)
+pub const DESTINATION_LOCK_TIMELOCK: i64 = 7 * SECONDS_PER_DAY;
+
+/// Pending timelocked withdrawals allowed when max_concurrent_timelocks is unset
+pub const DEFAULT_MAX_CONCURRENT_TIMELOCKS: u8 = 8;
+
+/// Upper bound for max_concurrent_timelocks
+pub const MAX_CONCURRENT_TIMELOCKS: u8 = 32;
+
+/// Delay before a proposed ZK verifier change can be applied (2 days)
+pub const VERIFIER_CHANGE_TIMELOCK: i64 = 2 * SECONDS_PER_DAY;
+
+/// How long the replaced verifier keeps being accepted after a change (7 days),
+/// so proofs prepared against it remain usable while clients migrate
+pub const VERIFIER_MIGRATION_WINDOW: i64 = 7 * SECONDS_PER_DAY;
+
+/// Delay between scheduling a VerifierRegistry entry's removal and removing it (7 days);
+/// the entry keeps verifying proofs until then
+pub const VERIFIER_REMOVAL_TIMELOCK: i64 = 7 * SECONDS_PER_DAY;
+
+/// Wait between initiate_recovery_private and recover_private (7 days), during which
+/// the owner can cancel
+pub const PRIVATE_RECOVERY_DELAY: i64 = 7 * SECONDS_PER_DAY;
+
+/// Age after which a Nullifier can be closed and its rent returned to the vault (30 days);
+/// the proof_nonce still rejects the proof once the nullifier is gone
+pub const NULLIFIER_RETENTION: i64 = 30 * SECONDS_PER_DAY;
+
+/// Max whitelist entries close_cloaked_agent can close in one transaction
+pub const MAX_WHITELIST_CLOSE_PER_TX: usize = 10;
+
+/// Max agents batch_freeze can freeze in one transaction (compute budget)
+pub const MAX_BATCH_FREEZE: usize = 8;
+
+/// Max registered owners of a multisig agent
+pub const MAX_MULTISIG_OWNERS: usize = 5;
+
+/// Current CloakedAgentState layout version (see migrate_agent_state)
+pub const STATE_VERSION: u8 = 5;
+
+/// instruction_whitelist bits - which delegate-signed instructions the primary delegate may call
+pub const INSTRUCTION_BIT_SPEND: u8 = 0;
+pub const INSTRUCTION_BIT_SPEND_WITH_SECP: u8 = 1;
+pub const INSTRUCTION_BIT_FREEZE_BY_DELEGATE: u8 = 2;
+pub const INSTRUCTION_BIT_CREATE_SESSION: u8 = 3;
+pub const INSTRUCTION_BIT_CREATE_SUB_AGENT: u8 = 4;
+pub const INSTRUCTION_BIT_SPEND_WITH_PASSKEY: u8 = 5;
+pub const INSTRUCTION_BIT_SPEND_SIGNED: u8 = 6;
+pub const INSTRUCTION_BIT_REDEEM_VOUCHER: u8 = 7;
+
+/// secp256r1 (P-256) signature verification precompile
+pub const SECP256R1_PROGRAM_ID: Pubkey = pubkey!("Secp256r1SigVerify1111111111111111111111111");
+
+/// Domain prefix of the message a passkey signs for spend_with_passkey
+pub const PASSKEY_SPEND_DOMAIN: &[u8] = b"cloaked:spend_with_passkey";
+/// Domain separator for stealth-key private operation messages
+pub const STEALTH_DOMAIN: &[u8] = b"cloaked:stealth";
+
+/// Domain prefix of the message the delegate signs for spend_signed
+pub const SPEND_INTENT_DOMAIN: &[u8] = b"cloaked:spend_signed";
+
+/// Pyth pull-oracle receiver program (owns PriceUpdateV2 accounts)
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+/// Anchor discriminator of the Pyth receiver's PriceUpdateV2 account
+pub const PYTH_PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+/// Oldest Pyth publish_time conditional_spend accepts, in seconds before now
+pub const MAX_PRICE_AGE_SECONDS: i64 = 60;
+
+/// freeze_reason recorded when a spend trips conditional_freeze_threshold
+pub const AUTO_FREEZE_LOW_BALANCE: [u8; 32] = *b"LowBalance\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+/// freeze_reason recorded by panic and panic_private
+pub const PANIC_FREEZE_REASON: [u8; 32] = *b"Panic\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+/// AgentAutoClosedEvent reason when a spend empties an auto_close_on_zero agent's vault
+pub const AUTO_CLOSE_ZERO_BALANCE: [u8; 32] = *b"ZeroBalance\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+/// Delay between propose_recovery and execute_recovery (1 day)
+pub const RECOVERY_DELAY: i64 = SECONDS_PER_DAY;
+
+/// Shortest rescue_delay accepted for an emergency_rescue_address (30 days)
+pub const MIN_RESCUE_DELAY: i64 = 30 * SECONDS_PER_DAY;
+
+/// Grace period after expiry before anyone can reclaim an abandoned agent (30 days)
+pub const RECLAIM_GRACE_PERIOD: i64 = 30 * SECONDS_PER_DAY;
+
+/// Incentive paid from vault to whoever cranks reclaim_expired
+pub const RECLAIM_CRANK_INCENTIVE: u64 = 10_000;
+
+/// Paid to whoever cranks sweep_surplus, out of the swept amount
+pub const SWEEP_CRANK_INCENTIVE: u64 = 5_000;
+
+/// ZK witness layout version expected by verify_zk_proof
+/// The version equals the number of public inputs, which the gnark header records:
+/// [public count (u32 BE)] [secret count (u32 BE), 0] [element count (u32 BE)]
+/// v1: [header (12)] [commitment (32)]
+/// v2: [header (12)] [commitment (32)] [proof_nonce (32, big-endian field element)]
+/// v3: v2 followed by [action_id (32, big-endian field element)]
+/// v4: v3 followed by [action_data (32)], see withdraw_action_data, close_action_data
+///     and ConstraintParams::params_hash; zero for other actions
+pub const WITNESS_LAYOUT_VERSION: u8 = 4;
+
+/// Commitment scheme of the built-in circuit: commitment = poseidon(agent_secret)
+/// Registry verifiers declare the scheme their circuit uses (VerifierEntry::commitment_version)
+pub const COMMITMENT_VERSION: u8 = 1;
+
+/// Proof wire format new private agents are created with
+/// 1: gnark Groth16 over BN254, sent to the verifier as [proof] [witness]
+pub const CURRENT_PROOF_FORMAT_VERSION: u8 = 1;
+
+/// Groth16 proof size (gnark, BN254)
+pub const PROOF_SIZE: usize = 324;
+
 /// ZK witness format sizes
-pub const WITNESS_HEADER_SIZE: usize = 12;
-pub const COMMITMENT_SIZE: usize = 32;
-pub const MIN_WITNESS_SIZE: usize = WITNESS_HEADER_SIZE + COMMITMENT_SIZE; // 44
+/// The IDL-exported sizes are u32: the IDL has no usize type
+#[constant]
+pub const WITNESS_HEADER_SIZE: u32 = 12;
+#[constant]
+pub const COMMITMENT_SIZE: u32 = 32;
+pub const PROOF_NONCE_SIZE: usize = 32;
+pub const ACTION_ID_SIZE: usize = 32;
+pub const ACTION_DATA_SIZE: usize = 32;
+pub const WITNESS_NONCE_OFFSET: usize = (WITNESS_HEADER_SIZE + COMMITMENT_SIZE) as usize; // 44
+pub const WITNESS_ACTION_OFFSET: usize = WITNESS_NONCE_OFFSET + PROOF_NONCE_SIZE; // 76
+pub const WITNESS_ACTION_DATA_OFFSET: usize = WITNESS_ACTION_OFFSET + ACTION_ID_SIZE; // 108
+/// Size of a WITNESS_LAYOUT_VERSION witness
+pub const WITNESS_SIZE: usize = WITNESS_ACTION_DATA_OFFSET + ACTION_DATA_SIZE; // 140
+/// Smallest witness check_witness accepts; later layout versions only append inputs
+#[constant]
+pub const MIN_WITNESS_SIZE: u32 = WITNESS_SIZE as u32;
+
+/// proof_bytes of a stealth-key agent: stealth_pubkey (32) || next_commitment (32)
+pub const STEALTH_PROOF_SIZE: usize = 64;
+
+/// Action ids bound into private-mode witnesses, one per instruction
+/// A proof made for one instruction is rejected by every other
+pub const ACTION_FREEZE: u8 = 1;
+pub const ACTION_UNFREEZE: u8 = 2;
+pub const ACTION_PANIC: u8 = 3;
+pub const ACTION_UPDATE_CONSTRAINTS: u8 = 4;
+pub const ACTION_LOCK_CONSTRAINTS: u8 = 5;
+pub const ACTION_CLOSE: u8 = 6;
+pub const ACTION_WITHDRAW: u8 = 7;
+pub const ACTION_ROTATE_COMMITMENT: u8 = 8;
+pub const ACTION_SET_RECOVERY: u8 = 9;
+pub const ACTION_INITIATE_RECOVERY: u8 = 10;
+pub const ACTION_CANCEL_RECOVERY: u8 = 11;
+pub const ACTION_RECOVER: u8 = 12;
+pub const ACTION_UPDATE_DELEGATE: u8 = 13;
+pub const ACTION_WITHDRAW_ALL: u8 = 14;
+pub const ACTION_CLAIM_NOTE: u8 = 15;
+pub const ACTION_MIGRATE_PROOF_VERSION: u8 = 16;
+pub const ACTION_SET_DEPOSITS_LOCKED: u8 = 17;
+
+/// sha256 of the concatenated parts with byte 0 cleared so the value fits in a
+/// BN254 field element (witness action_data)
+fn action_data_hash(parts: &[&[u8]]) -> [u8; 32] {
+    let mut data = hashv(parts).to_bytes();
+    data[0] = 0;
+    data
+}
+
+/// Witness action_data for withdraw_private, binding the proof to destination, amount and
+/// the owner's expiry opt-in:
+/// action_data_hash(destination (32) || amount (8, little-endian) || bypass_expiry (1))
+pub fn withdraw_action_data(destination: &Pubkey, amount: u64, bypass_expiry: bool) -> [u8; 32] {
+    action_data_hash(&[destination.as_ref(), &amount.to_le_bytes(), &[bypass_expiry as u8]])
+}
+
+/// Witness action_data for withdraw_all_private, binding the proof to the destination and
+/// the owner's expiry opt-in: action_data_hash(destination (32) || bypass_expiry (1))
+pub fn withdraw_all_action_data(destination: &Pubkey, bypass_expiry: bool) -> [u8; 32] {
+    action_data_hash(&[destination.as_ref(), &[bypass_expiry as u8]])
+}
+
+/// Witness action_data for claim_note_private, binding the proof to the crediting agent
+/// and the note's nullifier: action_data_hash(agent (32) || note_nullifier (32))
+pub fn claim_note_action_data(agent: &Pubkey, note_nullifier: &[u8; 32]) -> [u8; 32] {
+    action_data_hash(&[agent.as_ref(), note_nullifier])
+}
+
+/// Parent of two NoteSet tree nodes: action_data_hash(left (32) || right (32))
+pub fn note_tree_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    action_data_hash(&[left, right])
+}
+
+/// Witness action_data for migrate_proof_version: action_data_hash(new_version (1))
+pub fn proof_version_action_data(new_version: u8) -> [u8; 32] {
+    action_data_hash(&[&[new_version]])
+}
+
+/// Witness action_data for set_deposits_locked_private: action_data_hash(locked (1))
+pub fn deposits_locked_action_data(locked: bool) -> [u8; 32] {
+    action_data_hash(&[&[locked as u8]])
+}
+
+/// Witness action_data for close_cloaked_agent_private, binding the proof to where the
+/// vault balance and the state rent go:
+/// action_data_hash(destination (32) || fee_recipient (32))
+pub fn close_action_data(destination: &Pubkey, fee_recipient: &Pubkey) -> [u8; 32] {
+    action_data_hash(&[destination.as_ref(), fee_recipient.as_ref()])
+}
+
+/// Witness action_data for instructions that install a commitment (rotate, set
+/// recovery, recover), binding the proof to it: action_data_hash(new_commitment (32))
+pub fn commitment_action_data(new_commitment: &[u8; 32]) -> [u8; 32] {
+    action_data_hash(&[new_commitment])
+}
+
+/// Witness action_data for update_delegate_private: action_data_hash(new_delegate (32))
+pub fn delegate_action_data(new_delegate: &Pubkey) -> [u8; 32] {
+    action_data_hash(&[new_delegate.as_ref()])
+}
+
+/// Constraint changes passed to update_constraints_private, in argument order
+/// The proof commits to params_hash() so a relayer cannot alter any value
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct ConstraintParams {
+    pub max_per_tx: Option<u64>,
+    pub window_seconds: Option<u64>,
+    pub window_limit: Option<u64>,
+    pub total_limit: Option<u64>,
+    pub expires_at: Option<i64>,
+    pub max_vault_balance: Option<u64>,
+    pub min_deposit: Option<u64>,
+    pub min_spend_amount: Option<u64>,
+    pub block_deposits_when_inactive: Option<bool>,
+    pub allow_delegate_sessions: Option<bool>,
+    pub allowed_start_second_of_day: Option<u32>,
+    pub allowed_end_second_of_day: Option<u32>,
+    pub allowed_days_bitmask: Option<u8>,
+    pub rollover_cap: Option<u64>,
+    pub max_private_ops_per_day: Option<u16>,
+}
+
+impl ConstraintParams {
+    /// Witness action_data for update_constraints_private:
+    /// action_data_hash(canonical borsh encoding of self)
+    pub fn params_hash(&self) -> [u8; 32] {
+        let mut encoded = Vec::new();
+        self.serialize(&mut encoded).expect("writing to a Vec cannot fail");
+        action_data_hash(&[&encoded])
+    }
+}
+
+/// Check the witness public inputs: the agent's commitment, its current proof nonce,
+/// the action the proof was made for and that action's parameters
+fn check_witness(
+    witness_bytes: &[u8],
+    witness_version: u8,
+    expected_commitment: &[u8; 32],
+    expected_nonce: u64,
+    action_id: u8,
+    action_data: &[u8; 32],
+) -> Result<()> {
+    // Header: the selected layout version. The version is the public input count and
+    // later layouts only append inputs, so the v4 fields below are always present
+    require!(witness_version >= WITNESS_LAYOUT_VERSION, ErrorCode::UnsupportedWitnessVersion);
+    require!(witness_bytes.len() >= MIN_WITNESS_SIZE as usize, ErrorCode::InvalidWitnessSize);
+    let header_u32 = |i: usize| u32::from_be_bytes(witness_bytes[i..i + 4].try_into().unwrap());
+    let (public_count, secret_count, element_count) = (header_u32(0), header_u32(4), header_u32(8));
+    require!(
+        public_count == witness_version as u32
+            && secret_count == 0
+            && element_count == public_count,
+        ErrorCode::UnsupportedWitnessVersion
+    );
+    require!(
+        witness_bytes.len() == WITNESS_HEADER_SIZE as usize + witness_version as usize * 32,
+        ErrorCode::InvalidWitnessSize
+    );
+
+    let witness_commitment = &witness_bytes[WITNESS_HEADER_SIZE as usize..WITNESS_NONCE_OFFSET];
+    require!(
+        witness_commitment == expected_commitment,
+        ErrorCode::CommitmentMismatch
+    );
+
+    // u64 nonce in the low 8 bytes of a big-endian field element
+    let nonce_bytes = &witness_bytes[WITNESS_NONCE_OFFSET..WITNESS_ACTION_OFFSET];
+    let (high, low) = nonce_bytes.split_at(PROOF_NONCE_SIZE - 8);
+    require!(
+        high.iter().all(|&b| b == 0) && u64::from_be_bytes(low.try_into().unwrap()) == expected_nonce,
+        ErrorCode::ProofNonceMismatch
+    );
+
+    // action id in the last byte of a big-endian field element
+    let action_bytes = &witness_bytes[WITNESS_ACTION_OFFSET..WITNESS_ACTION_DATA_OFFSET];
+    let (high, low) = action_bytes.split_at(ACTION_ID_SIZE - 1);
+    require!(
+        high.iter().all(|&b| b == 0) && low[0] == action_id,
+        ErrorCode::ActionMismatch
+    );
+
+    require!(
+        &witness_bytes[WITNESS_ACTION_DATA_OFFSET..WITNESS_SIZE] == action_data,
+        ErrorCode::ActionDataMismatch
+    );
+    Ok(())
+}
+
+/// Proof and witness for a private instruction: from the ProofBuffer when one is passed
+/// (the args must then be empty), otherwise the instruction args
+fn load_proof(
+    buffer: Option<&ProofBuffer>,
+    agent: Pubkey,
+    action_id: u8,
+    proof_bytes: Vec<u8>,
+    witness_bytes: Vec<u8>,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let Some(buffer) = buffer else {
+        return Ok((proof_bytes, witness_bytes));
+    };
+    require!(
+        proof_bytes.is_empty() && witness_bytes.is_empty(),
+        ErrorCode::ProofBufferMismatch
+    );
+    require!(
+        buffer.agent == agent && buffer.action_id == action_id,
+        ErrorCode::ProofBufferMismatch
+    );
+    let (proof, witness) = buffer.data.split_at(buffer.proof_len as usize);
+    Ok((proof.to_vec(), witness.to_vec()))
+}
+
+/// Require that some accepted verifier produces commitments of `commitment_version`:
+/// the built-in COMMITMENT_VERSION, or an active VerifierRegistry entry's
+fn check_commitment_version(
+    registry: Option<&VerifierRegistry>,
+    commitment_version: u8,
+    now: i64,
+) -> Result<()> {
+    let known = commitment_version == COMMITMENT_VERSION
+        || registry.is_some_and(|registry| {
+            registry.entries.iter().any(|entry| {
+                entry.commitment_version == commitment_version
+                    && (entry.removable_at == 0 || now < entry.removable_at)
+            })
+        });
+    require!(known, ErrorCode::UnknownCommitmentVersion);
+    Ok(())
+}
+
+/// Proof wire formats verify_zk_proof can dispatch
+pub fn is_supported_proof_format(version: u8) -> bool {
+    version == 1
+}
+
+/// Registry entry for the `verifier_version` a private instruction was called with
+/// Without a VerifierRegistry only the built-in WITNESS_LAYOUT_VERSION is accepted
+fn select_verifier_entry(
+    registry: Option<&VerifierRegistry>,
+    verifier_version: u8,
+) -> Result<Option<VerifierEntry>> {
+    match registry {
+        Some(registry) => {
+            let entry = registry
+                .active_entry(verifier_version, Clock::get()?.unix_timestamp)
+                .ok_or(ErrorCode::UnknownVerifierVersion)?;
+            Ok(Some(*entry))
+        }
+        None => {
+            require!(verifier_version == WITNESS_LAYOUT_VERSION, ErrorCode::UnknownVerifierVersion);
+            Ok(None)
+        }
+    }
+}
 
-/// Verify ZK ownership proof via CPI to the verifier program
+/// Verify ZK ownership proof via CPI to the verifier program, then consume the agent's
+/// proof nonce so the proof cannot be replayed
 ///
 /// The verifier expects instruction data in format:
 /// [proof_bytes (324)] [witness_bytes (12 + N*32)]
+///
+/// `verifier_entry` (from select_verifier_entry) overrides the verifier, proof size and
+/// witness layout; without it the current ProgramConfig verifier and v4 layout are used
+///
+/// StealthEd25519 agents are checked by verify_stealth_signature instead, which needs
+/// the instructions sysvar
+#[allow(clippy::too_many_arguments)]
 fn verify_zk_proof(
     verifier_program: &AccountInfo,
+    instructions: Option<&AccountInfo>,
+    program_config: Option<&ProgramConfig>,
+    verifier_entry: Option<&VerifierEntry>,
     proof_bytes: &[u8],
     witness_bytes: &[u8],
-    expected_commitment: &[u8; 32],
+    agent_state: &mut CloakedAgentState,
+    action_id: u8,
+    action_data: &[u8; 32],
+) -> Result<()> {
+    if agent_state.commitment_scheme == CommitmentScheme::StealthEd25519 {
+        let instructions = instructions.ok_or(ErrorCode::MissingSignatureInstruction)?;
+        return verify_stealth_signature(
+            instructions,
+            proof_bytes,
+            witness_bytes,
+            agent_state,
+            action_id,
+            action_data,
+        );
+    }
+
+    let owner_commitment = agent_state.owner_commitment;
+    verify_zk_proof_against(
+        &owner_commitment,
+        verifier_program,
+        program_config,
+        verifier_entry,
+        proof_bytes,
+        witness_bytes,
+        agent_state,
+        action_id,
+        action_data,
+    )
+}
+
+/// verify_zk_proof for a proof against `commitment` instead of owner_commitment
+/// (recovery_commitment proofs). Shares the agent's proof nonce
+#[allow(clippy::too_many_arguments)]
+fn verify_zk_proof_against(
+    commitment: &[u8; 32],
+    verifier_program: &AccountInfo,
+    program_config: Option<&ProgramConfig>,
+    verifier_entry: Option<&VerifierEntry>,
+    proof_bytes: &[u8],
+    witness_bytes: &[u8],
+    agent_state: &mut CloakedAgentState,
+    action_id: u8,
+    action_data: &[u8; 32],
 ) -> Result<()> {
-    // Verify the correct verifier program is passed
+    // Verify the correct verifier program is passed (registry entry when selected,
+    // ProgramConfig when it exists, otherwise the built-in ZK_VERIFIER_PROGRAM_ID)
+    let (accepted, proof_size, witness_version, commitment_version) = match verifier_entry {
+        Some(entry) => (
+            verifier_program.key() == entry.verifier_program,
+            entry.proof_size as usize,
+            entry.witness_version,
+            entry.commitment_version,
+        ),
+        None => {
+            let accepted = match program_config {
+                Some(config) => config.accepts_verifier(verifier_program.key, Clock::get()?.unix_timestamp),
+                None => verifier_program.key() == ZK_VERIFIER_PROGRAM_ID,
+            };
+            (accepted, PROOF_SIZE, WITNESS_LAYOUT_VERSION, COMMITMENT_VERSION)
+        }
+    };
+    require!(accepted, ErrorCode::InvalidVerifierProgram);
+    // A circuit for another commitment scheme would check the wrong preimage
     require!(
-        verifier_program.key() == ZK_VERIFIER_PROGRAM_ID,
-        ErrorCode::InvalidVerifierProgram
+        commitment_version == agent_state.commitment_version(),
+        ErrorCode::CommitmentVersionMismatch
     );
+    // Clear errors instead of an opaque CPI failure when the verifier is missing
+    require!(verifier_program.lamports() > 0, ErrorCode::VerifierNotDeployed);
+    require!(verifier_program.executable, ErrorCode::VerifierNotExecutable);
+    require!(proof_bytes.len() == proof_size, ErrorCode::InvalidProofSize);
+
+    check_witness(
+        witness_bytes,
+        witness_version,
+        commitment,
+        agent_state.proof_nonce,
+        action_id,
+        action_data,
+    )?;
+    // Dispatch on the agent's proof wire format
+    match agent_state.proof_format_version() {
+        1 => invoke_verifier(verifier_program.key, proof_bytes, witness_bytes)?,
+        _ => return err!(ErrorCode::UnsupportedProofVersion),
+    }
 
-    // Verify witness contains the expected commitment
-    require!(witness_bytes.len() >= MIN_WITNESS_SIZE, ErrorCode::InvalidProof);
-    let witness_commitment = &witness_bytes[WITNESS_HEADER_SIZE..MIN_WITNESS_SIZE];
+    agent_state.proof_nonce = agent_state.proof_nonce
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    Ok(())
+}
+
+/// Canonical stealth-key message - the stealth key must sign exactly these bytes
+/// STEALTH_DOMAIN || agent || action_id || proof_nonce (little-endian) || action_data || next_commitment
+pub fn stealth_message(
+    agent: &Pubkey,
+    action_id: u8,
+    proof_nonce: u64,
+    action_data: &[u8; 32],
+    next_commitment: &[u8; 32],
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(STEALTH_DOMAIN.len() + 32 + 1 + 8 + 32 + 32);
+    message.extend_from_slice(STEALTH_DOMAIN);
+    message.extend_from_slice(agent.as_ref());
+    message.push(action_id);
+    message.extend_from_slice(&proof_nonce.to_le_bytes());
+    message.extend_from_slice(action_data);
+    message.extend_from_slice(next_commitment);
+    message
+}
+
+/// Stealth-key alternative to a ZK proof: `proof_bytes` is the one-time stealth_pubkey
+/// whose sha256 is owner_commitment, followed by the next commitment; `witness_bytes`
+/// is empty. The ed25519 precompile instruction right before this one must verify the
+/// stealth key's signature over stealth_message. A revealed key is spent, so
+/// owner_commitment rotates to next_commitment and the proof nonce is consumed
+fn verify_stealth_signature(
+    instructions: &AccountInfo,
+    proof_bytes: &[u8],
+    witness_bytes: &[u8],
+    agent_state: &mut CloakedAgentState,
+    action_id: u8,
+    action_data: &[u8; 32],
+) -> Result<()> {
     require!(
-        witness_commitment == expected_commitment,
+        proof_bytes.len() == STEALTH_PROOF_SIZE && witness_bytes.is_empty(),
+        ErrorCode::InvalidProofSize
+    );
+    let (stealth_pubkey, next_commitment) = proof_bytes.split_at(32);
+    let next_commitment: [u8; 32] = next_commitment.try_into().unwrap();
+    require!(
+        hashv(&[stealth_pubkey]).to_bytes() == agent_state.owner_commitment,
         ErrorCode::CommitmentMismatch
     );
+    require!(
+        next_commitment != [0u8; 32] && next_commitment != agent_state.owner_commitment,
+        ErrorCode::InvalidCommitment
+    );
+
+    let agent = Pubkey::create_program_address(
+        &[
+            b"cloaked_agent_state",
+            agent_state.created_by.as_ref(),
+            &agent_state.creation_nonce.to_le_bytes(),
+            &[agent_state.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| error!(ErrorCode::InvalidCommitment))?;
+    verify_precompile_signature(
+        instructions,
+        &ed25519_program::ID,
+        stealth_pubkey,
+        &stealth_message(&agent, action_id, agent_state.proof_nonce, action_data, &next_commitment),
+        None,
+    )?;
+
+    agent_state.owner_commitment = next_commitment;
+    agent_state.proof_nonce = agent_state.proof_nonce
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    Ok(())
+}
 
+/// CPI to the ZK verifier program
+#[cfg(not(all(feature = "skip-zk-verification", not(feature = "mainnet-deployment"))))]
+fn invoke_verifier(verifier_program_id: &Pubkey, proof_bytes: &[u8], witness_bytes: &[u8]) -> Result<()> {
     // Build instruction data: proof || witness
     let mut ix_data = Vec::with_capacity(proof_bytes.len() + witness_bytes.len());
     ix_data.extend_from_slice(proof_bytes);
@@ -64,7 +639,7 @@ fn verify_zk_proof(
 
     // CPI to ZK verifier - if proof invalid, this fails the transaction
     let verify_ix = Instruction {
-        program_id: ZK_VERIFIER_PROGRAM_ID,
+        program_id: *verifier_program_id,
         accounts: vec![],
         data: ix_data,
     };
@@ -74,1078 +649,13005 @@ fn verify_zk_proof(
     Ok(())
 }
 
-#[program]
-pub mod cloaked {
-    use super::*;
-
-    /// Create a new Cloaked Agent with constraints (standard mode)
-    pub fn create_cloaked_agent(
-        ctx: Context<CreateCloakedAgent>,
-        max_per_tx: u64,
-        daily_limit: u64,
-        total_limit: u64,
-        expires_at: i64,
-    ) -> Result<()> {
-        let agent_state = &mut ctx.accounts.cloaked_agent_state;
-        let clock = Clock::get()?;
+/// Localnet/devnet test stub: commitment is still checked by verify_zk_proof,
+/// but the verifier CPI is skipped so integration tests don't need the prover stack
+#[cfg(feature = "skip-zk-verification")]
+#[cfg(not(feature = "mainnet-deployment"))]
+fn invoke_verifier(_verifier_program_id: &Pubkey, _proof_bytes: &[u8], _witness_bytes: &[u8]) -> Result<()> {
+    msg!("skip-zk-verification: verifier CPI skipped");
+    Ok(())
+}
 
-        agent_state.owner = Some(ctx.accounts.owner.key());
-        agent_state.owner_commitment = [0; 32]; // Standard mode: no commitment
-        agent_state.delegate = ctx.accounts.delegate.key();
-        agent_state.max_per_tx = max_per_tx;
-        agent_state.daily_limit = daily_limit;
-        agent_state.total_limit = total_limit;
-        agent_state.expires_at = expires_at;
-        agent_state.frozen = false;
-        agent_state.total_spent = 0;
-        agent_state.daily_spent = 0;
-        agent_state.last_day = clock.unix_timestamp / SECONDS_PER_DAY;
-        agent_state.bump = ctx.bumps.cloaked_agent_state;
-        agent_state.created_at = clock.unix_timestamp;
+/// Validate an expiration timestamp: 0 (never) or strictly in the future
+fn validate_expiration(expires_at: i64, clock: &Clock) -> Result<()> {
+    require!(expires_at >= 0, ErrorCode::InvalidExpiration);
+    require!(
+        expires_at == 0 || expires_at > clock.unix_timestamp,
+        ErrorCode::InvalidExpiration
+    );
+    Ok(())
+}
 
-        Ok(())
+/// Owner-initiated payouts must go to withdraw_destination_lock when it is set
+fn check_withdraw_destination(agent_state: &CloakedAgentState, destination: &Pubkey) -> Result<()> {
+    if let Some(lock) = agent_state.withdraw_destination_lock {
+        require_keys_eq!(*destination, lock, ErrorCode::DestinationLocked);
     }
+    Ok(())
+}
 
-    /// Create a new Cloaked Agent in private mode (no wallet linked on-chain)
-    pub fn create_cloaked_agent_private(
-        ctx: Context<CreateCloakedAgentPrivate>,
-        owner_commitment: [u8; 32],
-        max_per_tx: u64,
-        daily_limit: u64,
-        total_limit: u64,
-        expires_at: i64,
-    ) -> Result<()> {
-        require!(owner_commitment != [0u8; 32], ErrorCode::InvalidCommitment);
+/// Replace the agent's delegate, moving it between the delegate registries when passed
+/// The new key must be able to sign, so a program delegate becomes a regular one
+fn apply_delegate_update(
+    agent_state: &mut Account<CloakedAgentState>,
+    new_delegate: &AccountInfo,
+    old_registry: Option<&mut Account<DelegateRegistry>>,
+    new_registry: Option<&mut Account<DelegateRegistry>>,
+    now: i64,
+) -> Result<()> {
+    require!(!new_delegate.executable, ErrorCode::DelegateIsProgram);
+    require!(
+        new_delegate.key() != agent_state.owner.unwrap_or_default(),
+        ErrorCode::DelegateIsOwner
+    );
 
-        let agent_state = &mut ctx.accounts.cloaked_agent_state;
-        let clock = Clock::get()?;
+    let agent = agent_state.key();
+    if let Some(registry) = old_registry {
+        registry.unregister(&agent);
+    }
+    if let Some(registry) = new_registry {
+        registry.register(agent)?;
+    }
 
-        agent_state.owner = None;
-        agent_state.owner_commitment = owner_commitment;
-        agent_state.delegate = ctx.accounts.delegate.key();
-        agent_state.max_per_tx = max_per_tx;
-        agent_state.daily_limit = daily_limit;
-        agent_state.total_limit = total_limit;
-        agent_state.expires_at = expires_at;
-        agent_state.frozen = false;
-        agent_state.total_spent = 0;
-        agent_state.daily_spent = 0;
-        agent_state.last_day = clock.unix_timestamp / SECONDS_PER_DAY;
-        agent_state.bump = ctx.bumps.cloaked_agent_state;
-        agent_state.created_at = clock.unix_timestamp;
+    let old_delegate = agent_state.delegate;
+    agent_state.delegate = new_delegate.key();
+    agent_state.program_delegate = false;
 
-        Ok(())
-    }
+    emit!(DelegateUpdatedEvent {
+        agent,
+        old_delegate,
+        new_delegate: agent_state.delegate,
+        timestamp: now,
+    });
+    Ok(())
+}
 
-    /// Deposit SOL to agent vault (anyone can call)
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        let transfer_ix = system_instruction::transfer(
-            ctx.accounts.depositor.key,
-            ctx.accounts.vault.key,
-            amount,
-        );
+/// Nullifier PDA seed for a private instruction's proof: sha256(proof_bytes)
+pub fn nullifier_proof_hash(proof_bytes: &[u8]) -> [u8; 32] {
+    hashv(&[proof_bytes]).to_bytes()
+}
 
-        invoke(
-            &transfer_ix,
-            &[
-                ctx.accounts.depositor.to_account_info(),
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
+/// Create the Nullifier for `proof_bytes`, funded by the vault
+/// Fails with ProofAlreadyUsed when the proof was submitted before
+fn create_nullifier<'info>(
+    nullifier_info: &AccountInfo<'info>,
+    vault: &SystemAccount<'info>,
+    system_program: &Program<'info, System>,
+    agent_key: Pubkey,
+    vault_bump: u8,
+    proof_bytes: &[u8],
+) -> Result<()> {
+    let proof_hash = nullifier_proof_hash(proof_bytes);
+    let (expected, bump) = Pubkey::find_program_address(&[b"nullifier", &proof_hash], &crate::ID);
+    require_keys_eq!(nullifier_info.key(), expected, ErrorCode::InvalidNullifier);
+    require!(nullifier_info.data_is_empty(), ErrorCode::ProofAlreadyUsed);
+
+    let rent = Rent::get()?.minimum_balance(Nullifier::SIZE);
+    let shortfall = rent.saturating_sub(nullifier_info.lamports());
+    require!(vault.lamports() >= shortfall, ErrorCode::InsufficientBalanceForFee);
+
+    let vault_seeds: &[&[u8]] = &[b"vault", agent_key.as_ref(), &[vault_bump]];
+    let nullifier_seeds: &[&[u8]] = &[b"nullifier", &proof_hash, &[bump]];
+    let accounts = [
+        vault.to_account_info(),
+        nullifier_info.clone(),
+        system_program.to_account_info(),
+    ];
+    if nullifier_info.lamports() == 0 {
+        invoke_signed(
+            &system_instruction::create_account(
+                vault.key,
+                nullifier_info.key,
+                rent,
+                Nullifier::SIZE as u64,
+                &crate::ID,
+            ),
+            &accounts,
+            &[vault_seeds, nullifier_seeds],
+        )?;
+    } else {
+        // Pre-funded address (e.g. lamports sent to block the proof): top up, allocate, assign
+        if shortfall > 0 {
+            invoke_signed(
+                &system_instruction::transfer(vault.key, nullifier_info.key, shortfall),
+                &accounts,
+                &[vault_seeds],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(nullifier_info.key, Nullifier::SIZE as u64),
+            &accounts,
+            &[nullifier_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(nullifier_info.key, &crate::ID),
+            &accounts,
+            &[nullifier_seeds],
         )?;
-
-        Ok(())
     }
 
-    /// Spend from vault to destination (delegate only, enforces constraints)
-    /// Fee payer fronts tx fee and is reimbursed from vault
-    pub fn spend(ctx: Context<Spend>, amount: u64) -> Result<()> {
-        let clock = Clock::get()?;
-        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+    let nullifier = Nullifier {
+        agent: agent_key,
+        proof_hash,
+        created_at: Clock::get()?.unix_timestamp,
+        bump,
+    };
+    nullifier.try_serialize(&mut &mut nullifier_info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
 
-        require!(!agent_state.frozen, ErrorCode::AgentFrozen);
+/// Agents created with require_registered_relayer only reimburse fee_recipients listed
+/// in the RelayerRegistry, which must then be passed
+fn check_relayer(
+    require_registered_relayer: bool,
+    registry: Option<&RelayerRegistry>,
+    fee_recipient: &Pubkey,
+) -> Result<()> {
+    if !require_registered_relayer {
+        return Ok(());
+    }
+    let registry = registry.ok_or(ErrorCode::UnauthorizedRelayer)?;
+    require!(registry.relayers.contains(fee_recipient), ErrorCode::UnauthorizedRelayer);
+    Ok(())
+}
 
-        if agent_state.expires_at > 0 {
-            require!(
-                clock.unix_timestamp < agent_state.expires_at,
-                ErrorCode::AgentExpired
-            );
-        }
+/// Relayer fee for private instructions: the ProgramConfig value when passed,
+/// otherwise PRIVATE_OPERATION_FEE
+fn private_operation_fee(program_config: Option<&ProgramConfig>) -> u64 {
+    program_config.map_or(PRIVATE_OPERATION_FEE, |config| config.private_operation_fee)
+}
 
-        // Check max per tx (0 = unlimited)
-        if agent_state.max_per_tx > 0 {
-            require!(
-                amount <= agent_state.max_per_tx,
-                ErrorCode::ExceedsPerTxLimit
-            );
-        }
+/// Fee a private instruction reimburses `fee_recipient`. A signing fee_recipient relays
+/// its own transaction and already paid the network fee, so it is only charged the
+/// self-relay share. Emits PrivateOperationFeeEvent with the amount and credits it to
+/// the fee_recipient's RelayerStats when passed
+fn private_fee_charged(
+    program_config: Option<&ProgramConfig>,
+    agent: Pubkey,
+    fee_recipient: &AccountInfo,
+    relayer_stats: Option<&mut RelayerStats>,
+) -> Result<u64> {
+    let fee = private_operation_fee(program_config);
+    let self_relayed = fee_recipient.is_signer;
+    let charged = if self_relayed {
+        let bps = program_config.map_or(SELF_RELAY_FEE_BPS, |config| config.self_relay_fee_bps);
+        (fee as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64
+    } else {
+        fee
+    };
+    if let Some(stats) = relayer_stats {
+        stats.record_fee(charged)?;
+    }
 
-        // Reset daily if new day
-        let current_day = clock.unix_timestamp / SECONDS_PER_DAY;
-        if current_day > agent_state.last_day {
-            agent_state.daily_spent = 0;
-            agent_state.last_day = current_day;
-        }
+    emit!(PrivateOperationFeeEvent {
+        agent,
+        fee_recipient: fee_recipient.key(),
+        fee: charged,
+        self_relayed,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(charged)
+}
 
-        // Check daily limit (0 = unlimited)
-        if agent_state.daily_limit > 0 {
-            require!(
-                agent_state.daily_spent.checked_add(amount).ok_or(ErrorCode::Overflow)?
-                    <= agent_state.daily_limit,
-                ErrorCode::ExceedsDailyLimit
-            );
-        }
+/// Fee payer reimbursement for spend: the ProgramConfig value when passed,
+/// otherwise SPEND_FEE_REIMBURSEMENT
+fn spend_fee_reimbursement(program_config: Option<&ProgramConfig>) -> u64 {
+    program_config.map_or(SPEND_FEE_REIMBURSEMENT, |config| config.spend_fee_reimbursement)
+}
 
-        // Check total limit (0 = unlimited)
-        if agent_state.total_limit > 0 {
-            require!(
-                agent_state.total_spent.checked_add(amount).ok_or(ErrorCode::Overflow)?
-                    <= agent_state.total_limit,
-                ErrorCode::ExceedsTotalLimit
-            );
-        }
+/// ProgramConfig stored at the [b"program_config"] PDA `info`, or None before
+/// initialize_program_config. The PDA is a required account so the pause cannot be
+/// skipped by omitting it; fails with ProgramPaused while the program is paused
+fn unpaused_program_config(info: &AccountInfo) -> Result<Option<ProgramConfig>> {
+    if info.data_is_empty() {
+        return Ok(None);
+    }
+    let config = ProgramConfig::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require!(!config.paused, ErrorCode::ProgramPaused);
+    Ok(Some(config))
+}
 
-        // Total required: amount + fee reimbursement
-        let total_required = amount.checked_add(SPEND_FEE_REIMBURSEMENT).ok_or(ErrorCode::Overflow)?;
+/// (day, count) after one more private operation at `now`; the count restarts at each
+/// UTC day boundary and may not exceed `limit`
+fn next_private_op_count(day: i64, count: u16, limit: u16, now: i64) -> Result<(i64, u16)> {
+    let today = now.div_euclid(SECONDS_PER_DAY);
+    let count = if today == day { count } else { 0 };
+    require!(count < limit, ErrorCode::TooManyPrivateOps);
+    Ok((today, count + 1))
+}
 
-        require!(
-            ctx.accounts.vault.lamports() >= total_required,
-            ErrorCode::InsufficientBalance
-        );
+/// Reimburse the relayer the private operation fee from the vault
+fn charge_private_operation_fee<'info>(
+    vault: &SystemAccount<'info>,
+    fee_recipient: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    agent_key: &Pubkey,
+    vault_bump: u8,
+    fee: u64,
+) -> Result<()> {
+    require!(
+        vault.lamports() >= fee,
+        ErrorCode::InsufficientBalanceForFee
+    );
+    invoke_signed(
+        &system_instruction::transfer(vault.key, fee_recipient.key, fee),
+        &[
+            vault.to_account_info(),
+            fee_recipient.clone(),
+            system_program.to_account_info(),
+        ],
+        &[&[b"vault", agent_key.as_ref(), &[vault_bump]]],
+    )?;
+    Ok(())
+}
 
-        // Update tracking before transfer
-        agent_state.daily_spent = agent_state.daily_spent
-            .checked_add(amount)
-            .ok_or(ErrorCode::Overflow)?;
-        agent_state.total_spent = agent_state.total_spent
-            .checked_add(amount)
-            .ok_or(ErrorCode::Overflow)?;
+/// UTC weekday of a unix timestamp, 0 = Sunday .. 6 = Saturday
+/// 1970-01-01 (day 0) was a Thursday
+pub fn weekday_from_timestamp(timestamp: i64) -> u8 {
+    let days = timestamp.div_euclid(SECONDS_PER_DAY);
+    (days + 4).rem_euclid(7) as u8
+}
 
-        // Get key after we're done with mutable borrow for state updates
-        let agent_state_key = agent_state.key();
-        let vault_bump = ctx.bumps.vault;
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"vault",
-            agent_state_key.as_ref(),
-            &[vault_bump],
-        ]];
+/// Validate a multisig owner set: 1..=MAX_MULTISIG_OWNERS distinct keys, 1 <= threshold <= owners
+fn validate_multisig(owners: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(
+        !owners.is_empty() && owners.len() <= MAX_MULTISIG_OWNERS,
+        ErrorCode::InvalidMultisigConfig
+    );
+    require!(
+        threshold > 0 && usize::from(threshold) <= owners.len(),
+        ErrorCode::InvalidMultisigConfig
+    );
+    for (i, owner) in owners.iter().enumerate() {
+        require!(*owner != Pubkey::default(), ErrorCode::InvalidMultisigConfig);
+        require!(!owners[..i].contains(owner), ErrorCode::InvalidMultisigConfig);
+    }
+    Ok(())
+}
 
-        // Transfer from vault to destination
-        invoke_signed(
-            &system_instruction::transfer(
-                ctx.accounts.vault.key,
-                ctx.accounts.destination.key,
-                amount,
-            ),
-            &[
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.destination.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+/// Multisig agents: require `multisig_threshold` distinct registered owners among `owner`
+/// and the signer accounts in `remaining_accounts`. No-op for single-owner agents
+fn check_multisig_approval(
+    agent_state: &CloakedAgentState,
+    owner: &Pubkey,
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    if !agent_state.is_multisig() {
+        return Ok(());
+    }
 
-        // Reimburse fee payer for transaction fee
-        invoke_signed(
-            &system_instruction::transfer(
-                ctx.accounts.vault.key,
-                ctx.accounts.fee_payer.key,
-                SPEND_FEE_REIMBURSEMENT,
-            ),
-            &[
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.fee_payer.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+    let owners = agent_state.multisig_owner_keys();
+    let mut approved = [false; MAX_MULTISIG_OWNERS];
+    let signers = remaining_accounts
+        .iter()
+        .filter(|info| info.is_signer)
+        .map(|info| info.key);
+    for key in std::iter::once(owner).chain(signers) {
+        if let Some(i) = owners.iter().position(|o| o == key) {
+            approved[i] = true;
+        }
+    }
 
-        Ok(())
+    let approvals = approved.iter().filter(|a| **a).count();
+    require!(
+        approvals >= usize::from(agent_state.multisig_threshold),
+        ErrorCode::MultisigThresholdNotMet
+    );
+    Ok(())
+}
+
+/// Where reclaim sends the vault balance: the locked destination when set, else the owner
+fn vault_payout_account<'info>(
+    agent_state: &CloakedAgentState,
+    owner: &AccountInfo<'info>,
+    withdraw_destination: Option<&AccountInfo<'info>>,
+) -> Result<AccountInfo<'info>> {
+    if agent_state.withdraw_destination_lock.is_none() {
+        return Ok(owner.clone());
     }
+    let destination = withdraw_destination.ok_or(ErrorCode::DestinationLocked)?;
+    check_withdraw_destination(agent_state, destination.key)?;
+    Ok(destination.clone())
+}
 
-    /// Withdraw from vault to any destination (owner only, standard mode, no constraints)
-    /// Works even if agent is frozen or expired - owner has full control
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        let agent_state = &ctx.accounts.cloaked_agent_state;
-        require!(!agent_state.is_private(), ErrorCode::IsPrivateMode);
-        require!(
-            agent_state.owner == Some(ctx.accounts.owner.key()),
-            ErrorCode::NotOwner
-        );
+/// Link a new agent to its parent (shared by create variants)
+/// The parent's delegate must sign; only one level of nesting is allowed
+fn attach_parent(
+    agent_state: &mut Account<CloakedAgentState>,
+    parent_state: Option<&mut Account<CloakedAgentState>>,
+    parent_delegate: Option<&Signer>,
+) -> Result<()> {
+    let Some(parent) = parent_state else {
+        return Ok(());
+    };
+    let parent_delegate = parent_delegate.ok_or(ErrorCode::InvalidParentAgent)?;
+    require_keys_eq!(parent_delegate.key(), parent.delegate, ErrorCode::InvalidParentAgent);
+    require!(parent.parent.is_none(), ErrorCode::InvalidParentAgent);
+    require!(
+        parent.allows_instruction(INSTRUCTION_BIT_CREATE_SUB_AGENT),
+        ErrorCode::InstructionNotAllowed
+    );
 
-        // Check balance
-        require!(
-            ctx.accounts.vault.lamports() >= amount,
-            ErrorCode::InsufficientBalance
-        );
+    parent.child_count = parent.child_count
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    agent_state.parent = Some(parent.key());
+    Ok(())
+}
 
-        // Get signer seeds for vault PDA
-        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
-        let vault_bump = ctx.bumps.vault;
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"vault",
-            agent_state_key.as_ref(),
-            &[vault_bump],
-        ]];
+/// Unlink an agent from its parent before it is closed (shared by close variants)
+/// Parents cannot close while sub-agents still reference them
+fn detach_parent(
+    agent_state: &CloakedAgentState,
+    parent_state: Option<&mut Account<CloakedAgentState>>,
+) -> Result<()> {
+    require!(agent_state.child_count == 0, ErrorCode::HasChildAgents);
+    let Some(parent_key) = agent_state.parent else {
+        return Ok(());
+    };
+    let parent = parent_state.ok_or(ErrorCode::InvalidParentAgent)?;
+    require_keys_eq!(parent.key(), parent_key, ErrorCode::InvalidParentAgent);
 
-        // Transfer from vault to destination
-        let transfer_ix = system_instruction::transfer(
-            ctx.accounts.vault.key,
-            ctx.accounts.destination.key,
-            amount,
-        );
+    parent.child_count = parent.child_count
+        .checked_sub(1)
+        .ok_or(ErrorCode::Overflow)?;
+    Ok(())
+}
 
-        invoke_signed(
-            &transfer_ix,
-            &[
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.destination.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+/// State close_cloaked_agent refuses to strand: active stake, pending timelocks, and
+/// whitelist, program whitelist or blacklist entry PDAs. `whitelist_entries` is the
+/// number of address whitelist entries still open
+fn check_closable(agent_state: &CloakedAgentState, whitelist_entries: u8) -> Result<()> {
+    // Stake accounts are controlled by the vault PDA and would be stranded
+    require!(agent_state.staked_amount == 0, ErrorCode::HasActiveStake);
+    // TimelockRecord PDAs would be orphaned
+    require!(agent_state.pending_timelocks == 0, ErrorCode::HasPendingTimelocks);
+    require!(
+        whitelist_entries == 0
+            && agent_state.program_whitelist_entry_count == 0
+            && agent_state.blacklist_entry_count == 0,
+        ErrorCode::HasOrphanedPDAs
+    );
+    Ok(())
+}
 
-        Ok(())
+/// Accounts shared by every deposit path (deposit, deposit_with_memo, topup_and_spend,
+/// deposit_with_program_authority)
+struct DepositAccounts<'a, 'info> {
+    cloaked_agent_state: &'a mut Account<'info, CloakedAgentState>,
+    vault: &'a SystemAccount<'info>,
+    depositor: &'a AccountInfo<'info>,
+    global_stats: Option<&'a mut Account<'info, GlobalStats>>,
+    system_program: &'a Program<'info, System>,
+}
+
+/// Transfer lamports from depositor to agent vault (shared by deposit variants)
+/// Returns the vault balance snapshot taken before this deposit
+fn process_deposit(accounts: DepositAccounts, amount: u64) -> Result<u64> {
+    let clock = Clock::get()?;
+    let agent_state = accounts.cloaked_agent_state;
+    require!(!agent_state.deposits_locked, ErrorCode::DepositsLocked);
+
+    // Optionally refuse funding an agent that cannot currently spend
+    if agent_state.block_deposits_when_inactive {
+        require!(!agent_state.frozen, ErrorCode::AgentFrozen);
+        require!(!agent_state.is_expired(&clock), ErrorCode::AgentExpired);
     }
 
-    /// Freeze agent (owner only, standard mode) - emergency stop
-    pub fn freeze(ctx: Context<Freeze>) -> Result<()> {
-        let agent_state = &mut ctx.accounts.cloaked_agent_state;
-        require!(!agent_state.is_private(), ErrorCode::IsPrivateMode);
+    // Reject dust deposits (0 = no minimum)
+    require!(amount >= agent_state.min_deposit, ErrorCode::DepositTooSmall);
+
+    // Check vault cap (0 = unlimited)
+    if agent_state.max_vault_balance > 0 {
         require!(
-            agent_state.owner == Some(ctx.accounts.owner.key()),
-            ErrorCode::NotOwner
+            accounts.vault.lamports().checked_add(amount).ok_or(ErrorCode::Overflow)?
+                <= agent_state.max_vault_balance,
+            ErrorCode::ExceedsVaultCap
         );
-        agent_state.frozen = true;
-        Ok(())
     }
 
-    /// Unfreeze agent (owner only, standard mode)
-    pub fn unfreeze(ctx: Context<Unfreeze>) -> Result<()> {
-        let agent_state = &mut ctx.accounts.cloaked_agent_state;
-        require!(!agent_state.is_private(), ErrorCode::IsPrivateMode);
-        require!(
-            agent_state.owner == Some(ctx.accounts.owner.key()),
-            ErrorCode::NotOwner
-        );
-        agent_state.frozen = false;
-        Ok(())
+    agent_state.total_deposited = agent_state.total_deposited
+        .checked_add(amount)
+        .ok_or(ErrorCode::Overflow)?;
+    let total_deposited = agent_state.total_deposited;
+    if let Some(stats) = accounts.global_stats {
+        stats.record_deposited(amount)?;
     }
 
-    /// Freeze agent with ZK proof (private mode)
-    pub fn freeze_private(
-        ctx: Context<FreezePrivate>,
-        proof_bytes: Vec<u8>,
-        witness_bytes: Vec<u8>,
-    ) -> Result<()> {
-        // Get keys before mutable borrow
-        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
-        let vault_bump = ctx.bumps.vault;
+    let transfer_ix = system_instruction::transfer(
+        accounts.depositor.key,
+        accounts.vault.key,
+        amount,
+    );
 
-        {
-            let agent_state = &ctx.accounts.cloaked_agent_state;
-            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+    invoke(
+        &transfer_ix,
+        &[
+            accounts.depositor.to_account_info(),
+            accounts.vault.to_account_info(),
+            accounts.system_program.to_account_info(),
+        ],
+    )?;
+
+    let vault_balance_after = accounts.vault.lamports();
+    let vault_balance_before =
+        agent_state.update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+    emit!(DepositEvent {
+        agent: agent_state.key(),
+        depositor: accounts.depositor.key(),
+        amount,
+        total_deposited,
+        vault_balance_before,
+        vault_balance_after,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(vault_balance_before)
+}
 
-            // Verify ZK proof via CPI
-            verify_zk_proof(
-                &ctx.accounts.zk_verifier,
-                &proof_bytes,
-                &witness_bytes,
-                &agent_state.owner_commitment,
-            )?;
-        }
+/// Hash a spend_with_secp authorization the way EVM wallets sign it (EIP-191 personal_sign)
+/// Payload: keccak256(agent || amount || destination || nonce || deadline), integers little-endian
+fn secp_spend_message_hash(
+    agent: &Pubkey,
+    amount: u64,
+    destination: &Pubkey,
+    nonce: u64,
+    deadline: i64,
+) -> [u8; 32] {
+    let payload = keccak::hashv(&[
+        agent.as_ref(),
+        &amount.to_le_bytes(),
+        destination.as_ref(),
+        &nonce.to_le_bytes(),
+        &deadline.to_le_bytes(),
+    ]);
+    keccak::hashv(&[b"\x19Ethereum Signed Message:\n32", payload.as_ref()]).to_bytes()
+}
 
-        // Check vault has enough for fee
-        require!(
-            ctx.accounts.vault.lamports() >= PRIVATE_OPERATION_FEE,
-            ErrorCode::InsufficientBalanceForFee
-        );
+/// Recover the 20-byte Ethereum address that produced `signature` over `message_hash`
+fn recover_eth_address(message_hash: &[u8; 32], signature: &[u8; 64], recovery_id: u8) -> Result<[u8; 20]> {
+    // Accept both raw (0/1) and Ethereum-style (27/28) recovery ids
+    let recovery_id = if recovery_id >= 27 { recovery_id - 27 } else { recovery_id };
+    let pubkey = secp256k1_recover(message_hash, recovery_id, signature)
+        .map_err(|_| error!(ErrorCode::InvalidSecpSignature))?;
+    let hash = keccak::hash(&pubkey.to_bytes());
+
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash.to_bytes()[12..]);
+    Ok(address)
+}
 
-        // Transfer fee to fee_recipient (relayer reimbursement)
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"vault",
-            agent_state_key.as_ref(),
-            &[vault_bump],
-        ]];
+/// Canonical spend_with_passkey message - clients must sign exactly these bytes
+/// PASSKEY_SPEND_DOMAIN || agent || amount || destination || nonce || deadline (integers little-endian)
+pub fn passkey_spend_message(
+    agent: &Pubkey,
+    amount: u64,
+    destination: &Pubkey,
+    nonce: u64,
+    deadline: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(PASSKEY_SPEND_DOMAIN.len() + 32 + 8 + 32 + 8 + 8);
+    message.extend_from_slice(PASSKEY_SPEND_DOMAIN);
+    message.extend_from_slice(agent.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(destination.as_ref());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&deadline.to_le_bytes());
+    message
+}
 
-        invoke_signed(
-            &system_instruction::transfer(
-                ctx.accounts.vault.key,
-                ctx.accounts.fee_recipient.key,
-                PRIVATE_OPERATION_FEE,
-            ),
-            &[
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.fee_recipient.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+/// Require that the instruction right before this one is a `precompile` (ed25519 /
+/// secp256r1) check of a single signature by `pubkey` over `message`, and, when given,
+/// that the verified signature is `signature`
+/// Both precompiles share the offsets layout: [count u8, padding u8, then per signature
+/// signature_offset, signature_ix, pubkey_offset, pubkey_ix, message_offset, message_size,
+/// message_ix as u16]. All data must live inside the precompile instruction (ix = u16::MAX)
+/// Only the immediately preceding instruction is inspected, by design: pairing the check
+/// with a fixed position means one precompile instruction can never authorize two
+/// instructions in the same transaction. Clients must place it directly before this one
+fn verify_precompile_signature(
+    instructions: &AccountInfo,
+    precompile: &Pubkey,
+    pubkey: &[u8],
+    message: &[u8],
+    signature: Option<&[u8]>,
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)?;
+    require!(current_index > 0, ErrorCode::MissingSignatureInstruction);
+    let ix = load_instruction_at_checked(usize::from(current_index - 1), instructions)?;
+    require_keys_eq!(ix.program_id, *precompile, ErrorCode::MissingSignatureInstruction);
+
+    let data = &ix.data;
+    require!(data.len() >= 16 && data[0] == 1, ErrorCode::InvalidSignatureInstruction);
+    let read_u16 = |field: usize| u16::from_le_bytes([data[2 + 2 * field], data[3 + 2 * field]]);
+    require!(
+        read_u16(1) == u16::MAX && read_u16(3) == u16::MAX && read_u16(6) == u16::MAX,
+        ErrorCode::InvalidSignatureInstruction
+    );
 
-        ctx.accounts.cloaked_agent_state.frozen = true;
-        Ok(())
+    let pubkey_offset = usize::from(read_u16(2));
+    let message_offset = usize::from(read_u16(4));
+    let message_size = usize::from(read_u16(5));
+    let signed_pubkey = data
+        .get(pubkey_offset..pubkey_offset + pubkey.len())
+        .ok_or(ErrorCode::InvalidSignatureInstruction)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(ErrorCode::InvalidSignatureInstruction)?;
+    require!(
+        signed_pubkey == pubkey && signed_message == message,
+        ErrorCode::InvalidSignatureInstruction
+    );
+
+    if let Some(signature) = signature {
+        let signature_offset = usize::from(read_u16(0));
+        let verified_signature = data
+            .get(signature_offset..signature_offset + signature.len())
+            .ok_or(ErrorCode::InvalidSignatureInstruction)?;
+        require!(verified_signature == signature, ErrorCode::InvalidSignatureInstruction);
     }
+    Ok(())
+}
 
-    /// Unfreeze agent with ZK proof (private mode)
-    pub fn unfreeze_private(
-        ctx: Context<UnfreezePrivate>,
-        proof_bytes: Vec<u8>,
-        witness_bytes: Vec<u8>,
-    ) -> Result<()> {
-        // Get keys before mutable borrow
-        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
-        let vault_bump = ctx.bumps.vault;
+/// Read the price from a fully verified Pyth PriceUpdateV2 account
+/// Layout after the discriminator: write_authority (32), verification_level (enum,
+/// Full = 1 byte), then feed_id (32), price i64, conf u64, exponent i32, publish_time i64.
+/// The price is returned in the feed's own exponent units
+fn read_pyth_price(price_feed: &AccountInfo, now: i64) -> Result<i64> {
+    require_keys_eq!(*price_feed.owner, PYTH_RECEIVER_PROGRAM_ID, ErrorCode::InvalidPriceFeed);
 
-        {
-            let agent_state = &ctx.accounts.cloaked_agent_state;
-            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+    let data = price_feed.try_borrow_data()?;
+    require!(
+        data.len() >= 101 && data[..8] == PYTH_PRICE_UPDATE_DISCRIMINATOR,
+        ErrorCode::InvalidPriceFeed
+    );
+    // Partially verified updates (tag 0) are not trusted
+    require!(data[40] == 1, ErrorCode::InvalidPriceFeed);
 
-            // Verify ZK proof via CPI
-            verify_zk_proof(
-                &ctx.accounts.zk_verifier,
-                &proof_bytes,
-                &witness_bytes,
-                &agent_state.owner_commitment,
-            )?;
-        }
+    let read_i64 = |offset: usize| i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let price = read_i64(73);
+    let publish_time = read_i64(93);
+    require!(
+        now.saturating_sub(publish_time) <= MAX_PRICE_AGE_SECONDS,
+        ErrorCode::StalePrice
+    );
 
-        // Check vault has enough for fee
-        require!(
-            ctx.accounts.vault.lamports() >= PRIVATE_OPERATION_FEE,
-            ErrorCode::InsufficientBalanceForFee
-        );
+    Ok(price)
+}
 
-        // Transfer fee to fee_recipient (relayer reimbursement)
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"vault",
-            agent_state_key.as_ref(),
-            &[vault_bump],
-        ]];
+/// Create (on first use) and update the DestinationStats PDA optionally passed to spend
+/// The fee payer funds the account rent when it is created
+fn record_destination_stats<'info>(
+    stats_info: &'info AccountInfo<'info>,
+    agent: Pubkey,
+    destination: Pubkey,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+    now: i64,
+) -> Result<()> {
+    let (expected, bump) = Pubkey::find_program_address(
+        &[b"dest_stats", agent.as_ref(), destination.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(stats_info.key(), expected, ErrorCode::InvalidDestinationStats);
+    require!(stats_info.is_writable, ErrorCode::InvalidDestinationStats);
 
+    if stats_info.data_is_empty() {
         invoke_signed(
-            &system_instruction::transfer(
-                ctx.accounts.vault.key,
-                ctx.accounts.fee_recipient.key,
-                PRIVATE_OPERATION_FEE,
+            &system_instruction::create_account(
+                payer.key,
+                stats_info.key,
+                Rent::get()?.minimum_balance(DestinationStats::SIZE),
+                DestinationStats::SIZE as u64,
+                &crate::ID,
             ),
             &[
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.fee_recipient.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
+                payer.to_account_info(),
+                stats_info.clone(),
+                system_program.to_account_info(),
             ],
-            signer_seeds,
+            &[&[b"dest_stats", agent.as_ref(), destination.as_ref(), &[bump]]],
         )?;
-
-        ctx.accounts.cloaked_agent_state.frozen = false;
-        Ok(())
+        let stats = DestinationStats {
+            agent,
+            destination,
+            total_sent: 0,
+            sent_count: 0,
+            last_sent_at: 0,
+            bump,
+        };
+        stats.try_serialize(&mut &mut stats_info.try_borrow_mut_data()?[..])?;
     }
 
-    /// Update agent constraints (owner only, standard mode)
-    pub fn update_constraints(
-        ctx: Context<UpdateConstraints>,
-        max_per_tx: Option<u64>,
-        daily_limit: Option<u64>,
-        total_limit: Option<u64>,
-        expires_at: Option<i64>,
-    ) -> Result<()> {
-        let agent_state = &mut ctx.accounts.cloaked_agent_state;
-        require!(!agent_state.is_private(), ErrorCode::IsPrivateMode);
-        require!(
-            agent_state.owner == Some(ctx.accounts.owner.key()),
-            ErrorCode::NotOwner
-        );
+    let mut stats: Account<'info, DestinationStats> = Account::try_from(stats_info)?;
+    stats.total_sent = stats.total_sent.checked_add(amount).ok_or(ErrorCode::Overflow)?;
+    stats.sent_count = stats.sent_count.checked_add(1).ok_or(ErrorCode::Overflow)?;
+    stats.last_sent_at = now;
+    stats.exit(&crate::ID)
+}
 
-        if let Some(v) = max_per_tx {
-            agent_state.max_per_tx = v;
-        }
-        if let Some(v) = daily_limit {
-            agent_state.daily_limit = v;
-        }
-        if let Some(v) = total_limit {
-            agent_state.total_limit = v;
-        }
-        if let Some(v) = expires_at {
-            agent_state.expires_at = v;
-        }
+/// CPI the agent's post_spend_hook with a borsh-encoded SpendNotification
+/// `hook_accounts` are forwarded as-is; the vault never signs for the hook
+fn notify_spend_hook<'info>(
+    hook_program: &AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    notification: &SpendNotification,
+) -> Result<()> {
+    require!(hook_program.executable, ErrorCode::InvalidSpendHook);
+
+    let mut data = Vec::new();
+    notification.serialize(&mut data).expect("writing to a Vec cannot fail");
+    let metas = hook_accounts
+        .iter()
+        .map(|info| AccountMeta {
+            pubkey: info.key(),
+            is_signer: info.is_signer,
+            is_writable: info.is_writable,
+        })
+        .collect();
+    let mut infos = hook_accounts.to_vec();
+    infos.push(hook_program.clone());
+
+    invoke(
+        &Instruction {
+            program_id: hook_program.key(),
+            accounts: metas,
+            data,
+        },
+        &infos,
+    )?;
+    Ok(())
+}
 
-        Ok(())
+/// Take a leading ProgramWhitelistEntry off spend's remaining_accounts. Returns whether
+/// it whitelists `destination_owner` for `agent`, and the accounts after it
+fn take_program_whitelist_entry<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    agent: Pubkey,
+    destination_owner: &Pubkey,
+) -> Result<(bool, &'a [AccountInfo<'info>])> {
+    let Some((entry_info, rest)) = remaining_accounts.split_first() else {
+        return Ok((false, remaining_accounts));
+    };
+    if entry_info.owner != &crate::ID
+        || !entry_info.try_borrow_data()?.starts_with(ProgramWhitelistEntry::DISCRIMINATOR)
+    {
+        return Ok((false, remaining_accounts));
     }
 
-    /// Update agent constraints with ZK proof (private mode)
-    pub fn update_constraints_private(
-        ctx: Context<UpdateConstraintsPrivate>,
-        proof_bytes: Vec<u8>,
-        witness_bytes: Vec<u8>,
-        max_per_tx: Option<u64>,
-        daily_limit: Option<u64>,
-        total_limit: Option<u64>,
-        expires_at: Option<i64>,
-    ) -> Result<()> {
-        // Get keys before mutable borrow
-        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
-        let vault_bump = ctx.bumps.vault;
-
-        {
-            let agent_state = &ctx.accounts.cloaked_agent_state;
-            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
-
-            // Verify ZK proof via CPI
-            verify_zk_proof(
-                &ctx.accounts.zk_verifier,
-                &proof_bytes,
-                &witness_bytes,
-                &agent_state.owner_commitment,
-            )?;
-        }
+    let entry = ProgramWhitelistEntry::try_deserialize(&mut &entry_info.try_borrow_data()?[..])?;
+    require_keys_eq!(entry.agent, agent, ErrorCode::InvalidProgramWhitelistEntry);
+    require_keys_eq!(entry.program, *destination_owner, ErrorCode::DestinationNotWhitelisted);
+    Ok((true, rest))
+}
 
-        // Check vault has enough for fee
-        require!(
-            ctx.accounts.vault.lamports() >= PRIVATE_OPERATION_FEE,
-            ErrorCode::InsufficientBalanceForFee
-        );
+/// Fail when spend's remaining_accounts start with a BlacklistEntry for `destination`;
+/// otherwise return them unchanged
+fn check_blacklist_entry<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    agent: Pubkey,
+    destination: &Pubkey,
+) -> Result<&'a [AccountInfo<'info>]> {
+    let Some(entry_info) = remaining_accounts.first() else {
+        return Ok(remaining_accounts);
+    };
+    if entry_info.owner != &crate::ID
+        || !entry_info.try_borrow_data()?.starts_with(BlacklistEntry::DISCRIMINATOR)
+    {
+        return Ok(remaining_accounts);
+    }
 
-        // Transfer fee to fee_recipient (relayer reimbursement)
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"vault",
-            agent_state_key.as_ref(),
-            &[vault_bump],
-        ]];
+    let entry = BlacklistEntry::try_deserialize(&mut &entry_info.try_borrow_data()?[..])?;
+    require_keys_eq!(entry.agent, agent, ErrorCode::InvalidBlacklistEntry);
+    require_keys_eq!(entry.destination, *destination, ErrorCode::InvalidBlacklistEntry);
+    err!(ErrorCode::DestinationBlacklisted)
+}
 
-        invoke_signed(
-            &system_instruction::transfer(
-                ctx.accounts.vault.key,
-                ctx.accounts.fee_recipient.key,
-                PRIVATE_OPERATION_FEE,
-            ),
-            &[
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.fee_recipient.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+/// withdraw_private / withdraw_all_private: `amount` None withdraws everything above the
+/// vault's rent reserve, with the proof made for ACTION_WITHDRAW_ALL
+fn process_withdraw_private(
+    ctx: Context<WithdrawPrivate>,
+    proof_bytes: Vec<u8>,
+    witness_bytes: Vec<u8>,
+    verifier_version: u8,
+    amount: Option<u64>,
+    bypass_expiry: bool,
+) -> Result<()> {
+    let (action_id, action_data) = match amount {
+        Some(amount) => (
+            ACTION_WITHDRAW,
+            withdraw_action_data(ctx.accounts.destination.key, amount, bypass_expiry),
+        ),
+        None => (
+            ACTION_WITHDRAW_ALL,
+            withdraw_all_action_data(ctx.accounts.destination.key, bypass_expiry),
+        ),
+    };
+    let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+    let (proof_bytes, witness_bytes) = load_proof(
+        ctx.accounts.proof_buffer.as_deref(),
+        ctx.accounts.cloaked_agent_state.key(),
+        action_id,
+        proof_bytes,
+        witness_bytes,
+    )?;
+    ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+    let operation_fee = private_fee_charged(
+        program_config.as_ref(),
+        ctx.accounts.cloaked_agent_state.key(),
+        &ctx.accounts.fee_recipient,
+        ctx.accounts.relayer_stats.as_deref_mut(),
+    )?;
+    let clock = Clock::get()?;
+    let agent_state = &mut ctx.accounts.cloaked_agent_state;
+    require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+
+    // Expired agents stay closed unless the owner explicitly opts in; the opt-in is part
+    // of action_data, so a relayer cannot flip it on a proof made without it
+    require!(
+        agent_state.expires_at == 0
+            || clock.unix_timestamp < agent_state.expires_at
+            || bypass_expiry,
+        ErrorCode::AgentExpired
+    );
 
-        let agent_state = &mut ctx.accounts.cloaked_agent_state;
-        if let Some(v) = max_per_tx {
-            agent_state.max_per_tx = v;
-        }
-        if let Some(v) = daily_limit {
-            agent_state.daily_limit = v;
-        }
-        if let Some(v) = total_limit {
-            agent_state.total_limit = v;
-        }
-        if let Some(v) = expires_at {
-            agent_state.expires_at = v;
+    // Verify ZK proof via CPI
+    let verifier_entry =
+        select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+    verify_zk_proof(
+        &ctx.accounts.zk_verifier,
+        ctx.accounts.instructions.as_ref(),
+        program_config.as_ref(),
+        verifier_entry.as_ref(),
+        &proof_bytes,
+        &witness_bytes,
+        agent_state,
+        action_id,
+        &action_data,
+    )?;
+    check_relayer(
+        agent_state.require_registered_relayer,
+        ctx.accounts.relayer_registry.as_deref(),
+        ctx.accounts.fee_recipient.key,
+    )?;
+    create_nullifier(
+        &ctx.accounts.nullifier,
+        &ctx.accounts.vault,
+        &ctx.accounts.system_program,
+        agent_state.key(),
+        agent_state.vault_bump,
+        &proof_bytes,
+    )?;
+
+    // Withdraw-all takes everything but the fee and the vault's rent reserve
+    let amount = match amount {
+        Some(amount) => amount,
+        None => {
+            let reserve = Rent::get()?
+                .minimum_balance(0)
+                .checked_add(operation_fee)
+                .ok_or(ErrorCode::Overflow)?;
+            let amount = ctx.accounts.vault.lamports().saturating_sub(reserve);
+            require!(amount > 0, ErrorCode::InsufficientBalance);
+            amount
         }
+    };
 
-        Ok(())
-    }
+    // Total required = amount + fee
+    let total_required = amount.checked_add(operation_fee).ok_or(ErrorCode::Overflow)?;
+    require!(
+        ctx.accounts.vault.lamports() >= total_required,
+        ErrorCode::InsufficientBalance
+    );
 
-    /// Close agent and return all funds to owner (standard mode)
-    pub fn close_cloaked_agent(ctx: Context<CloseCloakedAgent>) -> Result<()> {
-        let agent_state = &ctx.accounts.cloaked_agent_state;
-        require!(!agent_state.is_private(), ErrorCode::IsPrivateMode);
-        require!(
-            agent_state.owner == Some(ctx.accounts.owner.key()),
-            ErrorCode::NotOwner
-        );
+    // Update tracking before transfer
+    agent_state.total_withdrawn = agent_state.total_withdrawn
+        .checked_add(amount)
+        .ok_or(ErrorCode::Overflow)?;
+    if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+        stats.record_withdrawn(amount)?;
+    }
 
-        let vault = &ctx.accounts.vault;
-        let owner = &ctx.accounts.owner;
+    let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+    let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"vault",
+        agent_state_key.as_ref(),
+        &[vault_bump],
+    ]];
+
+    // Transfer fee to fee_recipient (relayer reimbursement)
+    invoke_signed(
+        &system_instruction::transfer(
+            ctx.accounts.vault.key,
+            ctx.accounts.fee_recipient.key,
+            operation_fee,
+        ),
+        &[
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.fee_recipient.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+        signer_seeds,
+    )?;
+
+    // Transfer requested amount to destination
+    invoke_signed(
+        &system_instruction::transfer(
+            ctx.accounts.vault.key,
+            ctx.accounts.destination.key,
+            amount,
+        ),
+        &[
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.destination.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+        signer_seeds,
+    )?;
+
+    let vault_balance_after = ctx.accounts.vault.lamports();
+    let vault_balance_before = ctx
+        .accounts
+        .cloaked_agent_state
+        .update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+    emit!(PrivateWithdrawEvent {
+        agent: agent_state_key,
+        destination: ctx.accounts.destination.key(),
+        amount,
+        vault_balance_before,
+        vault_balance_after,
+        timestamp: clock.unix_timestamp,
+    });
 
-        // Transfer vault balance to owner
-        let vault_balance = vault.lamports();
-        if vault_balance > 0 {
-            let agent_state_key = ctx.accounts.cloaked_agent_state.key();
-            let vault_bump = ctx.bumps.vault;
-            let signer_seeds: &[&[&[u8]]] = &[&[
-                b"vault",
-                agent_state_key.as_ref(),
-                &[vault_bump],
-            ]];
+    Ok(())
+}
 
-            let transfer_ix = system_instruction::transfer(
-                vault.key,
-                owner.key,
-                vault_balance,
-            );
+/// Accounts shared by every spend path (primary delegate, DelegateEntry, ...)
+struct SpendAccounts<'a, 'info> {
+    cloaked_agent_state: &'a mut Account<'info, CloakedAgentState>,
+    vault: &'a SystemAccount<'info>,
+    fee_payer: &'a Signer<'info>,
+    destination: &'a AccountInfo<'info>,
+    commission_recipient: Option<&'a AccountInfo<'info>>,
+    whitelist_entry: Option<&'a Account<'info, WhitelistEntry>>,
+    /// Destination's owner program matched a ProgramWhitelistEntry (spend only)
+    program_whitelisted: bool,
+    parent_state: Option<&'a mut Account<'info, CloakedAgentState>>,
+    global_stats: Option<&'a mut Account<'info, GlobalStats>>,
+    /// two_fa_key signer, required for spends at or above two_fa_threshold
+    second_factor: Option<&'a Signer<'info>>,
+    /// Lamports paid to fee_payer (SPEND_FEE_REIMBURSEMENT unless configured)
+    fee_reimbursement: u64,
+    system_program: &'a Program<'info, System>,
+}
 
-            invoke_signed(
-                &transfer_ix,
-                &[
-                    vault.to_account_info(),
-                    owner.to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
-                ],
-                signer_seeds,
-            )?;
+/// High-value spends need the agent's two_fa_key to co-sign (spend_with_2fa)
+fn check_two_fa(agent_state: &CloakedAgentState, amount: u64, second_factor: Option<&Signer>) -> Result<()> {
+    if let Some(two_fa_key) = agent_state.two_fa_key {
+        if amount >= agent_state.two_fa_threshold {
+            let signer = second_factor.ok_or(ErrorCode::Invalid2FA)?;
+            require_keys_eq!(signer.key(), two_fa_key, ErrorCode::Invalid2FA);
         }
-
-        // cloaked_agent_state account is closed by Anchor's close constraint
-        Ok(())
     }
+    Ok(())
+}
 
-    /// Close agent with ZK proof (private mode)
-    pub fn close_cloaked_agent_private(
-        ctx: Context<CloseCloakedAgentPrivate>,
-        proof_bytes: Vec<u8>,
-        witness_bytes: Vec<u8>,
-    ) -> Result<()> {
-        let agent_state = &ctx.accounts.cloaked_agent_state;
-        require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+/// Keep minimum_vault_balance_for_delegation in the vault after a delegate spend
+fn check_delegate_reserve(agent_state: &CloakedAgentState, vault_balance: u64, total_required: u64) -> Result<()> {
+    require!(
+        vault_balance.saturating_sub(total_required) >= agent_state.minimum_vault_balance_for_delegation,
+        ErrorCode::WouldViolateDelegateReserve
+    );
+    Ok(())
+}
 
-        // Verify ZK proof via CPI
-        verify_zk_proof(
-            &ctx.accounts.zk_verifier,
-            &proof_bytes,
-            &witness_bytes,
-            &agent_state.owner_commitment,
-        )?;
+/// Per-spend checks on the agent itself: active, time-of-day/day, per-tx bounds
+fn check_spend_allowed(agent_state: &CloakedAgentState, amount: u64, clock: &Clock) -> Result<()> {
+    require!(!agent_state.frozen, ErrorCode::AgentFrozen);
+    require!(!agent_state.is_expired(clock), ErrorCode::AgentExpired);
 
-        let vault = &ctx.accounts.vault;
-        let vault_balance = vault.lamports();
+    require!(
+        agent_state.is_within_spend_hours(clock.unix_timestamp),
+        ErrorCode::OutsideSpendWindow
+    );
+    require!(
+        agent_state.is_allowed_day(clock.unix_timestamp),
+        ErrorCode::DayNotAllowed
+    );
 
-        // Check vault has enough for fee
+    // Check max per tx (0 = unlimited)
+    if agent_state.max_per_tx > 0 {
         require!(
-            vault_balance >= PRIVATE_OPERATION_FEE,
-            ErrorCode::InsufficientBalanceForFee
+            amount <= agent_state.max_per_tx,
+            ErrorCode::ExceedsPerTxLimit
         );
+    }
 
-        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
-        let vault_bump = ctx.bumps.vault;
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"vault",
-            agent_state_key.as_ref(),
-            &[vault_bump],
-        ]];
+    // Check min spend (0 = no minimum)
+    require!(
+        amount >= agent_state.min_spend_amount || agent_state.min_spend_amount == 0,
+        ErrorCode::BelowMinSpend
+    );
 
-        // Transfer fee to fee_recipient (relayer reimbursement)
-        invoke_signed(
-            &system_instruction::transfer(
-                vault.key,
-                ctx.accounts.fee_recipient.key,
-                PRIVATE_OPERATION_FEE,
-            ),
-            &[
-                vault.to_account_info(),
-                ctx.accounts.fee_recipient.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+    Ok(())
+}
 
-        // Transfer remaining vault balance to destination
-        let remaining_balance = vault_balance
-            .checked_sub(PRIVATE_OPERATION_FEE)
+/// Charge `charged` against the agent's window and total limits and roll it up into the
+/// parent (which must be passed for sub-agents). The caller records agent total_spent
+fn record_spend_limits(
+    agent_state: &mut CloakedAgentState,
+    parent_state: Option<&mut Account<CloakedAgentState>>,
+    charged: u64,
+    clock: &Clock,
+) -> Result<()> {
+    // Check and record against the window limit (rolls an elapsed window, banks rollover)
+    agent_state.record_window_spend(clock.unix_timestamp, charged)?;
+    agent_state.record_sliding_window_spend(clock.unix_timestamp, charged)?;
+
+    // Check total limit (unlimited when total_limit is 0)
+    require!(
+        charged <= agent_state.remaining_total_allowance(),
+        ErrorCode::ExceedsTotalLimit
+    );
+
+    // Sub-agents also count against their parent's window and total limits
+    if let Some(parent_key) = agent_state.parent {
+        let parent = parent_state.ok_or(ErrorCode::InvalidParentAgent)?;
+        require_keys_eq!(parent.key(), parent_key, ErrorCode::InvalidParentAgent);
+        require!(!parent.frozen, ErrorCode::AgentFrozen);
+        require!(!parent.is_expired(clock), ErrorCode::AgentExpired);
+
+        parent.record_window_spend(clock.unix_timestamp, charged)?;
+        parent.total_spent = parent.total_spent
+            .checked_add(charged)
             .ok_or(ErrorCode::Overflow)?;
-        if remaining_balance > 0 {
-            invoke_signed(
-                &system_instruction::transfer(
-                    vault.key,
-                    ctx.accounts.destination.key,
-                    remaining_balance,
-                ),
-                &[
-                    vault.to_account_info(),
-                    ctx.accounts.destination.to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
-                ],
-                signer_seeds,
-            )?;
+        if parent.total_limit > 0 {
+            require!(
+                parent.total_spent <= parent.total_limit,
+                ErrorCode::ExceedsTotalLimit
+            );
         }
+    }
 
-        // cloaked_agent_state account is closed by Anchor's close constraint
-        Ok(())
+    Ok(())
+}
+
+/// Circuit breaker: halt further spends once the vault drops below the threshold
+fn apply_low_balance_freeze(agent_state: &mut Account<CloakedAgentState>, vault_balance: u64, clock: &Clock) {
+    if agent_state.conditional_freeze_threshold > 0
+        && vault_balance < agent_state.conditional_freeze_threshold
+    {
+        agent_state.frozen = true;
+        agent_state.freeze_reason = AUTO_FREEZE_LOW_BALANCE;
+
+        emit!(AutoFreezeEvent {
+            agent: agent_state.key(),
+            reason: AUTO_FREEZE_LOW_BALANCE,
+            vault_balance,
+            threshold: agent_state.conditional_freeze_threshold,
+            timestamp: clock.unix_timestamp,
+        });
     }
+}
 
-    /// Withdraw with ZK proof (private mode, bypasses constraints)
-    pub fn withdraw_private(
-        ctx: Context<WithdrawPrivate>,
-        proof_bytes: Vec<u8>,
-        witness_bytes: Vec<u8>,
-        amount: u64,
-    ) -> Result<()> {
-        let agent_state = &ctx.accounts.cloaked_agent_state;
-        require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+/// Enforce agent-level constraints, then pay destination, commission and fee payer
+fn process_spend(accounts: SpendAccounts, amount: u64) -> Result<()> {
+    let clock = Clock::get()?;
+    let agent_state = accounts.cloaked_agent_state;
 
-        // Verify ZK proof via CPI
-        verify_zk_proof(
-            &ctx.accounts.zk_verifier,
-            &proof_bytes,
-            &witness_bytes,
-            &agent_state.owner_commitment,
-        )?;
+    check_spend_allowed(agent_state, amount, &clock)?;
+    check_two_fa(agent_state, amount, accounts.second_factor)?;
 
-        // Total required = amount + fee
-        let total_required = amount.checked_add(PRIVATE_OPERATION_FEE).ok_or(ErrorCode::Overflow)?;
+    // Destination whitelist (enforced once any address or program entry exists)
+    if agent_state.whitelist_entry_count > 0 || agent_state.program_whitelist_entry_count > 0 {
         require!(
-            ctx.accounts.vault.lamports() >= total_required,
-            ErrorCode::InsufficientBalance
+            accounts.whitelist_entry.is_some() || accounts.program_whitelisted,
+            ErrorCode::DestinationNotWhitelisted
         );
+    }
 
-        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
-        let vault_bump = ctx.bumps.vault;
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"vault",
-            agent_state_key.as_ref(),
-            &[vault_bump],
-        ]];
+    // Delegate commission counts against window and total limits
+    let commission = agent_state.delegate_commission(amount)?;
+    let charged = amount.checked_add(commission).ok_or(ErrorCode::Overflow)?;
 
-        // Transfer fee to fee_recipient (relayer reimbursement)
-        invoke_signed(
-            &system_instruction::transfer(
-                ctx.accounts.vault.key,
-                ctx.accounts.fee_recipient.key,
-                PRIVATE_OPERATION_FEE,
-            ),
-            &[
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.fee_recipient.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+    record_spend_limits(agent_state, accounts.parent_state, charged, &clock)?;
+
+    // Total required: amount + commission + fee reimbursement
+    let total_required = charged.checked_add(accounts.fee_reimbursement).ok_or(ErrorCode::Overflow)?;
+
+    require!(
+        agent_state.spendable_balance(accounts.vault.lamports()) >= total_required,
+        ErrorCode::InsufficientBalance
+    );
+    check_delegate_reserve(agent_state, accounts.vault.lamports(), total_required)?;
+
+    // Update tracking before transfer
+    agent_state.total_spent = agent_state.total_spent
+        .checked_add(charged)
+        .ok_or(ErrorCode::Overflow)?;
+    if let Some(stats) = accounts.global_stats {
+        stats.record_spent(charged)?;
+    }
+    agent_state.spend_nonce = agent_state.spend_nonce
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    let commission_recipient = agent_state.delegate_commission_recipient;
+
+    // Get key after we're done with mutable borrow for state updates
+    let agent_state_key = agent_state.key();
+    let vault_bump = agent_state.vault_bump;
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"vault",
+        agent_state_key.as_ref(),
+        &[vault_bump],
+    ]];
+
+    // Transfer from vault to destination
+    invoke_signed(
+        &system_instruction::transfer(
+            accounts.vault.key,
+            accounts.destination.key,
+            amount,
+        ),
+        &[
+            accounts.vault.to_account_info(),
+            accounts.destination.to_account_info(),
+            accounts.system_program.to_account_info(),
+        ],
+        signer_seeds,
+    )?;
+
+    // Pay delegate commission (service fee) if configured
+    if commission > 0 {
+        let expected_recipient = commission_recipient.ok_or(ErrorCode::InvalidCommissionRecipient)?;
+        let recipient = accounts
+            .commission_recipient
+            .ok_or(ErrorCode::InvalidCommissionRecipient)?;
+        require_keys_eq!(
+            recipient.key(),
+            expected_recipient,
+            ErrorCode::InvalidCommissionRecipient
+        );
 
-        // Transfer requested amount to destination
         invoke_signed(
             &system_instruction::transfer(
-                ctx.accounts.vault.key,
-                ctx.accounts.destination.key,
-                amount,
+                accounts.vault.key,
+                recipient.key,
+                commission,
             ),
             &[
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.destination.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
+                accounts.vault.to_account_info(),
+                recipient.to_account_info(),
+                accounts.system_program.to_account_info(),
             ],
             signer_seeds,
         )?;
-
-        Ok(())
     }
+
+    // Reimburse fee payer for transaction fee
+    invoke_signed(
+        &system_instruction::transfer(
+            accounts.vault.key,
+            accounts.fee_payer.key,
+            accounts.fee_reimbursement,
+        ),
+        &[
+            accounts.vault.to_account_info(),
+            accounts.fee_payer.to_account_info(),
+            accounts.system_program.to_account_info(),
+        ],
+        signer_seeds,
+    )?;
+
+    let vault_balance_after = accounts.vault.lamports();
+    let vault_balance_before =
+        agent_state.update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+    emit!(SpentEvent {
+        agent: agent_state_key,
+        destination: accounts.destination.key(),
+        amount,
+        commission,
+        spend_nonce: agent_state.spend_nonce,
+        vault_balance_before,
+        vault_balance_after,
+        timestamp: clock.unix_timestamp,
+    });
+
+    apply_low_balance_freeze(agent_state, vault_balance_after, &clock);
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct CreateCloakedAgent<'info> {
-    #[account(
-        init,
+#[program]
+pub mod cloaked {
+    use super::*;
+
+    /// Create a new Cloaked Agent with constraints (standard mode)
+    /// window_seconds = None keeps the daily window (DEFAULT_WINDOW_SECONDS)
+#[allow(clippy::too_many_arguments)]
+    pub fn create_cloaked_agent(
+        mut ctx: Context<CreateCloakedAgent>,
+        creation_nonce: u64,
+        max_per_tx: u64,
+        window_limit: u64,
+        total_limit: u64,
+        expires_at: i64,
+        max_vault_balance: u64,
+        withdraw_destination_lock: Option<Pubkey>,
+        emergency_rescue_address: Option<Pubkey>,
+        rescue_delay: i64,
+        window_seconds: Option<u64>,
+        auto_close_on_zero: bool,
+    ) -> Result<()> {
+        // Programs cannot sign; use create_cloaked_agent_with_program_delegate instead
+        require!(!ctx.accounts.delegate.executable, ErrorCode::DelegateIsProgram);
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        let clock = Clock::get()?;
+        validate_expiration(expires_at, &clock)?;
+        if emergency_rescue_address.is_some() {
+            require!(rescue_delay >= MIN_RESCUE_DELAY, ErrorCode::InvalidRescueConfig);
+        }
+
+        agent_state.owner = Some(ctx.accounts.owner.key());
+        agent_state.owner_commitment = [0; 32]; // Standard mode: no commitment
+        agent_state.delegate = ctx.accounts.delegate.key();
+        agent_state.max_per_tx = max_per_tx;
+        agent_state.total_limit = total_limit;
+        agent_state.expires_at = expires_at;
+        agent_state.max_vault_balance = max_vault_balance;
+        agent_state.withdraw_destination_lock = withdraw_destination_lock;
+        agent_state.emergency_rescue_address = emergency_rescue_address;
+        agent_state.auto_close_on_zero = auto_close_on_zero;
+        if emergency_rescue_address.is_some() {
+            agent_state.rescue_delay = rescue_delay;
+            agent_state.rescue_available_at = clock.unix_timestamp
+                .checked_add(rescue_delay)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+        agent_state.frozen = false;
+        agent_state.total_spent = 0;
+        agent_state.spending_window = SpendingWindow {
+            window_seconds: window_seconds.unwrap_or(DEFAULT_WINDOW_SECONDS),
+            limit: window_limit,
+            spent: 0,
+            window_start: clock.unix_timestamp,
+        };
+        agent_state.bump = ctx.bumps.cloaked_agent_state;
+        agent_state.vault_bump = ctx.bumps.vault;
+        agent_state.created_at = clock.unix_timestamp;
+        agent_state.state_version = STATE_VERSION;
+        agent_state.created_by = ctx.accounts.payer.key();
+        agent_state.creation_nonce = creation_nonce;
+        agent_state.instruction_whitelist = u16::MAX;
+
+        agent_state.validate_limits()?;
+
+        let accounts = &mut ctx.accounts;
+        attach_parent(
+            &mut accounts.cloaked_agent_state,
+            accounts.parent_state.as_mut(),
+            accounts.parent_delegate.as_ref(),
+        )?;
+        if let Some(stats) = accounts.global_stats.as_mut() {
+            stats.record_agent_created()?;
+        }
+        if let Some(registry) = accounts.delegate_registry.as_mut() {
+            registry.register(accounts.cloaked_agent_state.key())?;
+        }
+
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        emit!(AgentCreatedEvent {
+            agent: agent_state.key(),
+            owner: agent_state.owner,
+            delegate: agent_state.delegate,
+            created_by: agent_state.created_by,
+            parent: agent_state.parent,
+            max_per_tx: agent_state.max_per_tx,
+            window_limit: agent_state.spending_window.limit,
+            total_limit: agent_state.total_limit,
+            expires_at: agent_state.expires_at,
+            constraints_locked: agent_state.constraints_locked,
+            owner_notes: agent_state.owner_notes,
+            delegate_notes: agent_state.delegate_notes,
+            timestamp: agent_state.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Create a new Cloaked Agent in private mode (no wallet linked on-chain)
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_cloaked_agent_private(
+        mut ctx: Context<CreateCloakedAgentPrivate>,
+        creation_nonce: u64,
+        owner_commitment: [u8; 32],
+        max_per_tx: u64,
+        window_limit: u64,
+        total_limit: u64,
+        expires_at: i64,
+        max_vault_balance: u64,
+        window_seconds: Option<u64>,
+        recovery_commitment: Option<[u8; 32]>,
+        require_registered_relayer: bool,
+        commitment_scheme: CommitmentScheme,
+        commitment_version: u8,
+    ) -> Result<()> {
+        require!(owner_commitment != [0u8; 32], ErrorCode::InvalidCommitment);
+        require!(
+            recovery_commitment.is_none_or(|c| c != [0u8; 32] && c != owner_commitment),
+            ErrorCode::InvalidCommitment
+        );
+        require!(!ctx.accounts.delegate.executable, ErrorCode::DelegateIsProgram);
+
+        let clock = Clock::get()?;
+        if commitment_scheme == CommitmentScheme::Zk {
+            check_commitment_version(
+                ctx.accounts.verifier_registry.as_deref(),
+                commitment_version,
+                clock.unix_timestamp,
+            )?;
+        }
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        validate_expiration(expires_at, &clock)?;
+
+        agent_state.owner = None;
+        agent_state.owner_commitment = owner_commitment;
+        agent_state.recovery_commitment = recovery_commitment.unwrap_or_default();
+        agent_state.require_registered_relayer = require_registered_relayer;
+        agent_state.commitment_scheme = commitment_scheme;
+        agent_state.commitment_version = commitment_version;
+        agent_state.proof_format_version = CURRENT_PROOF_FORMAT_VERSION;
+        agent_state.delegate = ctx.accounts.delegate.key();
+        agent_state.max_per_tx = max_per_tx;
+        agent_state.total_limit = total_limit;
+        agent_state.expires_at = expires_at;
+        agent_state.max_vault_balance = max_vault_balance;
+        agent_state.frozen = false;
+        agent_state.total_spent = 0;
+        agent_state.spending_window = SpendingWindow {
+            window_seconds: window_seconds.unwrap_or(DEFAULT_WINDOW_SECONDS),
+            limit: window_limit,
+            spent: 0,
+            window_start: clock.unix_timestamp,
+        };
+        agent_state.bump = ctx.bumps.cloaked_agent_state;
+        agent_state.vault_bump = ctx.bumps.vault;
+        agent_state.created_at = clock.unix_timestamp;
+        agent_state.state_version = STATE_VERSION;
+        agent_state.created_by = ctx.accounts.payer.key();
+        agent_state.creation_nonce = creation_nonce;
+        agent_state.instruction_whitelist = u16::MAX;
+
+        agent_state.validate_limits()?;
+
+        let accounts = &mut ctx.accounts;
+        attach_parent(
+            &mut accounts.cloaked_agent_state,
+            accounts.parent_state.as_mut(),
+            accounts.parent_delegate.as_ref(),
+        )?;
+        if let Some(stats) = accounts.global_stats.as_mut() {
+            stats.record_agent_created()?;
+        }
+        if let Some(registry) = accounts.delegate_registry.as_mut() {
+            registry.register(accounts.cloaked_agent_state.key())?;
+        }
+
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        emit!(AgentCreatedEvent {
+            agent: agent_state.key(),
+            owner: agent_state.owner,
+            delegate: agent_state.delegate,
+            created_by: agent_state.created_by,
+            parent: agent_state.parent,
+            max_per_tx: agent_state.max_per_tx,
+            window_limit: agent_state.spending_window.limit,
+            total_limit: agent_state.total_limit,
+            expires_at: agent_state.expires_at,
+            constraints_locked: agent_state.constraints_locked,
+            owner_notes: agent_state.owner_notes,
+            delegate_notes: agent_state.delegate_notes,
+            timestamp: agent_state.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Create a new Cloaked Agent owned by an m-of-n set of wallets
+    /// Owner-gated instructions then need `threshold` of `owners` to sign
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_cloaked_agent_multisig(
+        mut ctx: Context<CreateCloakedAgentMultisig>,
+        creation_nonce: u64,
+        owners: Vec<Pubkey>,
+        threshold: u8,
+        max_per_tx: u64,
+        window_limit: u64,
+        total_limit: u64,
+        expires_at: i64,
+        max_vault_balance: u64,
+        window_seconds: Option<u64>,
+    ) -> Result<()> {
+        validate_multisig(&owners, threshold)?;
+        require!(!ctx.accounts.delegate.executable, ErrorCode::DelegateIsProgram);
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        let clock = Clock::get()?;
+        validate_expiration(expires_at, &clock)?;
+
+        agent_state.owner = None;
+        agent_state.owner_commitment = [0; 32]; // Multisig mode: no commitment
+        agent_state.set_multisig_owners(&owners, threshold);
+        agent_state.delegate = ctx.accounts.delegate.key();
+        agent_state.max_per_tx = max_per_tx;
+        agent_state.total_limit = total_limit;
+        agent_state.expires_at = expires_at;
+        agent_state.max_vault_balance = max_vault_balance;
+        agent_state.frozen = false;
+        agent_state.total_spent = 0;
+        agent_state.spending_window = SpendingWindow {
+            window_seconds: window_seconds.unwrap_or(DEFAULT_WINDOW_SECONDS),
+            limit: window_limit,
+            spent: 0,
+            window_start: clock.unix_timestamp,
+        };
+        agent_state.bump = ctx.bumps.cloaked_agent_state;
+        agent_state.vault_bump = ctx.bumps.vault;
+        agent_state.created_at = clock.unix_timestamp;
+        agent_state.state_version = STATE_VERSION;
+        agent_state.created_by = ctx.accounts.payer.key();
+        agent_state.creation_nonce = creation_nonce;
+        agent_state.instruction_whitelist = u16::MAX;
+
+        agent_state.validate_limits()?;
+
+        let accounts = &mut ctx.accounts;
+        attach_parent(
+            &mut accounts.cloaked_agent_state,
+            accounts.parent_state.as_mut(),
+            accounts.parent_delegate.as_ref(),
+        )?;
+        if let Some(stats) = accounts.global_stats.as_mut() {
+            stats.record_agent_created()?;
+        }
+        if let Some(registry) = accounts.delegate_registry.as_mut() {
+            registry.register(accounts.cloaked_agent_state.key())?;
+        }
+
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        emit!(AgentCreatedEvent {
+            agent: agent_state.key(),
+            owner: agent_state.owner,
+            delegate: agent_state.delegate,
+            created_by: agent_state.created_by,
+            parent: agent_state.parent,
+            max_per_tx: agent_state.max_per_tx,
+            window_limit: agent_state.spending_window.limit,
+            total_limit: agent_state.total_limit,
+            expires_at: agent_state.expires_at,
+            constraints_locked: agent_state.constraints_locked,
+            owner_notes: agent_state.owner_notes,
+            delegate_notes: agent_state.delegate_notes,
+            timestamp: agent_state.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Create a standard-mode agent whose delegate is a program
+    /// The program spends via CPI to spend_as_program_delegate, signing with its
+    /// [b"delegate_authority", agent] PDA
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_cloaked_agent_with_program_delegate(
+        ctx: Context<CreateCloakedAgentWithProgramDelegate>,
+        creation_nonce: u64,
+        delegate_program: Pubkey,
+        max_per_tx: u64,
+        window_limit: u64,
+        total_limit: u64,
+        expires_at: i64,
+        max_vault_balance: u64,
+        withdraw_destination_lock: Option<Pubkey>,
+        emergency_rescue_address: Option<Pubkey>,
+        rescue_delay: i64,
+        window_seconds: Option<u64>,
+    ) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        let clock = Clock::get()?;
+        validate_expiration(expires_at, &clock)?;
+        if emergency_rescue_address.is_some() {
+            require!(rescue_delay >= MIN_RESCUE_DELAY, ErrorCode::InvalidRescueConfig);
+        }
+
+        agent_state.owner = Some(ctx.accounts.owner.key());
+        agent_state.owner_commitment = [0; 32]; // Standard mode: no commitment
+        agent_state.delegate = delegate_program;
+        agent_state.program_delegate = true;
+        agent_state.max_per_tx = max_per_tx;
+        agent_state.total_limit = total_limit;
+        agent_state.expires_at = expires_at;
+        agent_state.max_vault_balance = max_vault_balance;
+        agent_state.withdraw_destination_lock = withdraw_destination_lock;
+        agent_state.emergency_rescue_address = emergency_rescue_address;
+        if emergency_rescue_address.is_some() {
+            agent_state.rescue_delay = rescue_delay;
+            agent_state.rescue_available_at = clock.unix_timestamp
+                .checked_add(rescue_delay)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+        agent_state.frozen = false;
+        agent_state.total_spent = 0;
+        agent_state.spending_window = SpendingWindow {
+            window_seconds: window_seconds.unwrap_or(DEFAULT_WINDOW_SECONDS),
+            limit: window_limit,
+            spent: 0,
+            window_start: clock.unix_timestamp,
+        };
+        agent_state.bump = ctx.bumps.cloaked_agent_state;
+        agent_state.vault_bump = ctx.bumps.vault;
+        agent_state.created_at = clock.unix_timestamp;
+        agent_state.state_version = STATE_VERSION;
+        agent_state.created_by = ctx.accounts.payer.key();
+        agent_state.creation_nonce = creation_nonce;
+        agent_state.instruction_whitelist = u16::MAX;
+
+        agent_state.validate_limits()?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_agent_created()?;
+        }
+
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        emit!(AgentCreatedEvent {
+            agent: agent_state.key(),
+            owner: agent_state.owner,
+            delegate: agent_state.delegate,
+            created_by: agent_state.created_by,
+            parent: agent_state.parent,
+            max_per_tx: agent_state.max_per_tx,
+            window_limit: agent_state.spending_window.limit,
+            total_limit: agent_state.total_limit,
+            expires_at: agent_state.expires_at,
+            constraints_locked: agent_state.constraints_locked,
+            owner_notes: agent_state.owner_notes,
+            delegate_notes: agent_state.delegate_notes,
+            timestamp: agent_state.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Create the protocol-wide GlobalStats PDA (program upgrade authority only)
+    pub fn initialize_global_stats(ctx: Context<InitializeGlobalStats>) -> Result<()> {
+        let stats = &mut ctx.accounts.global_stats;
+        stats.bump = ctx.bumps.global_stats;
+        Ok(())
+    }
+
+    /// Create the ProgramConfig PDA (program upgrade authority only)
+    /// The signer becomes the config admin
+    pub fn initialize_program_config(
+        ctx: Context<InitializeProgramConfig>,
+        verifier_program: Pubkey,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.program_config;
+        config.admin = ctx.accounts.admin.key();
+        config.verifier_program = verifier_program;
+        config.bump = ctx.bumps.program_config;
+        config.private_operation_fee = PRIVATE_OPERATION_FEE;
+        config.spend_fee_reimbursement = SPEND_FEE_REIMBURSEMENT;
+        config.self_relay_fee_bps = SELF_RELAY_FEE_BPS;
+        config.pending_admin = None;
+        config.paused = false;
+        Ok(())
+    }
+
+    /// Propose a new config admin (config admin only)
+    /// The current admin stays in charge until `new_admin` calls accept_admin; proposing
+    /// again replaces the pending admin
+    pub fn propose_admin(ctx: Context<UpdateProgramConfig>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.program_config;
+        config.pending_admin = Some(new_admin);
+
+        emit!(AdminProposedEvent {
+            current_admin: config.admin,
+            pending_admin: new_admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Take over as config admin (pending admin only)
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.program_config;
+        let previous_admin = config.admin;
+        config.admin = ctx.accounts.new_admin.key();
+        config.pending_admin = None;
+
+        emit!(AdminTransferredEvent {
+            previous_admin,
+            new_admin: config.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Propose a new ZK verifier program (config admin only)
+    /// Takes effect via apply_verifier after VERIFIER_CHANGE_TIMELOCK; proposing again
+    /// replaces the pending change
+    pub fn set_verifier(ctx: Context<UpdateProgramConfig>, verifier_program: Pubkey) -> Result<()> {
+        let clock = Clock::get()?;
+        let config = &mut ctx.accounts.program_config;
+        config.pending_verifier = Some(verifier_program);
+        config.pending_verifier_eta = clock.unix_timestamp
+            .checked_add(VERIFIER_CHANGE_TIMELOCK)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(VerifierChangeProposedEvent {
+            current_verifier: config.verifier_program,
+            pending_verifier: verifier_program,
+            eta: config.pending_verifier_eta,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Apply a pending verifier change once its timelock has elapsed (config admin only)
+    /// The replaced verifier stays accepted for VERIFIER_MIGRATION_WINDOW
+    pub fn apply_verifier(ctx: Context<UpdateProgramConfig>) -> Result<()> {
+        let clock = Clock::get()?;
+        let config = &mut ctx.accounts.program_config;
+        let new_verifier = config.pending_verifier.ok_or(ErrorCode::VerifierChangeTimelocked)?;
+        require!(
+            clock.unix_timestamp >= config.pending_verifier_eta,
+            ErrorCode::VerifierChangeTimelocked
+        );
+
+        config.previous_verifier = Some(config.verifier_program);
+        config.previous_verifier_valid_until = clock.unix_timestamp
+            .checked_add(VERIFIER_MIGRATION_WINDOW)
+            .ok_or(ErrorCode::Overflow)?;
+        config.verifier_program = new_verifier;
+        config.pending_verifier = None;
+        config.pending_verifier_eta = 0;
+
+        Ok(())
+    }
+
+    /// Stop every delegate spend path and fee-charging private instruction for all agents
+    /// (config admin only). Owner withdraw, freeze and close keep working so funds can still leave
+    pub fn pause_program(ctx: Context<UpdateProgramConfig>) -> Result<()> {
+        ctx.accounts.program_config.paused = true;
+        emit!(ProgramPausedEvent {
+            paused: true,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Lift a pause_program (config admin only)
+    pub fn unpause_program(ctx: Context<UpdateProgramConfig>) -> Result<()> {
+        ctx.accounts.program_config.paused = false;
+        emit!(ProgramPausedEvent {
+            paused: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Set the private operation fee and spend fee reimbursement (config admin only)
+    /// Bounded by MAX_PRIVATE_OPERATION_FEE and MAX_SPEND_FEE_REIMBURSEMENT
+    pub fn set_fees(
+        ctx: Context<UpdateProgramConfig>,
+        private_operation_fee: u64,
+        spend_fee_reimbursement: u64,
+    ) -> Result<()> {
+        require!(
+            private_operation_fee <= MAX_PRIVATE_OPERATION_FEE
+                && spend_fee_reimbursement <= MAX_SPEND_FEE_REIMBURSEMENT,
+            ErrorCode::FeeTooHigh
+        );
+
+        let config = &mut ctx.accounts.program_config;
+        config.private_operation_fee = private_operation_fee;
+        config.spend_fee_reimbursement = spend_fee_reimbursement;
+
+        emit!(FeesUpdatedEvent {
+            private_operation_fee,
+            spend_fee_reimbursement,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set the share of the private operation fee charged to self-relayed operations
+    /// (config admin only): 0 waives it, 5_000 halves it
+    pub fn set_self_relay_fee(ctx: Context<UpdateProgramConfig>, self_relay_fee_bps: u16) -> Result<()> {
+        require!(self_relay_fee_bps as u64 <= BPS_DENOMINATOR, ErrorCode::FeeTooHigh);
+        ctx.accounts.program_config.self_relay_fee_bps = self_relay_fee_bps;
+        Ok(())
+    }
+
+    /// Create the VerifierRegistry PDA (config admin only)
+    pub fn initialize_verifier_registry(ctx: Context<InitializeVerifierRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.verifier_registry;
+        registry.entries = Vec::new();
+        registry.bump = ctx.bumps.verifier_registry;
+        Ok(())
+    }
+
+    /// Accept proofs for `witness_version` from `verifier_program` (config admin only)
+    /// `commitment_version` is the commitment scheme the verifier's circuit checks
+    pub fn add_verifier_entry(
+        ctx: Context<UpdateVerifierRegistry>,
+        verifier_program: Pubkey,
+        witness_version: u8,
+        proof_size: u32,
+        commitment_version: u8,
+    ) -> Result<()> {
+        require!(witness_version >= WITNESS_LAYOUT_VERSION, ErrorCode::UnsupportedWitnessVersion);
+        require!(commitment_version != 0, ErrorCode::UnknownCommitmentVersion);
+        ctx.accounts.verifier_registry.add(VerifierEntry {
+            verifier_program,
+            witness_version,
+            proof_size,
+            removable_at: 0,
+            commitment_version,
+        })
+    }
+
+    /// Start the removal timelock for a registry entry (config admin only)
+    /// The entry keeps verifying proofs for VERIFIER_REMOVAL_TIMELOCK
+    pub fn schedule_verifier_removal(ctx: Context<UpdateVerifierRegistry>, witness_version: u8) -> Result<()> {
+        let clock = Clock::get()?;
+        let entry = ctx
+            .accounts
+            .verifier_registry
+            .entries
+            .iter_mut()
+            .find(|entry| entry.witness_version == witness_version)
+            .ok_or(ErrorCode::UnknownVerifierVersion)?;
+        entry.removable_at = clock.unix_timestamp
+            .checked_add(VERIFIER_REMOVAL_TIMELOCK)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(VerifierRemovalScheduledEvent {
+            verifier_program: entry.verifier_program,
+            witness_version,
+            removable_at: entry.removable_at,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Drop a registry entry whose removal timelock has elapsed (config admin only)
+    pub fn remove_verifier_entry(ctx: Context<UpdateVerifierRegistry>, witness_version: u8) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let entries = &mut ctx.accounts.verifier_registry.entries;
+        let index = entries
+            .iter()
+            .position(|entry| entry.witness_version == witness_version)
+            .ok_or(ErrorCode::UnknownVerifierVersion)?;
+        require!(
+            entries[index].removable_at != 0 && now >= entries[index].removable_at,
+            ErrorCode::VerifierChangeTimelocked
+        );
+        entries.remove(index);
+        Ok(())
+    }
+
+    /// Create the global list of relayers that agents with require_registered_relayer
+    /// reimburse (config admin only)
+    pub fn initialize_relayer_registry(ctx: Context<InitializeRelayerRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.relayer_registry;
+        registry.relayers = Vec::new();
+        registry.bump = ctx.bumps.relayer_registry;
+        Ok(())
+    }
+
+    /// Approve a relayer as fee_recipient (config admin only)
+    pub fn add_relayer(ctx: Context<UpdateRelayerRegistry>, relayer: Pubkey) -> Result<()> {
+        ctx.accounts.relayer_registry.add(relayer)
+    }
+
+    /// Revoke a relayer's approval (config admin only)
+    pub fn remove_relayer(ctx: Context<UpdateRelayerRegistry>, relayer: Pubkey) -> Result<()> {
+        ctx.accounts.relayer_registry.remove(&relayer);
+        Ok(())
+    }
+
+    /// Create the relayer's RelayerStats PDA, credited by private operations that pass it
+    /// (relayer only, pays the rent)
+    pub fn register_relayer(ctx: Context<RegisterRelayer>) -> Result<()> {
+        let stats = &mut ctx.accounts.relayer_stats;
+        stats.relayer = ctx.accounts.relayer.key();
+        stats.total_fees_earned = 0;
+        stats.total_ops_relayed = 0;
+        stats.registered_at = Clock::get()?.unix_timestamp;
+        stats.bump = ctx.bumps.relayer_stats;
+        Ok(())
+    }
+
+    /// Close the relayer's RelayerStats PDA and reclaim its rent (relayer only)
+    pub fn close_relayer_registration(_ctx: Context<CloseRelayerRegistration>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Set the verifier for claim_note_private proofs (config admin only)
+    pub fn set_note_verifier(ctx: Context<UpdateProgramConfig>, note_verifier: Pubkey) -> Result<()> {
+        ctx.accounts.program_config.note_verifier = note_verifier;
+        Ok(())
+    }
+
+    /// Create the NoteSet PDA for `index` (anyone, payer funds the rent)
+    /// Sets fill up; a new one is started when the last is full
+    pub fn initialize_note_set(ctx: Context<InitializeNoteSet>, index: u32) -> Result<()> {
+        let note_set = &mut ctx.accounts.note_set;
+        note_set.index = index;
+        note_set.notes = Vec::new();
+        note_set.filled_subtrees = [[0; 32]; NOTE_TREE_DEPTH];
+        note_set.roots = [[0; 32]; NOTE_ROOT_HISTORY];
+        note_set.roots[0] = empty_note_root();
+        note_set.current_root_index = 0;
+        note_set.bump = ctx.bumps.note_set;
+        Ok(())
+    }
+
+    /// Lock NOTE_DENOMINATION in a NoteSet under `commitment`, a hash of a secret note
+    /// preimage. Whoever knows the preimage can later credit any private agent's vault
+    /// with claim_note_private, without revealing which deposit it was
+    /// circuits/note_claim fixes the scheme: commitment = action_data_hash(nullifier_secret
+    /// (32) || secret (32)), note_nullifier = action_data_hash(nullifier_secret)
+    pub fn deposit_note(ctx: Context<DepositNote>, commitment: [u8; 32]) -> Result<()> {
+        invoke(
+            &system_instruction::transfer(
+                ctx.accounts.depositor.key,
+                &ctx.accounts.note_set.key(),
+                NOTE_DENOMINATION,
+            ),
+            &[
+                ctx.accounts.depositor.to_account_info(),
+                ctx.accounts.note_set.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+
+        let note_set = &mut ctx.accounts.note_set;
+        let leaf_index = note_set.insert(commitment)?;
+
+        emit!(NoteDepositedEvent {
+            note_set: note_set.key(),
+            leaf_index,
+            commitment,
+            root: note_set.current_root(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Credit NOTE_DENOMINATION from a NoteSet to a private agent's vault
+    /// The proof (checked by ProgramConfig's note_verifier) shows knowledge of the
+    /// preimage of a note under `root` and derives `note_nullifier` from it, which is
+    /// recorded so the note cannot be claimed twice. The payer relays the transaction
+    /// and funds the NoteNullifier; fee_recipient is reimbursed the private operation
+    /// fee from the vault like any other private instruction
+    pub fn claim_note_private(
+        ctx: Context<ClaimNotePrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        root: [u8; 32],
+        note_nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_key = ctx.accounts.cloaked_agent_state.key();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+        agent_state.record_private_op(clock.unix_timestamp)?;
+        require!(ctx.accounts.note_set.is_known_root(&root), ErrorCode::UnknownNoteRoot);
+
+        require!(proof_bytes.len() == PROOF_SIZE, ErrorCode::InvalidProofSize);
+        check_witness(
+            &witness_bytes,
+            WITNESS_LAYOUT_VERSION,
+            &root,
+            0,
+            ACTION_CLAIM_NOTE,
+            &claim_note_action_data(&agent_key, &note_nullifier),
+        )?;
+        invoke_verifier(ctx.accounts.note_verifier.key, &proof_bytes, &witness_bytes)?;
+
+        let nullifier = &mut ctx.accounts.note_nullifier_account;
+        nullifier.note_set = ctx.accounts.note_set.key();
+        nullifier.nullifier = note_nullifier;
+        nullifier.claimed_at = clock.unix_timestamp;
+        nullifier.bump = ctx.bumps.note_nullifier_account;
+
+        // A claim is a deposit into the vault
+        require!(!agent_state.deposits_locked, ErrorCode::DepositsLocked);
+        if agent_state.max_vault_balance > 0 {
+            require!(
+                ctx.accounts.vault.lamports().checked_add(NOTE_DENOMINATION).ok_or(ErrorCode::Overflow)?
+                    <= agent_state.max_vault_balance,
+                ErrorCode::ExceedsVaultCap
+            );
+        }
+        agent_state.total_deposited = agent_state.total_deposited
+            .checked_add(NOTE_DENOMINATION)
+            .ok_or(ErrorCode::Overflow)?;
+
+        // NoteSet is program-owned, so its lamports are moved directly
+        let note_set_info = ctx.accounts.note_set.to_account_info();
+        **note_set_info.try_borrow_mut_lamports()? = note_set_info
+            .lamports()
+            .checked_sub(NOTE_DENOMINATION)
+            .ok_or(ErrorCode::InsufficientBalance)?;
+        let vault_info = ctx.accounts.vault.to_account_info();
+        **vault_info.try_borrow_mut_lamports()? = vault_info
+            .lamports()
+            .checked_add(NOTE_DENOMINATION)
+            .ok_or(ErrorCode::Overflow)?;
+
+        check_relayer(
+            ctx.accounts.cloaked_agent_state.require_registered_relayer,
+            ctx.accounts.relayer_registry.as_deref(),
+            ctx.accounts.fee_recipient.key,
+        )?;
+        let operation_fee = private_fee_charged(
+            Some(&*ctx.accounts.program_config),
+            agent_key,
+            &ctx.accounts.fee_recipient,
+            ctx.accounts.relayer_stats.as_deref_mut(),
+        )?;
+        charge_private_operation_fee(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            &agent_key,
+            ctx.accounts.cloaked_agent_state.vault_bump,
+            operation_fee,
+        )?;
+
+        let vault_balance_after = ctx.accounts.vault.lamports();
+        ctx.accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(NoteClaimedEvent {
+            agent: agent_key,
+            note_set: ctx.accounts.note_set.key(),
+            note_nullifier,
+            amount: NOTE_DENOMINATION,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Allocate a ProofBuffer to stage a proof too large to send with the private
+    /// instruction itself. It can only be consumed by `action_id` on this agent
+    pub fn init_proof_buffer(
+        ctx: Context<InitProofBuffer>,
+        action_id: u8,
+        proof_len: u32,
+        witness_len: u32,
+    ) -> Result<()> {
+        require!(
+            (ACTION_FREEZE..=ACTION_WITHDRAW_ALL).contains(&action_id)
+                || (ACTION_MIGRATE_PROOF_VERSION..=ACTION_SET_DEPOSITS_LOCKED).contains(&action_id),
+            ErrorCode::ActionMismatch
+        );
+        let data_len = proof_len as usize + witness_len as usize;
+        require!(data_len <= ProofBuffer::MAX_DATA_LEN, ErrorCode::ProofBufferOverflow);
+
+        let buffer = &mut ctx.accounts.proof_buffer;
+        buffer.submitter = ctx.accounts.submitter.key();
+        buffer.agent = ctx.accounts.cloaked_agent_state.key();
+        buffer.action_id = action_id;
+        buffer.proof_len = proof_len;
+        buffer.data = vec![0; data_len];
+        buffer.bump = ctx.bumps.proof_buffer;
+        Ok(())
+    }
+
+    /// Write `data` into the buffer's proof || witness bytes at `offset` (submitter only)
+    pub fn write_proof_chunk(ctx: Context<WriteProofChunk>, offset: u32, data: Vec<u8>) -> Result<()> {
+        let buffer = &mut ctx.accounts.proof_buffer;
+        let start = offset as usize;
+        let end = start.checked_add(data.len()).ok_or(ErrorCode::Overflow)?;
+        require!(end <= buffer.data.len(), ErrorCode::ProofBufferOverflow);
+        buffer.data[start..end].copy_from_slice(&data);
+        Ok(())
+    }
+
+    /// Discard an unused ProofBuffer and reclaim its rent (submitter only)
+    /// Private instructions close the buffer themselves when they consume it
+    pub fn close_proof_buffer(_ctx: Context<CloseProofBuffer>) -> Result<()> {
+        // proof_buffer account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Close a Nullifier older than NULLIFIER_RETENTION, returning its rent to the vault
+    /// that paid it. Permissionless: private owners have no signing key, and the rent
+    /// can only go back to the agent's vault
+    pub fn close_nullifier(ctx: Context<CloseNullifier>) -> Result<()> {
+        let age = Clock::get()?
+            .unix_timestamp
+            .saturating_sub(ctx.accounts.nullifier.created_at);
+        require!(age >= NULLIFIER_RETENTION, ErrorCode::NullifierNotExpired);
+        // nullifier account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Create the registry listing the agents a delegate key may spend from
+    pub fn create_delegate_registry(ctx: Context<CreateDelegateRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.delegate_registry;
+        registry.delegate = ctx.accounts.delegate.key();
+        registry.agents = Vec::new();
+        registry.bump = ctx.bumps.delegate_registry;
+        Ok(())
+    }
+
+    /// List an existing agent in the delegate's registry (delegate only)
+    pub fn add_to_delegate_registry(ctx: Context<UpdateDelegateRegistry>, agent_key: Pubkey) -> Result<()> {
+        let agent_state = ctx
+            .accounts
+            .cloaked_agent_state
+            .as_ref()
+            .ok_or(ErrorCode::InvalidDelegateRegistry)?;
+        require_keys_eq!(agent_state.key(), agent_key, ErrorCode::InvalidDelegateRegistry);
+        require_keys_eq!(
+            agent_state.delegate,
+            ctx.accounts.delegate.key(),
+            ErrorCode::InvalidDelegateRegistry
+        );
+
+        ctx.accounts.delegate_registry.register(agent_key)
+    }
+
+    /// Drop an agent from the delegate's registry (delegate only), e.g. one closed
+    /// without the registry being passed
+    pub fn remove_from_delegate_registry(ctx: Context<UpdateDelegateRegistry>, agent_key: Pubkey) -> Result<()> {
+        ctx.accounts.delegate_registry.unregister(&agent_key);
+        Ok(())
+    }
+
+    /// Replace the owner set and threshold of a multisig agent
+    /// Needs approval from the current threshold of current owners
+    pub fn update_multisig(
+        ctx: Context<UpdateMultisig>,
+        owners: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        check_multisig_approval(
+            &ctx.accounts.cloaked_agent_state,
+            ctx.accounts.owner.key,
+            ctx.remaining_accounts,
+        )?;
+        validate_multisig(&owners, threshold)?;
+
+        ctx.accounts
+            .cloaked_agent_state
+            .set_multisig_owners(&owners, threshold);
+
+        Ok(())
+    }
+
+    /// Deposit SOL to agent vault (anyone can call)
+    /// Programs can deposit via CPI from a PDA: sign the CPI with invoke_signed using the
+    /// PDA's seeds and the runtime marks it as a signer, which satisfies `Signer`. The PDA
+    /// must be system-owned and hold no data (it is the source of a system transfer)
+    pub fn deposit(mut ctx: Context<Deposit>, amount: u64) -> Result<()> {
+        let accounts = &mut ctx.accounts;
+        process_deposit(
+            DepositAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                depositor: &accounts.depositor,
+                global_stats: accounts.global_stats.as_mut(),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )?;
+        Ok(())
+    }
+
+    /// Deposit SOL with a 32-byte payment reference (invoice ID, subscription, UUID)
+    /// Memo is informational only and surfaced via DepositMemoEvent
+    pub fn deposit_with_memo(mut ctx: Context<Deposit>, amount: u64, memo: [u8; 32]) -> Result<()> {
+        let clock = Clock::get()?;
+        let accounts = &mut ctx.accounts;
+        let vault_balance_before = process_deposit(
+            DepositAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                depositor: &accounts.depositor,
+                global_stats: accounts.global_stats.as_mut(),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )?;
+
+        emit!(DepositMemoEvent {
+            agent: ctx.accounts.cloaked_agent_state.key(),
+            depositor: ctx.accounts.depositor.key(),
+            amount,
+            memo,
+            vault_balance_before,
+            vault_balance_after: ctx.accounts.vault.lamports(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit from a program-controlled authority (PDA) that signs only through CPI
+    /// Same checks as deposit; the system transfer fails unless the caller signed for depositor
+    pub fn deposit_with_program_authority(
+        mut ctx: Context<DepositWithProgramAuthority>,
+        amount: u64,
+    ) -> Result<()> {
+        let accounts = &mut ctx.accounts;
+        process_deposit(
+            DepositAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                depositor: &accounts.depositor,
+                global_stats: accounts.global_stats.as_mut(),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )?;
+        Ok(())
+    }
+
+    /// Spend from vault to destination (delegate only, enforces constraints)
+    /// Fee payer fronts tx fee and is reimbursed from vault. Passing the destination's
+    /// DestinationStats PDA as the first remaining account records the payment there.
+    /// A ProgramWhitelistEntry for the destination's owner program may precede it and
+    /// stands in for an address whitelist entry. A BlacklistEntry for the destination
+    /// passed first fails the spend.
+    /// With post_spend_hook set, remaining_accounts must also hold the hook program,
+    /// followed by the accounts its instruction takes; a failing hook reverts the spend
+    pub fn spend<'info>(
+        mut ctx: Context<'_, '_, 'info, 'info, Spend<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.cloaked_agent_state.allows_instruction(INSTRUCTION_BIT_SPEND),
+            ErrorCode::InstructionNotAllowed
+        );
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+
+        // [blacklist_entry?] [program_wl_entry?] [dest_stats?] [hook_program, hook accounts...]
+        let remaining_accounts = check_blacklist_entry(
+            ctx.remaining_accounts,
+            ctx.accounts.cloaked_agent_state.key(),
+            ctx.accounts.destination.key,
+        )?;
+        let (program_whitelisted, remaining_accounts) = take_program_whitelist_entry(
+            remaining_accounts,
+            ctx.accounts.cloaked_agent_state.key(),
+            ctx.accounts.destination.owner,
+        )?;
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )?;
+
+        let (stats_accounts, hook_accounts) = match ctx.accounts.cloaked_agent_state.post_spend_hook {
+            Some(hook) => {
+                let hook_index = remaining_accounts
+                    .iter()
+                    .position(|info| info.key() == hook)
+                    .ok_or(ErrorCode::InvalidSpendHook)?;
+                remaining_accounts.split_at(hook_index)
+            }
+            None => (remaining_accounts, &[][..]),
+        };
+
+        if let Some(stats_info) = stats_accounts.first() {
+            record_destination_stats(
+                stats_info,
+                ctx.accounts.cloaked_agent_state.key(),
+                ctx.accounts.destination.key(),
+                &ctx.accounts.fee_payer,
+                &ctx.accounts.system_program,
+                amount,
+                Clock::get()?.unix_timestamp,
+            )?;
+        }
+
+        if let Some((hook_program, hook_accounts)) = hook_accounts.split_first() {
+            notify_spend_hook(
+                hook_program,
+                hook_accounts,
+                &SpendNotification {
+                    agent: ctx.accounts.cloaked_agent_state.key(),
+                    amount,
+                    destination: ctx.accounts.destination.key(),
+                    timestamp: Clock::get()?.unix_timestamp,
+                    spend_nonce: ctx.accounts.cloaked_agent_state.spend_nonce,
+                },
+            )?;
+        }
+
+        // Single-use agents close once a spend empties the vault; the state rent is
+        // paid out to the destination along with the spend. An agent close_cloaked_agent
+        // would refuse (stake, timelocks, entry PDAs, children) stays open instead
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        if agent_state.auto_close_on_zero
+            && ctx.accounts.vault.lamports() == 0
+            && agent_state.child_count == 0
+            && check_closable(agent_state, agent_state.whitelist_entry_count).is_ok()
+        {
+            let accounts = &mut ctx.accounts;
+            detach_parent(&accounts.cloaked_agent_state, accounts.parent_state.as_mut())?;
+            if let Some(stats) = accounts.global_stats.as_mut() {
+                stats.record_agent_closed()?;
+            }
+            let agent = accounts.cloaked_agent_state.key();
+            if let Some(registry) = accounts.delegate_registry.as_mut() {
+                registry.unregister(&agent);
+            }
+            accounts.cloaked_agent_state.close(accounts.destination.to_account_info())?;
+
+            emit!(AgentAutoClosedEvent {
+                agent,
+                reason: AUTO_CLOSE_ZERO_BALANCE,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Fund the vault and spend from it atomically (just-in-time funding)
+    /// The spend is checked against the topped-up balance; if it fails the deposit reverts too
+    pub fn topup_and_spend(
+        mut ctx: Context<TopupAndSpend>,
+        topup_amount: u64,
+        spend_amount: u64,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        require!(
+            ctx.accounts.cloaked_agent_state.allows_instruction(INSTRUCTION_BIT_SPEND),
+            ErrorCode::InstructionNotAllowed
+        );
+
+        let accounts = &mut ctx.accounts;
+        process_deposit(
+            DepositAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                depositor: &accounts.depositor,
+                global_stats: accounts.global_stats.as_mut(),
+                system_program: &accounts.system_program,
+            },
+            topup_amount,
+        )?;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            spend_amount,
+        )
+    }
+
+    /// Spend as an additional delegate (DelegateEntry)
+    /// Per-delegate limits apply to the spend amount, on top of all agent-level limits
+    pub fn spend_as_delegate(mut ctx: Context<SpendAsDelegate>, amount: u64) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let clock = Clock::get()?;
+        let entry = &mut ctx.accounts.delegate_entry;
+
+        // Check per-delegate max per tx (0 = unlimited)
+        if entry.max_per_tx > 0 {
+            require!(amount <= entry.max_per_tx, ErrorCode::ExceedsPerTxLimit);
+        }
+        entry.spending_window.record_spend(clock.unix_timestamp, amount)?;
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )
+    }
+
+    /// Spend by a program delegate, called via CPI
+    /// The delegate program signs with its [b"delegate_authority", agent] PDA
+    pub fn spend_as_program_delegate(mut ctx: Context<SpendAsProgramDelegate>, amount: u64) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        require!(
+            ctx.accounts.cloaked_agent_state.allows_instruction(INSTRUCTION_BIT_SPEND),
+            ErrorCode::InstructionNotAllowed
+        );
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )
+    }
+
+    /// Spend co-signed by the agent's two_fa_key (hardware wallet / HSM)
+    /// Required for amounts at or above two_fa_threshold; smaller amounts may use spend
+    pub fn spend_with_2fa(mut ctx: Context<SpendWith2fa>, amount: u64) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        require!(
+            ctx.accounts.cloaked_agent_state.allows_instruction(INSTRUCTION_BIT_SPEND),
+            ErrorCode::InstructionNotAllowed
+        );
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: Some(&accounts.second_factor),
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )
+    }
+
+    /// Spend authorized by the delegate's Ethereum (secp256k1) key
+    /// Anyone may relay; the signed message binds agent, amount, destination, nonce and
+    /// deadline. Nonces must strictly increase to prevent replay
+    pub fn spend_with_secp(
+        mut ctx: Context<SpendWithSecp>,
+        amount: u64,
+        nonce: u64,
+        deadline: i64,
+        signature: [u8; 64],
+        recovery_id: u8,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            agent_state.allows_instruction(INSTRUCTION_BIT_SPEND_WITH_SECP),
+            ErrorCode::InstructionNotAllowed
+        );
+        require!(
+            agent_state.delegate_eth_address != [0u8; 20],
+            ErrorCode::EthDelegateNotSet
+        );
+        require!(clock.unix_timestamp <= deadline, ErrorCode::SignatureExpired);
+        require!(nonce > agent_state.delegate_eth_nonce, ErrorCode::InvalidSecpNonce);
+
+        let message_hash = secp_spend_message_hash(
+            &agent_state.key(),
+            amount,
+            ctx.accounts.destination.key,
+            nonce,
+            deadline,
+        );
+        let signer = recover_eth_address(&message_hash, &signature, recovery_id)?;
+        require!(
+            signer == agent_state.delegate_eth_address,
+            ErrorCode::InvalidSecpSignature
+        );
+        agent_state.delegate_eth_nonce = nonce;
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )
+    }
+
+    /// Spend authorized by the delegate's passkey (WebAuthn / secp256r1)
+    /// The transaction must carry a secp256r1 precompile instruction, immediately before this
+    /// one, verifying the passkey signature over passkey_spend_message. Anyone may relay
+    pub fn spend_with_passkey(
+        mut ctx: Context<SpendWithPasskey>,
+        amount: u64,
+        nonce: u64,
+        deadline: i64,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            agent_state.allows_instruction(INSTRUCTION_BIT_SPEND_WITH_PASSKEY),
+            ErrorCode::InstructionNotAllowed
+        );
+        require!(
+            agent_state.delegate_p256_pubkey != [0u8; 33],
+            ErrorCode::PasskeyDelegateNotSet
+        );
+        require!(clock.unix_timestamp <= deadline, ErrorCode::SignatureExpired);
+        require!(nonce > agent_state.delegate_p256_nonce, ErrorCode::InvalidPasskeyNonce);
+
+        let message = passkey_spend_message(
+            &agent_state.key(),
+            amount,
+            ctx.accounts.destination.key,
+            nonce,
+            deadline,
+        );
+        verify_precompile_signature(
+            &ctx.accounts.instructions,
+            &SECP256R1_PROGRAM_ID,
+            &agent_state.delegate_p256_pubkey,
+            &message,
+            None,
+        )?;
+        agent_state.delegate_p256_nonce = nonce;
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )
+    }
+
+    /// Spend from an intent signed off-chain by the delegate (meta-transaction)
+    /// The transaction must carry an ed25519 precompile instruction, immediately before this
+    /// one, verifying `ed25519_sig` by the delegate over SpendIntent::message. Anyone may relay
+    pub fn spend_signed(
+        mut ctx: Context<SpendSigned>,
+        intent: SpendIntent,
+        ed25519_sig: [u8; 64],
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            agent_state.allows_instruction(INSTRUCTION_BIT_SPEND_SIGNED),
+            ErrorCode::InstructionNotAllowed
+        );
+        require_keys_eq!(intent.agent, agent_state.key(), ErrorCode::InvalidSpendIntent);
+        require_keys_eq!(
+            intent.destination,
+            ctx.accounts.destination.key(),
+            ErrorCode::InvalidSpendIntent
+        );
+        require!(clock.unix_timestamp <= intent.expires_at, ErrorCode::SignatureExpired);
+        require!(
+            intent.nonce > agent_state.delegate_intent_nonce,
+            ErrorCode::InvalidSpendIntent
+        );
+
+        verify_precompile_signature(
+            &ctx.accounts.instructions,
+            &ed25519_program::ID,
+            agent_state.delegate.as_ref(),
+            &intent.message(),
+            Some(&ed25519_sig[..]),
+        )?;
+        agent_state.delegate_intent_nonce = intent.nonce;
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            intent.amount,
+        )
+    }
+
+    /// Spend only if a Pyth price satisfies `condition` against `price_threshold`
+    /// The PriceUpdateV2 account for `price_feed` is passed as the first remaining account;
+    /// price_threshold is in the feed's exponent units. Otherwise identical to spend
+    pub fn conditional_spend(
+        mut ctx: Context<ConditionalSpend>,
+        amount: u64,
+        price_feed: Pubkey,
+        price_threshold: i64,
+        condition: PriceCondition,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        require!(
+            ctx.accounts.cloaked_agent_state.allows_instruction(INSTRUCTION_BIT_SPEND),
+            ErrorCode::InstructionNotAllowed
+        );
+
+        let feed = ctx.remaining_accounts.first().ok_or(ErrorCode::InvalidPriceFeed)?;
+        require_keys_eq!(feed.key(), price_feed, ErrorCode::InvalidPriceFeed);
+        let price = read_pyth_price(feed, Clock::get()?.unix_timestamp)?;
+        require!(condition.is_met(price, price_threshold), ErrorCode::ConditionNotMet);
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )
+    }
+
+    /// Allow spend_via_program to CPI into `program_id` (owner only, standard mode)
+    pub fn add_allowed_program(ctx: Context<AddAllowedProgram>, program_id: Pubkey) -> Result<()> {
+        let entry = &mut ctx.accounts.allowed_program;
+        entry.agent = ctx.accounts.cloaked_agent_state.key();
+        entry.program_id = program_id;
+        entry.bump = ctx.bumps.allowed_program;
+        Ok(())
+    }
+
+    /// Remove a CPI target program, returning rent to the owner
+    pub fn remove_allowed_program(_ctx: Context<RemoveAllowedProgram>, _program_id: Pubkey) -> Result<()> {
+        // allowed_program account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// CPI into an owner-allowlisted program with the vault as signer (delegate only)
+    /// `amount` is charged against every limit up front; after the CPI the vault may have
+    /// lost at most `amount` lamports and must still be an empty system account.
+    /// remaining_accounts are the target instruction's accounts (the vault is signed for)
+    pub fn spend_via_program<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SpendViaProgram<'info>>,
+        amount: u64,
+        target_program_id: Pubkey,
+        ix_data: Vec<u8>,
+    ) -> Result<()> {
+        unpaused_program_config(&ctx.accounts.program_config)?;
+        require!(
+            ctx.accounts.cloaked_agent_state.allows_instruction(INSTRUCTION_BIT_SPEND),
+            ErrorCode::InstructionNotAllowed
+        );
+        let clock = Clock::get()?;
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        check_spend_allowed(agent_state, amount, &clock)?;
+        check_two_fa(agent_state, amount, None)?;
+        record_spend_limits(agent_state, ctx.accounts.parent_state.as_mut(), amount, &clock)?;
+        agent_state.total_spent = agent_state.total_spent
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_spent(amount)?;
+        }
+        agent_state.spend_nonce = agent_state.spend_nonce
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let agent_state_key = agent_state.key();
+        let vault_bump = agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        let vault_key = ctx.accounts.vault.key();
+        let metas = ctx
+            .remaining_accounts
+            .iter()
+            .map(|info| AccountMeta {
+                pubkey: info.key(),
+                is_signer: info.is_signer || info.key() == vault_key,
+                is_writable: info.is_writable,
+            })
+            .collect();
+        let mut infos = ctx.remaining_accounts.to_vec();
+        infos.push(ctx.accounts.target_program.to_account_info());
+
+        let vault_balance_pre_cpi = ctx.accounts.vault.lamports();
+        check_delegate_reserve(&ctx.accounts.cloaked_agent_state, vault_balance_pre_cpi, amount)?;
+        invoke_signed(
+            &Instruction {
+                program_id: target_program_id,
+                accounts: metas,
+                data: ix_data,
+            },
+            &infos,
+            signer_seeds,
+        )?;
+
+        // The target may only move lamports; ownership and data must be untouched
+        let vault_info = ctx.accounts.vault.to_account_info();
+        require!(
+            *vault_info.owner == anchor_lang::system_program::ID && vault_info.data_is_empty(),
+            ErrorCode::ProgramSpendExceeded
+        );
+        let vault_balance_after = vault_info.lamports();
+        require!(
+            vault_balance_pre_cpi.saturating_sub(vault_balance_after) <= amount,
+            ErrorCode::ProgramSpendExceeded
+        );
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            vault_balance_after >= agent_state.reserved_lamports,
+            ErrorCode::InsufficientBalance
+        );
+        let vault_balance_before =
+            agent_state.update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(ProgramSpendEvent {
+            agent: agent_state_key,
+            target_program: target_program_id,
+            amount,
+            vault_outflow: vault_balance_pre_cpi.saturating_sub(vault_balance_after),
+            spend_nonce: agent_state.spend_nonce,
+            vault_balance_before,
+            vault_balance_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        apply_low_balance_freeze(agent_state, vault_balance_after, &clock);
+
+        Ok(())
+    }
+
+    /// Spend into an Escrow PDA that release_escrow forwards to destination after
+    /// release_after; until then the owner can revoke_escrow. Limits are charged now
+    /// and the whitelist is checked against the final destination
+    pub fn spend_escrowed(
+        mut ctx: Context<SpendEscrowed>,
+        amount: u64,
+        destination: Pubkey,
+        release_after: i64,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        require!(
+            ctx.accounts.cloaked_agent_state.allows_instruction(INSTRUCTION_BIT_SPEND),
+            ErrorCode::InstructionNotAllowed
+        );
+        require!(
+            release_after > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidEscrow
+        );
+
+        // Escrow PDA is seeded by the spend_nonce before this spend increments it
+        let escrow_id = ctx.accounts.cloaked_agent_state.spend_nonce;
+        let escrow_info = ctx.accounts.escrow.to_account_info();
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &escrow_info,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )?;
+
+        let escrow = &mut accounts.escrow;
+        escrow.agent = accounts.cloaked_agent_state.key();
+        escrow.escrow_id = escrow_id;
+        escrow.destination = destination;
+        escrow.amount = amount;
+        escrow.release_after = release_after;
+        escrow.rent_payer = accounts.fee_payer.key();
+        escrow.window_start = accounts.cloaked_agent_state.spending_window.window_start;
+        escrow.parent_window_start = accounts
+            .parent_state
+            .as_ref()
+            .map_or(0, |parent| parent.spending_window.window_start);
+        escrow.bump = ctx.bumps.escrow;
+
+        Ok(())
+    }
+
+    /// Forward an escrowed spend to its destination once release_after has passed (permissionless)
+    pub fn release_escrow(ctx: Context<ReleaseEscrow>) -> Result<()> {
+        let clock = Clock::get()?;
+        let escrow = &ctx.accounts.escrow;
+        require!(clock.unix_timestamp >= escrow.release_after, ErrorCode::EscrowLocked);
+
+        let amount = escrow.amount;
+        ctx.accounts.escrow.sub_lamports(amount)?;
+        ctx.accounts.destination.add_lamports(amount)?;
+
+        emit!(EscrowReleasedEvent {
+            agent: ctx.accounts.cloaked_agent_state.key(),
+            escrow: ctx.accounts.escrow.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // escrow account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Claw back an escrowed spend into the vault before release_after (owner only,
+    /// standard mode). The spend is removed from total_spent, and from the window
+    /// allowance while the window it was charged to is still current
+    pub fn revoke_escrow(ctx: Context<RevokeEscrow>) -> Result<()> {
+        let clock = Clock::get()?;
+        let escrow = &ctx.accounts.escrow;
+        require!(clock.unix_timestamp < escrow.release_after, ErrorCode::EscrowReleasable);
+
+        let amount = escrow.amount;
+        let window_start = escrow.window_start;
+        let parent_window_start = escrow.parent_window_start;
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.refund_spend(amount, window_start);
+        if let Some(parent_key) = agent_state.parent {
+            let parent = ctx
+                .accounts
+                .parent_state
+                .as_mut()
+                .ok_or(ErrorCode::InvalidParentAgent)?;
+            require_keys_eq!(parent.key(), parent_key, ErrorCode::InvalidParentAgent);
+            parent.refund_spend(amount, parent_window_start);
+        }
+
+        ctx.accounts.escrow.sub_lamports(amount)?;
+        ctx.accounts.vault.add_lamports(amount)?;
+
+        let vault_balance_after = ctx.accounts.vault.lamports();
+        let vault_balance_before = ctx
+            .accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(EscrowRevokedEvent {
+            agent: ctx.accounts.cloaked_agent_state.key(),
+            escrow: ctx.accounts.escrow.key(),
+            amount,
+            vault_balance_before,
+            vault_balance_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // escrow account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Authorize an additional delegate key with its own limits (owner only, standard mode)
+    pub fn add_delegate(
+        ctx: Context<AddDelegate>,
+        delegate: Pubkey,
+        max_per_tx: u64,
+        window_limit: u64,
+    ) -> Result<()> {
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+
+        let clock = Clock::get()?;
+        let entry = &mut ctx.accounts.delegate_entry;
+        entry.agent = agent_state.key();
+        entry.delegate = delegate;
+        entry.max_per_tx = max_per_tx;
+        entry.spending_window = SpendingWindow {
+            window_seconds: DEFAULT_WINDOW_SECONDS,
+            limit: window_limit,
+            spent: 0,
+            window_start: clock.unix_timestamp,
+        };
+        entry.bump = ctx.bumps.delegate_entry;
+
+        Ok(())
+    }
+
+    /// Revoke an additional delegate, returning rent to the owner
+    pub fn remove_delegate(_ctx: Context<RemoveDelegate>, _delegate: Pubkey) -> Result<()> {
+        // Owner is verified by account constraints; delegate_entry is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Create a short-lived session key with its own budget
+    /// Authority is the owner (standard mode) or, if allow_delegate_sessions is set, the delegate
+    pub fn create_session(
+        ctx: Context<CreateSession>,
+        session_key: Pubkey,
+        expires_at: i64,
+        budget: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        let authority = ctx.accounts.authority.key();
+        let is_owner = agent_state.owner == Some(authority);
+        if !is_owner {
+            let is_delegate = agent_state.allow_delegate_sessions && agent_state.delegate == authority;
+            require!(is_delegate, ErrorCode::NotOwner);
+            require!(
+                agent_state.allows_instruction(INSTRUCTION_BIT_CREATE_SESSION),
+                ErrorCode::InstructionNotAllowed
+            );
+        }
+        require!(expires_at > clock.unix_timestamp, ErrorCode::InvalidExpiration);
+
+        let session = &mut ctx.accounts.session;
+        session.agent = agent_state.key();
+        session.session_key = session_key;
+        session.authority = authority;
+        session.expires_at = expires_at;
+        session.budget = budget;
+        session.bump = ctx.bumps.session;
+
+        Ok(())
+    }
+
+    /// Spend signed by a session key
+    /// Decrements the session budget; all agent-level limits still apply
+    pub fn spend_with_session(mut ctx: Context<SpendWithSession>, amount: u64) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let clock = Clock::get()?;
+        let session = &mut ctx.accounts.session;
+        require!(clock.unix_timestamp < session.expires_at, ErrorCode::SessionExpired);
+        session.budget = session
+            .budget
+            .checked_sub(amount)
+            .ok_or(ErrorCode::SessionBudgetExhausted)?;
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.fee_payer,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )
+    }
+
+    /// Close a session early (owner, delegate or session creator); rent returns to the creator
+    pub fn revoke_session(ctx: Context<RevokeSession>) -> Result<()> {
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        let revoker = ctx.accounts.revoker.key();
+        require!(
+            agent_state.owner == Some(revoker)
+                || agent_state.delegate == revoker
+                || ctx.accounts.session.authority == revoker,
+            ErrorCode::NotOwner
+        );
+
+        // session account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Withdraw from vault to any destination (owner only, standard mode, no constraints)
+    /// Works even if agent is frozen or expired - owner has full control
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        check_multisig_approval(agent_state, ctx.accounts.owner.key, ctx.remaining_accounts)?;
+        check_withdraw_destination(agent_state, ctx.accounts.destination.key)?;
+
+        // Check balance
+        require!(
+            ctx.accounts.vault.lamports() >= amount,
+            ErrorCode::InsufficientBalance
+        );
+
+        // Update tracking before transfer
+        agent_state.total_withdrawn = agent_state.total_withdrawn
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_withdrawn(amount)?;
+        }
+
+        // Get signer seeds for vault PDA
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        // Transfer from vault to destination
+        let transfer_ix = system_instruction::transfer(
+            ctx.accounts.vault.key,
+            ctx.accounts.destination.key,
+            amount,
+        );
+
+        invoke_signed(
+            &transfer_ix,
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.destination.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let vault_balance_after = ctx.accounts.vault.lamports();
+        let vault_balance_before = ctx
+            .accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(WithdrawEvent {
+            agent: agent_state_key,
+            owner: ctx.accounts.owner.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            vault_balance_before,
+            vault_balance_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw the whole vault balance except its rent reserve, keeping the agent open
+    /// (owner only, standard mode). Same checks as withdraw
+    pub fn withdraw_all(ctx: Context<Withdraw>) -> Result<()> {
+        let amount = ctx
+            .accounts
+            .vault
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(0));
+        require!(amount > 0, ErrorCode::InsufficientBalance);
+        withdraw(ctx, amount)
+    }
+
+    /// Freeze agent (owner only, standard mode) - emergency stop
+    /// `reason` is an opaque compliance note (ticket ID, incident reference)
+    pub fn freeze(ctx: Context<Freeze>, reason: [u8; 32]) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        check_multisig_approval(agent_state, ctx.accounts.owner.key, ctx.remaining_accounts)?;
+        agent_state.frozen = true;
+        agent_state.freeze_reason = reason;
+
+        emit!(FreezeEvent {
+            agent: agent_state.key(),
+            by_delegate: false,
+            freeze_reason: reason,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Freeze several agents owned by the signer in one transaction (standard mode)
+    /// remaining_accounts: the writable agent states, in the same order as `agent_keys`
+    pub fn batch_freeze<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchFreeze<'info>>,
+        agent_keys: Vec<Pubkey>,
+        reason: [u8; 32],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let owner = ctx.accounts.owner.key();
+        require!(
+            !agent_keys.is_empty() && agent_keys.len() <= MAX_BATCH_FREEZE,
+            ErrorCode::TooManyBatchAgents
+        );
+        require!(
+            ctx.remaining_accounts.len() == agent_keys.len(),
+            ErrorCode::InvalidBatchAgent
+        );
+
+        for (state_info, agent_key) in ctx.remaining_accounts.iter().zip(agent_keys.iter()) {
+            require_keys_eq!(*state_info.key, *agent_key, ErrorCode::InvalidBatchAgent);
+            require!(state_info.is_writable, ErrorCode::InvalidBatchAgent);
+
+            // Owner program and discriminator are checked by try_from
+            let mut agent_state: Account<'info, CloakedAgentState> = Account::try_from(state_info)?;
+            require!(!agent_state.is_private(), ErrorCode::IsPrivateMode);
+            require!(agent_state.owner == Some(owner), ErrorCode::NotOwner);
+
+            agent_state.frozen = true;
+            agent_state.freeze_reason = reason;
+            agent_state.exit(&crate::ID)?;
+        }
+
+        emit!(BatchFreezeEvent {
+            owner,
+            agents: agent_keys,
+            freeze_reason: reason,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Freeze agent from the delegate key (e.g. on suspected compromise)
+    /// The delegate can only lock itself out - unfreezing still requires the owner
+    pub fn freeze_by_delegate(ctx: Context<FreezeByDelegate>, reason: [u8; 32]) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            agent_state.allows_instruction(INSTRUCTION_BIT_FREEZE_BY_DELEGATE),
+            ErrorCode::InstructionNotAllowed
+        );
+        agent_state.frozen = true;
+        agent_state.frozen_by_delegate = true;
+        agent_state.freeze_reason = reason;
+
+        emit!(FreezeEvent {
+            agent: agent_state.key(),
+            by_delegate: true,
+            freeze_reason: reason,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Unfreeze agent (owner only, standard mode)
+    pub fn unfreeze(ctx: Context<Unfreeze>) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        check_multisig_approval(agent_state, ctx.accounts.owner.key, ctx.remaining_accounts)?;
+        agent_state.frozen = false;
+        agent_state.frozen_by_delegate = false;
+        agent_state.freeze_reason = [0; 32];
+        Ok(())
+    }
+
+    /// Panic (owner only, standard mode) - freeze and drain vault atomically
+    /// Closes the gap between freeze and withdraw when the delegate is compromised
+    pub fn panic(ctx: Context<Panic>) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        check_multisig_approval(agent_state, ctx.accounts.owner.key, ctx.remaining_accounts)?;
+        check_withdraw_destination(agent_state, ctx.accounts.destination.key)?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+
+        // Freeze and update tracking before transfer
+        agent_state.frozen = true;
+        agent_state.freeze_reason = PANIC_FREEZE_REASON;
+        agent_state.total_withdrawn = agent_state.total_withdrawn
+            .checked_add(vault_balance)
+            .ok_or(ErrorCode::Overflow)?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_withdrawn(vault_balance)?;
+        }
+
+        // Transfer entire vault balance to destination
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        if vault_balance > 0 {
+            let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"vault",
+                agent_state_key.as_ref(),
+                &[vault_bump],
+            ]];
+
+            invoke_signed(
+                &system_instruction::transfer(
+                    ctx.accounts.vault.key,
+                    ctx.accounts.destination.key,
+                    vault_balance,
+                ),
+                &[
+                    ctx.accounts.vault.to_account_info(),
+                    ctx.accounts.destination.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        let vault_balance_before = ctx
+            .accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(ctx.accounts.vault.lamports(), clock.unix_timestamp);
+
+        emit!(PanicEvent {
+            agent: agent_state_key,
+            destination: ctx.accounts.destination.key(),
+            amount: vault_balance,
+            private: false,
+            vault_balance_before,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Freeze agent with ZK proof (private mode)
+    pub fn freeze_private(
+        ctx: Context<FreezePrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        reason: [u8; 32],
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_FREEZE,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let operation_fee = private_fee_charged(
+            program_config.as_ref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            &ctx.accounts.fee_recipient,
+            ctx.accounts.relayer_stats.as_deref_mut(),
+        )?;
+        let clock = Clock::get()?;
+
+        // Get keys before mutable borrow
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_FREEZE,
+                &[0; 32],
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        // Check vault has enough for fee
+        require!(
+            ctx.accounts.vault.lamports() >= operation_fee,
+            ErrorCode::InsufficientBalanceForFee
+        );
+
+        // Transfer fee to fee_recipient (relayer reimbursement)
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.fee_recipient.key,
+                operation_fee,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.fee_recipient.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.frozen = true;
+        agent_state.freeze_reason = reason;
+        agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        emit!(FreezeEvent {
+            agent: agent_state_key,
+            by_delegate: false,
+            freeze_reason: reason,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Unfreeze agent with ZK proof (private mode)
+    pub fn unfreeze_private(
+        ctx: Context<UnfreezePrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_UNFREEZE,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let operation_fee = private_fee_charged(
+            program_config.as_ref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            &ctx.accounts.fee_recipient,
+            ctx.accounts.relayer_stats.as_deref_mut(),
+        )?;
+        let clock = Clock::get()?;
+
+        // Get keys before mutable borrow
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_UNFREEZE,
+                &[0; 32],
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        // Check vault has enough for fee
+        require!(
+            ctx.accounts.vault.lamports() >= operation_fee,
+            ErrorCode::InsufficientBalanceForFee
+        );
+
+        // Transfer fee to fee_recipient (relayer reimbursement)
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.fee_recipient.key,
+                operation_fee,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.fee_recipient.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.frozen = false;
+        agent_state.frozen_by_delegate = false;
+        agent_state.freeze_reason = [0; 32];
+        agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+        Ok(())
+    }
+
+    /// Panic with ZK proof (private mode) - freeze and drain vault atomically
+    pub fn panic_private(
+        ctx: Context<PanicPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_PANIC,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let operation_fee = private_fee_charged(
+            program_config.as_ref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            &ctx.accounts.fee_recipient,
+            ctx.accounts.relayer_stats.as_deref_mut(),
+        )?;
+        let clock = Clock::get()?;
+
+        // Get keys before mutable borrow
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_PANIC,
+                &[0; 32],
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        let vault_balance = ctx.accounts.vault.lamports();
+
+        // Check vault has enough for fee
+        require!(
+            vault_balance >= operation_fee,
+            ErrorCode::InsufficientBalanceForFee
+        );
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        // Transfer fee to fee_recipient (relayer reimbursement)
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.fee_recipient.key,
+                operation_fee,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.fee_recipient.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        // Transfer remaining vault balance to destination
+        let remaining_balance = vault_balance
+            .checked_sub(operation_fee)
+            .ok_or(ErrorCode::Overflow)?;
+        if remaining_balance > 0 {
+            invoke_signed(
+                &system_instruction::transfer(
+                    ctx.accounts.vault.key,
+                    ctx.accounts.destination.key,
+                    remaining_balance,
+                ),
+                &[
+                    ctx.accounts.vault.to_account_info(),
+                    ctx.accounts.destination.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.frozen = true;
+        agent_state.freeze_reason = PANIC_FREEZE_REASON;
+        agent_state.total_withdrawn = agent_state.total_withdrawn
+            .checked_add(remaining_balance)
+            .ok_or(ErrorCode::Overflow)?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_withdrawn(remaining_balance)?;
+        }
+        let vault_balance_before =
+            agent_state.update_vault_snapshot(ctx.accounts.vault.lamports(), clock.unix_timestamp);
+
+        emit!(PanicEvent {
+            agent: agent_state_key,
+            destination: ctx.accounts.destination.key(),
+            amount: remaining_balance,
+            private: true,
+            vault_balance_before,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Update agent constraints (owner only, standard mode)
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_constraints(
+        ctx: Context<UpdateConstraints>,
+        max_per_tx: Option<u64>,
+        window_seconds: Option<u64>,
+        window_limit: Option<u64>,
+        total_limit: Option<u64>,
+        expires_at: Option<i64>,
+        max_vault_balance: Option<u64>,
+        min_deposit: Option<u64>,
+        min_spend_amount: Option<u64>,
+        block_deposits_when_inactive: Option<bool>,
+        allow_delegate_sessions: Option<bool>,
+        allowed_start_second_of_day: Option<u32>,
+        allowed_end_second_of_day: Option<u32>,
+        allowed_days_bitmask: Option<u8>,
+        rollover_cap: Option<u64>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        check_multisig_approval(agent_state, ctx.accounts.owner.key, ctx.remaining_accounts)?;
+
+        if let Some(v) = max_per_tx {
+            agent_state.max_per_tx = v;
+        }
+        if let Some(v) = window_seconds {
+            agent_state.spending_window.window_seconds = v;
+        }
+        if let Some(v) = window_limit {
+            agent_state.spending_window.limit = v;
+        }
+        if let Some(v) = total_limit {
+            agent_state.total_limit = v;
+        }
+        if let Some(v) = expires_at {
+            validate_expiration(v, &clock)?;
+            agent_state.expires_at = v;
+        }
+        if let Some(v) = max_vault_balance {
+            agent_state.max_vault_balance = v;
+        }
+        if let Some(v) = min_deposit {
+            agent_state.min_deposit = v;
+        }
+        if let Some(v) = min_spend_amount {
+            agent_state.min_spend_amount = v;
+        }
+        if let Some(v) = block_deposits_when_inactive {
+            agent_state.block_deposits_when_inactive = v;
+        }
+        if let Some(v) = allow_delegate_sessions {
+            agent_state.allow_delegate_sessions = v;
+        }
+        if let Some(v) = allowed_start_second_of_day {
+            agent_state.allowed_start_second_of_day = v;
+        }
+        if let Some(v) = allowed_end_second_of_day {
+            agent_state.allowed_end_second_of_day = v;
+        }
+        if let Some(v) = allowed_days_bitmask {
+            agent_state.allowed_days_bitmask = v;
+        }
+        if let Some(v) = rollover_cap {
+            agent_state.rollover_cap = v;
+        }
+
+        agent_state.validate_limits()?;
+
+        Ok(())
+    }
+
+    /// Configure the delegate commission paid on each spend (owner only, standard mode)
+    /// recipient = None disables the commission regardless of bps
+    pub fn set_delegate_commission(
+        ctx: Context<SetDelegateCommission>,
+        bps: u16,
+        recipient: Option<Pubkey>,
+    ) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            u64::from(bps) <= BPS_DENOMINATOR,
+            ErrorCode::InvalidCommissionBps
+        );
+
+        agent_state.delegate_commission_bps = bps;
+        agent_state.delegate_commission_recipient = recipient;
+
+        Ok(())
+    }
+
+    /// Set the Ethereum address allowed to authorize spend_with_secp (owner only, standard mode)
+    /// [0; 20] disables secp256k1 spending. Changing the address keeps the nonce, so old
+    /// signatures stay unusable
+    pub fn set_delegate_eth_address(
+        ctx: Context<SetDelegateEthAddress>,
+        eth_address: [u8; 20],
+    ) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.delegate_eth_address = eth_address;
+        Ok(())
+    }
+
+    /// Restrict which delegate-signed instructions the primary delegate may call
+    /// (owner only, standard mode). See INSTRUCTION_BIT_*; u16::MAX allows everything
+    pub fn set_instruction_whitelist(ctx: Context<SetInstructionWhitelist>, mask: u16) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.instruction_whitelist = mask;
+        Ok(())
+    }
+
+    /// Set the compressed P-256 passkey allowed to authorize spend_with_passkey
+    /// (owner only, standard mode). [0; 33] disables passkey spending
+    pub fn set_delegate_passkey(ctx: Context<SetDelegatePasskey>, pubkey: [u8; 33]) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.delegate_p256_pubkey = pubkey;
+        Ok(())
+    }
+
+    /// Require `key` to co-sign spends of `threshold` lamports or more (owner only,
+    /// standard mode). To rotate the key, remove_two_fa first
+    pub fn set_two_fa(ctx: Context<SetTwoFa>, key: Pubkey, threshold: u64) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        // Replacing the key would bypass remove_two_fa's second signature
+        require!(agent_state.two_fa_key.is_none(), ErrorCode::TwoFAAlreadySet);
+        agent_state.two_fa_key = Some(key);
+        agent_state.two_fa_threshold = threshold;
+        Ok(())
+    }
+
+    /// Disable 2FA (owner and the current two_fa_key must both sign)
+    pub fn remove_two_fa(ctx: Context<RemoveTwoFa>) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.two_fa_key = None;
+        agent_state.two_fa_threshold = 0;
+        Ok(())
+    }
+
+    /// Replace the delegate key, e.g. after it was compromised (owner only, standard mode)
+    pub fn update_delegate(mut ctx: Context<UpdateDelegate>, new_delegate: Pubkey) -> Result<()> {
+        require_keys_eq!(ctx.accounts.new_delegate.key(), new_delegate, ErrorCode::InvalidNewDelegate);
+        let accounts = &mut ctx.accounts;
+        apply_delegate_update(
+            &mut accounts.cloaked_agent_state,
+            &accounts.new_delegate,
+            accounts.old_delegate_registry.as_mut(),
+            accounts.new_delegate_registry.as_mut(),
+            Clock::get()?.unix_timestamp,
+        )
+    }
+
+    /// Free-form annotation owned by the owner (UTF-8 label, IPFS CID, ...); not read
+    /// by the program (owner only, standard mode)
+    pub fn set_owner_notes(ctx: Context<SetOwnerNotes>, notes: [u8; 32]) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.owner_notes = notes;
+        Ok(())
+    }
+
+    /// Free-form annotation owned by the delegate; not read by the program (delegate only)
+    pub fn set_delegate_notes(ctx: Context<SetDelegateNotes>, notes: [u8; 32]) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.delegate_notes = notes;
+        Ok(())
+    }
+
+    /// Program CPI'd with a SpendNotification after every spend (owner only, standard mode)
+    pub fn set_spend_hook(ctx: Context<SetSpendHook>, hook_program: Pubkey) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.post_spend_hook = Some(hook_program);
+        Ok(())
+    }
+
+    /// Stop notifying the post_spend_hook (owner only, standard mode)
+    pub fn clear_spend_hook(ctx: Context<SetSpendHook>) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.post_spend_hook = None;
+        Ok(())
+    }
+
+    /// Lamports delegate spends must leave in the vault (owner only, standard mode)
+    /// 0 disables the reserve. Owner withdrawals are not limited by it
+    pub fn set_minimum_vault_balance_for_delegation(
+        ctx: Context<SetMinimumVaultBalanceForDelegation>,
+        min: u64,
+    ) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.minimum_vault_balance_for_delegation = min;
+        Ok(())
+    }
+
+    /// Cap spending over any rolling `window_seconds` at `limit` (owner only, standard mode)
+    /// Applies on top of the fixed spending window; limit 0 disables it. Recorded spends are
+    /// kept, so reconfiguring cannot clear recent history
+    pub fn set_sliding_window(ctx: Context<SetSlidingWindow>, window_seconds: u64, limit: u64) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(!agent_state.constraints_locked, ErrorCode::ConstraintsLocked);
+        require!(
+            limit == 0 || (window_seconds > 0 && i64::try_from(window_seconds).is_ok()),
+            ErrorCode::InvalidSpendingWindow
+        );
+        agent_state.sliding_window_seconds = window_seconds;
+        agent_state.sliding_window_limit = limit;
+        Ok(())
+    }
+
+    /// Auto-freeze the agent when a spend leaves the vault below `threshold`
+    /// (owner only, standard mode). 0 disables the circuit breaker
+    pub fn set_conditional_freeze_threshold(
+        ctx: Context<SetConditionalFreezeThreshold>,
+        threshold: u64,
+    ) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.conditional_freeze_threshold = threshold;
+        Ok(())
+    }
+
+    /// Change withdraw_destination_lock (owner only, standard mode)
+    /// Setting a lock on an unlocked agent applies immediately; changing or removing an
+    /// existing lock is queued for DESTINATION_LOCK_TIMELOCK. Proposing again replaces
+    /// (or, with the current value, cancels) a pending change
+    pub fn propose_destination_lock(
+        ctx: Context<UpdateDestinationLock>,
+        new_lock: Option<Pubkey>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+
+        if agent_state.withdraw_destination_lock.is_none() {
+            agent_state.withdraw_destination_lock = new_lock;
+            agent_state.pending_destination_lock = None;
+            agent_state.pending_destination_lock_eta = 0;
+        } else {
+            agent_state.pending_destination_lock = new_lock;
+            agent_state.pending_destination_lock_eta = clock.unix_timestamp
+                .checked_add(DESTINATION_LOCK_TIMELOCK)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+
+        emit!(DestinationLockProposedEvent {
+            agent: agent_state.key(),
+            current_lock: agent_state.withdraw_destination_lock,
+            pending_lock: agent_state.pending_destination_lock,
+            eta: agent_state.pending_destination_lock_eta,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Apply a pending withdraw_destination_lock change once its timelock has elapsed
+    pub fn apply_destination_lock(ctx: Context<UpdateDestinationLock>) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            agent_state.pending_destination_lock_eta > 0
+                && clock.unix_timestamp >= agent_state.pending_destination_lock_eta,
+            ErrorCode::DestinationLockTimelocked
+        );
+
+        agent_state.withdraw_destination_lock = agent_state.pending_destination_lock;
+        agent_state.pending_destination_lock = None;
+        agent_state.pending_destination_lock_eta = 0;
+
+        Ok(())
+    }
+
+    /// Queue a withdrawal executable after DESTINATION_LOCK_TIMELOCK (owner only, standard mode)
+    /// `amount` is reserved so delegate spends cannot draw on it; at most
+    /// max_concurrent_timelocks withdrawals may be pending at once
+    pub fn propose_timelocked_withdrawal(
+        ctx: Context<ProposeTimelockedWithdrawal>,
+        destination: Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            agent_state.pending_timelocks < agent_state.timelock_cap(),
+            ErrorCode::TooManyPendingTimelocks
+        );
+        check_withdraw_destination(agent_state, &destination)?;
+
+        let reserved = agent_state.reserved_lamports
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        require!(
+            ctx.accounts.vault.lamports() >= reserved,
+            ErrorCode::InsufficientBalance
+        );
+        let eta = clock.unix_timestamp
+            .checked_add(DESTINATION_LOCK_TIMELOCK)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let record = &mut ctx.accounts.timelock_record;
+        record.agent = agent_state.key();
+        record.destination = destination;
+        record.amount = amount;
+        record.eta = eta;
+        record.nonce = agent_state.timelock_nonce;
+        record.bump = ctx.bumps.timelock_record;
+
+        agent_state.reserved_lamports = reserved;
+        agent_state.pending_timelocks += 1;
+        agent_state.timelock_nonce = agent_state.timelock_nonce
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(TimelockedWithdrawalProposedEvent {
+            agent: record.agent,
+            timelock: record.key(),
+            destination,
+            amount,
+            eta,
+            pending_timelocks: agent_state.pending_timelocks,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Execute a timelocked withdrawal once its eta has passed (owner only, standard mode)
+    /// Record rent is returned to the owner
+    pub fn execute_timelocked_withdrawal(ctx: Context<ExecuteTimelockedWithdrawal>) -> Result<()> {
+        let clock = Clock::get()?;
+        let record = &ctx.accounts.timelock_record;
+        require!(clock.unix_timestamp >= record.eta, ErrorCode::WithdrawalTimelocked);
+        let amount = record.amount;
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        // The destination lock may have changed since the proposal
+        check_withdraw_destination(agent_state, ctx.accounts.destination.key)?;
+        require!(
+            ctx.accounts.vault.lamports() >= amount,
+            ErrorCode::InsufficientBalance
+        );
+
+        agent_state.reserved_lamports = agent_state.reserved_lamports.saturating_sub(amount);
+        agent_state.pending_timelocks = agent_state.pending_timelocks.saturating_sub(1);
+        agent_state.total_withdrawn = agent_state.total_withdrawn
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_withdrawn(amount)?;
+        }
+
+        let agent_state_key = agent_state.key();
+        let vault_bump = agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.destination.key,
+                amount,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.destination.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let vault_balance_after = ctx.accounts.vault.lamports();
+        let vault_balance_before = ctx
+            .accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(WithdrawEvent {
+            agent: agent_state_key,
+            owner: ctx.accounts.owner.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            vault_balance_before,
+            vault_balance_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a pending timelocked withdrawal, releasing its reservation (owner only, standard mode)
+    pub fn cancel_timelocked_withdrawal(ctx: Context<CancelTimelockedWithdrawal>) -> Result<()> {
+        let clock = Clock::get()?;
+        let amount = ctx.accounts.timelock_record.amount;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.reserved_lamports = agent_state.reserved_lamports.saturating_sub(amount);
+        agent_state.pending_timelocks = agent_state.pending_timelocks.saturating_sub(1);
+
+        emit!(TimelockedWithdrawalCancelledEvent {
+            agent: agent_state.key(),
+            timelock: ctx.accounts.timelock_record.key(),
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set how many timelocked withdrawals may be pending at once (owner only, standard mode)
+    /// Lowering the cap does not affect withdrawals already pending
+    pub fn set_max_concurrent_timelocks(ctx: Context<UpdateDestinationLock>, max: u8) -> Result<()> {
+        require!(
+            (1..=MAX_CONCURRENT_TIMELOCKS).contains(&max),
+            ErrorCode::InvalidTimelockCap
+        );
+        ctx.accounts.cloaked_agent_state.max_concurrent_timelocks = max;
+        Ok(())
+    }
+
+    /// Allow spends to `destination` (owner only, standard mode)
+    /// Once any entry exists, spend only accepts whitelisted destinations
+    pub fn add_whitelist_entry(ctx: Context<AddWhitelistEntry>, destination: Pubkey) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+
+        agent_state.whitelist_entry_count = agent_state
+            .whitelist_entry_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let entry = &mut ctx.accounts.whitelist_entry;
+        entry.agent = agent_state.key();
+        entry.destination = destination;
+        entry.bump = ctx.bumps.whitelist_entry;
+
+        Ok(())
+    }
+
+    /// Remove a whitelisted destination, returning rent to the owner
+    pub fn remove_whitelist_entry(ctx: Context<RemoveWhitelistEntry>, _destination: Pubkey) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+
+        agent_state.whitelist_entry_count = agent_state
+            .whitelist_entry_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        // whitelist_entry account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Allow spends to any account owned by `program_id` (owner only, standard mode)
+    /// Counts as a whitelist entry: once any exists, spend only accepts whitelisted destinations
+    pub fn add_program_whitelist_entry(ctx: Context<AddProgramWhitelistEntry>, program_id: Pubkey) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+
+        agent_state.program_whitelist_entry_count = agent_state
+            .program_whitelist_entry_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let entry = &mut ctx.accounts.program_whitelist_entry;
+        entry.agent = agent_state.key();
+        entry.program = program_id;
+        entry.bump = ctx.bumps.program_whitelist_entry;
+
+        Ok(())
+    }
+
+    /// Remove a whitelisted owner program, returning rent to the owner
+    pub fn remove_program_whitelist_entry(ctx: Context<RemoveProgramWhitelistEntry>, _program_id: Pubkey) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+
+        agent_state.program_whitelist_entry_count = agent_state
+            .program_whitelist_entry_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        // program_whitelist_entry account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Block spends to `destination` (owner only, standard mode)
+    /// Only enforced when spend is passed the entry
+    pub fn add_blacklist_entry(ctx: Context<AddBlacklistEntry>, destination: Pubkey) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+
+        agent_state.blacklist_entry_count = agent_state
+            .blacklist_entry_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let entry = &mut ctx.accounts.blacklist_entry;
+        entry.agent = agent_state.key();
+        entry.destination = destination;
+        entry.bump = ctx.bumps.blacklist_entry;
+
+        Ok(())
+    }
+
+    /// Remove a blacklisted destination, returning rent to the owner
+    pub fn remove_blacklist_entry(ctx: Context<RemoveBlacklistEntry>, _destination: Pubkey) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+
+        agent_state.blacklist_entry_count = agent_state
+            .blacklist_entry_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        // blacklist_entry account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Close a destination's DestinationStats, returning rent to the owner
+    pub fn close_dest_stats(_ctx: Context<CloseDestStats>, _dest_key: Pubkey) -> Result<()> {
+        // dest_stats account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Create a recurring payment to destination (owner only, standard mode)
+    /// catch_up = true lets missed periods be paid one execute_schedule at a time;
+    /// otherwise a late execution pays once and skips to the next future period
+    pub fn create_schedule(
+        ctx: Context<CreateSchedule>,
+        destination: Pubkey,
+        amount: u64,
+        interval_seconds: u64,
+        start_at: i64,
+        catch_up: bool,
+    ) -> Result<()> {
+        require!(
+            amount > 0 && interval_seconds > 0 && interval_seconds <= i64::MAX as u64,
+            ErrorCode::InvalidSchedule
+        );
+
+        let schedule = &mut ctx.accounts.payment_schedule;
+        schedule.agent = ctx.accounts.cloaked_agent_state.key();
+        schedule.destination = destination;
+        schedule.amount = amount;
+        schedule.interval_seconds = interval_seconds;
+        schedule.next_due = start_at;
+        schedule.catch_up = catch_up;
+        schedule.bump = ctx.bumps.payment_schedule;
+
+        Ok(())
+    }
+
+    /// Pay a due schedule from the vault (permissionless crank)
+    /// Counts against window and total limits like any other spend; the cranker
+    /// is reimbursed as fee payer
+    pub fn execute_schedule(mut ctx: Context<ExecuteSchedule>) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let now = Clock::get()?.unix_timestamp;
+        let schedule = &mut ctx.accounts.payment_schedule;
+        require!(now >= schedule.next_due, ErrorCode::ScheduleNotDue);
+
+        let interval = schedule.interval_seconds as i64;
+        let periods = if schedule.catch_up {
+            1
+        } else {
+            (now - schedule.next_due) / interval + 1
+        };
+        schedule.next_due = periods
+            .checked_mul(interval)
+            .and_then(|advance| schedule.next_due.checked_add(advance))
+            .ok_or(ErrorCode::Overflow)?;
+        let amount = schedule.amount;
+
+        let accounts = &mut ctx.accounts;
+        process_spend(
+            SpendAccounts {
+                cloaked_agent_state: &mut accounts.cloaked_agent_state,
+                vault: &accounts.vault,
+                fee_payer: &accounts.cranker,
+                destination: &accounts.destination,
+                commission_recipient: accounts.commission_recipient.as_ref(),
+                whitelist_entry: accounts.whitelist_entry.as_ref(),
+                program_whitelisted: false,
+                parent_state: accounts.parent_state.as_mut(),
+                global_stats: accounts.global_stats.as_mut(),
+                second_factor: None,
+                fee_reimbursement: spend_fee_reimbursement(program_config.as_ref()),
+                system_program: &accounts.system_program,
+            },
+            amount,
+        )
+    }
+
+    /// Cancel a payment schedule, returning rent to the owner
+    pub fn cancel_schedule(_ctx: Context<CancelSchedule>, _destination: Pubkey) -> Result<()> {
+        // payment_schedule account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Pre-approve a one-time payment the delegate can redeem before expires_at
+    /// (owner only, standard mode). Vouchers bypass max_per_tx; counts_toward_limits
+    /// controls whether redemption is checked against window and total limits
+    pub fn create_voucher(
+        ctx: Context<CreateVoucher>,
+        destination: Pubkey,
+        amount: u64,
+        expires_at: i64,
+        counts_toward_limits: bool,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            amount > 0 && expires_at > clock.unix_timestamp,
+            ErrorCode::InvalidVoucher
+        );
+
+        let voucher = &mut ctx.accounts.voucher;
+        voucher.agent = ctx.accounts.cloaked_agent_state.key();
+        voucher.owner = ctx.accounts.owner.key();
+        voucher.destination = destination;
+        voucher.amount = amount;
+        voucher.expires_at = expires_at;
+        voucher.counts_toward_limits = counts_toward_limits;
+        voucher.bump = ctx.bumps.voucher;
+
+        Ok(())
+    }
+
+    /// Pay a voucher from the vault (delegate only); the voucher is consumed and
+    /// its rent returned to the owner who created it
+    pub fn redeem_voucher(ctx: Context<RedeemVoucher>) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        let voucher = &ctx.accounts.voucher;
+
+        require!(
+            agent_state.allows_instruction(INSTRUCTION_BIT_REDEEM_VOUCHER),
+            ErrorCode::InstructionNotAllowed
+        );
+        require!(!agent_state.frozen, ErrorCode::AgentFrozen);
+        require!(!agent_state.is_expired(&clock), ErrorCode::AgentExpired);
+        require!(clock.unix_timestamp < voucher.expires_at, ErrorCode::VoucherExpired);
+
+        let amount = voucher.amount;
+        if voucher.counts_toward_limits {
+            agent_state.record_window_spend(clock.unix_timestamp, amount)?;
+            if agent_state.total_limit > 0 {
+                require!(
+                    agent_state.total_spent.checked_add(amount).ok_or(ErrorCode::Overflow)?
+                        <= agent_state.total_limit,
+                    ErrorCode::ExceedsTotalLimit
+                );
+            }
+            agent_state.total_spent = agent_state.total_spent
+                .checked_add(amount)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_spent(amount)?;
+        }
+
+        let fee_reimbursement = spend_fee_reimbursement(program_config.as_ref());
+        let total_required = amount.checked_add(fee_reimbursement).ok_or(ErrorCode::Overflow)?;
+        require!(
+            agent_state.spendable_balance(ctx.accounts.vault.lamports()) >= total_required,
+            ErrorCode::InsufficientBalance
+        );
+
+        agent_state.spend_nonce = agent_state.spend_nonce
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let agent_state_key = agent_state.key();
+        let vault_bump = agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.destination.key,
+                amount,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.destination.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        // Reimburse fee payer for transaction fee
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.fee_payer.key,
+                fee_reimbursement,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.fee_payer.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let vault_balance_after = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        let vault_balance_before =
+            agent_state.update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(SpentEvent {
+            agent: agent_state_key,
+            destination: ctx.accounts.destination.key(),
+            amount,
+            commission: 0,
+            spend_nonce: agent_state.spend_nonce,
+            vault_balance_before,
+            vault_balance_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // voucher account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Close an expired, unredeemed voucher (permissionless), refunding rent to its owner
+    pub fn close_expired_voucher(ctx: Context<CloseExpiredVoucher>, _destination: Pubkey) -> Result<()> {
+        require!(
+            Clock::get()?.unix_timestamp >= ctx.accounts.voucher.expires_at,
+            ErrorCode::VoucherNotExpired
+        );
+
+        // voucher account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Update agent constraints with ZK proof (private mode)
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_constraints_private(
+        ctx: Context<UpdateConstraintsPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        max_per_tx: Option<u64>,
+        window_seconds: Option<u64>,
+        window_limit: Option<u64>,
+        total_limit: Option<u64>,
+        expires_at: Option<i64>,
+        max_vault_balance: Option<u64>,
+        min_deposit: Option<u64>,
+        min_spend_amount: Option<u64>,
+        block_deposits_when_inactive: Option<bool>,
+        allow_delegate_sessions: Option<bool>,
+        allowed_start_second_of_day: Option<u32>,
+        allowed_end_second_of_day: Option<u32>,
+        allowed_days_bitmask: Option<u8>,
+        rollover_cap: Option<u64>,
+        max_private_ops_per_day: Option<u16>,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_UPDATE_CONSTRAINTS,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let operation_fee = private_fee_charged(
+            program_config.as_ref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            &ctx.accounts.fee_recipient,
+            ctx.accounts.relayer_stats.as_deref_mut(),
+        )?;
+        let clock = Clock::get()?;
+
+        // Get keys before mutable borrow
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+            require!(!agent_state.constraints_locked, ErrorCode::ConstraintsLocked);
+
+            // The proof must commit to exactly these parameter values
+            let params_hash = ConstraintParams {
+                max_per_tx,
+                window_seconds,
+                window_limit,
+                total_limit,
+                expires_at,
+                max_vault_balance,
+                min_deposit,
+                min_spend_amount,
+                block_deposits_when_inactive,
+                allow_delegate_sessions,
+                allowed_start_second_of_day,
+                allowed_end_second_of_day,
+                allowed_days_bitmask,
+                rollover_cap,
+                max_private_ops_per_day,
+            }
+            .params_hash();
+            require!(
+                witness_bytes.get(WITNESS_ACTION_DATA_OFFSET..WITNESS_SIZE) == Some(&params_hash[..]),
+                ErrorCode::ParamsMismatch
+            );
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_UPDATE_CONSTRAINTS,
+                &params_hash,
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        // Check vault has enough for fee
+        require!(
+            ctx.accounts.vault.lamports() >= operation_fee,
+            ErrorCode::InsufficientBalanceForFee
+        );
+
+        // Transfer fee to fee_recipient (relayer reimbursement)
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.fee_recipient.key,
+                operation_fee,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.fee_recipient.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+
+        if let Some(v) = max_per_tx {
+            agent_state.max_per_tx = v;
+        }
+        if let Some(v) = window_seconds {
+            agent_state.spending_window.window_seconds = v;
+        }
+        if let Some(v) = window_limit {
+            agent_state.spending_window.limit = v;
+        }
+        if let Some(v) = total_limit {
+            agent_state.total_limit = v;
+        }
+        if let Some(v) = expires_at {
+            validate_expiration(v, &clock)?;
+            agent_state.expires_at = v;
+        }
+        if let Some(v) = max_vault_balance {
+            agent_state.max_vault_balance = v;
+        }
+        if let Some(v) = min_deposit {
+            agent_state.min_deposit = v;
+        }
+        if let Some(v) = min_spend_amount {
+            agent_state.min_spend_amount = v;
+        }
+        if let Some(v) = block_deposits_when_inactive {
+            agent_state.block_deposits_when_inactive = v;
+        }
+        if let Some(v) = allow_delegate_sessions {
+            agent_state.allow_delegate_sessions = v;
+        }
+        if let Some(v) = allowed_start_second_of_day {
+            agent_state.allowed_start_second_of_day = v;
+        }
+        if let Some(v) = allowed_end_second_of_day {
+            agent_state.allowed_end_second_of_day = v;
+        }
+        if let Some(v) = allowed_days_bitmask {
+            agent_state.allowed_days_bitmask = v;
+        }
+        if let Some(v) = rollover_cap {
+            agent_state.rollover_cap = v;
+        }
+        if let Some(v) = max_private_ops_per_day {
+            agent_state.max_private_ops_per_day = v;
+        }
+
+        agent_state.validate_limits()?;
+        agent_state.update_vault_snapshot(ctx.accounts.vault.lamports(), clock.unix_timestamp);
+
+        Ok(())
+    }
+
+    /// Permanently lock constraints (owner only, standard mode)
+    /// Irreversible - there is no unlock, so counterparties can rely on the current limits
+    pub fn lock_constraints(ctx: Context<LockConstraints>) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.constraints_locked = true;
+        Ok(())
+    }
+
+    /// Permanently lock constraints with ZK proof (private mode)
+    pub fn lock_constraints_private(
+        ctx: Context<LockConstraintsPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_LOCK_CONSTRAINTS,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let operation_fee = private_fee_charged(
+            program_config.as_ref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            &ctx.accounts.fee_recipient,
+            ctx.accounts.relayer_stats.as_deref_mut(),
+        )?;
+        let clock = Clock::get()?;
+
+        // Get keys before mutable borrow
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_LOCK_CONSTRAINTS,
+                &[0; 32],
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        // Check vault has enough for fee
+        require!(
+            ctx.accounts.vault.lamports() >= operation_fee,
+            ErrorCode::InsufficientBalanceForFee
+        );
+
+        // Transfer fee to fee_recipient (relayer reimbursement)
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.fee_recipient.key,
+                operation_fee,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.fee_recipient.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.constraints_locked = true;
+        agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        Ok(())
+    }
+
+    /// Switch the agent to another proof wire format with a ZK proof in the current one
+    /// (private mode). The proof binds new_version
+    pub fn migrate_proof_version(
+        ctx: Context<MigrateProofVersionPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        new_version: u8,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_MIGRATE_PROOF_VERSION,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        require!(is_supported_proof_format(new_version), ErrorCode::UnsupportedProofVersion);
+        let clock = Clock::get()?;
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+
+        let old_version = {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+            let old_version = agent_state.proof_format_version();
+            require!(new_version != old_version, ErrorCode::ProofVersionUnchanged);
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_MIGRATE_PROOF_VERSION,
+                &proof_version_action_data(new_version),
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+
+            agent_state.proof_format_version = new_version;
+            old_version
+        };
+
+        charge_private_operation_fee(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            &agent_state_key,
+            ctx.accounts.cloaked_agent_state.vault_bump,
+            private_fee_charged(
+                program_config.as_ref(),
+                ctx.accounts.cloaked_agent_state.key(),
+                &ctx.accounts.fee_recipient,
+                ctx.accounts.relayer_stats.as_deref_mut(),
+            )?,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        ctx.accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        emit!(ProofVersionMigratedEvent {
+            agent: agent_state_key,
+            old_version,
+            new_version,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Stop accepting deposits (owner only, standard mode)
+    /// Spending and withdrawals continue, so the balance can be wound down
+    pub fn lock_new_deposits(ctx: Context<LockDeposits>) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.deposits_locked = true;
+        emit!(DepositsLockedEvent {
+            agent: ctx.accounts.cloaked_agent_state.key(),
+            locked: true,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Accept deposits again (owner only, standard mode)
+    pub fn unlock_deposits(ctx: Context<UnlockDeposits>) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.deposits_locked = false;
+        emit!(DepositsLockedEvent {
+            agent: ctx.accounts.cloaked_agent_state.key(),
+            locked: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Lock or unlock deposits with ZK proof (private mode)
+    /// The proof binds locked
+    pub fn set_deposits_locked_private(
+        ctx: Context<SetDepositsLockedPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        locked: bool,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_SET_DEPOSITS_LOCKED,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let clock = Clock::get()?;
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_SET_DEPOSITS_LOCKED,
+                &deposits_locked_action_data(locked),
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+
+            agent_state.deposits_locked = locked;
+        }
+
+        charge_private_operation_fee(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            &agent_state_key,
+            ctx.accounts.cloaked_agent_state.vault_bump,
+            private_fee_charged(
+                program_config.as_ref(),
+                ctx.accounts.cloaked_agent_state.key(),
+                &ctx.accounts.fee_recipient,
+                ctx.accounts.relayer_stats.as_deref_mut(),
+            )?,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        ctx.accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        emit!(DepositsLockedEvent {
+            agent: agent_state_key,
+            locked,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Replace the delegate key with ZK proof (private mode)
+    /// The proof binds new_delegate
+    pub fn update_delegate_private(
+        mut ctx: Context<UpdateDelegatePrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        new_delegate: Pubkey,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_UPDATE_DELEGATE,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        require_keys_eq!(ctx.accounts.new_delegate.key(), new_delegate, ErrorCode::InvalidNewDelegate);
+        let clock = Clock::get()?;
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_UPDATE_DELEGATE,
+                &delegate_action_data(&new_delegate),
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        charge_private_operation_fee(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            &agent_state_key,
+            ctx.accounts.cloaked_agent_state.vault_bump,
+            private_fee_charged(
+                program_config.as_ref(),
+                ctx.accounts.cloaked_agent_state.key(),
+                &ctx.accounts.fee_recipient,
+                ctx.accounts.relayer_stats.as_deref_mut(),
+            )?,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let accounts = &mut ctx.accounts;
+        apply_delegate_update(
+            &mut accounts.cloaked_agent_state,
+            &accounts.new_delegate,
+            accounts.old_delegate_registry.as_mut(),
+            accounts.new_delegate_registry.as_mut(),
+            clock.unix_timestamp,
+        )?;
+        accounts.cloaked_agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        Ok(())
+    }
+
+    /// Replace the owner commitment with ZK proof against the current one (private mode)
+    /// The proof binds new_commitment; proofs for the old commitment stop verifying
+    pub fn rotate_commitment_private(
+        ctx: Context<RotateCommitmentPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        new_commitment: [u8; 32],
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_ROTATE_COMMITMENT,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let operation_fee = private_fee_charged(
+            program_config.as_ref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            &ctx.accounts.fee_recipient,
+            ctx.accounts.relayer_stats.as_deref_mut(),
+        )?;
+        let clock = Clock::get()?;
+
+        // Get keys before mutable borrow
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+            require!(new_commitment != [0u8; 32], ErrorCode::InvalidCommitment);
+            require!(
+                new_commitment != agent_state.owner_commitment,
+                ErrorCode::CommitmentUnchanged
+            );
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_ROTATE_COMMITMENT,
+                &commitment_action_data(&new_commitment),
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        // Check vault has enough for fee
+        require!(
+            ctx.accounts.vault.lamports() >= operation_fee,
+            ErrorCode::InsufficientBalanceForFee
+        );
+
+        // Transfer fee to fee_recipient (relayer reimbursement)
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.fee_recipient.key,
+                operation_fee,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.fee_recipient.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.owner_commitment = new_commitment;
+        agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        emit!(CommitmentRotatedEvent {
+            agent: agent_state_key,
+            new_commitment,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set, replace or (with all zeros) remove the recovery commitment with ZK proof
+    /// against the owner commitment (private mode). Cancels any pending recovery
+    pub fn set_recovery_commitment_private(
+        ctx: Context<RecoveryPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        new_recovery_commitment: [u8; 32],
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_SET_RECOVERY,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let clock = Clock::get()?;
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+            require!(
+                new_recovery_commitment != agent_state.owner_commitment,
+                ErrorCode::InvalidCommitment
+            );
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_SET_RECOVERY,
+                &commitment_action_data(&new_recovery_commitment),
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        charge_private_operation_fee(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            &agent_state_key,
+            ctx.accounts.cloaked_agent_state.vault_bump,
+            private_fee_charged(
+                program_config.as_ref(),
+                ctx.accounts.cloaked_agent_state.key(),
+                &ctx.accounts.fee_recipient,
+                ctx.accounts.relayer_stats.as_deref_mut(),
+            )?,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.recovery_commitment = new_recovery_commitment;
+        agent_state.recovery_initiated_at = 0;
+        agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        Ok(())
+    }
+
+    /// Start the PRIVATE_RECOVERY_DELAY clock with ZK proof against the recovery commitment
+    /// (private mode). recover_private can complete it once the delay has passed
+    pub fn initiate_recovery_private(
+        ctx: Context<RecoveryPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_INITIATE_RECOVERY,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let clock = Clock::get()?;
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+            require!(
+                agent_state.recovery_commitment != [0u8; 32],
+                ErrorCode::NoRecoveryCommitment
+            );
+            require!(
+                agent_state.recovery_initiated_at == 0,
+                ErrorCode::RecoveryAlreadyInitiated
+            );
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            let recovery_commitment = agent_state.recovery_commitment;
+            verify_zk_proof_against(
+                &recovery_commitment,
+                &ctx.accounts.zk_verifier,
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_INITIATE_RECOVERY,
+                &[0; 32],
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        charge_private_operation_fee(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            &agent_state_key,
+            ctx.accounts.cloaked_agent_state.vault_bump,
+            private_fee_charged(
+                program_config.as_ref(),
+                ctx.accounts.cloaked_agent_state.key(),
+                &ctx.accounts.fee_recipient,
+                ctx.accounts.relayer_stats.as_deref_mut(),
+            )?,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.recovery_initiated_at = clock.unix_timestamp;
+        emit!(RecoveryInitiatedEvent {
+            agent: agent_state_key,
+            executable_at: clock.unix_timestamp
+                .checked_add(PRIVATE_RECOVERY_DELAY)
+                .ok_or(ErrorCode::Overflow)?,
+            timestamp: clock.unix_timestamp,
+        });
+        agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        Ok(())
+    }
+
+    /// Abort a pending recovery with ZK proof against the owner commitment (private mode)
+    pub fn cancel_recovery_private(
+        ctx: Context<RecoveryPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_CANCEL_RECOVERY,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let clock = Clock::get()?;
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+            require!(
+                agent_state.recovery_initiated_at != 0,
+                ErrorCode::RecoveryNotInitiated
+            );
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            verify_zk_proof(
+                &ctx.accounts.zk_verifier,
+                ctx.accounts.instructions.as_ref(),
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_CANCEL_RECOVERY,
+                &[0; 32],
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        charge_private_operation_fee(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            &agent_state_key,
+            ctx.accounts.cloaked_agent_state.vault_bump,
+            private_fee_charged(
+                program_config.as_ref(),
+                ctx.accounts.cloaked_agent_state.key(),
+                &ctx.accounts.fee_recipient,
+                ctx.accounts.relayer_stats.as_deref_mut(),
+            )?,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.recovery_initiated_at = 0;
+        emit!(RecoveryCancelledEvent {
+            agent: agent_state_key,
+            timestamp: clock.unix_timestamp,
+        });
+        agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        Ok(())
+    }
+
+    /// Replace the owner commitment with ZK proof against the recovery commitment, at
+    /// least PRIVATE_RECOVERY_DELAY after initiate_recovery_private (private mode)
+    pub fn recover_private(
+        ctx: Context<RecoveryPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        new_owner_commitment: [u8; 32],
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_RECOVER,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        ctx.accounts.cloaked_agent_state.record_private_op(Clock::get()?.unix_timestamp)?;
+        let clock = Clock::get()?;
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+
+        {
+            let agent_state = &mut ctx.accounts.cloaked_agent_state;
+            require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+            require!(new_owner_commitment != [0u8; 32], ErrorCode::InvalidCommitment);
+            require!(
+                agent_state.recovery_initiated_at != 0,
+                ErrorCode::RecoveryNotInitiated
+            );
+            require!(
+                clock.unix_timestamp
+                    >= agent_state.recovery_initiated_at
+                        .checked_add(PRIVATE_RECOVERY_DELAY)
+                        .ok_or(ErrorCode::Overflow)?,
+                ErrorCode::RecoveryTimelocked
+            );
+
+            // Verify ZK proof via CPI
+            let verifier_entry =
+                select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+            let recovery_commitment = agent_state.recovery_commitment;
+            verify_zk_proof_against(
+                &recovery_commitment,
+                &ctx.accounts.zk_verifier,
+                program_config.as_ref(),
+                verifier_entry.as_ref(),
+                &proof_bytes,
+                &witness_bytes,
+                agent_state,
+                ACTION_RECOVER,
+                &commitment_action_data(&new_owner_commitment),
+            )?;
+            check_relayer(
+                agent_state.require_registered_relayer,
+                ctx.accounts.relayer_registry.as_deref(),
+                ctx.accounts.fee_recipient.key,
+            )?;
+            create_nullifier(
+                &ctx.accounts.nullifier,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                agent_state.key(),
+                agent_state.vault_bump,
+                &proof_bytes,
+            )?;
+        }
+
+        charge_private_operation_fee(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            &agent_state_key,
+            ctx.accounts.cloaked_agent_state.vault_bump,
+            private_fee_charged(
+                program_config.as_ref(),
+                ctx.accounts.cloaked_agent_state.key(),
+                &ctx.accounts.fee_recipient,
+                ctx.accounts.relayer_stats.as_deref_mut(),
+            )?,
+        )?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.owner_commitment = new_owner_commitment;
+        agent_state.recovery_initiated_at = 0;
+        emit!(RecoveryCompletedEvent {
+            agent: agent_state_key,
+            new_commitment: new_owner_commitment,
+            timestamp: clock.unix_timestamp,
+        });
+        agent_state.update_vault_snapshot(vault_balance, clock.unix_timestamp);
+
+        Ok(())
+    }
+
+    /// Close agent and return all funds to owner (standard mode)
+    /// Whitelist entries may be passed in remaining_accounts (up to
+    /// MAX_WHITELIST_CLOSE_PER_TX) and are closed to the owner in the same
+    /// transaction; fails if any entry would be left orphaned. Program whitelist and
+    /// blacklist entries must be removed beforehand
+    pub fn close_cloaked_agent<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseCloakedAgent<'info>>,
+    ) -> Result<()> {
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        check_multisig_approval(agent_state, ctx.accounts.owner.key, ctx.remaining_accounts)?;
+        detach_parent(agent_state, ctx.accounts.parent_state.as_mut())?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_agent_closed()?;
+        }
+        if let Some(registry) = ctx.accounts.delegate_registry.as_mut() {
+            registry.unregister(&agent_state.key());
+        }
+
+        // Signers are multisig co-owners; every other remaining account is a whitelist entry
+        let entry_infos: Vec<&AccountInfo<'info>> = ctx
+            .remaining_accounts
+            .iter()
+            .filter(|info| !info.is_signer)
+            .collect();
+        require!(
+            entry_infos.len() <= MAX_WHITELIST_CLOSE_PER_TX,
+            ErrorCode::TooManyWhitelistEntries
+        );
+
+        // Close whitelist entries so their rent is not orphaned
+        let agent_state_key = agent_state.key();
+        let mut remaining_entries = agent_state.whitelist_entry_count;
+        for entry_info in entry_infos {
+            require!(entry_info.is_writable, ErrorCode::InvalidWhitelistEntry);
+            let entry: Account<'info, WhitelistEntry> = Account::try_from(entry_info)?;
+            require_keys_eq!(entry.agent, agent_state_key, ErrorCode::InvalidWhitelistEntry);
+            entry.close(ctx.accounts.owner.to_account_info())?;
+            remaining_entries = remaining_entries
+                .checked_sub(1)
+                .ok_or(ErrorCode::InvalidWhitelistEntry)?;
+        }
+        check_closable(agent_state, remaining_entries)?;
+
+        let vault = &ctx.accounts.vault;
+        let vault_recipient = &ctx.accounts.vault_recipient;
+        check_withdraw_destination(&ctx.accounts.cloaked_agent_state, vault_recipient.key)?;
+
+        // Transfer vault balance to vault_recipient; state rent goes to owner
+        let vault_balance = vault.lamports();
+        if vault_balance > 0 {
+            let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+            let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"vault",
+                agent_state_key.as_ref(),
+                &[vault_bump],
+            ]];
+
+            let transfer_ix = system_instruction::transfer(
+                vault.key,
+                vault_recipient.key,
+                vault_balance,
+            );
+
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    vault.to_account_info(),
+                    vault_recipient.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        // cloaked_agent_state account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Reclaim an abandoned expired agent (anyone can call, standard mode)
+    /// Returns vault balance and state rent to owner, pays cranker a small incentive
+    pub fn reclaim_expired(ctx: Context<ReclaimExpired>) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+        require!(!agent_state.is_private(), ErrorCode::IsPrivateMode);
+        require!(
+            agent_state.owner == Some(ctx.accounts.owner.key()),
+            ErrorCode::NotOwner
+        );
+
+        // Only agents expired for longer than the grace period
+        require!(agent_state.expires_at > 0, ErrorCode::NotReclaimable);
+        let reclaimable_at = agent_state.expires_at
+            .checked_add(RECLAIM_GRACE_PERIOD)
+            .ok_or(ErrorCode::Overflow)?;
+        require!(
+            clock.unix_timestamp > reclaimable_at,
+            ErrorCode::NotReclaimable
+        );
+        require!(agent_state.staked_amount == 0, ErrorCode::HasActiveStake);
+        require!(agent_state.pending_timelocks == 0, ErrorCode::HasPendingTimelocks);
+        detach_parent(agent_state, ctx.accounts.parent_state.as_mut())?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_agent_closed()?;
+        }
+
+        let vault = &ctx.accounts.vault;
+        let vault_balance = vault.lamports();
+        let incentive = vault_balance.min(RECLAIM_CRANK_INCENTIVE);
+        let refund = vault_balance
+            .checked_sub(incentive)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        // Pay cranker incentive
+        if incentive > 0 {
+            invoke_signed(
+                &system_instruction::transfer(
+                    vault.key,
+                    ctx.accounts.cranker.key,
+                    incentive,
+                ),
+                &[
+                    vault.to_account_info(),
+                    ctx.accounts.cranker.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        // Return remaining vault balance to owner (or the locked destination)
+        if refund > 0 {
+            let payout = vault_payout_account(
+                agent_state,
+                &ctx.accounts.owner,
+                ctx.accounts.withdraw_destination.as_ref(),
+            )?;
+            invoke_signed(
+                &system_instruction::transfer(
+                    vault.key,
+                    payout.key,
+                    refund,
+                ),
+                &[
+                    vault.to_account_info(),
+                    payout,
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        emit!(AgentReclaimedEvent {
+            agent: agent_state_key,
+            owner: ctx.accounts.owner.key(),
+            cranker: ctx.accounts.cranker.key(),
+            refunded: refund,
+            incentive,
+            vault_balance_before: ctx.accounts.cloaked_agent_state.vault_balance_snapshot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // cloaked_agent_state account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Allow staking vault SOL to a validator (owner only, standard mode)
+    pub fn add_validator(ctx: Context<AddValidator>, vote_account: Pubkey) -> Result<()> {
+        let entry = &mut ctx.accounts.validator_entry;
+        entry.agent = ctx.accounts.cloaked_agent_state.key();
+        entry.vote_account = vote_account;
+        entry.bump = ctx.bumps.validator_entry;
+        Ok(())
+    }
+
+    /// Remove a validator from the staking allowlist, returning rent to the owner
+    /// Existing stake delegated to it is unaffected
+    pub fn remove_validator(_ctx: Context<RemoveValidator>, _vote_account: Pubkey) -> Result<()> {
+        // validator_entry account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Move `amount` from the vault into a new stake account delegated to an allowlisted
+    /// validator (owner only, standard mode). The vault PDA is staker and withdrawer.
+    /// Staked lamports leave the vault, so spends can never draw on them
+    pub fn stake_from_vault(
+        ctx: Context<StakeFromVault>,
+        amount: u64,
+        validator_vote_account: Pubkey,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let space = StakeStateV2::size_of();
+        require!(
+            amount >= ctx.accounts.rent.minimum_balance(space),
+            ErrorCode::StakeTooSmall
+        );
+        require!(
+            ctx.accounts.vault.lamports() >= amount,
+            ErrorCode::InsufficientBalance
+        );
+
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_key = ctx.accounts.vault.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        // Fund the stake account from the vault
+        invoke_signed(
+            &system_instruction::create_account(
+                &vault_key,
+                ctx.accounts.stake_account.key,
+                amount,
+                space as u64,
+                &stake::program::ID,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        invoke(
+            &stake_instruction::initialize(
+                ctx.accounts.stake_account.key,
+                &Authorized {
+                    staker: vault_key,
+                    withdrawer: vault_key,
+                },
+                &Lockup::default(),
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.rent.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+        )?;
+
+        invoke_signed(
+            &stake_instruction::delegate_stake(
+                ctx.accounts.stake_account.key,
+                &vault_key,
+                &validator_vote_account,
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.vote_account.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.stake_history.to_account_info(),
+                ctx.accounts.stake_config.to_account_info(),
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let record = &mut ctx.accounts.stake_record;
+        record.agent = agent_state_key;
+        record.stake_account = ctx.accounts.stake_account.key();
+        record.vote_account = validator_vote_account;
+        record.amount = amount;
+        record.bump = ctx.bumps.stake_record;
+
+        let vault_balance_after = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.staked_amount = agent_state.staked_amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        let vault_balance_before =
+            agent_state.update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(StakeDelegatedEvent {
+            agent: agent_state_key,
+            stake_account: ctx.accounts.stake_account.key(),
+            vote_account: validator_vote_account,
+            amount,
+            vault_balance_before,
+            vault_balance_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Begin cooldown of a vault stake account (owner only, standard mode)
+    pub fn deactivate_stake(ctx: Context<DeactivateStake>) -> Result<()> {
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &stake_instruction::deactivate_stake(
+                ctx.accounts.stake_account.key,
+                ctx.accounts.vault.key,
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        Ok(())
+    }
+
+    /// Return a deactivated stake account's full balance (principal and rewards) to the
+    /// vault and close its record (owner only, standard mode)
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        let withdrawn = ctx.accounts.stake_account.lamports();
+        invoke_signed(
+            &stake_instruction::withdraw(
+                ctx.accounts.stake_account.key,
+                ctx.accounts.vault.key,
+                ctx.accounts.vault.key,
+                withdrawn,
+                None,
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.stake_history.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let principal = ctx.accounts.stake_record.amount;
+        let vault_balance_after = ctx.accounts.vault.lamports();
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        agent_state.staked_amount = agent_state.staked_amount.saturating_sub(principal);
+        let vault_balance_before =
+            agent_state.update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(StakeWithdrawnEvent {
+            agent: agent_state_key,
+            stake_account: ctx.accounts.stake_account.key(),
+            principal,
+            withdrawn,
+            vault_balance_before,
+            vault_balance_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // stake_record account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Configure automatic surplus sweeping (owner only, standard mode)
+    /// destination = None disables sweep_surplus
+    pub fn set_sweep_config(
+        ctx: Context<SetSweepConfig>,
+        target: u64,
+        destination: Option<Pubkey>,
+    ) -> Result<()> {
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        if let Some(destination) = destination {
+            check_withdraw_destination(agent_state, &destination)?;
+        }
+        agent_state.sweep_target = target;
+        agent_state.sweep_destination = destination;
+        Ok(())
+    }
+
+    /// Return vault balance above sweep_target to sweep_destination (permissionless)
+    /// The cranker earns SWEEP_CRANK_INCENTIVE from the surplus. Sweeps are owner
+    /// withdrawals, not spends, so they do not count against spending limits
+    pub fn sweep_surplus(ctx: Context<SweepSurplus>) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(agent_state.sweep_destination.is_some(), ErrorCode::NoSurplusToSweep);
+        check_withdraw_destination(agent_state, ctx.accounts.sweep_destination.key)?;
+
+        let vault_balance = ctx.accounts.vault.lamports();
+        let surplus = vault_balance.saturating_sub(agent_state.sweep_target);
+        require!(surplus > SWEEP_CRANK_INCENTIVE, ErrorCode::NoSurplusToSweep);
+        let swept = surplus - SWEEP_CRANK_INCENTIVE;
+
+        agent_state.total_withdrawn = agent_state.total_withdrawn
+            .checked_add(surplus)
+            .ok_or(ErrorCode::Overflow)?;
+
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+
+            stats.record_withdrawn(surplus)?;
+
+        }
+
+        let agent_state_key = agent_state.key();
+        let vault_bump = agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.sweep_destination.key,
+                swept,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.sweep_destination.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        // Pay cranker incentive
+        invoke_signed(
+            &system_instruction::transfer(
+                ctx.accounts.vault.key,
+                ctx.accounts.cranker.key,
+                SWEEP_CRANK_INCENTIVE,
+            ),
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.cranker.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let vault_balance_after = ctx.accounts.vault.lamports();
+        let vault_balance_before = ctx
+            .accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(vault_balance_after, clock.unix_timestamp);
+
+        emit!(SurplusSweptEvent {
+            agent: agent_state_key,
+            destination: ctx.accounts.sweep_destination.key(),
+            cranker: ctx.accounts.cranker.key(),
+            amount: swept,
+            incentive: SWEEP_CRANK_INCENTIVE,
+            vault_balance_before,
+            vault_balance_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Last-resort key-loss recovery (permissionless)
+    /// Once rescue_available_at passes without the owner cancelling, anyone can freeze the
+    /// agent and drain the vault to the immutable emergency_rescue_address
+    pub fn execute_rescue(ctx: Context<ExecuteRescue>) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            agent_state.emergency_rescue_address.is_some()
+                && clock.unix_timestamp >= agent_state.rescue_available_at,
+            ErrorCode::RescueNotAvailable
+        );
+
+        let vault_balance = ctx.accounts.vault.lamports();
+
+        // Freeze and update tracking before transfer
+        agent_state.frozen = true;
+        agent_state.total_withdrawn = agent_state.total_withdrawn
+            .checked_add(vault_balance)
+            .ok_or(ErrorCode::Overflow)?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_withdrawn(vault_balance)?;
+        }
+
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        if vault_balance > 0 {
+            let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"vault",
+                agent_state_key.as_ref(),
+                &[vault_bump],
+            ]];
+
+            invoke_signed(
+                &system_instruction::transfer(
+                    ctx.accounts.vault.key,
+                    ctx.accounts.rescue_destination.key,
+                    vault_balance,
+                ),
+                &[
+                    ctx.accounts.vault.to_account_info(),
+                    ctx.accounts.rescue_destination.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        let vault_balance_before = ctx
+            .accounts
+            .cloaked_agent_state
+            .update_vault_snapshot(ctx.accounts.vault.lamports(), clock.unix_timestamp);
+
+        emit!(RescueExecutedEvent {
+            agent: agent_state_key,
+            destination: ctx.accounts.rescue_destination.key(),
+            amount: vault_balance,
+            vault_balance_before,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Prove the owner key is still live (owner only, standard mode)
+    /// Restarts the rescue delay, pushing execute_rescue out by another rescue_delay
+    pub fn cancel_rescue(ctx: Context<CancelRescue>) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(
+            agent_state.emergency_rescue_address.is_some(),
+            ErrorCode::RescueNotAvailable
+        );
+
+        agent_state.rescue_available_at = clock.unix_timestamp
+            .checked_add(agent_state.rescue_delay)
+            .ok_or(ErrorCode::Overflow)?;
+
+        Ok(())
+    }
+
+    /// Set or clear the key that can recover ownership if the owner key is lost
+    /// (owner only, standard mode). The recovery delegate can never spend
+    pub fn set_recovery_delegate(
+        ctx: Context<SetRecoveryDelegate>,
+        recovery_delegate: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.cloaked_agent_state.recovery_delegate = recovery_delegate;
+        Ok(())
+    }
+
+    /// Start an ownership recovery (recovery delegate only)
+    /// The owner has RECOVERY_DELAY to cancel_recovery before it can be executed
+    pub fn propose_recovery(ctx: Context<ProposeRecovery>, new_owner: Pubkey) -> Result<()> {
+        let clock = Clock::get()?;
+        let proposal = &mut ctx.accounts.recovery_proposal;
+        proposal.agent = ctx.accounts.cloaked_agent_state.key();
+        proposal.proposer = ctx.accounts.recovery_delegate.key();
+        proposal.new_owner = new_owner;
+        proposal.proposed_at = clock.unix_timestamp;
+        proposal.bump = ctx.bumps.recovery_proposal;
+
+        emit!(RecoveryProposedEvent {
+            agent: proposal.agent,
+            new_owner,
+            executable_at: clock.unix_timestamp
+                .checked_add(RECOVERY_DELAY)
+                .ok_or(ErrorCode::Overflow)?,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Hand ownership to the proposed owner once RECOVERY_DELAY has passed
+    /// (recovery delegate only). Clears recovery_delegate
+    pub fn execute_recovery(ctx: Context<ExecuteRecovery>, new_owner: Pubkey) -> Result<()> {
+        let clock = Clock::get()?;
+        let proposal = &ctx.accounts.recovery_proposal;
+        require_keys_eq!(proposal.new_owner, new_owner, ErrorCode::InvalidRecoveryProposal);
+        require!(
+            clock.unix_timestamp >= proposal.proposed_at.saturating_add(RECOVERY_DELAY),
+            ErrorCode::RecoveryTimelocked
+        );
+
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        let previous_owner = agent_state.owner;
+        agent_state.owner = Some(new_owner);
+        agent_state.recovery_delegate = None;
+
+        emit!(OwnershipRecoveredEvent {
+            agent: agent_state.key(),
+            previous_owner,
+            new_owner,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // recovery_proposal account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Cancel a pending ownership recovery (owner only, standard mode)
+    pub fn cancel_recovery(_ctx: Context<CancelRecovery>) -> Result<()> {
+        // recovery_proposal account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Close agent with ZK proof (private mode)
+    /// Fee is capped at the vault balance so dust-only agents remain closable
+    pub fn close_cloaked_agent_private(
+        ctx: Context<CloseCloakedAgentPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+    ) -> Result<()> {
+        let program_config = unpaused_program_config(&ctx.accounts.program_config)?;
+        let (proof_bytes, witness_bytes) = load_proof(
+            ctx.accounts.proof_buffer.as_deref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            ACTION_CLOSE,
+            proof_bytes,
+            witness_bytes,
+        )?;
+        let operation_fee = private_fee_charged(
+            program_config.as_ref(),
+            ctx.accounts.cloaked_agent_state.key(),
+            &ctx.accounts.fee_recipient,
+            ctx.accounts.relayer_stats.as_deref_mut(),
+        )?;
+        let agent_state = &mut ctx.accounts.cloaked_agent_state;
+        require!(agent_state.is_private(), ErrorCode::NotPrivateMode);
+
+        // Verify ZK proof via CPI
+        let verifier_entry =
+            select_verifier_entry(ctx.accounts.verifier_registry.as_deref(), verifier_version)?;
+        verify_zk_proof(
+            &ctx.accounts.zk_verifier,
+            ctx.accounts.instructions.as_ref(),
+            program_config.as_ref(),
+            verifier_entry.as_ref(),
+            &proof_bytes,
+            &witness_bytes,
+            agent_state,
+            ACTION_CLOSE,
+            &close_action_data(ctx.accounts.destination.key, ctx.accounts.fee_recipient.key),
+        )?;
+        check_relayer(
+            agent_state.require_registered_relayer,
+            ctx.accounts.relayer_registry.as_deref(),
+            ctx.accounts.fee_recipient.key,
+        )?;
+        create_nullifier(
+            &ctx.accounts.nullifier,
+            &ctx.accounts.vault,
+            &ctx.accounts.system_program,
+            agent_state.key(),
+            agent_state.vault_bump,
+            &proof_bytes,
+        )?;
+        detach_parent(agent_state, ctx.accounts.parent_state.as_mut())?;
+        if let Some(stats) = ctx.accounts.global_stats.as_mut() {
+            stats.record_agent_closed()?;
+        }
+        if let Some(registry) = ctx.accounts.delegate_registry.as_mut() {
+            registry.unregister(&agent_state.key());
+        }
+
+        let vault = &ctx.accounts.vault;
+        let vault_balance = vault.lamports();
+
+        // Close is terminal: cap the fee at what the vault holds so a nearly
+        // empty agent can still be closed and its state rent recovered
+        let fee = vault_balance.min(operation_fee);
+
+        let agent_state_key = ctx.accounts.cloaked_agent_state.key();
+        let vault_bump = ctx.accounts.cloaked_agent_state.vault_bump;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            agent_state_key.as_ref(),
+            &[vault_bump],
+        ]];
+
+        // Transfer fee to fee_recipient (relayer reimbursement)
+        if fee > 0 {
+            invoke_signed(
+                &system_instruction::transfer(
+                    vault.key,
+                    ctx.accounts.fee_recipient.key,
+                    fee,
+                ),
+                &[
+                    vault.to_account_info(),
+                    ctx.accounts.fee_recipient.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        // Transfer remaining vault balance to destination
+        let remaining_balance = vault_balance
+            .checked_sub(fee)
+            .ok_or(ErrorCode::Overflow)?;
+        if remaining_balance > 0 {
+            invoke_signed(
+                &system_instruction::transfer(
+                    vault.key,
+                    ctx.accounts.destination.key,
+                    remaining_balance,
+                ),
+                &[
+                    vault.to_account_info(),
+                    ctx.accounts.destination.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        // cloaked_agent_state account is closed by Anchor's close constraint
+        Ok(())
+    }
+
+    /// Withdraw with ZK proof (private mode, bypasses constraints except expiry)
+    /// bypass_expiry must be set to withdraw from an expired agent, and is bound by the proof
+    pub fn withdraw_private(
+        ctx: Context<WithdrawPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        amount: u64,
+        bypass_expiry: bool,
+    ) -> Result<()> {
+        process_withdraw_private(ctx, proof_bytes, witness_bytes, verifier_version, Some(amount), bypass_expiry)
+    }
+
+    /// withdraw_private for the whole vault balance except its rent reserve, leaving the
+    /// agent open. The proof binds only the destination (ACTION_WITHDRAW_ALL)
+    pub fn withdraw_all_private(
+        ctx: Context<WithdrawPrivate>,
+        proof_bytes: Vec<u8>,
+        witness_bytes: Vec<u8>,
+        verifier_version: u8,
+        bypass_expiry: bool,
+    ) -> Result<()> {
+        process_withdraw_private(ctx, proof_bytes, witness_bytes, verifier_version, None, bypass_expiry)
+    }
+
+    /// Emit a snapshot of agent state (read-only, no signer required)
+    /// Intended for simulateTransaction - clients read AgentSummaryEvent from the logs
+    pub fn get_agent_summary(ctx: Context<GetAgentSummary>) -> Result<()> {
+        let clock = Clock::get()?;
+        let agent_state = &ctx.accounts.cloaked_agent_state;
+
+        emit!(AgentSummaryEvent {
+            agent: agent_state.key(),
+            vault_balance: ctx.accounts.vault.lamports(),
+            window_remaining: agent_state.remaining_daily_allowance(&clock),
+            total_remaining: agent_state.remaining_total_allowance(),
+            is_frozen: agent_state.frozen,
+            is_expired: agent_state.is_expired(&clock),
+            total_deposited: agent_state.total_deposited,
+            total_withdrawn: agent_state.total_withdrawn,
+            net_position: agent_state.net_position(),
+            seconds_until_reset: agent_state.spending_window.seconds_until_reset(clock.unix_timestamp),
+            constraints_locked: agent_state.constraints_locked,
+            vault_balance_before: agent_state.vault_balance_snapshot,
+            owner_notes: agent_state.owner_notes,
+            delegate_notes: agent_state.delegate_notes,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Grow a legacy agent state account to the current layout (anyone can call, payer funds rent)
+    /// Appended fields are zero-initialized; version-specific defaults are backfilled here
+    pub fn migrate_agent_state(ctx: Context<MigrateAgentState>) -> Result<()> {
+        let state_info = ctx.accounts.cloaked_agent_state.to_account_info();
+
+        if state_info.data_len() < CloakedAgentState::SIZE {
+            // Top up rent for the larger account
+            let required = Rent::get()?
+                .minimum_balance(CloakedAgentState::SIZE)
+                .saturating_sub(state_info.lamports());
+            if required > 0 {
+                invoke(
+                    &system_instruction::transfer(
+                        ctx.accounts.payer.key,
+                        state_info.key,
+                        required,
+                    ),
+                    &[
+                        ctx.accounts.payer.to_account_info(),
+                        state_info.clone(),
+                        ctx.accounts.system_program.to_account_info(),
+                    ],
+                )?;
+            }
+
+            state_info.resize(CloakedAgentState::SIZE)?;
+        }
+
+        // Discriminator is checked on deserialize
+        let mut agent_state = CloakedAgentState::try_deserialize(&mut &state_info.try_borrow_data()?[..])?;
+        require!(
+            agent_state.state_version < STATE_VERSION,
+            ErrorCode::AlreadyMigrated
+        );
+
+        // v2: daily_limit/daily_spent/last_day replaced by spending_window
+        if agent_state.state_version < 2 {
+            let current_day = Clock::get()?.unix_timestamp / SECONDS_PER_DAY;
+            let spent = if current_day > agent_state.legacy_last_day {
+                0
+            } else {
+                agent_state.legacy_daily_spent
+            };
+            agent_state.spending_window = SpendingWindow {
+                window_seconds: DEFAULT_WINDOW_SECONDS,
+                limit: agent_state.legacy_daily_limit,
+                spent,
+                window_start: current_day * SECONDS_PER_DAY,
+            };
+            agent_state.legacy_daily_limit = 0;
+            agent_state.legacy_daily_spent = 0;
+            agent_state.legacy_last_day = 0;
+        }
+
+        // v3: created_by/creation_nonce appended (zeroed); they are set when
+        // migrate_agent_address moves the account to its new PDA
+
+        // v4: instruction_whitelist appended - zero would block the delegate entirely
+        if agent_state.state_version < 4 {
+            agent_state.instruction_whitelist = u16::MAX;
+        }
+
+        // v5: vault_bump appended - vault contexts verify against the stored bump
+        if agent_state.state_version < 5 {
+            agent_state.vault_bump =
+                Pubkey::find_program_address(&[b"vault", state_info.key.as_ref()], &crate::ID).1;
+        }
+
+        agent_state.state_version = STATE_VERSION;
+
+        let mut data = state_info.try_borrow_mut_data()?;
+        agent_state.try_serialize(&mut &mut data[..])?;
+
+        Ok(())
+    }
+
+    /// Move an agent from the legacy [b"cloaked_agent_state", delegate] PDA to
+    /// [b"cloaked_agent_state", authority, creation_nonce], carrying its vault along
+    /// Authority is the owner (standard mode) or the delegate (private mode)
+    /// Run migrate_agent_state first; whitelist entries must be removed beforehand
+    pub fn migrate_agent_address(ctx: Context<MigrateAgentAddress>, creation_nonce: u64) -> Result<()> {
+        let legacy_state = &ctx.accounts.legacy_state;
+        let authority = ctx.accounts.authority.key();
+        if legacy_state.is_private() {
+            require_keys_eq!(legacy_state.delegate, authority, ErrorCode::NotOwner);
+        } else {
+            require!(legacy_state.owner == Some(authority), ErrorCode::NotOwner);
+        }
+        // Whitelist entries are seeded by the agent address and would be orphaned
+        require!(legacy_state.whitelist_entry_count == 0, ErrorCode::HasOrphanedPDAs);
+
+        let mut migrated = (**legacy_state).clone();
+        migrated.bump = ctx.bumps.cloaked_agent_state;
+        migrated.created_by = authority;
+        migrated.creation_nonce = creation_nonce;
+        migrated.vault_bump = ctx.bumps.vault;
+        ctx.accounts.cloaked_agent_state.set_inner(migrated);
+
+        // Move vault balance to the vault of the new address
+        let legacy_vault = &ctx.accounts.legacy_vault;
+        let vault_balance = legacy_vault.lamports();
+        if vault_balance > 0 {
+            let legacy_state_key = legacy_state.key();
+            let legacy_vault_bump = ctx.bumps.legacy_vault;
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"vault",
+                legacy_state_key.as_ref(),
+                &[legacy_vault_bump],
+            ]];
+
+            invoke_signed(
+                &system_instruction::transfer(
+                    legacy_vault.key,
+                    ctx.accounts.vault.key,
+                    vault_balance,
+                ),
+                &[
+                    legacy_vault.to_account_info(),
+                    ctx.accounts.vault.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        // legacy_state account is closed by Anchor's close constraint
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(creation_nonce: u64)]
+pub struct CreateCloakedAgent<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = CloakedAgentState::SIZE,
+        seeds = [b"cloaked_agent_state", payer.key().as_ref(), &creation_nonce.to_le_bytes()],
+        bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Vault PDA to hold funds
+    #[account(
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Owner of the agent (human wallet)
+    pub owner: Signer<'info>,
+
+    /// Delegate key (agent's public key)
+    /// CHECK: Any pubkey can be delegate
+    pub delegate: AccountInfo<'info>,
+
+    /// Pays for account creation
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Parent agent, when creating a sub-agent
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Parent's delegate, must sign when parent_state is provided
+    pub parent_delegate: Option<Signer<'info>>,
+
+    /// Delegate's registry, when the new agent should be listed there
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", delegate.key().as_ref()],
+        bump = delegate_registry.bump,
+    )]
+    pub delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(creation_nonce: u64)]
+pub struct CreateCloakedAgentWithProgramDelegate<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = CloakedAgentState::SIZE,
+        seeds = [b"cloaked_agent_state", payer.key().as_ref(), &creation_nonce.to_le_bytes()],
+        bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Vault PDA to hold funds
+    #[account(
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Owner of the agent (human wallet)
+    pub owner: Signer<'info>,
+
+    /// Pays for account creation
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeGlobalStats<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = GlobalStats::SIZE,
+        seeds = [b"global_stats"],
+        bump,
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Cloaked>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::NotAdmin)]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// Program upgrade authority
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProgramConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = ProgramConfig::SIZE,
+        seeds = [b"program_config"],
+        bump,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Cloaked>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::NotAdmin)]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// Program upgrade authority, becomes the config admin
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProgramConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_config"],
+        bump = program_config.bump,
+        has_one = admin @ ErrorCode::NotAdmin,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    /// Config admin (verified by has_one)
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"program_config"],
+        bump = program_config.bump,
+        constraint = program_config.pending_admin == Some(new_admin.key()) @ ErrorCode::NotPendingAdmin,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    /// Admin proposed by propose_admin
+    pub new_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVerifierRegistry<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = VerifierRegistry::SIZE,
+        seeds = [b"verifier_registry"],
+        bump,
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    #[account(
+        seeds = [b"program_config"],
+        bump = program_config.bump,
+        has_one = admin @ ErrorCode::NotAdmin,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    /// Config admin (verified by has_one)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRelayerRegistry<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = RelayerRegistry::SIZE,
+        seeds = [b"relayer_registry"],
+        bump,
+    )]
+    pub relayer_registry: Account<'info, RelayerRegistry>,
+
+    #[account(
+        seeds = [b"program_config"],
+        bump = program_config.bump,
+        has_one = admin @ ErrorCode::NotAdmin,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    /// Config admin (verified by has_one)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRelayerRegistry<'info> {
+    #[account(
+        mut,
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Account<'info, RelayerRegistry>,
+
+    #[account(
+        seeds = [b"program_config"],
+        bump = program_config.bump,
+        has_one = admin @ ErrorCode::NotAdmin,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    /// Config admin (verified by has_one)
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterRelayer<'info> {
+    #[account(
+        init,
+        payer = relayer,
+        space = RelayerStats::SIZE,
+        seeds = [b"relayer_stats", relayer.key().as_ref()],
+        bump,
+    )]
+    pub relayer_stats: Account<'info, RelayerStats>,
+
+    /// Relayer (fee_recipient key) the stats belong to
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRelayerRegistration<'info> {
+    #[account(
+        mut,
+        close = relayer,
+        seeds = [b"relayer_stats", relayer.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Account<'info, RelayerStats>,
+
+    /// Relayer the stats belong to, receives the rent
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u32)]
+pub struct InitializeNoteSet<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = NoteSet::SIZE,
+        seeds = [b"note_set", index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub note_set: Account<'info, NoteSet>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositNote<'info> {
+    #[account(
+        mut,
+        seeds = [b"note_set", &note_set.index.to_le_bytes()],
+        bump = note_set.bump,
+    )]
+    pub note_set: Account<'info, NoteSet>,
+
+    /// Pays NOTE_DENOMINATION into the note set
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(proof_bytes: Vec<u8>, witness_bytes: Vec<u8>, root: [u8; 32], note_nullifier: [u8; 32])]
+pub struct ClaimNotePrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"note_set", &note_set.index.to_le_bytes()],
+        bump = note_set.bump,
+    )]
+    pub note_set: Account<'info, NoteSet>,
+
+    /// Marks the note as claimed; already existing when it was claimed before
+    #[account(
+        init,
+        payer = payer,
+        space = NoteNullifier::SIZE,
+        seeds = [b"note_nullifier", note_nullifier.as_ref()],
+        bump,
+    )]
+    pub note_nullifier_account: Account<'info, NoteNullifier>,
+
+    #[account(
+        seeds = [b"program_config"],
+        bump = program_config.bump,
+        constraint = program_config.note_verifier != Pubkey::default() @ ErrorCode::NoteVerifierNotSet,
+        constraint = !program_config.paused @ ErrorCode::ProgramPaused,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    /// ZK verifier for note claims
+    /// CHECK: Must be program_config.note_verifier
+    #[account(address = program_config.note_verifier @ ErrorCode::InvalidVerifierProgram)]
+    pub note_verifier: AccountInfo<'info>,
+
+    /// Relayer: funds the NoteNullifier
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVerifierRegistry<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Account<'info, VerifierRegistry>,
+
+    #[account(
+        seeds = [b"program_config"],
+        bump = program_config.bump,
+        has_one = admin @ ErrorCode::NotAdmin,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    /// Config admin (verified by has_one)
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(action_id: u8, proof_len: u32, witness_len: u32)]
+pub struct InitProofBuffer<'info> {
+    #[account(
+        init,
+        payer = submitter,
+        space = ProofBuffer::size(proof_len as usize + witness_len as usize),
+        seeds = [b"proof_buffer", submitter.key().as_ref(), cloaked_agent_state.key().as_ref()],
+        bump,
+    )]
+    pub proof_buffer: Account<'info, ProofBuffer>,
+
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = cloaked_agent_state.is_private() @ ErrorCode::NotPrivateMode,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Uploads the chunks and gets the rent back when the buffer is closed
+    #[account(mut)]
+    pub submitter: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WriteProofChunk<'info> {
+    #[account(
+        mut,
+        seeds = [b"proof_buffer", submitter.key().as_ref(), proof_buffer.agent.as_ref()],
+        bump = proof_buffer.bump,
+    )]
+    pub proof_buffer: Account<'info, ProofBuffer>,
+
+    /// Submitter that created the buffer (verified by seeds)
+    pub submitter: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseProofBuffer<'info> {
+    #[account(
+        mut,
+        seeds = [b"proof_buffer", submitter.key().as_ref(), proof_buffer.agent.as_ref()],
+        bump = proof_buffer.bump,
+        close = submitter,
+    )]
+    pub proof_buffer: Account<'info, ProofBuffer>,
+
+    /// Submitter that created the buffer (verified by seeds)
+    #[account(mut)]
+    pub submitter: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseNullifier<'info> {
+    #[account(
+        mut,
+        seeds = [b"nullifier", nullifier.proof_hash.as_ref()],
+        bump = nullifier.bump,
+        close = vault,
+    )]
+    pub nullifier: Account<'info, Nullifier>,
+
+    /// Vault of the agent whose private instruction created the nullifier
+    #[account(
+        mut,
+        seeds = [b"vault", nullifier.agent.as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateDelegateRegistry<'info> {
+    #[account(
+        init,
+        payer = delegate,
+        space = DelegateRegistry::SIZE,
+        seeds = [b"delegate_registry", delegate.key().as_ref()],
+        bump,
+    )]
+    pub delegate_registry: Account<'info, DelegateRegistry>,
+
+    /// Delegate key the registry belongs to, pays its rent
+    #[account(mut)]
+    pub delegate: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDelegateRegistry<'info> {
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", delegate.key().as_ref()],
+        bump = delegate_registry.bump,
+    )]
+    pub delegate_registry: Account<'info, DelegateRegistry>,
+
+    /// Agent being added (required by add_to_delegate_registry, verified in instruction)
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Option<Account<'info, CloakedAgentState>>,
+
+    pub delegate: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    /// Agent state (to derive vault PDA and track deposits)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Vault PDA to receive funds
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Source of funds
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositWithProgramAuthority<'info> {
+    /// Agent state (to derive vault PDA and track deposits)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Vault PDA to receive funds
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Source of funds - a PDA of the calling program
+    /// CHECK: Verified via CPI signer (the system transfer requires its signature)
+    #[account(mut)]
+    pub depositor: AccountInfo<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Spend<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+
+    /// Fee payer - fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries,
+    /// unless a ProgramWhitelistEntry for the destination's owner is passed)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Delegate's registry, to drop the agent from it when the spend auto-closes it
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", cloaked_agent_state.delegate.as_ref()],
+        bump = delegate_registry.bump,
+    )]
+    pub delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SpendWith2fa<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+
+    /// Agent's two_fa_key (verified in instruction)
+    pub second_factor: Signer<'info>,
+
+    /// Fee payer - fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConditionalSpend<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+
+    /// Fee payer - fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey)]
+pub struct AddAllowedProgram<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = AllowedProgram::SIZE,
+        seeds = [b"allowed_program", cloaked_agent_state.key().as_ref(), program_id.as_ref()],
+        bump,
+    )]
+    pub allowed_program: Account<'info, AllowedProgram>,
+
+    /// Owner signing the transaction (verified by constraint), pays entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey)]
+pub struct RemoveAllowedProgram<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"allowed_program", cloaked_agent_state.key().as_ref(), program_id.as_ref()],
+        bump = allowed_program.bump,
+    )]
+    pub allowed_program: Account<'info, AllowedProgram>,
+
+    /// Owner signing the transaction (verified by constraint), receives entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, target_program_id: Pubkey)]
+pub struct SpendViaProgram<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Target program must be on the owner's allowlist
+    #[account(
+        seeds = [b"allowed_program", cloaked_agent_state.key().as_ref(), target_program_id.as_ref()],
+        bump = allowed_program.bump,
+    )]
+    pub allowed_program: Account<'info, AllowedProgram>,
+
+    /// CHECK: Must be the allowlisted program (verified by address constraint)
+    #[account(address = target_program_id, executable)]
+    pub target_program: AccountInfo<'info>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Program-wide settings; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, destination: Pubkey)]
+pub struct SpendEscrowed<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = fee_payer,
+        space = Escrow::SIZE,
+        seeds = [b"escrow", cloaked_agent_state.key().as_ref(), &cloaked_agent_state.spend_nonce.to_le_bytes()],
+        bump,
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+
+    /// Fee payer - fronts tx fee and escrow rent, gets rent back when the escrow closes
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for the final destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseEscrow<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [b"escrow", cloaked_agent_state.key().as_ref(), &escrow.escrow_id.to_le_bytes()],
+        bump = escrow.bump,
+        has_one = destination,
+        has_one = rent_payer,
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// Escrow payee
+    /// CHECK: Verified by has_one on escrow
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Receives escrow rent
+    /// CHECK: Verified by has_one on escrow
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeEscrow<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [b"escrow", cloaked_agent_state.key().as_ref(), &escrow.escrow_id.to_le_bytes()],
+        bump = escrow.bump,
+        has_one = rent_payer,
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Parent agent (required when revoking a sub-agent escrow)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+
+    /// Receives escrow rent
+    /// CHECK: Verified by has_one on escrow
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SpendWithSecp<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Relayer - fronts tx fee, gets reimbursed from vault (need not be the delegate)
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds (bound by the signed message)
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SpendWithPasskey<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Relayer - fronts tx fee, gets reimbursed from vault (need not be the delegate)
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds (bound by the signed message)
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Instructions sysvar - used to inspect the secp256r1 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SpendSigned<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Relayer - fronts tx fee, gets reimbursed from vault (need not be the delegate)
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds (must match intent.destination)
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Instructions sysvar - used to inspect the ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopupAndSpend<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Source of the top-up
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+
+    /// Fee payer - fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SpendAsDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"delegate", cloaked_agent_state.key().as_ref(), delegate.key().as_ref()],
+        bump = delegate_entry.bump,
+    )]
+    pub delegate_entry: Account<'info, DelegateEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match delegate_entry.delegate (enforced by seeds)
+    pub delegate: Signer<'info>,
+
+    /// Fee payer - fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SpendAsProgramDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = cloaked_agent_state.program_delegate @ ErrorCode::NotProgramDelegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// PDA of the delegate program, signed via invoke_signed
+    #[account(
+        seeds = [b"delegate_authority", cloaked_agent_state.key().as_ref()],
+        bump,
+        seeds::program = cloaked_agent_state.delegate,
+    )]
+    pub delegate_authority: Signer<'info>,
+
+    /// Fee payer - fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.is_owner_key(&owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Owner (or, for multisig agents, one of the owners) signing the transaction
+    /// Further multisig co-owners sign as remaining accounts
+    pub owner: Signer<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Freeze<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.is_owner_key(&owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner (or, for multisig agents, one of the owners) signing the transaction
+    /// Further multisig co-owners sign as remaining accounts
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BatchFreeze<'info> {
+    /// Owner of every agent passed in remaining_accounts
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeByDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Unfreeze<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.is_owner_key(&owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner (or, for multisig agents, one of the owners) signing the transaction
+    /// Further multisig co-owners sign as remaining accounts
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Panic<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.is_owner_key(&owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Owner (or, for multisig agents, one of the owners) signing the transaction
+    /// Further multisig co-owners sign as remaining accounts
+    pub owner: Signer<'info>,
+
+    /// Destination for the entire vault balance
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConstraints<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.is_owner_key(&owner.key()) @ ErrorCode::NotOwner,
+        constraint = !cloaked_agent_state.constraints_locked @ ErrorCode::ConstraintsLocked,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner (or, for multisig agents, one of the owners) signing the transaction
+    /// Further multisig co-owners sign as remaining accounts
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegateEthAddress<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetInstructionWhitelist<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegatePasskey<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LockConstraints<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LockDeposits<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnlockDeposits<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTwoFa<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveTwoFa<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+        constraint = cloaked_agent_state.two_fa_key == Some(second_factor.key()) @ ErrorCode::Invalid2FA,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+
+    /// Current two_fa_key (verified by constraint)
+    pub second_factor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+
+    /// CHECK: Any non-executable key other than the owner (verified in instruction)
+    pub new_delegate: AccountInfo<'info>,
+
+    /// Current delegate's registry, to drop the agent from it
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", cloaked_agent_state.delegate.as_ref()],
+        bump = old_delegate_registry.bump,
+    )]
+    pub old_delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    /// New delegate's registry, to list the agent in it
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", new_delegate.key().as_ref()],
+        bump = new_delegate_registry.bump,
+    )]
+    pub new_delegate_registry: Option<Account<'info, DelegateRegistry>>,
+}
+
+#[derive(Accounts)]
+pub struct SetOwnerNotes<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegateNotes<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetSpendHook<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMinimumVaultBalanceForDelegation<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetSlidingWindow<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetConditionalFreezeThreshold<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegateCommission<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(destination: Pubkey)]
+pub struct AddWhitelistEntry<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = WhitelistEntry::SIZE,
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump,
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+
+    /// Owner signing the transaction (verified by constraint), pays entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(destination: Pubkey)]
+pub struct RemoveWhitelistEntry<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+
+    /// Owner signing the transaction (verified by constraint), receives entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey)]
+pub struct AddProgramWhitelistEntry<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = ProgramWhitelistEntry::SIZE,
+        seeds = [b"program_wl", cloaked_agent_state.key().as_ref(), program_id.as_ref()],
+        bump,
+    )]
+    pub program_whitelist_entry: Account<'info, ProgramWhitelistEntry>,
+
+    /// Owner signing the transaction (verified by constraint), pays entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey)]
+pub struct RemoveProgramWhitelistEntry<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"program_wl", cloaked_agent_state.key().as_ref(), program_id.as_ref()],
+        bump = program_whitelist_entry.bump,
+    )]
+    pub program_whitelist_entry: Account<'info, ProgramWhitelistEntry>,
+
+    /// Owner signing the transaction (verified by constraint), receives entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(destination: Pubkey)]
+pub struct AddBlacklistEntry<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = BlacklistEntry::SIZE,
+        seeds = [b"blacklist", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump,
+    )]
+    pub blacklist_entry: Account<'info, BlacklistEntry>,
+
+    /// Owner signing the transaction (verified by constraint), pays entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(destination: Pubkey)]
+pub struct RemoveBlacklistEntry<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"blacklist", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump = blacklist_entry.bump,
+    )]
+    pub blacklist_entry: Account<'info, BlacklistEntry>,
+
+    /// Owner signing the transaction (verified by constraint), receives entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(dest_key: Pubkey)]
+pub struct CloseDestStats<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"dest_stats", cloaked_agent_state.key().as_ref(), dest_key.as_ref()],
+        bump = dest_stats.bump,
+    )]
+    pub dest_stats: Account<'info, DestinationStats>,
+
+    /// Owner signing the transaction (verified by constraint), receives stats rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(destination: Pubkey)]
+pub struct CreateSchedule<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = PaymentSchedule::SIZE,
+        seeds = [b"schedule", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump,
+    )]
+    pub payment_schedule: Account<'info, PaymentSchedule>,
+
+    /// Owner signing the transaction (verified by constraint), pays schedule rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSchedule<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"schedule", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = payment_schedule.bump,
+    )]
+    pub payment_schedule: Account<'info, PaymentSchedule>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Anyone can crank a due schedule; fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// Scheduled payee (bound by the schedule PDA seeds)
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when paying from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(destination: Pubkey)]
+pub struct CancelSchedule<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"schedule", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump = payment_schedule.bump,
+    )]
+    pub payment_schedule: Account<'info, PaymentSchedule>,
+
+    /// Owner signing the transaction (verified by constraint), receives schedule rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(destination: Pubkey)]
+pub struct CreateVoucher<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = Voucher::SIZE,
+        seeds = [b"voucher", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    /// Owner signing the transaction (verified by constraint), pays voucher rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemVoucher<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        has_one = delegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"voucher", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = voucher.bump,
+        has_one = owner,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match cloaked_agent_state.delegate
+    pub delegate: Signer<'info>,
+
+    /// Fee payer - fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Voucher payee (bound by the voucher PDA seeds)
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Voucher creator, receives voucher rent
+    /// CHECK: Verified by has_one on voucher
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(destination: Pubkey)]
+pub struct CloseExpiredVoucher<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"voucher", cloaked_agent_state.key().as_ref(), destination.as_ref()],
+        bump = voucher.bump,
+        has_one = owner,
+    )]
+    pub voucher: Account<'info, Voucher>,
+
+    /// Voucher creator, receives voucher rent
+    /// CHECK: Verified by has_one on voucher
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct AddDelegate<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = DelegateEntry::SIZE,
+        seeds = [b"delegate", cloaked_agent_state.key().as_ref(), delegate.as_ref()],
+        bump,
+    )]
+    pub delegate_entry: Account<'info, DelegateEntry>,
+
+    /// Owner signing the transaction (verified by constraint), pays entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct RemoveDelegate<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"delegate", cloaked_agent_state.key().as_ref(), delegate.as_ref()],
+        bump = delegate_entry.bump,
+    )]
+    pub delegate_entry: Account<'info, DelegateEntry>,
+
+    /// Owner signing the transaction (verified by constraint), receives entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+pub struct CreateSession<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Session::SIZE,
+        seeds = [b"session", cloaked_agent_state.key().as_ref(), session_key.as_ref()],
+        bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Owner or delegate (verified in instruction), pays session rent
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SpendWithSession<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"session", cloaked_agent_state.key().as_ref(), session_key.key().as_ref()],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match session.session_key (enforced by seeds)
+    pub session_key: Signer<'info>,
+
+    /// Fee payer - fronts tx fee, gets reimbursed from vault
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Delegate commission recipient (required when a commission is configured)
+    /// CHECK: Verified in instruction to match delegate_commission_recipient
+    #[account(mut)]
+    pub commission_recipient: Option<AccountInfo<'info>>,
+
+    /// Whitelist entry for destination (required once the agent has any entries)
+    #[account(
+        seeds = [b"whitelist", cloaked_agent_state.key().as_ref(), destination.key().as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>,
+
+    /// Parent agent (required when spending from a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Overrides SPEND_FEE_REIMBURSEMENT once initialized; the spend fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSession<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = session_authority,
+        seeds = [b"session", cloaked_agent_state.key().as_ref(), session.session_key.as_ref()],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Owner, delegate or session creator (verified in instruction)
+    pub revoker: Signer<'info>,
+
+    /// Session creator, receives rent
+    /// CHECK: Must match session.authority
+    #[account(mut, address = session.authority)]
+    pub session_authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDestinationLock<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeTimelockedWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = TimelockRecord::SIZE,
+        seeds = [b"timelock", cloaked_agent_state.key().as_ref(), &cloaked_agent_state.timelock_nonce.to_le_bytes()],
+        bump,
+    )]
+    pub timelock_record: Account<'info, TimelockRecord>,
+
+    #[account(
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Owner signing the transaction (verified by constraint), pays record rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTimelockedWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"timelock", cloaked_agent_state.key().as_ref(), &timelock_record.nonce.to_le_bytes()],
+        bump = timelock_record.bump,
+    )]
+    pub timelock_record: Account<'info, TimelockRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Owner signing the transaction (verified by constraint), receives record rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Destination recorded at proposal
+    /// CHECK: Address checked against the record
+    #[account(mut, address = timelock_record.destination)]
+    pub destination: AccountInfo<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTimelockedWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"timelock", cloaked_agent_state.key().as_ref(), &timelock_record.nonce.to_le_bytes()],
+        bump = timelock_record.bump,
+    )]
+    pub timelock_record: Account<'info, TimelockRecord>,
+
+    /// Owner signing the transaction (verified by constraint), receives record rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCloakedAgent<'info> {
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.is_owner_key(&owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Owner (or, for multisig agents, one of the owners) signing the transaction,
+    /// receives the state account rent. Further multisig co-owners sign as remaining accounts
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Receives the vault balance (must be withdraw_destination_lock when set)
+    /// CHECK: Verified in instruction against withdraw_destination_lock
+    #[account(mut)]
+    pub vault_recipient: AccountInfo<'info>,
+
+    /// Parent agent (required when closing a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Delegate's registry, to drop the closed agent from it
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", cloaked_agent_state.delegate.as_ref()],
+        bump = delegate_registry.bump,
+    )]
+    pub delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimExpired<'info> {
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Stored owner - receives vault balance and state rent
+    /// CHECK: Verified in instruction to match cloaked_agent_state.owner
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+
+    /// Anyone can crank reclaim and receives RECLAIM_CRANK_INCENTIVE
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// Required when withdraw_destination_lock is set - receives the refund
+    /// CHECK: Verified in instruction to match withdraw_destination_lock
+    #[account(mut)]
+    pub withdraw_destination: Option<AccountInfo<'info>>,
+
+    /// Parent agent (required when closing a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vote_account: Pubkey)]
+pub struct AddValidator<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = ValidatorEntry::SIZE,
+        seeds = [b"validator", cloaked_agent_state.key().as_ref(), vote_account.as_ref()],
+        bump,
+    )]
+    pub validator_entry: Account<'info, ValidatorEntry>,
+
+    /// Owner signing the transaction (verified by constraint), pays entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vote_account: Pubkey)]
+pub struct RemoveValidator<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"validator", cloaked_agent_state.key().as_ref(), vote_account.as_ref()],
+        bump = validator_entry.bump,
+    )]
+    pub validator_entry: Account<'info, ValidatorEntry>,
+
+    /// Owner signing the transaction (verified by constraint), receives entry rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, validator_vote_account: Pubkey)]
+pub struct StakeFromVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Validator must be on the owner's allowlist
+    #[account(
+        seeds = [b"validator", cloaked_agent_state.key().as_ref(), validator_vote_account.as_ref()],
+        bump = validator_entry.bump,
+    )]
+    pub validator_entry: Account<'info, ValidatorEntry>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = StakeRecord::SIZE,
+        seeds = [b"stake", cloaked_agent_state.key().as_ref(), stake_account.key().as_ref()],
+        bump,
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    /// Fresh keypair for the new stake account
+    #[account(mut)]
+    pub stake_account: Signer<'info>,
+
+    /// CHECK: Must be the allowlisted vote account; validated by the stake program
+    #[account(address = validator_vote_account)]
+    pub vote_account: AccountInfo<'info>,
+
+    /// Owner signing the transaction (verified by constraint), pays stake record rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+
+    /// CHECK: Stake history sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: AccountInfo<'info>,
+
+    /// CHECK: Stake config account
+    #[account(address = stake::config::ID)]
+    pub stake_config: AccountInfo<'info>,
+
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeactivateStake<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"stake", cloaked_agent_state.key().as_ref(), stake_account.key().as_ref()],
+        bump = stake_record.bump,
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    /// CHECK: Bound by the stake record seeds; authority checked by the stake program
+    #[account(mut)]
+    pub stake_account: AccountInfo<'info>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"stake", cloaked_agent_state.key().as_ref(), stake_account.key().as_ref()],
+        bump = stake_record.bump,
+    )]
+    pub stake_record: Account<'info, StakeRecord>,
+
+    /// CHECK: Bound by the stake record seeds; authority checked by the stake program
+    #[account(mut)]
+    pub stake_account: AccountInfo<'info>,
+
+    /// Owner signing the transaction (verified by constraint), receives stake record rent
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: Stake history sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: AccountInfo<'info>,
+
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetSweepConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SweepSurplus<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match sweep_destination
+    /// CHECK: Verified by constraint
+    #[account(
+        mut,
+        constraint = cloaked_agent_state.sweep_destination == Some(sweep_destination.key()) @ ErrorCode::InvalidSweepDestination,
+    )]
+    pub sweep_destination: AccountInfo<'info>,
+
+    /// Anyone can crank the sweep and receives SWEEP_CRANK_INCENTIVE
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRescue<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Must match emergency_rescue_address
+    /// CHECK: Verified by constraint
+    #[account(
+        mut,
+        constraint = cloaked_agent_state.emergency_rescue_address == Some(rescue_destination.key()) @ ErrorCode::RescueNotAvailable,
+    )]
+    pub rescue_destination: AccountInfo<'info>,
+
+    /// Anyone can trigger the rescue once it is available
+    pub caller: Signer<'info>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRescue<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRecoveryDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeRecovery<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.recovery_delegate == Some(recovery_delegate.key()) @ ErrorCode::NotRecoveryDelegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        init,
+        payer = recovery_delegate,
+        space = RecoveryProposal::SIZE,
+        seeds = [b"recovery", cloaked_agent_state.key().as_ref()],
+        bump,
+    )]
+    pub recovery_proposal: Account<'info, RecoveryProposal>,
+
+    /// Must match cloaked_agent_state.recovery_delegate, pays proposal rent
+    #[account(mut)]
+    pub recovery_delegate: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRecovery<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.recovery_delegate == Some(recovery_delegate.key()) @ ErrorCode::NotRecoveryDelegate,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Proposal made by the current recovery delegate (rent returned on execute)
+    #[account(
+        mut,
+        close = recovery_delegate,
+        seeds = [b"recovery", cloaked_agent_state.key().as_ref()],
+        bump = recovery_proposal.bump,
+        constraint = recovery_proposal.proposer == recovery_delegate.key() @ ErrorCode::InvalidRecoveryProposal,
+    )]
+    pub recovery_proposal: Account<'info, RecoveryProposal>,
+
+    /// Must match cloaked_agent_state.recovery_delegate
+    #[account(mut)]
+    pub recovery_delegate: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRecovery<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = !cloaked_agent_state.is_private() @ ErrorCode::IsPrivateMode,
+        constraint = cloaked_agent_state.owner == Some(owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"recovery", cloaked_agent_state.key().as_ref()],
+        bump = recovery_proposal.bump,
+    )]
+    pub recovery_proposal: Account<'info, RecoveryProposal>,
+
+    /// Owner signing the transaction (verified by constraint)
+    pub owner: Signer<'info>,
+
+    /// Recovery delegate that paid for the proposal, receives rent
+    /// CHECK: Must match recovery_proposal.proposer
+    #[account(mut, address = recovery_proposal.proposer)]
+    pub proposer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetAgentSummary<'info> {
+    #[account(
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAgentState<'info> {
+    /// Agent state in a possibly legacy layout (cannot use Account<> until migrated)
+    /// CHECK: Program ownership checked here, discriminator checked on deserialize
+    #[account(mut, owner = crate::ID)]
+    pub cloaked_agent_state: AccountInfo<'info>,
+
+    /// Pays rent for the grown account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(creation_nonce: u64)]
+pub struct MigrateAgentAddress<'info> {
+    /// Agent at the legacy delegate-seeded address
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"cloaked_agent_state", legacy_state.delegate.as_ref()],
+        bump = legacy_state.bump,
+    )]
+    pub legacy_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", legacy_state.key().as_ref()],
+        bump,
+    )]
+    pub legacy_vault: SystemAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = CloakedAgentState::SIZE,
+        seeds = [b"cloaked_agent_state", authority.key().as_ref(), &creation_nonce.to_le_bytes()],
+        bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Owner (standard mode) or delegate (private mode), verified in instruction
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// === Private Mode Account Contexts ===
+
+#[derive(Accounts)]
+#[instruction(creation_nonce: u64)]
+pub struct CreateCloakedAgentPrivate<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = CloakedAgentState::SIZE,
+        seeds = [b"cloaked_agent_state", payer.key().as_ref(), &creation_nonce.to_le_bytes()],
+        bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Vault PDA to hold funds
+    #[account(
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Delegate key (agent's public key)
+    /// CHECK: Any pubkey can be delegate
+    pub delegate: AccountInfo<'info>,
+
+    /// Pays for account creation
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Parent agent, when creating a sub-agent
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Parent's delegate, must sign when parent_state is provided
+    pub parent_delegate: Option<Signer<'info>>,
+
+    /// Delegate's registry, when the new agent should be listed there
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", delegate.key().as_ref()],
+        bump = delegate_registry.bump,
+    )]
+    pub delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    /// Accepted verifiers, when commitment_version is not the built-in COMMITMENT_VERSION
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(creation_nonce: u64)]
+pub struct CreateCloakedAgentMultisig<'info> {
+    #[account(
+        init,
         payer = payer,
         space = CloakedAgentState::SIZE,
-        seeds = [b"cloaked_agent_state", delegate.key().as_ref()],
+        seeds = [b"cloaked_agent_state", payer.key().as_ref(), &creation_nonce.to_le_bytes()],
+        bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// Vault PDA to hold funds
+    #[account(
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
         bump,
     )]
+    pub vault: SystemAccount<'info>,
+
+    /// Delegate key (agent's public key)
+    /// CHECK: Any pubkey can be delegate
+    pub delegate: AccountInfo<'info>,
+
+    /// Pays for account creation
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Parent agent, when creating a sub-agent
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Parent's delegate, must sign when parent_state is provided
+    pub parent_delegate: Option<Signer<'info>>,
+
+    /// Delegate's registry, when the new agent should be listed there
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", delegate.key().as_ref()],
+        bump = delegate_registry.bump,
+    )]
+    pub delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMultisig<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+        constraint = cloaked_agent_state.is_multisig() @ ErrorCode::NotMultisig,
+        constraint = cloaked_agent_state.is_owner_key(&owner.key()) @ ErrorCode::NotOwner,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    /// One of the current owners; further co-owners sign as remaining accounts
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FreezePrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LockConstraintsPrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateProofVersionPrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetDepositsLockedPrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RotateCommitmentPrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDelegatePrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    /// CHECK: Any non-executable key other than the owner (verified in instruction)
+    pub new_delegate: AccountInfo<'info>,
+
+    /// Current delegate's registry, to drop the agent from it
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", cloaked_agent_state.delegate.as_ref()],
+        bump = old_delegate_registry.bump,
+    )]
+    pub old_delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    /// New delegate's registry, to list the agent in it
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", new_delegate.key().as_ref()],
+        bump = new_delegate_registry.bump,
+    )]
+    pub new_delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecoveryPrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnfreezePrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PanicPrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// Destination for remaining vault funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConstraintsPrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump = cloaked_agent_state.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCloakedAgentPrivate<'info> {
+    #[account(
+        mut,
+        close = fee_recipient,  // Rent goes to relayer to recover creation cost
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
+    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Destination for remaining vault funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// Fee recipient (relayer) - gets operation fee + account rent on close
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    /// Parent agent (required when closing a sub-agent)
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", parent_state.created_by.as_ref(), &parent_state.creation_nonce.to_le_bytes()],
+        bump = parent_state.bump,
+    )]
+    pub parent_state: Option<Account<'info, CloakedAgentState>>,
+
+    /// Delegate's registry, to drop the closed agent from it
+    #[account(
+        mut,
+        seeds = [b"delegate_registry", cloaked_agent_state.delegate.as_ref()],
+        bump = delegate_registry.bump,
+    )]
+    pub delegate_registry: Option<Account<'info, DelegateRegistry>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawPrivate<'info> {
+    #[account(
+        mut,
+        seeds = [b"cloaked_agent_state", cloaked_agent_state.created_by.as_ref(), &cloaked_agent_state.creation_nonce.to_le_bytes()],
+        bump = cloaked_agent_state.bump,
+    )]
     pub cloaked_agent_state: Account<'info, CloakedAgentState>,
 
-    /// Vault PDA to hold funds
-    #[account(
-        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
+        bump = cloaked_agent_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Destination for funds
+    /// CHECK: Any account can receive
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+
+    /// CHECK: Any account can receive fee reimbursement
+    #[account(mut)]
+    pub fee_recipient: AccountInfo<'info>,
+
+    /// ZK Verifier program for proof validation
+    /// CHECK: Verified in instruction to match ProgramConfig (or ZK_VERIFIER_PROGRAM_ID)
+    pub zk_verifier: AccountInfo<'info>,
+
+    /// Current verifier configuration (ZK_VERIFIER_PROGRAM_ID is used until it is
+    /// initialized); the instruction fails while it is paused
+    /// CHECK: PDA, loaded by unpaused_program_config
+    #[account(seeds = [b"program_config"], bump)]
+    pub program_config: UncheckedAccount<'info>,
+
+    /// Selects the proof format by verifier_version (v4 only when omitted)
+    #[account(
+        seeds = [b"verifier_registry"],
+        bump = verifier_registry.bump,
+    )]
+    pub verifier_registry: Option<Account<'info, VerifierRegistry>>,
+
+    /// Staged proof + witness read instead of the instruction args, then closed to
+    /// fee_recipient, which must be the buffer's submitter
+    #[account(
+        mut,
+        close = fee_recipient,
+        constraint = proof_buffer.submitter == fee_recipient.key() @ ErrorCode::ProofBufferMismatch,
+    )]
+    pub proof_buffer: Option<Account<'info, ProofBuffer>>,
+
+    /// CHECK: Nullifier PDA [b"nullifier", sha256(proof)], created by the instruction
+    #[account(mut)]
+    pub nullifier: UncheckedAccount<'info>,
+
+    /// Approved relayers (required when the agent set require_registered_relayer)
+    #[account(
+        seeds = [b"relayer_registry"],
+        bump = relayer_registry.bump,
+    )]
+    pub relayer_registry: Option<Account<'info, RelayerRegistry>>,
+
+    /// Instructions sysvar (required for StealthEd25519 agents) - used to inspect the
+    /// ed25519 verify instruction
+    /// CHECK: Address constraint
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// fee_recipient's stats, credited with the fee when passed
+    #[account(
+        mut,
+        seeds = [b"relayer_stats", fee_recipient.key().as_ref()],
+        bump = relayer_stats.bump,
+    )]
+    pub relayer_stats: Option<Account<'info, RelayerStats>>,
+
+    /// Protocol-wide counters (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"global_stats"],
+        bump = global_stats.bump,
+    )]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// === Events ===
+
+/// Emitted when the config admin proposes a successor
+#[event]
+pub struct AdminProposedEvent {
+    pub current_admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when the pending admin accepts the config
+#[event]
+pub struct AdminTransferredEvent {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when the config admin pauses or unpauses the program
+#[event]
+pub struct ProgramPausedEvent {
+    pub paused: bool,
+    pub timestamp: i64,
+}
+
+/// Emitted when the config admin changes the fees
+#[event]
+pub struct FeesUpdatedEvent {
+    pub private_operation_fee: u64,
+    pub spend_fee_reimbursement: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when an agent's owner locks or unlocks deposits
+#[event]
+pub struct DepositsLockedEvent {
+    pub agent: Pubkey,
+    pub locked: bool,
+    pub timestamp: i64,
+}
+
+/// Emitted when a private agent switches proof wire format
+#[event]
+pub struct ProofVersionMigratedEvent {
+    pub agent: Pubkey,
+    pub old_version: u8,
+    pub new_version: u8,
+    pub timestamp: i64,
+}
+
+/// Emitted when a note is locked in a NoteSet
+#[event]
+pub struct NoteDepositedEvent {
+    pub note_set: Pubkey,
+    pub leaf_index: u32,
+    pub commitment: [u8; 32],
+    /// NoteSet root after this note
+    pub root: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted when a note is claimed into a private agent's vault
+#[event]
+pub struct NoteClaimedEvent {
+    pub agent: Pubkey,
+    pub note_set: Pubkey,
+    pub note_nullifier: [u8; 32],
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted by every fee-charging private instruction with the fee actually charged
+#[event]
+pub struct PrivateOperationFeeEvent {
+    pub agent: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub fee: u64,
+    /// fee_recipient signed, relaying its own operation
+    pub self_relayed: bool,
+    pub timestamp: i64,
+}
+
+/// Emitted when the config admin proposes a new ZK verifier
+#[event]
+pub struct VerifierChangeProposedEvent {
+    pub current_verifier: Pubkey,
+    pub pending_verifier: Pubkey,
+    /// When apply_verifier can be called
+    pub eta: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the config admin starts a VerifierRegistry entry's removal timelock
+#[event]
+pub struct VerifierRemovalScheduledEvent {
+    pub verifier_program: Pubkey,
+    pub witness_version: u8,
+    /// When remove_verifier_entry can be called; proofs are accepted until then
+    pub removable_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a private agent's owner commitment is replaced
+#[event]
+pub struct CommitmentRotatedEvent {
+    pub agent: Pubkey,
+    pub new_commitment: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted when the delegate key is replaced
+#[event]
+pub struct DelegateUpdatedEvent {
+    pub agent: Pubkey,
+    pub old_delegate: Pubkey,
+    pub new_delegate: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when a private agent's recovery clock starts
+#[event]
+pub struct RecoveryInitiatedEvent {
+    pub agent: Pubkey,
+    /// When recover_private can be called; the owner can cancel until then
+    pub executable_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the owner cancels a pending recovery
+#[event]
+pub struct RecoveryCancelledEvent {
+    pub agent: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when recover_private installs a new owner commitment
+#[event]
+pub struct RecoveryCompletedEvent {
+    pub agent: Pubkey,
+    pub new_commitment: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted by every create variant
+/// created_by is the payer, the only accountable identity for private-mode agents
+#[event]
+pub struct AgentCreatedEvent {
+    pub agent: Pubkey,
+    /// None in private and multisig mode
+    pub owner: Option<Pubkey>,
+    pub delegate: Pubkey,
+    pub created_by: Pubkey,
+    pub parent: Option<Pubkey>,
+    pub max_per_tx: u64,
+    pub window_limit: u64,
+    pub total_limit: u64,
+    pub expires_at: i64,
+    /// Constraints can no longer be changed (see lock_constraints)
+    pub constraints_locked: bool,
+    /// Always zero at creation (see set_owner_notes / set_delegate_notes)
+    pub owner_notes: [u8; 32],
+    pub delegate_notes: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted on every deposit for inflow reconciliation
+#[event]
+pub struct DepositEvent {
+    pub agent: Pubkey,
+    pub depositor: Pubkey,
+    pub amount: u64,
+    pub total_deposited: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    /// Vault lamports after the transfer - lets dashboards track balance without polling
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted on deposit_with_memo for payment reference tracking
+#[event]
+pub struct DepositMemoEvent {
+    pub agent: Pubkey,
+    pub depositor: Pubkey,
+    pub amount: u64,
+    pub memo: [u8; 32],
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Snapshot of agent state emitted by get_agent_summary
+/// Remaining allowances are u64::MAX when the limit is unlimited
+#[event]
+pub struct AgentSummaryEvent {
+    pub agent: Pubkey,
+    pub vault_balance: u64,
+    pub window_remaining: u64,
+    pub total_remaining: u64,
+    pub is_frozen: bool,
+    pub is_expired: bool,
+    pub total_deposited: u64,
+    pub total_withdrawn: u64,
+    /// total_deposited - total_spent - total_withdrawn
+    pub net_position: i128,
+    pub seconds_until_reset: i64,
+    /// Constraints can no longer be changed (see lock_constraints)
+    pub constraints_locked: bool,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub owner_notes: [u8; 32],
+    pub delegate_notes: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted when an agent is frozen by the owner or by its own delegate
+#[event]
+pub struct FreezeEvent {
+    pub agent: Pubkey,
+    /// True when the delegate froze itself (freeze_by_delegate)
+    pub by_delegate: bool,
+    pub freeze_reason: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted when a spend trips conditional_freeze_threshold and freezes the agent
+#[event]
+pub struct AutoFreezeEvent {
+    pub agent: Pubkey,
+    /// Same value recorded in freeze_reason (AUTO_FREEZE_LOW_BALANCE)
+    pub reason: [u8; 32],
+    pub vault_balance: u64,
+    pub threshold: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a spend empties an auto_close_on_zero agent's vault and closes the agent
+#[event]
+pub struct AgentAutoClosedEvent {
+    pub agent: Pubkey,
+    /// AUTO_CLOSE_ZERO_BALANCE
+    pub reason: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted once per batch_freeze with every agent it froze
+#[event]
+pub struct BatchFreezeEvent {
+    pub owner: Pubkey,
+    pub agents: Vec<Pubkey>,
+    /// Same value recorded in each agent's freeze_reason
+    pub freeze_reason: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Emitted when a timelocked withdrawal is queued
+#[event]
+pub struct TimelockedWithdrawalProposedEvent {
+    pub agent: Pubkey,
+    pub timelock: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub eta: i64,
+    pub pending_timelocks: u8,
+    pub timestamp: i64,
+}
+
+/// Emitted when a pending timelocked withdrawal is cancelled
+#[event]
+pub struct TimelockedWithdrawalCancelledEvent {
+    pub agent: Pubkey,
+    pub timelock: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the withdraw destination lock is set or a change is queued
+#[event]
+pub struct DestinationLockProposedEvent {
+    pub agent: Pubkey,
+    pub current_lock: Option<Pubkey>,
+    pub pending_lock: Option<Pubkey>,
+    /// When the pending change can be applied (0 = nothing pending)
+    pub eta: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when an agent is frozen and drained in one instruction
+#[event]
+pub struct PanicEvent {
+    pub agent: Pubkey,
+    pub destination: Pubkey,
+    /// Lamports sent to destination (after operation fee in private mode)
+    pub amount: u64,
+    pub private: bool,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when an expired agent is reclaimed by a cranker
+#[event]
+pub struct AgentReclaimedEvent {
+    pub agent: Pubkey,
+    pub owner: Pubkey,
+    pub cranker: Pubkey,
+    /// Lamports returned to owner (excluding state rent)
+    pub refunded: u64,
+    pub incentive: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when stake_from_vault delegates vault SOL
+#[event]
+pub struct StakeDelegatedEvent {
+    pub agent: Pubkey,
+    pub stake_account: Pubkey,
+    pub vote_account: Pubkey,
+    pub amount: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when withdraw_stake returns a stake account's balance to the vault
+#[event]
+pub struct StakeWithdrawnEvent {
+    pub agent: Pubkey,
+    pub stake_account: Pubkey,
+    pub principal: u64,
+    /// Principal plus rewards
+    pub withdrawn: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when sweep_surplus returns vault balance above sweep_target
+#[event]
+pub struct SurplusSweptEvent {
+    pub agent: Pubkey,
+    pub destination: Pubkey,
+    pub cranker: Pubkey,
+    /// Lamports sent to destination (surplus minus incentive)
+    pub amount: u64,
+    pub incentive: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when execute_rescue drains a vault to its emergency_rescue_address
+#[event]
+pub struct RescueExecutedEvent {
+    pub agent: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the recovery delegate starts an ownership recovery
+#[event]
+pub struct RecoveryProposedEvent {
+    pub agent: Pubkey,
+    pub new_owner: Pubkey,
+    pub executable_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when execute_recovery replaces the owner
+#[event]
+pub struct OwnershipRecoveredEvent {
+    pub agent: Pubkey,
+    pub previous_owner: Option<Pubkey>,
+    pub new_owner: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted on every spend (all delegate, session and sub-agent paths)
+#[event]
+pub struct SpentEvent {
+    pub agent: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    /// Delegate commission paid on top of amount
+    pub commission: u64,
+    /// Sequence number of this spend (agent's spend_nonce after increment)
+    pub spend_nonce: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when spend_via_program completes its CPI
+#[event]
+pub struct ProgramSpendEvent {
+    pub agent: Pubkey,
+    pub target_program: Pubkey,
+    /// Amount charged against limits
+    pub amount: u64,
+    /// Lamports the vault actually lost during the CPI (at most amount)
+    pub vault_outflow: u64,
+    pub spend_nonce: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when release_escrow forwards an escrowed spend
+#[event]
+pub struct EscrowReleasedEvent {
+    pub agent: Pubkey,
+    pub escrow: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the owner returns an escrowed spend to the vault
+#[event]
+pub struct EscrowRevokedEvent {
+    pub agent: Pubkey,
+    pub escrow: Pubkey,
+    pub amount: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted on owner withdrawal (standard mode)
+#[event]
+pub struct WithdrawEvent {
+    pub agent: Pubkey,
+    pub owner: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted on ZK-authorized withdrawal (private mode, no owner identity)
+#[event]
+pub struct PrivateWithdrawEvent {
+    pub agent: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    /// Vault balance snapshot recorded by the previous state-mutating instruction
+    pub vault_balance_before: u64,
+    pub vault_balance_after: u64,
+    pub timestamp: i64,
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("Insufficient balance in agent")]
+    InsufficientBalance,
+    #[msg("Arithmetic overflow")]
+    Overflow,
+    #[msg("Agent is frozen")]
+    AgentFrozen,
+    #[msg("Agent has expired")]
+    AgentExpired,
+    #[msg("Amount exceeds per-transaction limit")]
+    ExceedsPerTxLimit,
+    #[msg("Amount exceeds spending window limit")]
+    ExceedsWindowLimit,
+    #[msg("Amount exceeds total limit")]
+    ExceedsTotalLimit,
+    #[msg("Unauthorized: not owner")]
+    NotOwner,
+    #[msg("Agent is in private mode - use ZK proof instructions")]
+    IsPrivateMode,
+    #[msg("Agent is not in private mode")]
+    NotPrivateMode,
+    #[msg("Commitment mismatch in ZK proof")]
+    CommitmentMismatch,
+    #[msg("Invalid ZK proof")]
+    InvalidProof,
+    #[msg("ZK proof verification failed")]
+    ProofVerificationFailed,
+    #[msg("Invalid ZK verifier program")]
+    InvalidVerifierProgram,
+    #[msg("Insufficient balance for operation fee")]
+    InsufficientBalanceForFee,
+    #[msg("Invalid commitment: cannot be all zeros")]
+    InvalidCommitment,
+    #[msg("Agent is not past its expiry grace period")]
+    NotReclaimable,
+    #[msg("Inconsistent limits: require min_spend_amount <= max_per_tx <= window_limit <= total_limit")]
+    InconsistentLimits,
+    #[msg("Invalid expiration: must be 0 (never) or a future timestamp")]
+    InvalidExpiration,
+    #[msg("Agent state is already at the current version")]
+    AlreadyMigrated,
+    #[msg("Invalid spending window: window_seconds must be between 1 and i64::MAX")]
+    InvalidSpendingWindow,
+    #[msg("Deposit would exceed the vault balance cap")]
+    ExceedsVaultCap,
+    #[msg("Commission cannot exceed 10_000 basis points")]
+    InvalidCommissionBps,
+    #[msg("Commission recipient missing or does not match agent configuration")]
+    InvalidCommissionRecipient,
+    #[msg("Deposit amount below agent minimum")]
+    DepositTooSmall,
+    #[msg("Amount below minimum spend threshold")]
+    BelowMinSpend,
+    #[msg("Destination is not whitelisted for this agent")]
+    DestinationNotWhitelisted,
+    #[msg("Whitelist entries still exist - pass them in remaining_accounts to close")]
+    HasOrphanedPDAs,
+    #[msg("Too many whitelist entries for a single transaction")]
+    TooManyWhitelistEntries,
+    #[msg("Account is not a whitelist entry of this agent")]
+    InvalidWhitelistEntry,
+    #[msg("Session has expired")]
+    SessionExpired,
+    #[msg("Session budget exhausted")]
+    SessionBudgetExhausted,
+    #[msg("Parent agent missing, mismatched or not allowed")]
+    InvalidParentAgent,
+    #[msg("Agent still has sub-agents")]
+    HasChildAgents,
+    #[msg("Funds can only be sent to the locked withdraw destination")]
+    DestinationLocked,
+    #[msg("Destination lock change is still timelocked or not pending")]
+    DestinationLockTimelocked,
+    #[msg("ZK verifier account is not an executable program")]
+    VerifierNotExecutable,
+    #[msg("ZK verifier program is not deployed on this cluster")]
+    VerifierNotDeployed,
+    #[msg("Multisig needs 1-5 distinct owners and a threshold between 1 and the owner count")]
+    InvalidMultisigConfig,
+    #[msg("Not enough multisig owners signed")]
+    MultisigThresholdNotMet,
+    #[msg("Agent is not a multisig agent")]
+    NotMultisig,
+    #[msg("No Ethereum delegate address configured")]
+    EthDelegateNotSet,
+    #[msg("secp256k1 signature does not match the delegate Ethereum address")]
+    InvalidSecpSignature,
+    #[msg("secp256k1 spend nonce must be greater than the last used nonce")]
+    InvalidSecpNonce,
+    #[msg("Signed authorization has passed its deadline")]
+    SignatureExpired,
+    #[msg("Instruction is not allowed for the delegate")]
+    InstructionNotAllowed,
+    #[msg("batch_freeze takes between 1 and 8 agents")]
+    TooManyBatchAgents,
+    #[msg("Batch agent accounts must be writable and match agent_keys")]
+    InvalidBatchAgent,
+    #[msg("No passkey delegate configured")]
+    PasskeyDelegateNotSet,
+    #[msg("Passkey spend nonce must be greater than the last used nonce")]
+    InvalidPasskeyNonce,
+    #[msg("Signature verify instruction must immediately precede this instruction")]
+    MissingSignatureInstruction,
+    #[msg("Signature verify instruction does not match the expected key or message")]
+    InvalidSignatureInstruction,
+    #[msg("Spend intent does not match this agent or destination, or its nonce was used")]
+    InvalidSpendIntent,
+    #[msg("Rescue delay must be at least 30 days")]
+    InvalidRescueConfig,
+    #[msg("No emergency rescue configured or rescue delay has not passed")]
+    RescueNotAvailable,
+    #[msg("Spending is not allowed at this time of day")]
+    OutsideSpendWindow,
+    #[msg("Spend hours must be seconds of day below 86400 with start != end")]
+    InvalidSpendHours,
+    #[msg("Constraints are permanently locked")]
+    ConstraintsLocked,
+    #[msg("Spending is not allowed on this day of the week")]
+    DayNotAllowed,
+    #[msg("Days bitmask only has bits 0 (Sunday) to 6 (Saturday)")]
+    InvalidDaysBitmask,
+    #[msg("Signer is not the recovery delegate")]
+    NotRecoveryDelegate,
+    #[msg("Recovery proposal does not match")]
+    InvalidRecoveryProposal,
+    #[msg("Recovery delay has not passed")]
+    RecoveryTimelocked,
+    #[msg("Sweeping is disabled or the vault surplus does not cover the crank incentive")]
+    NoSurplusToSweep,
+    #[msg("Account is not the configured sweep destination")]
+    InvalidSweepDestination,
+    #[msg("Schedule amount and interval must be non-zero")]
+    InvalidSchedule,
+    #[msg("Scheduled payment is not due yet")]
+    ScheduleNotDue,
+    #[msg("Voucher amount must be non-zero and expiry in the future")]
+    InvalidVoucher,
+    #[msg("Voucher has expired")]
+    VoucherExpired,
+    #[msg("Voucher has not expired")]
+    VoucherNotExpired,
+    #[msg("Price feed is not a fully verified Pyth price update for the requested feed")]
+    InvalidPriceFeed,
+    #[msg("Oracle price is too old")]
+    StalePrice,
+    #[msg("Price condition not met")]
+    ConditionNotMet,
+    #[msg("Escrow release time must be in the future")]
+    InvalidEscrow,
+    #[msg("Escrow cannot be released before release_after")]
+    EscrowLocked,
+    #[msg("Escrow is releasable and can no longer be revoked")]
+    EscrowReleasable,
+    #[msg("Stake amount must cover the stake account rent")]
+    StakeTooSmall,
+    #[msg("Agent has vault SOL staked - withdraw stake before closing")]
+    HasActiveStake,
+    #[msg("Account is not this destination's stats PDA")]
+    InvalidDestinationStats,
+    #[msg("CPI target took more than the spend amount from the vault or modified it")]
+    ProgramSpendExceeded,
+    #[msg("Proof witness does not commit to the agent's current proof nonce")]
+    ProofNonceMismatch,
+    #[msg("Proof was made for a different action")]
+    ActionMismatch,
+    #[msg("Proof does not commit to this instruction's parameters")]
+    ActionDataMismatch,
+    #[msg("Delegate is an executable program; use create_cloaked_agent_with_program_delegate")]
+    DelegateIsProgram,
+    #[msg("Agent does not have a program delegate")]
+    NotProgramDelegate,
+    #[msg("Signer is not the program upgrade authority")]
+    NotAdmin,
+    #[msg("Proof does not commit to these constraint parameters")]
+    ParamsMismatch,
+    #[msg("Agent is not delegated to this registry's key")]
+    InvalidDelegateRegistry,
+    #[msg("Agent is already in the delegate registry")]
+    AgentAlreadyRegistered,
+    #[msg("Delegate registry is full")]
+    DelegateRegistryFull,
+    #[msg("Too many pending timelocked withdrawals")]
+    TooManyPendingTimelocks,
+    #[msg("max_concurrent_timelocks must be between 1 and MAX_CONCURRENT_TIMELOCKS")]
+    InvalidTimelockCap,
+    #[msg("Timelocked withdrawal is not yet executable")]
+    WithdrawalTimelocked,
+    #[msg("Agent has pending timelocked withdrawals - execute or cancel them before closing")]
+    HasPendingTimelocks,
+    #[msg("Proof is not PROOF_SIZE bytes")]
+    InvalidProofSize,
+    #[msg("Witness is not WITNESS_SIZE bytes")]
+    InvalidWitnessSize,
+    #[msg("Witness header does not match the supported witness layout version")]
+    UnsupportedWitnessVersion,
+    #[msg("Spend requires the agent's 2FA key to co-sign")]
+    Invalid2FA,
+    #[msg("2FA is already configured; remove it first")]
+    TwoFAAlreadySet,
+    #[msg("Verifier change is still timelocked or not pending")]
+    VerifierChangeTimelocked,
+    #[msg("Spend would leave the vault below the delegate reserve")]
+    WouldViolateDelegateReserve,
+    #[msg("post_spend_hook program missing from remaining accounts or not executable")]
+    InvalidSpendHook,
+    #[msg("No active verifier registry entry for this verifier_version")]
+    UnknownVerifierVersion,
+    #[msg("Verifier registry already has an entry for this witness version")]
+    VerifierVersionExists,
+    #[msg("Verifier registry is full")]
+    VerifierRegistryFull,
+    #[msg("Proof buffer is for a different agent, action or submitter")]
+    ProofBufferMismatch,
+    #[msg("Write exceeds the proof buffer")]
+    ProofBufferOverflow,
+    #[msg("New commitment equals the current one")]
+    CommitmentUnchanged,
+    #[msg("Exceeds sliding window spending limit")]
+    ExceedsSlidingWindowLimit,
+    #[msg("Agent has no recovery commitment")]
+    NoRecoveryCommitment,
+    #[msg("Recovery already initiated")]
+    RecoveryAlreadyInitiated,
+    #[msg("No recovery pending")]
+    RecoveryNotInitiated,
+    #[msg("Delegate cannot be the owner")]
+    DelegateIsOwner,
+    #[msg("new_delegate account does not match the new_delegate argument")]
+    InvalidNewDelegate,
+    #[msg("Proof already used")]
+    ProofAlreadyUsed,
+    #[msg("Nullifier account does not match the proof")]
+    InvalidNullifier,
+    #[msg("Nullifier retention period has not elapsed")]
+    NullifierNotExpired,
+    #[msg("fee_recipient is not an approved relayer")]
+    UnauthorizedRelayer,
+    #[msg("Relayer is already approved")]
+    RelayerAlreadyRegistered,
+    #[msg("Relayer registry is full")]
+    RelayerRegistryFull,
+    #[msg("Fee exceeds its ceiling")]
+    FeeTooHigh,
+    #[msg("Daily private operation limit reached")]
+    TooManyPrivateOps,
+    #[msg("Program whitelist entry does not belong to this agent")]
+    InvalidProgramWhitelistEntry,
+    #[msg("Destination is blacklisted")]
+    DestinationBlacklisted,
+    #[msg("Blacklist entry does not match this agent and destination")]
+    InvalidBlacklistEntry,
+    #[msg("No accepted verifier uses this commitment version")]
+    UnknownCommitmentVersion,
+    #[msg("Proof verifier uses a different commitment version than the agent")]
+    CommitmentVersionMismatch,
+    #[msg("Note set is full")]
+    NoteSetFull,
+    #[msg("Root is not a recent note set root")]
+    UnknownNoteRoot,
+    #[msg("No note verifier is configured")]
+    NoteVerifierNotSet,
+    #[msg("Unsupported proof format version")]
+    UnsupportedProofVersion,
+    #[msg("Agent already uses this proof format version")]
+    ProofVersionUnchanged,
+    #[msg("Signer is not the pending config admin")]
+    NotPendingAdmin,
+    #[msg("Agent is not accepting deposits")]
+    DepositsLocked,
+    #[msg("Program is paused")]
+    ProgramPaused,
+}
+
+/// Cloaked Agent state - stores constraints and spending tracking
+///
+/// Supports two ownership modes:
+/// - Standard mode: owner = Some(wallet), owner_commitment = [0; 32]
+/// - Private mode: owner = None, owner_commitment = hash(secret)
+#[account]
+pub struct CloakedAgentState {
+    /// Human wallet - full control over agent (None for private mode)
+    pub owner: Option<Pubkey>,
+    /// Commitment hash for private mode ownership (zeros for standard mode)
+    /// In private mode: commitment = poseidon(agent_secret) (see commitment_version)
+    pub owner_commitment: [u8; 32],
+    /// Agent key - can spend within limits
+    pub delegate: Pubkey,
+
+    /// Max lamports per transaction (0 = unlimited)
+    pub max_per_tx: u64,
+    /// Superseded by spending_window; kept for layout compatibility (zeroed on migration)
+    pub legacy_daily_limit: u64,
+    /// Max lifetime lamports (0 = unlimited)
+    pub total_limit: u64,
+    /// Unix timestamp expiration (0 = never)
+    pub expires_at: i64,
+    /// Emergency stop
+    pub frozen: bool,
+
+    /// Lifetime spending
+    pub total_spent: u64,
+    /// Superseded by spending_window; kept for layout compatibility (zeroed on migration)
+    pub legacy_daily_spent: u64,
+    /// Superseded by spending_window; kept for layout compatibility (zeroed on migration)
+    pub legacy_last_day: i64,
+
+    /// PDA bump
+    pub bump: u8,
+    /// Creation timestamp
+    pub created_at: i64,
+
+    /// Lifetime owner withdrawals (withdraw + panic)
+    pub total_withdrawn: u64,
+    /// Lifetime deposits into the vault
+    pub total_deposited: u64,
+
+    /// Layout version (0 = legacy account created before versioning)
+    pub state_version: u8,
+
+    /// Per-window spending limit (replaces the fixed daily limit)
+    pub spending_window: SpendingWindow,
+
+    /// Max lamports the vault may hold after a deposit (0 = unlimited)
+    /// Only blocks deposits - lowering below the current balance is allowed
+    pub max_vault_balance: u64,
+
+    /// Service fee paid to the commission recipient on each spend (basis points, 0 = none)
+    pub delegate_commission_bps: u16,
+    /// Commission recipient (None = no commission)
+    pub delegate_commission_recipient: Option<Pubkey>,
+
+    /// Minimum deposit in base units (0 = no minimum) - prevents dust griefing
+    pub min_deposit: u64,
+    /// Minimum lamports per spend (0 = no minimum)
+    pub min_spend_amount: u64,
+    /// Reject deposits while frozen or expired (default off)
+    pub block_deposits_when_inactive: bool,
+    /// Number of live WhitelistEntry PDAs (0 = any destination allowed)
+    pub whitelist_entry_count: u8,
+
+    /// Payer that created the agent - first PDA seed
+    /// Also the only accountable identity of a private-mode agent (see AgentCreatedEvent)
+    pub created_by: Pubkey,
+    /// Creator-chosen nonce - second PDA seed, so several agents can share a delegate key
+    pub creation_nonce: u64,
+
+    /// Let the delegate create session keys (owner can always)
+    pub allow_delegate_sessions: bool,
+
+    /// Parent agent whose window and total limits this sub-agent's spends also count against
+    pub parent: Option<Pubkey>,
+    /// Number of live sub-agents (agent cannot close while non-zero)
+    pub child_count: u16,
+
+    /// Vault lamports at the end of the last instruction that touched the vault
+    /// Cheap estimate for clients - getBalance(vault) is authoritative
+    pub vault_balance_snapshot: u64,
+    /// When vault_balance_snapshot was recorded
+    pub snapshot_timestamp: i64,
+
+    /// Set when the delegate froze itself; cleared on unfreeze
+    pub frozen_by_delegate: bool,
+    /// Caller-supplied reason for the current freeze (zeros when unfrozen)
+    pub freeze_reason: [u8; 32],
+
+    /// Only address owner withdrawals, panic, close and reclaim may pay out to (None = any)
+    pub withdraw_destination_lock: Option<Pubkey>,
+    /// Queued replacement for withdraw_destination_lock
+    pub pending_destination_lock: Option<Pubkey>,
+    /// When the pending lock change can be applied (0 = nothing pending)
+    pub pending_destination_lock_eta: i64,
+
+    /// Number of successful spends - sequence number for off-chain correlation
+    pub spend_nonce: u64,
+
+    /// Multisig owner keys (first multisig_owner_count are live, rest zeroed)
+    pub multisig_owners: [Pubkey; 5],
+    /// Number of registered multisig owners
+    pub multisig_owner_count: u8,
+    /// Owner signatures required for owner-gated instructions (0 = not a multisig agent)
+    pub multisig_threshold: u8,
+
+    /// Ethereum address allowed to authorize spend_with_secp ([0; 20] = disabled)
+    pub delegate_eth_address: [u8; 20],
+    /// Last nonce used by spend_with_secp
+    pub delegate_eth_nonce: u64,
+
+    /// Bitmask of delegate-signed instructions the primary delegate may call (see INSTRUCTION_BIT_*)
+    pub instruction_whitelist: u16,
+
+    /// Compressed P-256 passkey allowed to authorize spend_with_passkey ([0; 33] = disabled)
+    pub delegate_p256_pubkey: [u8; 33],
+    /// Last nonce used by spend_with_passkey
+    pub delegate_p256_nonce: u64,
+
+    /// Last nonce used by spend_signed
+    pub delegate_intent_nonce: u64,
+
+    /// Immutable last-resort payout address for execute_rescue (None = no rescue)
+    pub emergency_rescue_address: Option<Pubkey>,
+    /// Owner inactivity period before execute_rescue is allowed
+    pub rescue_delay: i64,
+    /// When execute_rescue becomes callable; pushed back by cancel_rescue
+    pub rescue_available_at: i64,
+
+    /// Start of the allowed UTC spend window in seconds of day (both zero = any time)
+    pub allowed_start_second_of_day: u32,
+    /// End (exclusive) of the allowed UTC spend window; earlier than start wraps midnight
+    pub allowed_end_second_of_day: u32,
+
+    /// Set once by lock_constraints; update_constraints paths fail afterwards
+    pub constraints_locked: bool,
+
+    /// UTC weekdays spending is allowed on, bit 0 = Sunday .. bit 6 = Saturday (0 = every day)
+    pub allowed_days_bitmask: u8,
+
+    /// Max unused window allowance that carries over to later windows (0 = no rollover)
+    pub rollover_cap: u64,
+    /// Banked allowance from earlier windows, spent before the current window
+    pub accrued_allowance: u64,
+
+    /// Freeze automatically when a spend leaves the vault below this (0 = disabled)
+    pub conditional_freeze_threshold: u64,
+
+    /// Key that can transfer ownership after RECOVERY_DELAY (never spend)
+    pub recovery_delegate: Option<Pubkey>,
+
+    /// Vault balance sweep_surplus leaves behind
+    pub sweep_target: u64,
+    /// Where sweep_surplus sends the surplus (None = sweeping disabled)
+    pub sweep_destination: Option<Pubkey>,
+
+    /// Vault PDA bump, stored so vault signing skips the bump search
+    pub vault_bump: u8,
+
+    /// Principal held in stake accounts (outside the vault, not spendable)
+    pub staked_amount: u64,
+
+    /// Nonce the next private-mode proof must commit to (incremented on each use)
+    pub proof_nonce: u64,
+
+    /// Delegate is a program that spends via CPI (spend_as_program_delegate)
+    pub program_delegate: bool,
+
+    /// Cap on pending timelocked withdrawals (0 = DEFAULT_MAX_CONCURRENT_TIMELOCKS)
+    pub max_concurrent_timelocks: u8,
+
+    /// Timelocked withdrawals proposed and not yet executed or cancelled
+    pub pending_timelocks: u8,
+
+    /// Nonce for the next TimelockRecord PDA
+    pub timelock_nonce: u64,
+
+    /// Vault lamports held back for pending timelocked withdrawals
+    pub reserved_lamports: u64,
+    /// Key that must co-sign spends at or above two_fa_threshold (spend_with_2fa)
+    pub two_fa_key: Option<Pubkey>,
+
+    /// Spends of at least this many lamports need two_fa_key (when set)
+    pub two_fa_threshold: u64,
+
+    /// Working balance delegate spends must leave in the vault (0 = no minimum)
+    pub minimum_vault_balance_for_delegation: u64,
+
+    /// Program notified via CPI after each spend (None = no hook)
+    pub post_spend_hook: Option<Pubkey>,
+
+    /// Informational bytes set by the owner (set_owner_notes)
+    pub owner_notes: [u8; 32],
+
+    /// Informational bytes set by the delegate (set_delegate_notes)
+    pub delegate_notes: [u8; 32],
+
+    /// Sliding window length in seconds, checked alongside spending_window
+    pub sliding_window_seconds: u64,
+
+    /// Max lamports spent in any sliding_window_seconds (0 = disabled)
+    pub sliding_window_limit: u64,
+
+    /// Ring buffer of recent spend amounts (see record_sliding_window_spend)
+    pub recent_spend_amounts: [u64; 8],
+
+    /// Timestamps matching recent_spend_amounts (0 = empty slot)
+    pub recent_spend_times: [i64; 8],
+
+    /// Next ring slot to write
+    pub ring_head: u8,
+
+    /// Backup commitment accepted only by the delayed recovery flow (zeros = none)
+    pub recovery_commitment: [u8; 32],
+
+    /// When initiate_recovery_private started the PRIVATE_RECOVERY_DELAY clock (0 = none pending)
+    pub recovery_initiated_at: i64,
+
+    /// Private instructions only reimburse relayers listed in the RelayerRegistry (set at creation)
+    pub require_registered_relayer: bool,
+
+    /// Close the agent when a `spend` leaves the vault empty (set at creation)
+    /// Only `spend` honors it; other spend paths leave an emptied agent open. The
+    /// agent also stays open while close_cloaked_agent would refuse to close it
+    pub auto_close_on_zero: bool,
+
+    /// UTC day (unix_timestamp / SECONDS_PER_DAY) private_ops_today counts
+    pub private_ops_day: i64,
+
+    /// Fee-charging private operations performed on private_ops_day
+    pub private_ops_today: u16,
+
+    /// Max fee-charging private operations per UTC day (0 = DEFAULT_MAX_PRIVATE_OPS_PER_DAY)
+    pub max_private_ops_per_day: u16,
+
+    /// Number of live ProgramWhitelistEntry PDAs
+    pub program_whitelist_entry_count: u8,
+
+    /// How the private-mode owner proves control of owner_commitment (set at creation)
+    pub commitment_scheme: CommitmentScheme,
+
+    /// Number of live BlacklistEntry PDAs
+    pub blacklist_entry_count: u8,
+
+    /// Scheme that produced owner_commitment / recovery_commitment (0 = COMMITMENT_VERSION)
+    /// Proofs are only accepted from verifiers for the same scheme
+    pub commitment_version: u8,
+
+    /// Wire format of the agent's proofs (0 = 1, see CURRENT_PROOF_FORMAT_VERSION)
+    pub proof_format_version: u8,
+
+    /// Deposits are refused while set; spending and withdrawals are unaffected
+    pub deposits_locked: bool,
+}
+
+/// Allowed spend destination for an agent
+/// PDA: [b"whitelist", cloaked_agent_state, destination]
+#[account]
+pub struct WhitelistEntry {
+    /// Agent this entry belongs to
+    pub agent: Pubkey,
+    /// Whitelisted destination
+    pub destination: Pubkey,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl WhitelistEntry {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // destination
+        + 1; // bump
+}
+
+/// Owner withdrawal queued behind DESTINATION_LOCK_TIMELOCK
+/// PDA: [b"timelock", cloaked_agent_state, nonce]
+#[account]
+pub struct TimelockRecord {
+    /// Agent the withdrawal draws from
+    pub agent: Pubkey,
+    /// Where the funds go on execution
+    pub destination: Pubkey,
+    /// Lamports reserved in the vault for this withdrawal
+    pub amount: u64,
+    /// Earliest execution time
+    pub eta: i64,
+    /// timelock_nonce at proposal (PDA seed)
+    pub nonce: u64,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl TimelockRecord {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // destination
+        + 8 // amount
+        + 8 // eta
+        + 8 // nonce
+        + 1; // bump
+}
+
+/// Allowed owner program for spend destinations
+/// PDA: [b"program_wl", cloaked_agent_state, program]
+#[account]
+pub struct ProgramWhitelistEntry {
+    /// Agent this entry belongs to
+    pub agent: Pubkey,
+    /// Program whose accounts may receive spends
+    pub program: Pubkey,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl ProgramWhitelistEntry {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // program
+        + 1; // bump
+}
+
+/// Blocked spend destination for an agent
+/// PDA: [b"blacklist", cloaked_agent_state, destination]
+#[account]
+pub struct BlacklistEntry {
+    /// Agent this entry belongs to
+    pub agent: Pubkey,
+    /// Blacklisted destination
+    pub destination: Pubkey,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl BlacklistEntry {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // destination
+        + 1; // bump
+}
+
+/// Per-destination payment totals recorded by spend
+/// PDA: [b"dest_stats", cloaked_agent_state, destination]
+#[account]
+pub struct DestinationStats {
+    /// Agent paying the destination
+    pub agent: Pubkey,
+    /// Destination these totals are for
+    pub destination: Pubkey,
+    /// Lamports sent (excluding commission and fee reimbursement)
+    pub total_sent: u64,
+    /// Number of spends recorded
+    pub sent_count: u64,
+    /// Timestamp of the last recorded spend
+    pub last_sent_at: i64,
+    /// PDA bump
+    pub bump: u8,
+}
+
+/// Protocol-wide counters, updated by instructions that are passed this PDA
+/// PDA: [b"global_stats"]
+#[account]
+pub struct GlobalStats {
+    /// Agents created (all create variants)
+    pub total_agents_created: u64,
+    /// Agents closed or reclaimed
+    pub total_agents_closed: u64,
+    /// Lamports charged by spends (including commission)
+    pub total_lamports_spent: u64,
+    /// Lamports deposited into vaults
+    pub total_lamports_deposited: u64,
+    /// Lamports withdrawn from vaults (withdraw, panic, sweep, rescue)
+    pub total_lamports_withdrawn: u64,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl GlobalStats {
+    pub const SIZE: usize = 8 // discriminator
+        + 8 // total_agents_created
+        + 8 // total_agents_closed
+        + 8 // total_lamports_spent
+        + 8 // total_lamports_deposited
+        + 8 // total_lamports_withdrawn
+        + 1; // bump
+
+    pub fn record_agent_created(&mut self) -> Result<()> {
+        self.total_agents_created = self.total_agents_created
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    pub fn record_agent_closed(&mut self) -> Result<()> {
+        self.total_agents_closed = self.total_agents_closed
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    pub fn record_spent(&mut self, amount: u64) -> Result<()> {
+        self.total_lamports_spent = self.total_lamports_spent
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    pub fn record_deposited(&mut self, amount: u64) -> Result<()> {
+        self.total_lamports_deposited = self.total_lamports_deposited
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    pub fn record_withdrawn(&mut self, amount: u64) -> Result<()> {
+        self.total_lamports_withdrawn = self.total_lamports_withdrawn
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+}
+
+/// Program-wide settings, created once by the upgrade authority
+/// PDA: [b"program_config"]
+#[account]
+pub struct ProgramConfig {
+    /// Authority allowed to change the config
+    pub admin: Pubkey,
+    /// ZK verifier program private-mode proofs are checked with
+    pub verifier_program: Pubkey,
+    /// Verifier replaced by the last apply_verifier, accepted during the migration window
+    pub previous_verifier: Option<Pubkey>,
+    /// previous_verifier is accepted until this timestamp
+    pub previous_verifier_valid_until: i64,
+    /// Verifier proposed by set_verifier
+    pub pending_verifier: Option<Pubkey>,
+    /// When pending_verifier can be applied (0 = nothing pending)
+    pub pending_verifier_eta: i64,
+    /// PDA bump
+    pub bump: u8,
+    /// Relayer fee charged by private instructions (at most MAX_PRIVATE_OPERATION_FEE),
+    /// on top of the Nullifier rent the vault funds
+    pub private_operation_fee: u64,
+    /// Fee payer reimbursement paid by the delegate spend paths (at most MAX_SPEND_FEE_REIMBURSEMENT)
+    pub spend_fee_reimbursement: u64,
+    /// Verifier for claim_note_private proofs (default = note claims disabled)
+    pub note_verifier: Pubkey,
+    /// Share of the private operation fee charged to self-relayed operations, in bps
+    pub self_relay_fee_bps: u16,
+    /// Admin proposed by propose_admin, takes over once it calls accept_admin
+    pub pending_admin: Option<Pubkey>,
+    /// Spends and fee-charging private instructions are refused while set
+    pub paused: bool,
+}
+
+impl ProgramConfig {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // admin
+        + 32 // verifier_program
+        + 33 // previous_verifier
+        + 8 // previous_verifier_valid_until
+        + 33 // pending_verifier
+        + 8 // pending_verifier_eta
+        + 1 // bump
+        + 8 // private_operation_fee
+        + 8 // spend_fee_reimbursement
+        + 32 // note_verifier
+        + 2 // self_relay_fee_bps
+        + 33 // pending_admin
+        + 1; // paused
+
+    /// Current verifier, or the previous one while its migration window is open
+    pub fn accepts_verifier(&self, key: &Pubkey, now: i64) -> bool {
+        *key == self.verifier_program
+            || (self.previous_verifier == Some(*key) && now < self.previous_verifier_valid_until)
+    }
+}
+
+/// Proof + witness uploaded in chunks for one private instruction on one agent
+/// PDA: [b"proof_buffer", submitter, agent]
+#[account]
+pub struct ProofBuffer {
+    /// Uploader; receives the rent when the buffer is closed
+    pub submitter: Pubkey,
+    /// Agent the proof is for
+    pub agent: Pubkey,
+    /// ACTION_* of the only instruction that may consume the buffer
+    pub action_id: u8,
+    /// Length of the proof at the start of data; the witness follows it
+    pub proof_len: u32,
+    /// proof || witness
+    pub data: Vec<u8>,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl ProofBuffer {
+    pub const MAX_DATA_LEN: usize = 2048;
+
+    pub const fn size(data_len: usize) -> usize {
+        8 // discriminator
+            + 32 // submitter
+            + 32 // agent
+            + 1 // action_id
+            + 4 // proof_len
+            + 4 + data_len // data
+            + 1 // bump
+    }
+}
+
+/// Marks a proof as consumed by a private instruction; rent is paid by the agent's vault
+/// PDA: [b"nullifier", sha256(proof_bytes)]
+#[account]
+pub struct Nullifier {
+    /// Agent whose vault paid the rent
+    pub agent: Pubkey,
+    /// sha256 of the proof bytes
+    pub proof_hash: [u8; 32],
+    /// When the proof was used
+    pub created_at: i64,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl Nullifier {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // proof_hash
+        + 8 // created_at
+        + 1; // bump
+}
+
+/// Proof formats the private instructions accept, selected by their verifier_version
+/// PDA: [b"verifier_registry"]
+#[account]
+pub struct VerifierRegistry {
+    /// Accepted formats, one per witness_version (up to MAX_VERIFIER_ENTRIES)
+    pub entries: Vec<VerifierEntry>,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl VerifierRegistry {
+    pub const MAX_VERIFIER_ENTRIES: usize = 8;
+
+    pub const SIZE: usize = 8 // discriminator
+        + 4 + VerifierEntry::SIZE * Self::MAX_VERIFIER_ENTRIES // entries
+        + 1; // bump
+
+    pub fn add(&mut self, entry: VerifierEntry) -> Result<()> {
+        require!(
+            self.entries.iter().all(|e| e.witness_version != entry.witness_version),
+            ErrorCode::VerifierVersionExists
+        );
+        require!(
+            self.entries.len() < Self::MAX_VERIFIER_ENTRIES,
+            ErrorCode::VerifierRegistryFull
+        );
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Entry for `witness_version`, unless its scheduled removal time has passed
+    pub fn active_entry(&self, witness_version: u8, now: i64) -> Option<&VerifierEntry> {
+        self.entries.iter().find(|entry| {
+            entry.witness_version == witness_version
+                && (entry.removable_at == 0 || now < entry.removable_at)
+        })
+    }
+}
+
+/// One accepted verifier / proof format
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifierEntry {
+    /// Verifier program the proof is checked with
+    pub verifier_program: Pubkey,
+    /// Witness public input count; inputs beyond the v4 layout are appended after it
+    pub witness_version: u8,
+    /// Exact proof length in bytes
+    pub proof_size: u32,
+    /// Set by schedule_verifier_removal (0 = not scheduled)
+    pub removable_at: i64,
+    /// Commitment scheme the circuit checks (see COMMITMENT_VERSION)
+    pub commitment_version: u8,
+}
+
+impl VerifierEntry {
+    pub const SIZE: usize = 32 // verifier_program
+        + 1 // witness_version
+        + 4 // proof_size
+        + 8 // removable_at
+        + 1; // commitment_version
+}
+
+/// Relayers that agents created with require_registered_relayer reimburse
+/// PDA: [b"relayer_registry"]
+#[account]
+pub struct RelayerRegistry {
+    /// Approved fee_recipient keys (up to MAX_RELAYERS)
+    pub relayers: Vec<Pubkey>,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl RelayerRegistry {
+    pub const MAX_RELAYERS: usize = 32;
+
+    pub const SIZE: usize = 8 // discriminator
+        + 4 + 32 * Self::MAX_RELAYERS // relayers
+        + 1; // bump
+
+    pub fn add(&mut self, relayer: Pubkey) -> Result<()> {
+        require!(!self.relayers.contains(&relayer), ErrorCode::RelayerAlreadyRegistered);
+        require!(self.relayers.len() < Self::MAX_RELAYERS, ErrorCode::RelayerRegistryFull);
+        self.relayers.push(relayer);
+        Ok(())
+    }
+
+    /// No-op when the relayer is not listed
+    pub fn remove(&mut self, relayer: &Pubkey) {
+        self.relayers.retain(|r| r != relayer);
+    }
+}
+
+/// Fees a relayer has been reimbursed by private operations
+/// PDA: [b"relayer_stats", relayer]
+#[account]
+pub struct RelayerStats {
+    /// Relayer (fee_recipient key) the stats belong to
+    pub relayer: Pubkey,
+    /// Lamports of private operation fees received
+    pub total_fees_earned: u64,
+    /// Private operations relayed
+    pub total_ops_relayed: u64,
+    /// register_relayer timestamp
+    pub registered_at: i64,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl RelayerStats {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // relayer
+        + 8 // total_fees_earned
+        + 8 // total_ops_relayed
+        + 8 // registered_at
+        + 1; // bump
+
+    /// Count one relayed operation that paid `fee`
+    pub fn record_fee(&mut self, fee: u64) -> Result<()> {
+        self.total_fees_earned = self.total_fees_earned.checked_add(fee).ok_or(ErrorCode::Overflow)?;
+        self.total_ops_relayed = self.total_ops_relayed.checked_add(1).ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+}
+
+/// Append-only set of note commitments and the lamports locked behind them
+/// Leaves form a Merkle tree (note_tree_hash) that claims are proven against
+/// PDA: [b"note_set", index (u32 le)]
+#[account]
+pub struct NoteSet {
+    /// Index in the PDA seeds
+    pub index: u32,
+    /// Note commitments in deposit order (up to MAX_NOTES)
+    pub notes: Vec<[u8; 32]>,
+    /// Last left node seen at each tree level (incremental Merkle tree)
+    pub filled_subtrees: [[u8; 32]; NOTE_TREE_DEPTH],
+    /// Ring buffer of the most recent roots
+    pub roots: [[u8; 32]; NOTE_ROOT_HISTORY],
+    /// Position of the current root in roots
+    pub current_root_index: u8,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl NoteSet {
+    pub const MAX_NOTES: usize = 1 << NOTE_TREE_DEPTH;
+
+    pub const SIZE: usize = 8 // discriminator
+        + 4 // index
+        + 4 + 32 * Self::MAX_NOTES // notes
+        + 32 * NOTE_TREE_DEPTH // filled_subtrees
+        + 32 * NOTE_ROOT_HISTORY // roots
+        + 1 // current_root_index
+        + 1; // bump
+
+    pub fn current_root(&self) -> [u8; 32] {
+        self.roots[self.current_root_index as usize]
+    }
+
+    pub fn is_known_root(&self, root: &[u8; 32]) -> bool {
+        *root != [0u8; 32] && self.roots.contains(root)
+    }
+
+    /// Append a note and record the new root; returns the note's leaf index
+    pub fn insert(&mut self, commitment: [u8; 32]) -> Result<u32> {
+        require!(self.notes.len() < Self::MAX_NOTES, ErrorCode::NoteSetFull);
+        let leaf_index = self.notes.len();
+
+        let mut node = commitment;
+        let mut zero = [0u8; 32];
+        let mut index = leaf_index;
+        for level in 0..NOTE_TREE_DEPTH {
+            node = if index.is_multiple_of(2) {
+                self.filled_subtrees[level] = node;
+                note_tree_hash(&node, &zero)
+            } else {
+                note_tree_hash(&self.filled_subtrees[level], &node)
+            };
+            zero = note_tree_hash(&zero, &zero);
+            index /= 2;
+        }
+
+        self.notes.push(commitment);
+        self.current_root_index = ((self.current_root_index as usize + 1) % NOTE_ROOT_HISTORY) as u8;
+        self.roots[self.current_root_index as usize] = node;
+        Ok(leaf_index as u32)
+    }
+}
 
-    /// Owner of the agent (human wallet)
-    pub owner: Signer<'info>,
+/// Root of a NoteSet tree with no notes (all leaves zero)
+pub fn empty_note_root() -> [u8; 32] {
+    (0..NOTE_TREE_DEPTH).fold([0u8; 32], |zero, _| note_tree_hash(&zero, &zero))
+}
 
-    /// Delegate key (agent's public key)
-    /// CHECK: Any pubkey can be delegate
-    pub delegate: AccountInfo<'info>,
+/// Claimed note, created by claim_note_private
+/// PDA: [b"note_nullifier", nullifier]
+#[account]
+pub struct NoteNullifier {
+    /// NoteSet the note was claimed from
+    pub note_set: Pubkey,
+    /// Nullifier derived from the note preimage
+    pub nullifier: [u8; 32],
+    /// Claim timestamp
+    pub claimed_at: i64,
+    /// PDA bump
+    pub bump: u8,
+}
 
-    /// Pays for account creation
-    #[account(mut)]
-    pub payer: Signer<'info>,
+impl NoteNullifier {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // note_set
+        + 32 // nullifier
+        + 8 // claimed_at
+        + 1; // bump
+}
 
-    pub system_program: Program<'info, System>,
+/// Agents a delegate key is authorized to spend from, for discovery with one read
+/// PDA: [b"delegate_registry", delegate]
+#[account]
+pub struct DelegateRegistry {
+    /// Delegate key this registry belongs to
+    pub delegate: Pubkey,
+    /// Registered agents (up to MAX_REGISTRY_AGENTS)
+    pub agents: Vec<Pubkey>,
+    /// PDA bump
+    pub bump: u8,
 }
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    /// Agent state (to derive vault PDA)
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+impl DelegateRegistry {
+    pub const MAX_REGISTRY_AGENTS: usize = 32;
 
-    /// Vault PDA to receive funds
-    #[account(
-        mut,
-        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // delegate
+        + 4 + 32 * Self::MAX_REGISTRY_AGENTS // agents
+        + 1; // bump
 
-    /// Source of funds
-    #[account(mut)]
-    pub depositor: Signer<'info>,
+    pub fn register(&mut self, agent: Pubkey) -> Result<()> {
+        require!(!self.agents.contains(&agent), ErrorCode::AgentAlreadyRegistered);
+        require!(
+            self.agents.len() < Self::MAX_REGISTRY_AGENTS,
+            ErrorCode::DelegateRegistryFull
+        );
+        self.agents.push(agent);
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
+    /// No-op when the agent is not listed
+    pub fn unregister(&mut self, agent: &Pubkey) {
+        self.agents.retain(|a| a != agent);
+    }
 }
 
-#[derive(Accounts)]
-pub struct Spend<'info> {
-    #[account(
-        mut,
-        seeds = [b"cloaked_agent_state", delegate.key().as_ref()],
-        bump = cloaked_agent_state.bump,
-        has_one = delegate,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+impl DestinationStats {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // destination
+        + 8 // total_sent
+        + 8 // sent_count
+        + 8 // last_sent_at
+        + 1; // bump
+}
 
-    #[account(
-        mut,
-        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+/// Recurring payment paid from the vault by execute_schedule
+/// PDA: [b"schedule", cloaked_agent_state, destination]
+#[account]
+pub struct PaymentSchedule {
+    /// Agent paying this schedule
+    pub agent: Pubkey,
+    /// Payee
+    pub destination: Pubkey,
+    /// Lamports paid per period
+    pub amount: u64,
+    /// Period length in seconds
+    pub interval_seconds: u64,
+    /// Earliest time the next payment can be executed
+    pub next_due: i64,
+    /// Pay missed periods individually (true) or skip them (false)
+    pub catch_up: bool,
+    /// PDA bump
+    pub bump: u8,
+}
 
-    /// Must match cloaked_agent_state.delegate
-    pub delegate: Signer<'info>,
+impl PaymentSchedule {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // destination
+        + 8 // amount
+        + 8 // interval_seconds
+        + 8 // next_due
+        + 1 // catch_up
+        + 1; // bump
+}
 
-    /// Fee payer - fronts tx fee, gets reimbursed from vault
-    #[account(mut)]
-    pub fee_payer: Signer<'info>,
+/// One-time payment pre-approved by the owner, redeemed by the delegate
+/// PDA: [b"voucher", cloaked_agent_state, destination]
+#[account]
+pub struct Voucher {
+    /// Agent paying this voucher
+    pub agent: Pubkey,
+    /// Owner who created the voucher (receives rent on close)
+    pub owner: Pubkey,
+    /// Payee
+    pub destination: Pubkey,
+    /// Lamports paid on redemption
+    pub amount: u64,
+    /// Redeemable until this timestamp; closable by anyone after
+    pub expires_at: i64,
+    /// Check and record the redemption against window and total limits
+    pub counts_toward_limits: bool,
+    /// PDA bump
+    pub bump: u8,
+}
 
-    /// Destination for funds
-    /// CHECK: Any account can receive
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
+impl Voucher {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // owner
+        + 32 // destination
+        + 8 // amount
+        + 8 // expires_at
+        + 1 // counts_toward_limits
+        + 1; // bump
+}
 
-    pub system_program: Program<'info, System>,
+/// Program spend_via_program may CPI into with the vault as signer
+/// PDA: [b"allowed_program", cloaked_agent_state, program_id]
+#[account]
+pub struct AllowedProgram {
+    /// Agent this entry belongs to
+    pub agent: Pubkey,
+    /// Allowlisted program
+    pub program_id: Pubkey,
+    /// PDA bump
+    pub bump: u8,
 }
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+impl AllowedProgram {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // program_id
+        + 1; // bump
+}
 
-    #[account(
-        mut,
-        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+/// Lamports held between spend_escrowed and release_escrow / revoke_escrow
+/// PDA: [b"escrow", cloaked_agent_state, escrow_id (spend_nonce at creation)]
+#[account]
+pub struct Escrow {
+    /// Agent that made the spend
+    pub agent: Pubkey,
+    /// PDA seed; the agent's spend_nonce when the escrow was created
+    pub escrow_id: u64,
+    /// Payee once released
+    pub destination: Pubkey,
+    /// Lamports held (excludes rent)
+    pub amount: u64,
+    /// release_escrow allowed from this timestamp; revoke_escrow only before it
+    pub release_after: i64,
+    /// Paid the escrow rent and receives it back on close
+    pub rent_payer: Pubkey,
+    /// Agent window the spend was charged to (refunded by revoke only if still current)
+    pub window_start: i64,
+    /// Parent window the spend was charged to (sub-agents only)
+    pub parent_window_start: i64,
+    /// PDA bump
+    pub bump: u8,
+}
 
-    /// Owner signing the transaction (verified in instruction)
-    pub owner: Signer<'info>,
+impl Escrow {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 8 // escrow_id
+        + 32 // destination
+        + 8 // amount
+        + 8 // release_after
+        + 32 // rent_payer
+        + 8 // window_start
+        + 8 // parent_window_start
+        + 1; // bump
+}
 
-    /// Destination for funds
-    /// CHECK: Any account can receive
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
+/// Validator the owner allows vault SOL to be staked with
+/// PDA: [b"validator", cloaked_agent_state, vote_account]
+#[account]
+pub struct ValidatorEntry {
+    /// Agent this entry belongs to
+    pub agent: Pubkey,
+    /// Allowlisted vote account
+    pub vote_account: Pubkey,
+    /// PDA bump
+    pub bump: u8,
+}
 
-    pub system_program: Program<'info, System>,
+impl ValidatorEntry {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // vote_account
+        + 1; // bump
 }
 
-#[derive(Accounts)]
-pub struct Freeze<'info> {
-    #[account(
-        mut,
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+/// Stake account funded from the vault by stake_from_vault
+/// PDA: [b"stake", cloaked_agent_state, stake_account]
+#[account]
+pub struct StakeRecord {
+    /// Agent whose vault funded the stake
+    pub agent: Pubkey,
+    /// Stake account (vault PDA is staker and withdrawer)
+    pub stake_account: Pubkey,
+    /// Validator the stake is delegated to
+    pub vote_account: Pubkey,
+    /// Lamports moved out of the vault (principal)
+    pub amount: u64,
+    /// PDA bump
+    pub bump: u8,
+}
 
-    /// Owner signing the transaction (verified in instruction)
-    pub owner: Signer<'info>,
+impl StakeRecord {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // stake_account
+        + 32 // vote_account
+        + 8 // amount
+        + 1; // bump
 }
 
-#[derive(Accounts)]
-pub struct Unfreeze<'info> {
-    #[account(
-        mut,
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+/// Short-lived spending key with its own budget
+/// PDA: [b"session", cloaked_agent_state, session_key]
+#[account]
+pub struct Session {
+    /// Agent this session belongs to
+    pub agent: Pubkey,
+    /// Key allowed to call spend_with_session
+    pub session_key: Pubkey,
+    /// Creator (owner or delegate) - receives rent on revoke
+    pub authority: Pubkey,
+    /// Unix timestamp after which the session cannot spend
+    pub expires_at: i64,
+    /// Remaining lamports the session may spend
+    pub budget: u64,
+    /// PDA bump
+    pub bump: u8,
+}
 
-    /// Owner signing the transaction (verified in instruction)
-    pub owner: Signer<'info>,
+impl Session {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // session_key
+        + 32 // authority
+        + 8 // expires_at
+        + 8 // budget
+        + 1; // bump
 }
 
-#[derive(Accounts)]
-pub struct UpdateConstraints<'info> {
-    #[account(
-        mut,
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+/// Pending ownership recovery
+/// PDA: [b"recovery", cloaked_agent_state]
+#[account]
+pub struct RecoveryProposal {
+    /// Agent being recovered
+    pub agent: Pubkey,
+    /// Recovery delegate that proposed (and paid for) this recovery
+    pub proposer: Pubkey,
+    /// Owner to install on execute_recovery
+    pub new_owner: Pubkey,
+    /// When the proposal was made - executable after RECOVERY_DELAY
+    pub proposed_at: i64,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl RecoveryProposal {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // proposer
+        + 32 // new_owner
+        + 8 // proposed_at
+        + 1; // bump
+}
+
+/// Additional delegate key with its own limits (checked on top of the agent's)
+/// PDA: [b"delegate", cloaked_agent_state, delegate]
+#[account]
+pub struct DelegateEntry {
+    /// Agent this entry belongs to
+    pub agent: Pubkey,
+    /// Delegate key allowed to call spend_as_delegate
+    pub delegate: Pubkey,
+    /// Max lamports per transaction for this delegate (0 = unlimited)
+    pub max_per_tx: u64,
+    /// Per-delegate window limit (daily by default)
+    pub spending_window: SpendingWindow,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl DelegateEntry {
+    pub const SIZE: usize = 8 // discriminator
+        + 32 // agent
+        + 32 // delegate
+        + 8 // max_per_tx
+        + SpendingWindow::SIZE // spending_window
+        + 1; // bump
+}
+
+/// Spend authorization the delegate signs off-chain for spend_signed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpendIntent {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub destination: Pubkey,
+    /// Must exceed the agent's delegate_intent_nonce
+    pub nonce: u64,
+    /// Unix timestamp after which the intent can no longer be submitted
+    pub expires_at: i64,
+}
+
+impl SpendIntent {
+    /// Bytes the delegate signs: SPEND_INTENT_DOMAIN || agent || amount || destination ||
+    /// nonce || expires_at (integers little-endian)
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(SPEND_INTENT_DOMAIN.len() + 32 + 8 + 32 + 8 + 8);
+        message.extend_from_slice(SPEND_INTENT_DOMAIN);
+        message.extend_from_slice(self.agent.as_ref());
+        message.extend_from_slice(&self.amount.to_le_bytes());
+        message.extend_from_slice(self.destination.as_ref());
+        message.extend_from_slice(&self.nonce.to_le_bytes());
+        message.extend_from_slice(&self.expires_at.to_le_bytes());
+        message
+    }
+}
+
+/// Instruction data spend sends to the agent's post_spend_hook (borsh, no discriminator)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpendNotification {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub timestamp: i64,
+    /// Agent's spend_nonce after this spend
+    pub spend_nonce: u64,
+}
+
+/// Ownership proof accepted by a private-mode agent's owner-commitment instructions
+/// Recovery-commitment proofs are always ZK
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommitmentScheme {
+    /// Groth16 proof of the secret behind owner_commitment
+    #[default]
+    Zk,
+    /// ed25519 signature by a one-time stealth key; owner_commitment = sha256(stealth_pubkey)
+    StealthEd25519,
+}
+
+/// Comparison conditional_spend applies between the oracle price and the threshold
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceCondition {
+    Above,
+    Below,
+    AtOrAbove,
+    AtOrBelow,
+}
+
+impl PriceCondition {
+    pub fn is_met(&self, price: i64, threshold: i64) -> bool {
+        match self {
+            PriceCondition::Above => price > threshold,
+            PriceCondition::Below => price < threshold,
+            PriceCondition::AtOrAbove => price >= threshold,
+            PriceCondition::AtOrBelow => price <= threshold,
+        }
+    }
+}
+
+/// Time-period spending limit: at most `limit` lamports per `window_seconds`
+/// The window restarts on the first spend after it elapses
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpendingWindow {
+    /// Window length in seconds (DEFAULT_WINDOW_SECONDS = daily)
+    pub window_seconds: u64,
+    /// Max lamports per window (0 = unlimited)
+    pub limit: u64,
+    /// Lamports spent in the current window
+    pub spent: u64,
+    /// Start of the current window (unix timestamp)
+    pub window_start: i64,
+}
+
+impl SpendingWindow {
+    pub const SIZE: usize = 8 + 8 + 8 + 8;
+
+    /// Check if the current window has elapsed at `now`
+    pub fn is_elapsed(&self, now: i64) -> bool {
+        u64::try_from(now.saturating_sub(self.window_start))
+            .is_ok_and(|elapsed| elapsed >= self.window_seconds)
+    }
+
+    /// Start a new window at `now` if the current one has elapsed
+    pub fn roll(&mut self, now: i64) {
+        if self.is_elapsed(now) {
+            self.spent = 0;
+            self.window_start = now;
+        }
+    }
+
+    /// Lamports still spendable in the window effective at `now`
+    /// Returns u64::MAX when the limit is unlimited
+    pub fn remaining(&self, now: i64) -> u64 {
+        if self.limit == 0 {
+            return u64::MAX;
+        }
+        let spent = if self.is_elapsed(now) { 0 } else { self.spent };
+        self.limit.saturating_sub(spent)
+    }
+
+    /// Roll the window at `now`, then count `amount` against the limit (0 = unlimited)
+    pub fn record_spend(&mut self, now: i64, amount: u64) -> Result<()> {
+        self.roll(now);
+        let spent = self.spent.checked_add(amount).ok_or(ErrorCode::Overflow)?;
+        if self.limit > 0 {
+            require!(spent <= self.limit, ErrorCode::ExceedsWindowLimit);
+        }
+        self.spent = spent;
+        Ok(())
+    }
+
+    /// Allowance left unused by every window that has fully elapsed at `now`: the rest of
+    /// the current window plus the full limit of each window skipped without a spend
+    /// Returns 0 while the current window is still open or when the limit is unlimited
+    pub fn unused_allowance(&self, now: i64) -> u64 {
+        if self.limit == 0 || !self.is_elapsed(now) {
+            return 0;
+        }
+        let elapsed = u64::try_from(now.saturating_sub(self.window_start)).unwrap_or(0);
+        let skipped = (elapsed / self.window_seconds).saturating_sub(1);
+        self.limit
+            .saturating_sub(self.spent)
+            .saturating_add(self.limit.saturating_mul(skipped))
+    }
+
+    /// Seconds until the current window elapses (0 if already elapsed)
+    pub fn seconds_until_reset(&self, now: i64) -> i64 {
+        let window_end = self
+            .window_start
+            .saturating_add(i64::try_from(self.window_seconds).unwrap_or(i64::MAX));
+        window_end.saturating_sub(now).max(0)
+    }
+}
+
+impl CloakedAgentState {
+    /// Account size (max serialized length, Option fields counted as Some)
+    /// New fields are appended so migrate_agent_state can grow legacy accounts in place
+    pub const SIZE: usize = 8 // discriminator
+        + 33 // owner: Option<Pubkey>
+        + 32 // owner_commitment
+        + 32 // delegate
+        + 8 * 4 // max_per_tx, legacy_daily_limit, total_limit, expires_at
+        + 1 // frozen
+        + 8 * 3 // total_spent, legacy_daily_spent, legacy_last_day
+        + 1 // bump
+        + 8 // created_at
+        + 8 // total_withdrawn
+        + 8 // total_deposited
+        + 1 // state_version
+        + SpendingWindow::SIZE // spending_window
+        + 8 // max_vault_balance
+        + 2 // delegate_commission_bps
+        + 33 // delegate_commission_recipient: Option<Pubkey>
+        + 8 // min_deposit
+        + 8 // min_spend_amount
+        + 1 // block_deposits_when_inactive
+        + 1 // whitelist_entry_count
+        + 32 // created_by
+        + 8 // creation_nonce
+        + 1 // allow_delegate_sessions
+        + 33 // parent: Option<Pubkey>
+        + 2 // child_count
+        + 8 // vault_balance_snapshot
+        + 8 // snapshot_timestamp
+        + 1 // frozen_by_delegate
+        + 32 // freeze_reason
+        + 33 // withdraw_destination_lock: Option<Pubkey>
+        + 33 // pending_destination_lock: Option<Pubkey>
+        + 8 // pending_destination_lock_eta
+        + 8 // spend_nonce
+        + 32 * MAX_MULTISIG_OWNERS // multisig_owners
+        + 1 // multisig_owner_count
+        + 1 // multisig_threshold
+        + 20 // delegate_eth_address
+        + 8 // delegate_eth_nonce
+        + 2 // instruction_whitelist
+        + 33 // delegate_p256_pubkey
+        + 8 // delegate_p256_nonce
+        + 8 // delegate_intent_nonce
+        + 33 // emergency_rescue_address: Option<Pubkey>
+        + 8 // rescue_delay
+        + 8 // rescue_available_at
+        + 4 // allowed_start_second_of_day
+        + 4 // allowed_end_second_of_day
+        + 1 // constraints_locked
+        + 1 // allowed_days_bitmask
+        + 8 // rollover_cap
+        + 8 // accrued_allowance
+        + 8 // conditional_freeze_threshold
+        + 33 // recovery_delegate: Option<Pubkey>
+        + 8 // sweep_target
+        + 33 // sweep_destination: Option<Pubkey>
+        + 1 // vault_bump
+        + 8 // staked_amount
+        + 8 // proof_nonce
+        + 1 // program_delegate
+        + 1 // max_concurrent_timelocks
+        + 1 // pending_timelocks
+        + 8 // timelock_nonce
+        + 8 // reserved_lamports
+        + 33 // two_fa_key
+        + 8 // two_fa_threshold
+        + 8 // minimum_vault_balance_for_delegation
+        + 33 // post_spend_hook
+        + 32 // owner_notes
+        + 32 // delegate_notes
+        + 8 // sliding_window_seconds
+        + 8 // sliding_window_limit
+        + 8 * SLIDING_WINDOW_SLOTS // recent_spend_amounts
+        + 8 * SLIDING_WINDOW_SLOTS // recent_spend_times
+        + 1 // ring_head
+        + 32 // recovery_commitment
+        + 8 // recovery_initiated_at
+        + 1 // require_registered_relayer
+        + 1 // auto_close_on_zero
+        + 8 // private_ops_day
+        + 2 // private_ops_today
+        + 2 // max_private_ops_per_day
+        + 1 // program_whitelist_entry_count
+        + 1 // commitment_scheme
+        + 1 // blacklist_entry_count
+        + 1 // commitment_version
+        + 1 // proof_format_version
+        + 1; // deposits_locked
+
+    /// Check if this is a private mode agent
+    pub fn is_private(&self) -> bool {
+        self.owner.is_none() && !self.is_multisig()
+    }
+
+    /// Whether the primary delegate may call the instruction at `bit` of instruction_whitelist
+    pub fn allows_instruction(&self, bit: u8) -> bool {
+        (self.instruction_whitelist >> bit) & 1 == 1
+    }
+
+    /// Whether `now` falls in the allowed UTC time-of-day window [start, end)
+    /// Both zero = always allowed; start > end wraps past midnight
+    pub fn is_within_spend_hours(&self, now: i64) -> bool {
+        let (start, end) = (self.allowed_start_second_of_day, self.allowed_end_second_of_day);
+        if start == 0 && end == 0 {
+            return true;
+        }
+        let second_of_day = now.rem_euclid(SECONDS_PER_DAY) as u32;
+        if start < end {
+            second_of_day >= start && second_of_day < end
+        } else {
+            second_of_day >= start || second_of_day < end
+        }
+    }
+
+    /// Whether spending is allowed on the UTC weekday of `now` (bitmask 0 = every day)
+    pub fn is_allowed_day(&self, now: i64) -> bool {
+        self.allowed_days_bitmask == 0
+            || (self.allowed_days_bitmask >> weekday_from_timestamp(now)) & 1 == 1
+    }
+
+    /// Check if this agent is owned by an m-of-n owner set
+    pub fn is_multisig(&self) -> bool {
+        self.multisig_threshold > 0
+    }
+
+    /// Registered multisig owners (empty for single-owner agents)
+    pub fn multisig_owner_keys(&self) -> &[Pubkey] {
+        let count = usize::from(self.multisig_owner_count).min(MAX_MULTISIG_OWNERS);
+        &self.multisig_owners[..count]
+    }
+
+    /// Whether `key` is the owner or, for multisig agents, one of the registered owners
+    pub fn is_owner_key(&self, key: &Pubkey) -> bool {
+        self.owner == Some(*key) || self.multisig_owner_keys().contains(key)
+    }
+
+    /// Store a validated multisig owner set (see validate_multisig)
+    pub fn set_multisig_owners(&mut self, owners: &[Pubkey], threshold: u8) {
+        self.multisig_owners = [Pubkey::default(); MAX_MULTISIG_OWNERS];
+        self.multisig_owners[..owners.len()].copy_from_slice(owners);
+        self.multisig_owner_count = owners.len() as u8;
+        self.multisig_threshold = threshold;
+    }
+
+    /// Check if the agent has passed its expiration (expires_at = 0 never expires)
+    pub fn is_expired(&self, clock: &Clock) -> bool {
+        self.expires_at > 0 && clock.unix_timestamp >= self.expires_at
+    }
+
+    /// Whether the agent can currently spend (not frozen and not expired)
+    pub fn is_active(&self, clock: &Clock) -> bool {
+        !self.frozen && !self.is_expired(clock)
+    }
+
+    /// Lamports still spendable before the spending window resets (daily by default)
+    /// An elapsed window counts as reset, so a new day returns the full limit
+    /// Returns u64::MAX when the window limit is unlimited
+    pub fn remaining_daily_allowance(&self, clock: &Clock) -> u64 {
+        self.remaining_window_allowance(clock)
+    }
+
+    /// Lamports still spendable in the current window, accounting for a pending reset
+    /// Returns u64::MAX when the window limit is unlimited
+    pub fn remaining_window_allowance(&self, clock: &Clock) -> u64 {
+        self.spending_window
+            .remaining(clock.unix_timestamp)
+            .saturating_add(self.rollover_allowance(clock.unix_timestamp))
+    }
+
+    /// Banked allowance available at `now`, including what elapsed windows left unused
+    /// Capped at rollover_cap (0 = rollover disabled)
+    pub fn rollover_allowance(&self, now: i64) -> u64 {
+        if self.rollover_cap == 0 {
+            return 0;
+        }
+        self.accrued_allowance
+            .saturating_add(self.spending_window.unused_allowance(now))
+            .min(self.rollover_cap)
+    }
+
+    /// Count `amount` against the spending window, drawing on banked rollover first
+    pub fn record_window_spend(&mut self, now: i64, amount: u64) -> Result<()> {
+        let accrued = self.rollover_allowance(now);
+        let from_accrual = amount.min(accrued);
+        self.accrued_allowance = accrued - from_accrual;
+        self.spending_window.record_spend(now, amount - from_accrual)
+    }
+
+    /// Commitment scheme of the agent's commitments; agents migrated from before
+    /// commitment_version existed use the built-in scheme
+    pub fn commitment_version(&self) -> u8 {
+        match self.commitment_version {
+            0 => COMMITMENT_VERSION,
+            version => version,
+        }
+    }
+
+    /// Proof wire format; agents migrated from before proof_format_version existed use 1
+    pub fn proof_format_version(&self) -> u8 {
+        match self.proof_format_version {
+            0 => 1,
+            version => version,
+        }
+    }
+
+    /// Count a fee-charging private operation against max_private_ops_per_day
+    pub fn record_private_op(&mut self, now: i64) -> Result<()> {
+        let limit = match self.max_private_ops_per_day {
+            0 => DEFAULT_MAX_PRIVATE_OPS_PER_DAY,
+            limit => limit,
+        };
+        (self.private_ops_day, self.private_ops_today) =
+            next_private_op_count(self.private_ops_day, self.private_ops_today, limit, now)?;
+        Ok(())
+    }
+
+    /// Count `amount` against the sliding window: spends in the last sliding_window_seconds
+    /// may not exceed sliding_window_limit. When the ring slot being reused still holds an
+    /// in-window spend, its amount is carried into the new entry (stricter, never looser)
+    pub fn record_sliding_window_spend(&mut self, now: i64, amount: u64) -> Result<()> {
+        if self.sliding_window_limit == 0 {
+            return Ok(());
+        }
+        let window = i64::try_from(self.sliding_window_seconds).unwrap_or(i64::MAX);
+        let cutoff = now.saturating_sub(window);
+
+        let mut in_window = 0u64;
+        for (spent, at) in self.recent_spend_amounts.iter_mut().zip(self.recent_spend_times.iter_mut()) {
+            if *at > cutoff {
+                in_window = in_window.checked_add(*spent).ok_or(ErrorCode::Overflow)?;
+            } else {
+                *spent = 0;
+                *at = 0;
+            }
+        }
+        require!(
+            in_window.checked_add(amount).ok_or(ErrorCode::Overflow)? <= self.sliding_window_limit,
+            ErrorCode::ExceedsSlidingWindowLimit
+        );
+
+        let head = self.ring_head as usize % SLIDING_WINDOW_SLOTS;
+        self.recent_spend_amounts[head] = self.recent_spend_amounts[head]
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        self.recent_spend_times[head] = now;
+        self.ring_head = ((head + 1) % SLIDING_WINDOW_SLOTS) as u8;
+        Ok(())
+    }
+
+    /// Reverse a spend of `amount` charged to the window that started at `window_start`
+    /// The window allowance is only restored while that window is still current
+    pub fn refund_spend(&mut self, amount: u64, window_start: i64) {
+        self.total_spent = self.total_spent.saturating_sub(amount);
+        if self.spending_window.window_start == window_start {
+            self.spending_window.spent = self.spending_window.spent.saturating_sub(amount);
+        }
+    }
+
+    /// Lamports still spendable over the agent lifetime
+    /// Returns u64::MAX when total_limit is unlimited
+    pub fn remaining_total_allowance(&self) -> u64 {
+        if self.total_limit == 0 {
+            return u64::MAX;
+        }
+        self.total_limit.saturating_sub(self.total_spent)
+    }
 
-    /// Owner signing the transaction (verified in instruction)
-    pub owner: Signer<'info>,
-}
+    /// Delegate commission owed on a spend of `amount` (0 when no recipient is configured)
+    pub fn delegate_commission(&self, amount: u64) -> Result<u64> {
+        if self.delegate_commission_recipient.is_none() {
+            return Ok(0);
+        }
+        let commission = u128::from(amount)
+            .checked_mul(u128::from(self.delegate_commission_bps))
+            .ok_or(ErrorCode::Overflow)?
+            / u128::from(BPS_DENOMINATOR);
+        u64::try_from(commission).map_err(|_| error!(ErrorCode::Overflow))
+    }
 
-#[derive(Accounts)]
-pub struct CloseCloakedAgent<'info> {
-    #[account(
-        mut,
-        close = owner,
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+    /// Effective cap on pending timelocked withdrawals
+    pub fn timelock_cap(&self) -> u8 {
+        if self.max_concurrent_timelocks == 0 {
+            DEFAULT_MAX_CONCURRENT_TIMELOCKS
+        } else {
+            self.max_concurrent_timelocks
+        }
+    }
 
-    #[account(
-        mut,
-        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+    /// Vault balance not reserved for pending timelocked withdrawals
+    pub fn spendable_balance(&self, vault_balance: u64) -> u64 {
+        vault_balance.saturating_sub(self.reserved_lamports)
+    }
 
-    /// Owner signing the transaction (verified in instruction)
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    /// Record the current vault balance, returning the previous snapshot
+    pub fn update_vault_snapshot(&mut self, vault_balance: u64, timestamp: i64) -> u64 {
+        let previous = self.vault_balance_snapshot;
+        self.vault_balance_snapshot = vault_balance;
+        self.snapshot_timestamp = timestamp;
+        previous
+    }
 
-    pub system_program: Program<'info, System>,
+    /// Net vault position: lifetime deposits minus spends and owner withdrawals
+    pub fn net_position(&self) -> i128 {
+        self.total_deposited as i128 - self.total_spent as i128 - self.total_withdrawn as i128
+    }
+
+    /// Check limits are mutually consistent:
+    /// min_spend_amount <= max_per_tx <= window limit <= total_limit
+    /// Only non-zero limits are compared (0 = unlimited)
+    pub fn validate_limits(&self) -> Result<()> {
+        require!(
+            self.spending_window.window_seconds > 0
+                && i64::try_from(self.spending_window.window_seconds).is_ok(),
+            ErrorCode::InvalidSpendingWindow
+        );
+
+        let (start, end) = (self.allowed_start_second_of_day, self.allowed_end_second_of_day);
+        require!(
+            i64::from(start) < SECONDS_PER_DAY
+                && i64::from(end) < SECONDS_PER_DAY
+                && (start != end || start == 0),
+            ErrorCode::InvalidSpendHours
+        );
+        require!(self.allowed_days_bitmask < 0x80, ErrorCode::InvalidDaysBitmask);
+
+        let mut previous = 0u64;
+        for limit in [
+            self.min_spend_amount,
+            self.max_per_tx,
+            self.spending_window.limit,
+            self.total_limit,
+        ] {
+            if limit > 0 {
+                require!(limit >= previous, ErrorCode::InconsistentLimits);
+                previous = limit;
+            }
+        }
+        Ok(())
+    }
 }
 
-// === Private Mode Account Contexts ===
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(Accounts)]
-pub struct CreateCloakedAgentPrivate<'info> {
-    #[account(
-        init,
-        payer = payer,
-        space = CloakedAgentState::SIZE,
-        seeds = [b"cloaked_agent_state", delegate.key().as_ref()],
-        bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+    #[test]
+    fn weekday_from_timestamp_matches_known_dates() {
+        // 1970-01-01 Thursday
+        assert_eq!(weekday_from_timestamp(0), 4);
+        // 1969-12-31 23:59:59 Wednesday
+        assert_eq!(weekday_from_timestamp(-1), 3);
+        // 2000-01-01 00:00:00 Saturday
+        assert_eq!(weekday_from_timestamp(946_684_800), 6);
+        // 2024-02-29 12:00:00 Thursday (leap day)
+        assert_eq!(weekday_from_timestamp(1_709_208_000), 4);
+        // 2024-03-03 23:59:59 Sunday, 2024-03-04 00:00:00 Monday
+        assert_eq!(weekday_from_timestamp(1_709_510_399), 0);
+        assert_eq!(weekday_from_timestamp(1_709_510_400), 1);
+        // 2025-01-03 Friday
+        assert_eq!(weekday_from_timestamp(1_735_905_600), 5);
+    }
 
-    /// Vault PDA to hold funds
-    #[account(
-        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+    #[test]
+    fn price_condition_boundaries() {
+        assert!(PriceCondition::Above.is_met(101, 100));
+        assert!(!PriceCondition::Above.is_met(100, 100));
+        assert!(PriceCondition::AtOrAbove.is_met(100, 100));
+        assert!(PriceCondition::Below.is_met(99, 100));
+        assert!(!PriceCondition::Below.is_met(100, 100));
+        assert!(PriceCondition::AtOrBelow.is_met(100, 100));
+        assert!(!PriceCondition::AtOrBelow.is_met(101, 100));
+    }
 
-    /// Delegate key (agent's public key)
-    /// CHECK: Any pubkey can be delegate
-    pub delegate: AccountInfo<'info>,
+    fn witness_with_data(commitment: [u8; 32], nonce: u64, action_id: u8, action_data: [u8; 32]) -> Vec<u8> {
+        let public_count = (WITNESS_LAYOUT_VERSION as u32).to_be_bytes();
+        let mut witness = Vec::with_capacity(WITNESS_SIZE);
+        witness.extend_from_slice(&public_count);
+        witness.extend_from_slice(&0u32.to_be_bytes());
+        witness.extend_from_slice(&public_count);
+        witness.extend_from_slice(&commitment);
+        witness.extend_from_slice(&[0u8; PROOF_NONCE_SIZE - 8]);
+        witness.extend_from_slice(&nonce.to_be_bytes());
+        witness.extend_from_slice(&[0u8; ACTION_ID_SIZE - 1]);
+        witness.push(action_id);
+        witness.extend_from_slice(&action_data);
+        witness
+    }
 
-    /// Pays for account creation
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    fn witness(commitment: [u8; 32], nonce: u64, action_id: u8) -> Vec<u8> {
+        witness_with_data(commitment, nonce, action_id, [0; 32])
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    #[test]
+    fn witness_must_carry_current_proof_nonce() {
+        let commitment = [7u8; 32];
+        assert!(check_witness(&witness(commitment, 3, ACTION_FREEZE), WITNESS_LAYOUT_VERSION, &commitment, 3, ACTION_FREEZE, &[0; 32]).is_ok());
+        assert!(check_witness(&witness(commitment, 2, ACTION_FREEZE), WITNESS_LAYOUT_VERSION, &commitment, 3, ACTION_FREEZE, &[0; 32]).is_err());
+        assert!(check_witness(&witness([8u8; 32], 3, ACTION_FREEZE), WITNESS_LAYOUT_VERSION, &commitment, 3, ACTION_FREEZE, &[0; 32]).is_err());
+        // v1 witness (no nonce) is rejected
+        let v1 = witness(commitment, 3, ACTION_FREEZE);
+        assert!(check_witness(&v1[..WITNESS_NONCE_OFFSET], WITNESS_LAYOUT_VERSION, &commitment, 3, ACTION_FREEZE, &[0; 32]).is_err());
+    }
 
-#[derive(Accounts)]
-pub struct FreezePrivate<'info> {
-    #[account(
-        mut,
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+    #[test]
+    fn witness_is_bound_to_its_action() {
+        let commitment = [7u8; 32];
+        let actions = [
+            ACTION_FREEZE,
+            ACTION_UNFREEZE,
+            ACTION_PANIC,
+            ACTION_UPDATE_CONSTRAINTS,
+            ACTION_LOCK_CONSTRAINTS,
+            ACTION_CLOSE,
+            ACTION_WITHDRAW,
+        ];
+        for &made_for in &actions {
+            let w = witness(commitment, 0, made_for);
+            for &used_for in &actions {
+                let result = check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, used_for, &[0; 32]);
+                assert_eq!(result.is_ok(), made_for == used_for);
+            }
+        }
+    }
 
-    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump)]
-    pub vault: SystemAccount<'info>,
+    #[test]
+    fn withdraw_witness_is_bound_to_destination_and_amount() {
+        let commitment = [7u8; 32];
+        let destination = Pubkey::new_unique();
+        let data = withdraw_action_data(&destination, 1_000, false);
+        assert_eq!(data[0], 0);
+        let w = witness_with_data(commitment, 0, ACTION_WITHDRAW, data);
+
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_WITHDRAW, &data).is_ok());
+        // Relayer swaps in its own destination
+        let redirected = withdraw_action_data(&Pubkey::new_unique(), 1_000, false);
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_WITHDRAW, &redirected).is_err());
+        // Relayer changes the amount
+        let inflated = withdraw_action_data(&destination, 1_001, false);
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_WITHDRAW, &inflated).is_err());
+        // Relayer opts an expired agent into the withdrawal
+        let bypassed = withdraw_action_data(&destination, 1_000, true);
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_WITHDRAW, &bypassed).is_err());
+    }
 
-    /// CHECK: Any account can receive fee reimbursement
-    #[account(mut)]
-    pub fee_recipient: AccountInfo<'info>,
+    #[test]
+    fn withdraw_all_witness_is_bound_to_bypass_expiry() {
+        let destination = Pubkey::new_unique();
+        assert_ne!(
+            withdraw_all_action_data(&destination, false),
+            withdraw_all_action_data(&destination, true)
+        );
+        assert_ne!(
+            withdraw_all_action_data(&destination, false),
+            withdraw_all_action_data(&Pubkey::new_unique(), false)
+        );
+    }
 
-    /// ZK Verifier program for proof validation
-    /// CHECK: Verified in instruction to match ZK_VERIFIER_PROGRAM_ID
-    pub zk_verifier: AccountInfo<'info>,
+    #[test]
+    fn constraint_params_hash_covers_every_field() {
+        let base = ConstraintParams::default();
+        let unlimited_total = ConstraintParams { total_limit: Some(0), ..Default::default() };
+        let never_expires = ConstraintParams { expires_at: Some(0), ..Default::default() };
+
+        assert_eq!(base.params_hash(), ConstraintParams::default().params_hash());
+        assert_ne!(base.params_hash(), unlimited_total.params_hash());
+        assert_ne!(unlimited_total.params_hash(), never_expires.params_hash());
+        // Some(0) and None are distinct requests
+        assert_ne!(base.params_hash(), ConstraintParams { max_per_tx: Some(0), ..Default::default() }.params_hash());
+        assert_eq!(base.params_hash()[0], 0);
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    #[test]
+    fn close_witness_is_bound_to_destination_and_fee_recipient() {
+        let commitment = [7u8; 32];
+        let destination = Pubkey::new_unique();
+        let fee_recipient = Pubkey::new_unique();
+        let data = close_action_data(&destination, &fee_recipient);
+        let w = witness_with_data(commitment, 0, ACTION_CLOSE, data);
+
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_CLOSE, &data).is_ok());
+        let redirected = close_action_data(&Pubkey::new_unique(), &fee_recipient);
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_CLOSE, &redirected).is_err());
+        let rent_grab = close_action_data(&destination, &Pubkey::new_unique());
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_CLOSE, &rent_grab).is_err());
+    }
 
-#[derive(Accounts)]
-pub struct UnfreezePrivate<'info> {
-    #[account(
-        mut,
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+    #[test]
+    fn witness_header_and_size_are_strict() {
+        let commitment = [7u8; 32];
+        let w = witness(commitment, 0, ACTION_FREEZE);
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_FREEZE, &[0; 32]).is_ok());
+
+        // Trailing garbage
+        let mut long = w.clone();
+        long.push(0);
+        assert!(check_witness(&long, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_FREEZE, &[0; 32]).is_err());
+
+        // Older layout (fewer public inputs) declared in the header
+        let mut old = w.clone();
+        old[..4].copy_from_slice(&3u32.to_be_bytes());
+        old[8..12].copy_from_slice(&3u32.to_be_bytes());
+        assert!(check_witness(&old, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_FREEZE, &[0; 32]).is_err());
+
+        // Secret inputs are never part of a public witness
+        let mut secret = w.clone();
+        secret[4..8].copy_from_slice(&1u32.to_be_bytes());
+        assert!(check_witness(&secret, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_FREEZE, &[0; 32]).is_err());
+
+        assert!(check_witness(&w[..8], WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_FREEZE, &[0; 32]).is_err());
+    }
 
-    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump)]
-    pub vault: SystemAccount<'info>,
+    #[test]
+    fn previous_verifier_accepted_only_during_migration_window() {
+        let old = Pubkey::new_unique();
+        let new = Pubkey::new_unique();
+        let config = ProgramConfig {
+            admin: Pubkey::new_unique(),
+            verifier_program: new,
+            previous_verifier: Some(old),
+            previous_verifier_valid_until: 1_000,
+            pending_verifier: None,
+            pending_verifier_eta: 0,
+            bump: 255,
+            private_operation_fee: PRIVATE_OPERATION_FEE,
+            spend_fee_reimbursement: SPEND_FEE_REIMBURSEMENT,
+            note_verifier: Pubkey::default(),
+            self_relay_fee_bps: 0,
+            pending_admin: None,
+            paused: false,
+        };
+
+        // During the window both are accepted
+        assert!(config.accepts_verifier(&new, 999));
+        assert!(config.accepts_verifier(&old, 999));
+        // Afterwards only the current one
+        assert!(config.accepts_verifier(&new, 1_000));
+        assert!(!config.accepts_verifier(&old, 1_000));
+        assert!(!config.accepts_verifier(&Pubkey::new_unique(), 999));
+    }
 
-    /// CHECK: Any account can receive fee reimbursement
-    #[account(mut)]
-    pub fee_recipient: AccountInfo<'info>,
+    #[test]
+    fn registry_selects_witness_layout_until_removal() {
+        let commitment = [7u8; 32];
+        let mut registry = VerifierRegistry { entries: Vec::new(), bump: 0 };
+        let v5 = VerifierEntry {
+            verifier_program: Pubkey::new_unique(),
+            witness_version: 5,
+            proof_size: 388,
+            removable_at: 0,
+            commitment_version: 2,
+        };
+        registry.add(v5).unwrap();
+        assert!(registry.add(v5).is_err());
+
+        // v5 appends one public input after the v4 fields
+        let mut w = witness(commitment, 0, ACTION_FREEZE);
+        w[..4].copy_from_slice(&5u32.to_be_bytes());
+        w[8..12].copy_from_slice(&5u32.to_be_bytes());
+        w.extend_from_slice(&[0u8; 32]);
+        assert!(check_witness(&w, 5, &commitment, 0, ACTION_FREEZE, &[0; 32]).is_ok());
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &commitment, 0, ACTION_FREEZE, &[0; 32]).is_err());
+        assert!(check_witness(&w[..WITNESS_SIZE], 5, &commitment, 0, ACTION_FREEZE, &[0; 32]).is_err());
+
+        // Scheduled removal keeps the entry usable until removable_at
+        registry.entries[0].removable_at = 1_000;
+        assert_eq!(registry.active_entry(5, 999), Some(&registry.entries[0]));
+        assert!(registry.active_entry(5, 1_000).is_none());
+        assert!(registry.active_entry(4, 0).is_none());
+
+        // Agents may use the built-in commitment scheme or an active entry's
+        assert!(check_commitment_version(Some(&registry), 2, 999).is_ok());
+        assert!(check_commitment_version(Some(&registry), 2, 1_000).is_err());
+        assert!(check_commitment_version(None, COMMITMENT_VERSION, 0).is_ok());
+        assert!(check_commitment_version(None, 2, 0).is_err());
+    }
 
-    /// ZK Verifier program for proof validation
-    /// CHECK: Verified in instruction to match ZK_VERIFIER_PROGRAM_ID
-    pub zk_verifier: AccountInfo<'info>,
+    #[test]
+    fn rotation_witness_is_bound_to_new_commitment() {
+        let old_commitment = [7u8; 32];
+        let new_commitment = [9u8; 32];
+        let data = commitment_action_data(&new_commitment);
+        let w = witness_with_data(old_commitment, 0, ACTION_ROTATE_COMMITMENT, data);
+
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &old_commitment, 0, ACTION_ROTATE_COMMITMENT, &data).is_ok());
+        // Relayer substitutes a commitment it controls
+        let hijack = commitment_action_data(&[1u8; 32]);
+        assert!(check_witness(&w, WITNESS_LAYOUT_VERSION, &old_commitment, 0, ACTION_ROTATE_COMMITMENT, &hijack).is_err());
+        // After rotation, proofs for the old commitment no longer verify
+        let stale = witness(old_commitment, 1, ACTION_FREEZE);
+        assert!(check_witness(&stale, WITNESS_LAYOUT_VERSION, &new_commitment, 1, ACTION_FREEZE, &[0; 32]).is_err());
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    #[test]
+    fn nullifier_is_bound_to_the_exact_proof() {
+        let proof = [3u8; PROOF_SIZE];
+        let mut altered = proof;
+        assert_eq!(nullifier_proof_hash(&proof), nullifier_proof_hash(&[3u8; PROOF_SIZE]));
+        // Any change to the proof bytes yields a different nullifier address
+        altered[PROOF_SIZE - 1] ^= 1;
+        assert_ne!(nullifier_proof_hash(&proof), nullifier_proof_hash(&altered));
+    }
 
-#[derive(Accounts)]
-pub struct UpdateConstraintsPrivate<'info> {
-    #[account(
-        mut,
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+    #[test]
+    fn registered_relayer_is_only_enforced_on_opt_in() {
+        let relayer = Pubkey::new_unique();
+        let registry = RelayerRegistry { relayers: vec![relayer], bump: 0 };
+        // Open behavior: any fee_recipient, registry or not
+        assert!(check_relayer(false, None, &Pubkey::new_unique()).is_ok());
+
+        assert!(check_relayer(true, Some(&registry), &relayer).is_ok());
+        assert!(check_relayer(true, Some(&registry), &Pubkey::new_unique()).is_err());
+        // Omitting the registry does not bypass the check
+        assert!(check_relayer(true, None, &relayer).is_err());
+    }
 
-    #[account(mut, seeds = [b"vault", cloaked_agent_state.key().as_ref()], bump)]
-    pub vault: SystemAccount<'info>,
+    #[test]
+    fn private_ops_counter_resets_each_day() {
+        let day = 19_000;
+        let now = day * SECONDS_PER_DAY + 100;
+        assert_eq!(next_private_op_count(day, 0, 2, now).unwrap(), (day, 1));
+        assert_eq!(next_private_op_count(day, 1, 2, now).unwrap(), (day, 2));
+        assert!(next_private_op_count(day, 2, 2, now).is_err());
+        // The next UTC day starts a fresh count
+        assert_eq!(next_private_op_count(day, 2, 2, now + SECONDS_PER_DAY).unwrap(), (day + 1, 1));
+    }
 
-    /// CHECK: Any account can receive fee reimbursement
-    #[account(mut)]
-    pub fee_recipient: AccountInfo<'info>,
+    #[test]
+    fn note_set_root_matches_full_tree() {
+        let mut note_set = NoteSet {
+            index: 0,
+            notes: Vec::new(),
+            filled_subtrees: [[0; 32]; NOTE_TREE_DEPTH],
+            roots: [[0; 32]; NOTE_ROOT_HISTORY],
+            current_root_index: 0,
+            bump: 0,
+        };
+        note_set.roots[0] = empty_note_root();
+        let empty_root = note_set.current_root();
+
+        for i in 0..3u8 {
+            assert_eq!(note_set.insert([i + 1; 32]).unwrap(), i as u32);
+        }
 
-    /// ZK Verifier program for proof validation
-    /// CHECK: Verified in instruction to match ZK_VERIFIER_PROGRAM_ID
-    pub zk_verifier: AccountInfo<'info>,
+        // Recompute the root from every leaf
+        let mut level: Vec<[u8; 32]> = (0..NoteSet::MAX_NOTES)
+            .map(|i| note_set.notes.get(i).copied().unwrap_or([0; 32]))
+            .collect();
+        while level.len() > 1 {
+            level = level.chunks(2).map(|pair| note_tree_hash(&pair[0], &pair[1])).collect();
+        }
+        assert_eq!(note_set.current_root(), level[0]);
 
-    pub system_program: Program<'info, System>,
-}
+        // Earlier roots stay claimable
+        assert!(note_set.is_known_root(&empty_root));
+        assert!(!note_set.is_known_root(&[0; 32]));
+    }
 
-#[derive(Accounts)]
-pub struct CloseCloakedAgentPrivate<'info> {
-    #[account(
-        mut,
-        close = fee_recipient,  // Rent goes to relayer to recover creation cost
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+    #[test]
+    fn relayer_stats_record_fee() {
+        let mut stats = RelayerStats {
+            relayer: Pubkey::new_unique(),
+            total_fees_earned: 0,
+            total_ops_relayed: 0,
+            registered_at: 0,
+            bump: 0,
+        };
+        stats.record_fee(5_000).unwrap();
+        // Self-relayed operations can pay no fee but are still counted
+        stats.record_fee(0).unwrap();
+        assert_eq!(stats.total_fees_earned, 5_000);
+        assert_eq!(stats.total_ops_relayed, 2);
+
+        stats.total_fees_earned = u64::MAX;
+        assert!(stats.record_fee(1).is_err());
+    }
 
-    #[account(
-        mut,
-        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+    fn daily_window(spent: u64) -> SpendingWindow {
+        SpendingWindow {
+            window_seconds: DEFAULT_WINDOW_SECONDS,
+            limit: 1_000,
+            spent,
+            window_start: 0,
+        }
+    }
 
-    /// Destination for remaining vault funds
-    /// CHECK: Any account can receive
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
+    #[test]
+    fn unused_allowance_is_zero_while_window_is_open() {
+        assert_eq!(daily_window(300).unused_allowance(SECONDS_PER_DAY - 1), 0);
+    }
 
-    /// Fee recipient (relayer) - gets operation fee + account rent on close
-    /// CHECK: Any account can receive fee reimbursement
-    #[account(mut)]
-    pub fee_recipient: AccountInfo<'info>,
+    #[test]
+    fn unused_allowance_counts_rest_of_elapsed_window() {
+        assert_eq!(daily_window(300).unused_allowance(SECONDS_PER_DAY), 700);
+        assert_eq!(daily_window(300).unused_allowance(2 * SECONDS_PER_DAY - 1), 700);
+    }
 
-    /// ZK Verifier program for proof validation
-    /// CHECK: Verified in instruction to match ZK_VERIFIER_PROGRAM_ID
-    pub zk_verifier: AccountInfo<'info>,
+    #[test]
+    fn unused_allowance_counts_skipped_windows_in_full() {
+        // Three days without a spend after a day that used 300
+        assert_eq!(daily_window(300).unused_allowance(4 * SECONDS_PER_DAY), 700 + 3 * 1_000);
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    #[test]
+    fn unused_allowance_is_zero_for_unlimited_window() {
+        let mut window = daily_window(0);
+        window.limit = 0;
+        assert_eq!(window.unused_allowance(10 * SECONDS_PER_DAY), 0);
+    }
 
-#[derive(Accounts)]
-pub struct WithdrawPrivate<'info> {
-    #[account(
-        seeds = [b"cloaked_agent_state", cloaked_agent_state.delegate.as_ref()],
-        bump = cloaked_agent_state.bump,
-    )]
-    pub cloaked_agent_state: Account<'info, CloakedAgentState>,
+    /// All-zero agent state (what a fresh account deserializes to)
+    fn zeroed_agent() -> CloakedAgentState {
+        let data = vec![0u8; CloakedAgentState::SIZE];
+        CloakedAgentState::try_deserialize_unchecked(&mut &data[..]).unwrap()
+    }
 
-    #[account(
-        mut,
-        seeds = [b"vault", cloaked_agent_state.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+    fn clock_at(unix_timestamp: i64) -> Clock {
+        Clock { unix_timestamp, ..Clock::default() }
+    }
 
-    /// Destination for funds
-    /// CHECK: Any account can receive
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
+    #[test]
+    fn remaining_daily_allowance_resets_on_new_day() {
+        let mut agent = zeroed_agent();
+        agent.spending_window = daily_window(300);
+        assert_eq!(agent.remaining_daily_allowance(&clock_at(SECONDS_PER_DAY - 1)), 700);
+        assert_eq!(agent.remaining_daily_allowance(&clock_at(SECONDS_PER_DAY)), 1_000);
 
-    /// CHECK: Any account can receive fee reimbursement
-    #[account(mut)]
-    pub fee_recipient: AccountInfo<'info>,
+        agent.spending_window.limit = 0;
+        assert_eq!(agent.remaining_daily_allowance(&clock_at(0)), u64::MAX);
+    }
 
-    /// ZK Verifier program for proof validation
-    /// CHECK: Verified in instruction to match ZK_VERIFIER_PROGRAM_ID
-    pub zk_verifier: AccountInfo<'info>,
+    #[test]
+    fn remaining_total_allowance_saturates() {
+        let mut agent = zeroed_agent();
+        agent.total_spent = 400;
+        assert_eq!(agent.remaining_total_allowance(), u64::MAX);
 
-    pub system_program: Program<'info, System>,
-}
+        agent.total_limit = 1_000;
+        assert_eq!(agent.remaining_total_allowance(), 600);
 
-#[error_code]
-pub enum ErrorCode {
-    #[msg("Insufficient balance in agent")]
-    InsufficientBalance,
-    #[msg("Arithmetic overflow")]
-    Overflow,
-    #[msg("Agent is frozen")]
-    AgentFrozen,
-    #[msg("Agent has expired")]
-    AgentExpired,
-    #[msg("Amount exceeds per-transaction limit")]
-    ExceedsPerTxLimit,
-    #[msg("Amount exceeds daily limit")]
-    ExceedsDailyLimit,
-    #[msg("Amount exceeds total limit")]
-    ExceedsTotalLimit,
-    #[msg("Unauthorized: not owner")]
-    NotOwner,
-    #[msg("Agent is in private mode - use ZK proof instructions")]
-    IsPrivateMode,
-    #[msg("Agent is not in private mode")]
-    NotPrivateMode,
-    #[msg("Commitment mismatch in ZK proof")]
-    CommitmentMismatch,
-    #[msg("Invalid ZK proof")]
-    InvalidProof,
-    #[msg("ZK proof verification failed")]
-    ProofVerificationFailed,
-    #[msg("Invalid ZK verifier program")]
-    InvalidVerifierProgram,
-    #[msg("Insufficient balance for operation fee")]
-    InsufficientBalanceForFee,
-    #[msg("Invalid commitment: cannot be all zeros")]
-    InvalidCommitment,
-}
+        agent.total_spent = 1_200;
+        assert_eq!(agent.remaining_total_allowance(), 0);
+    }
 
-/// Cloaked Agent state - stores constraints and spending tracking
-///
-/// Supports two ownership modes:
-/// - Standard mode: owner = Some(wallet), owner_commitment = [0; 32]
-/// - Private mode: owner = None, owner_commitment = hash(secret)
-#[account]
-pub struct CloakedAgentState {
-    /// Human wallet - full control over agent (None for private mode)
-    pub owner: Option<Pubkey>,
-    /// Commitment hash for private mode ownership (zeros for standard mode)
-    /// In private mode: commitment = poseidon(agent_secret)
-    pub owner_commitment: [u8; 32],
-    /// Agent key - can spend within limits
-    pub delegate: Pubkey,
+    #[test]
+    fn is_active_requires_unfrozen_and_unexpired() {
+        let mut agent = zeroed_agent();
+        assert!(agent.is_active(&clock_at(1_000)));
 
-    /// Max lamports per transaction (0 = unlimited)
-    pub max_per_tx: u64,
-    /// Max lamports per day (0 = unlimited)
-    pub daily_limit: u64,
-    /// Max lifetime lamports (0 = unlimited)
-    pub total_limit: u64,
-    /// Unix timestamp expiration (0 = never)
-    pub expires_at: i64,
-    /// Emergency stop
-    pub frozen: bool,
+        agent.expires_at = 1_000;
+        assert!(agent.is_active(&clock_at(999)));
+        assert!(!agent.is_active(&clock_at(1_000)));
 
-    /// Lifetime spending
-    pub total_spent: u64,
-    /// Today's spending
-    pub daily_spent: u64,
-    /// Day tracker for reset (unix_timestamp / SECONDS_PER_DAY)
-    pub last_day: i64,
+        agent.expires_at = 0;
+        agent.frozen = true;
+        assert!(!agent.is_active(&clock_at(1_000)));
+    }
 
-    /// PDA bump
-    pub bump: u8,
-    /// Creation timestamp
-    pub created_at: i64,
-}
+    #[test]
+    fn timelock_cap_defaults_when_unset() {
+        let mut agent = zeroed_agent();
+        assert_eq!(agent.timelock_cap(), DEFAULT_MAX_CONCURRENT_TIMELOCKS);
 
-impl CloakedAgentState {
-    /// Account size: 8 (discriminator) + 33 (Option<Pubkey>) + 32 (commitment) + 32 (delegate)
-    ///              + 8*4 (u64 constraints) + 1 (frozen) + 8*3 (tracking) + 1 (bump) + 8 (created_at) = 171 bytes
-    pub const SIZE: usize = 8 + 33 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 8 + 1 + 8;
+        agent.max_concurrent_timelocks = 2;
+        assert_eq!(agent.timelock_cap(), 2);
+    }
 
-    /// Check if this is a private mode agent
-    pub fn is_private(&self) -> bool {
-        self.owner.is_none()
+    #[test]
+    fn spendable_balance_excludes_reserved_lamports() {
+        let mut agent = zeroed_agent();
+        assert_eq!(agent.spendable_balance(1_000), 1_000);
+
+        agent.reserved_lamports = 400;
+        assert_eq!(agent.spendable_balance(1_000), 600);
+        assert_eq!(agent.spendable_balance(300), 0);
     }
 }
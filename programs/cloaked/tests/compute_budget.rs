@@ -0,0 +1,339 @@
+//! Compute unit ceilings, measured with solana-program-test against the SBF build.
+//! Build it with the verifier CPI stubbed before running these tests:
+//!   CLOAKED_ALLOW_SKIP_ZK_RELEASE=1 cargo build-sbf --features skip-zk-verification
+//!   SBF_OUT_DIR=target/deploy cargo test -p cloaked --test compute_budget
+//! Private instruction figures cover this program only; the verifier's own CUs come on top
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, system_instruction};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use cloaked::{
+    close_action_data, nullifier_proof_hash, CommitmentScheme, ConstraintParams, ACTION_CLOSE,
+    ACTION_FREEZE, ACTION_ID_SIZE, ACTION_UPDATE_CONSTRAINTS, COMMITMENT_VERSION, PROOF_NONCE_SIZE,
+    PROOF_SIZE, WITNESS_LAYOUT_VERSION, WITNESS_SIZE, ZK_VERIFIER_PROGRAM_ID,
+};
+use solana_program_test::{BanksClient, ProgramTest};
+use solana_sdk::{
+    account::Account as SdkAccount,
+    hash::Hash,
+    signature::{Keypair, Signer as _},
+    transaction::Transaction,
+};
+use solana_sdk_ids::bpf_loader;
+
+const SPEND_MAX_CU: u64 = 50_000;
+const FREEZE_PRIVATE_MAX_CU: u64 = 150_000;
+const UPDATE_CONSTRAINTS_PRIVATE_MAX_CU: u64 = 150_000;
+const CLOSE_PRIVATE_MAX_CU: u64 = 150_000;
+
+const OWNER_COMMITMENT: [u8; 32] = [7; 32];
+
+async fn start() -> (BanksClient, Keypair) {
+    let mut program_test = ProgramTest::new("cloaked", cloaked::ID, None);
+    program_test.prefer_bpf(true);
+    // Only checked for lamports + executable: skip-zk-verification never invokes it
+    program_test.add_account(
+        ZK_VERIFIER_PROGRAM_ID,
+        SdkAccount {
+            lamports: 1_000_000_000,
+            data: vec![0; 1],
+            owner: bpf_loader::id(),
+            executable: true,
+            rent_epoch: 0,
+        },
+    );
+    let (banks, payer, _) = program_test.start().await;
+    (banks, payer)
+}
+
+fn transaction(payer: &Keypair, signers: &[&Keypair], ix: Instruction, blockhash: Hash) -> Transaction {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &all_signers, blockhash)
+}
+
+async fn process(banks: &mut BanksClient, payer: &Keypair, signers: &[&Keypair], ix: Instruction) {
+    let blockhash = banks.get_latest_blockhash().await.unwrap();
+    banks.process_transaction(transaction(payer, signers, ix, blockhash)).await.unwrap();
+}
+
+/// Simulate `ix`, require it to succeed and return the compute units it used
+async fn units_consumed(banks: &mut BanksClient, payer: &Keypair, signers: &[&Keypair], ix: Instruction) -> u64 {
+    let blockhash = banks.get_latest_blockhash().await.unwrap();
+    let simulation = banks
+        .simulate_transaction(transaction(payer, signers, ix, blockhash))
+        .await
+        .unwrap();
+    assert!(matches!(simulation.result, Some(Ok(()))), "{:?}", simulation.result);
+    simulation.simulation_details.unwrap().units_consumed
+}
+
+fn agent_pdas(payer: &Pubkey) -> (Pubkey, Pubkey) {
+    let (agent, _) = Pubkey::find_program_address(
+        &[b"cloaked_agent_state", payer.as_ref(), &0u64.to_le_bytes()],
+        &cloaked::ID,
+    );
+    let (vault, _) = Pubkey::find_program_address(&[b"vault", agent.as_ref()], &cloaked::ID);
+    (agent, vault)
+}
+
+fn program_config_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"program_config"], &cloaked::ID).0
+}
+
+fn nullifier_address(proof: &[u8]) -> Pubkey {
+    Pubkey::find_program_address(&[b"nullifier", &nullifier_proof_hash(proof)], &cloaked::ID).0
+}
+
+/// Witness for the agent's first proof (nonce 0)
+fn first_witness(action_id: u8, action_data: [u8; 32]) -> Vec<u8> {
+    let public_count = (WITNESS_LAYOUT_VERSION as u32).to_be_bytes();
+    let mut witness = Vec::with_capacity(WITNESS_SIZE);
+    witness.extend_from_slice(&public_count);
+    witness.extend_from_slice(&0u32.to_be_bytes());
+    witness.extend_from_slice(&public_count);
+    witness.extend_from_slice(&OWNER_COMMITMENT);
+    witness.extend_from_slice(&[0u8; PROOF_NONCE_SIZE]);
+    witness.extend_from_slice(&[0u8; ACTION_ID_SIZE - 1]);
+    witness.push(action_id);
+    witness.extend_from_slice(&action_data);
+    witness
+}
+
+/// Create a funded private agent and return (agent, vault)
+async fn private_agent(banks: &mut BanksClient, payer: &Keypair) -> (Pubkey, Pubkey) {
+    let (agent, vault) = agent_pdas(&payer.pubkey());
+    let create = Instruction {
+        program_id: cloaked::ID,
+        accounts: cloaked::accounts::CreateCloakedAgentPrivate {
+            cloaked_agent_state: agent,
+            vault,
+            delegate: Keypair::new().pubkey(),
+            payer: payer.pubkey(),
+            parent_state: None,
+            parent_delegate: None,
+            delegate_registry: None,
+            global_stats: None,
+            verifier_registry: None,
+            system_program: System::id(),
+        }
+        .to_account_metas(None),
+        data: cloaked::instruction::CreateCloakedAgentPrivate {
+            creation_nonce: 0,
+            owner_commitment: OWNER_COMMITMENT,
+            max_per_tx: 0,
+            window_limit: 0,
+            total_limit: 0,
+            expires_at: 0,
+            max_vault_balance: 0,
+            window_seconds: None,
+            recovery_commitment: None,
+            require_registered_relayer: false,
+            commitment_scheme: CommitmentScheme::Zk,
+            commitment_version: COMMITMENT_VERSION,
+        }
+        .data(),
+    };
+    process(banks, payer, &[], create).await;
+    let fund = system_instruction::transfer(&payer.pubkey(), &vault, 2_000_000_000);
+    process(banks, payer, &[], fund).await;
+    (agent, vault)
+}
+
+#[tokio::test]
+async fn spend_stays_under_budget() {
+    let (mut banks, payer) = start().await;
+    let delegate = Keypair::new();
+    let (agent, vault) = agent_pdas(&payer.pubkey());
+    let create = Instruction {
+        program_id: cloaked::ID,
+        accounts: cloaked::accounts::CreateCloakedAgent {
+            cloaked_agent_state: agent,
+            vault,
+            owner: payer.pubkey(),
+            delegate: delegate.pubkey(),
+            payer: payer.pubkey(),
+            parent_state: None,
+            parent_delegate: None,
+            delegate_registry: None,
+            global_stats: None,
+            system_program: System::id(),
+        }
+        .to_account_metas(None),
+        data: cloaked::instruction::CreateCloakedAgent {
+            creation_nonce: 0,
+            max_per_tx: 0,
+            window_limit: 0,
+            total_limit: 0,
+            expires_at: 0,
+            max_vault_balance: 0,
+            withdraw_destination_lock: None,
+            emergency_rescue_address: None,
+            rescue_delay: 0,
+            window_seconds: None,
+            auto_close_on_zero: false,
+        }
+        .data(),
+    };
+    process(&mut banks, &payer, &[], create).await;
+    let fund = system_instruction::transfer(&payer.pubkey(), &vault, 2_000_000_000);
+    process(&mut banks, &payer, &[], fund).await;
+
+    let spend = Instruction {
+        program_id: cloaked::ID,
+        accounts: cloaked::accounts::Spend {
+            cloaked_agent_state: agent,
+            vault,
+            delegate: delegate.pubkey(),
+            fee_payer: payer.pubkey(),
+            destination: Keypair::new().pubkey(),
+            commission_recipient: None,
+            whitelist_entry: None,
+            parent_state: None,
+            global_stats: None,
+            delegate_registry: None,
+            program_config: program_config_address(),
+            system_program: System::id(),
+        }
+        .to_account_metas(None),
+        data: cloaked::instruction::Spend { amount: 1_000_000_000 }.data(),
+    };
+    let units = units_consumed(&mut banks, &payer, &[&delegate], spend).await;
+    assert!(units < SPEND_MAX_CU, "spend used {units} CU (limit {SPEND_MAX_CU})");
+}
+
+#[tokio::test]
+async fn freeze_private_stays_under_budget() {
+    let (mut banks, payer) = start().await;
+    let (agent, vault) = private_agent(&mut banks, &payer).await;
+    let proof = vec![1u8; PROOF_SIZE];
+
+    let freeze = Instruction {
+        program_id: cloaked::ID,
+        accounts: cloaked::accounts::FreezePrivate {
+            cloaked_agent_state: agent,
+            vault,
+            fee_recipient: payer.pubkey(),
+            zk_verifier: ZK_VERIFIER_PROGRAM_ID,
+            program_config: program_config_address(),
+            verifier_registry: None,
+            proof_buffer: None,
+            nullifier: nullifier_address(&proof),
+            relayer_registry: None,
+            instructions: None,
+            relayer_stats: None,
+            system_program: System::id(),
+        }
+        .to_account_metas(None),
+        data: cloaked::instruction::FreezePrivate {
+            witness_bytes: first_witness(ACTION_FREEZE, [0; 32]),
+            proof_bytes: proof,
+            verifier_version: WITNESS_LAYOUT_VERSION,
+            reason: [0; 32],
+        }
+        .data(),
+    };
+    let units = units_consumed(&mut banks, &payer, &[], freeze).await;
+    assert!(
+        units < FREEZE_PRIVATE_MAX_CU,
+        "freeze_private used {units} CU (limit {FREEZE_PRIVATE_MAX_CU})"
+    );
+}
+
+#[tokio::test]
+async fn update_constraints_private_stays_under_budget() {
+    let (mut banks, payer) = start().await;
+    let (agent, vault) = private_agent(&mut banks, &payer).await;
+    let proof = vec![2u8; PROOF_SIZE];
+    let params = ConstraintParams {
+        max_per_tx: Some(1_000_000),
+        ..Default::default()
+    };
+
+    let update = Instruction {
+        program_id: cloaked::ID,
+        accounts: cloaked::accounts::UpdateConstraintsPrivate {
+            cloaked_agent_state: agent,
+            vault,
+            fee_recipient: payer.pubkey(),
+            zk_verifier: ZK_VERIFIER_PROGRAM_ID,
+            program_config: program_config_address(),
+            verifier_registry: None,
+            proof_buffer: None,
+            nullifier: nullifier_address(&proof),
+            relayer_registry: None,
+            instructions: None,
+            relayer_stats: None,
+            system_program: System::id(),
+        }
+        .to_account_metas(None),
+        data: cloaked::instruction::UpdateConstraintsPrivate {
+            witness_bytes: first_witness(ACTION_UPDATE_CONSTRAINTS, params.params_hash()),
+            proof_bytes: proof,
+            verifier_version: WITNESS_LAYOUT_VERSION,
+            max_per_tx: params.max_per_tx,
+            window_seconds: params.window_seconds,
+            window_limit: params.window_limit,
+            total_limit: params.total_limit,
+            expires_at: params.expires_at,
+            max_vault_balance: params.max_vault_balance,
+            min_deposit: params.min_deposit,
+            min_spend_amount: params.min_spend_amount,
+            block_deposits_when_inactive: params.block_deposits_when_inactive,
+            allow_delegate_sessions: params.allow_delegate_sessions,
+            allowed_start_second_of_day: params.allowed_start_second_of_day,
+            allowed_end_second_of_day: params.allowed_end_second_of_day,
+            allowed_days_bitmask: params.allowed_days_bitmask,
+            rollover_cap: params.rollover_cap,
+            max_private_ops_per_day: params.max_private_ops_per_day,
+        }
+        .data(),
+    };
+    let units = units_consumed(&mut banks, &payer, &[], update).await;
+    assert!(
+        units < UPDATE_CONSTRAINTS_PRIVATE_MAX_CU,
+        "update_constraints_private used {units} CU (limit {UPDATE_CONSTRAINTS_PRIVATE_MAX_CU})"
+    );
+}
+
+#[tokio::test]
+async fn close_cloaked_agent_private_stays_under_budget() {
+    let (mut banks, payer) = start().await;
+    let (agent, vault) = private_agent(&mut banks, &payer).await;
+    let proof = vec![3u8; PROOF_SIZE];
+    let destination = Keypair::new().pubkey();
+
+    let close = Instruction {
+        program_id: cloaked::ID,
+        accounts: cloaked::accounts::CloseCloakedAgentPrivate {
+            cloaked_agent_state: agent,
+            vault,
+            destination,
+            fee_recipient: payer.pubkey(),
+            zk_verifier: ZK_VERIFIER_PROGRAM_ID,
+            program_config: program_config_address(),
+            verifier_registry: None,
+            proof_buffer: None,
+            nullifier: nullifier_address(&proof),
+            relayer_registry: None,
+            instructions: None,
+            relayer_stats: None,
+            parent_state: None,
+            delegate_registry: None,
+            global_stats: None,
+            system_program: System::id(),
+        }
+        .to_account_metas(None),
+        data: cloaked::instruction::CloseCloakedAgentPrivate {
+            witness_bytes: first_witness(ACTION_CLOSE, close_action_data(&destination, &payer.pubkey())),
+            proof_bytes: proof,
+            verifier_version: WITNESS_LAYOUT_VERSION,
+        }
+        .data(),
+    };
+    let units = units_consumed(&mut banks, &payer, &[], close).await;
+    assert!(
+        units < CLOSE_PRIVATE_MAX_CU,
+        "close_cloaked_agent_private used {units} CU (limit {CLOSE_PRIVATE_MAX_CU})"
+    );
+}
@@ -0,0 +1,49 @@
+//! Stub ZK verifier for devnet/localnet deployments where ZK_VERIFIER_PROGRAM_ID is not
+//! deployed. Accepts any `proof || witness` instruction data, so the cloaked program's
+//! commitment, nonce and action checks are the only ones applied.
+//!
+//! Build and deploy it alongside the main program, then point cloaked at it with
+//! set_verifier / apply_verifier or add_verifier_entry:
+//!   cargo build-sbf --manifest-path programs/mock-zk-verifier/Cargo.toml --features mock-zk-verifier
+//!   solana program deploy target/deploy/mock_zk_verifier.so
+
+use anchor_lang::solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[cfg(all(feature = "mock-zk-verifier", feature = "mainnet-deployment"))]
+compile_error!("features `mock-zk-verifier` and `mainnet-deployment` are mutually exclusive");
+
+/// Whether this build accepts proofs. Off unless built with `mock-zk-verifier`, and never
+/// on mainnet builds
+pub const MOCK_VERIFIER_ENABLED: bool =
+    cfg!(all(feature = "mock-zk-verifier", not(feature = "mainnet-deployment")));
+
+#[cfg(all(not(feature = "no-entrypoint"), not(feature = "mainnet-deployment")))]
+anchor_lang::solana_program::entrypoint!(process_instruction);
+
+/// Accept any proof when MOCK_VERIFIER_ENABLED, reject everything otherwise
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if !MOCK_VERIFIER_ENABLED {
+        msg!("mock-zk-verifier: disabled in this build");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    msg!("mock-zk-verifier: accepted {} bytes without verification", instruction_data.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_proofs_only_when_enabled() {
+        let result = process_instruction(&Pubkey::default(), &[], &[0u8; 464]);
+        assert_eq!(result.is_ok(), MOCK_VERIFIER_ENABLED);
+    }
+}
@@ -7,9 +7,18 @@ import {
   SystemProgram,
   LAMPORTS_PER_SOL,
 } from "@solana/web3.js";
-import { Program, AnchorProvider, BN, Wallet } from "@coral-xyz/anchor";
+import { Program, AnchorProvider, BN, Wallet, BorshAccountsCoder } from "@coral-xyz/anchor";
 import bs58 from "bs58";
-import { CLOAKED_PROGRAM_ID, ZK_VERIFIER_PROGRAM_ID } from "./constants";
+import nacl from "tweetnacl";
+import {
+  CLOAKED_PROGRAM_ID,
+  ZK_VERIFIER_PROGRAM_ID,
+  COMMITMENT_VERSION,
+  AGENT_STATE_DISCRIMINATOR,
+  OWNER_TAG_OFFSET,
+  PRIVATE_DELEGATE_OFFSET,
+  STANDARD_DELEGATE_OFFSET,
+} from "./constants";
 import IDL from "./idl.json";
 import {
   CloakedAgentState,
@@ -27,6 +36,15 @@ import {
   generateOwnershipProof,
   proofToInstructionArgs,
   isProverReady,
+  ACTION_FREEZE,
+  ACTION_UNFREEZE,
+  ACTION_UPDATE_CONSTRAINTS,
+  ACTION_CLOSE,
+  ACTION_WITHDRAW,
+  EMPTY_ACTION_DATA,
+  constraintsActionData,
+  closeActionData,
+  withdrawActionData,
 } from "./zk";
 import {
   createPrivateAgentViaRelayer,
@@ -40,9 +58,6 @@ import {
   getRelayerPublicKey,
 } from "./relayer";
 
-// Seconds in a day for daily limit calculations
-const SECONDS_PER_DAY = 86400;
-
 /**
  * CloakedAgent - Represents a Cloaked Agent on Solana
  *
@@ -67,7 +82,8 @@ export class CloakedAgent {
   private _agentSecret: bigint | null = null;
   private _nonce: number | null = null;
 
-  // Cached PDAs (set by forPrivateOwner to avoid getter issues)
+  // Cached PDAs (the state address is seeded by creator + nonce, so it is
+  // passed in, looked up by delegate, or set by forPrivateOwner)
   private _agentStatePda: PublicKey | null = null;
   private _vaultPda: PublicKey | null = null;
 
@@ -75,8 +91,9 @@ export class CloakedAgent {
    * Create a CloakedAgent from an Agent Key (agent mode - can spend)
    * @param agentKey - Base58 encoded secret key
    * @param rpcUrl - Solana RPC endpoint URL
+   * @param agentStatePda - Optional CloakedAgentState address (looked up by delegate if omitted)
    */
-  constructor(agentKey: string, rpcUrl: string) {
+  constructor(agentKey: string, rpcUrl: string, agentStatePda?: PublicKey) {
     const secretKey = bs58.decode(agentKey);
     this.keypair = Keypair.fromSecretKey(secretKey);
     this.delegatePubkey = this.keypair.publicKey;
@@ -95,6 +112,11 @@ export class CloakedAgent {
     );
     this._pda = pda;
     this._bump = bump;
+
+    if (agentStatePda) {
+      this._agentStatePda = agentStatePda;
+      this._vaultPda = CloakedAgent.deriveVaultPda(agentStatePda);
+    }
   }
 
   /**
@@ -104,8 +126,13 @@ export class CloakedAgent {
    *
    * @param delegatePubkey - Public key of the delegate (from URL or on-chain data)
    * @param rpcUrl - Solana RPC endpoint URL
+   * @param agentStatePda - Optional CloakedAgentState address (looked up by delegate if omitted)
    */
-  static forOwner(delegatePubkey: PublicKey | string, rpcUrl: string): CloakedAgent {
+  static forOwner(
+    delegatePubkey: PublicKey | string,
+    rpcUrl: string,
+    agentStatePda?: PublicKey
+  ): CloakedAgent {
     const pubkey = typeof delegatePubkey === "string"
       ? new PublicKey(delegatePubkey)
       : delegatePubkey;
@@ -132,6 +159,9 @@ export class CloakedAgent {
     instance._pda = pda;
     instance._bump = bump;
 
+    instance._agentStatePda = agentStatePda ?? null;
+    instance._vaultPda = agentStatePda ? CloakedAgent.deriveVaultPda(agentStatePda) : null;
+
     return instance;
   }
 
@@ -181,17 +211,8 @@ export class CloakedAgent {
     instance._bump = bump;
 
     // Cache agentStatePda and vaultPda for private mode operations
-    const [agentStatePda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("cloaked_agent_state"), found.delegate.toBuffer()],
-      CLOAKED_PROGRAM_ID
-    );
-    instance._agentStatePda = agentStatePda;
-
-    const [vaultPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("vault"), agentStatePda.toBuffer()],
-      CLOAKED_PROGRAM_ID
-    );
-    instance._vaultPda = vaultPda;
+    instance._agentStatePda = found.address;
+    instance._vaultPda = CloakedAgent.deriveVaultPda(found.address);
 
     return instance;
   }
@@ -232,34 +253,42 @@ export class CloakedAgent {
   }
 
   /**
-   * Get the CloakedAgentState PDA address for this delegate
+   * Get the CloakedAgentState PDA address for this agent
+   * The address is seeded by creator + creation nonce, not the delegate, so it
+   * must be known up front or resolved with resolveAgentStatePda() first.
    */
   get agentStatePda(): PublicKey {
-    // Use cached value if available (set by forPrivateOwner)
-    if (this._agentStatePda) {
-      return this._agentStatePda;
+    if (!this._agentStatePda) {
+      throw new Error("Agent state address not resolved - call resolveAgentStatePda() first");
     }
-    const [pda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("cloaked_agent_state"), this.delegatePubkey.toBuffer()],
-      CLOAKED_PROGRAM_ID
-    );
-    return pda;
+    return this._agentStatePda;
   }
 
   /**
    * Get the Vault PDA address for this agent
    */
   get vaultPda(): PublicKey {
-    // Use cached value if available (set by forPrivateOwner)
-    if (this._vaultPda) {
-      return this._vaultPda;
+    if (!this._vaultPda) {
+      this._vaultPda = CloakedAgent.deriveVaultPda(this.agentStatePda);
     }
-    const agentStatePda = this.agentStatePda;
-    const [vault] = PublicKey.findProgramAddressSync(
-      [Buffer.from("vault"), agentStatePda.toBuffer()],
-      CLOAKED_PROGRAM_ID
-    );
-    return vault;
+    return this._vaultPda;
+  }
+
+  /**
+   * Resolve (and cache) the CloakedAgentState address by looking up the delegate on-chain
+   * @returns The CloakedAgentState PDA
+   */
+  async resolveAgentStatePda(): Promise<PublicKey> {
+    if (this._agentStatePda) {
+      return this._agentStatePda;
+    }
+    const agentStatePda = await CloakedAgent.findAgentStatePda(this.connection, this.delegatePubkey);
+    if (!agentStatePda) {
+      throw new Error(`No Cloaked Agent found for delegate ${this.delegatePubkey.toBase58()}`);
+    }
+    this._agentStatePda = agentStatePda;
+    this._vaultPda = CloakedAgent.deriveVaultPda(agentStatePda);
+    return agentStatePda;
   }
 
   /**
@@ -267,8 +296,7 @@ export class CloakedAgent {
    * @returns Balance in SOL
    */
   async getBalance(): Promise<number> {
-    const lamports = await this.connection.getBalance(this.vaultPda);
-    return lamports / LAMPORTS_PER_SOL;
+    return (await this.getBalanceLamports()) / LAMPORTS_PER_SOL;
   }
 
   /**
@@ -276,6 +304,7 @@ export class CloakedAgent {
    * @returns Balance in lamports
    */
   async getBalanceLamports(): Promise<number> {
+    await this.resolveAgentStatePda();
     return await this.connection.getBalance(this.vaultPda);
   }
 
@@ -317,16 +346,54 @@ export class CloakedAgent {
   }
 
   /**
-   * Derive CloakedAgentState PDA for a delegate
+   * Derive CloakedAgentState PDA from its creator (the payer) and creation nonce
    */
-  static deriveAgentStatePda(delegate: PublicKey): PublicKey {
+  static deriveAgentStatePda(createdBy: PublicKey, creationNonce: BN): PublicKey {
     const [pda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("cloaked_agent_state"), delegate.toBuffer()],
+      [
+        Buffer.from("cloaked_agent_state"),
+        createdBy.toBuffer(),
+        creationNonce.toArrayLike(Buffer, "le", 8),
+      ],
       CLOAKED_PROGRAM_ID
     );
     return pda;
   }
 
+  /**
+   * Generate a random creation nonce for a new agent
+   */
+  static randomCreationNonce(): BN {
+    return new BN(nacl.randomBytes(8));
+  }
+
+  /**
+   * Look up the CloakedAgentState address for a delegate
+   * If several agents share the delegate, the first match is returned.
+   * @returns The CloakedAgentState PDA, or null if no agent uses this delegate
+   */
+  static async findAgentStatePda(connection: Connection, delegate: PublicKey): Promise<PublicKey | null> {
+    // owner is an Option<Pubkey>, so the delegate offset depends on its tag
+    const layouts: [number, number][] = [
+      [0, PRIVATE_DELEGATE_OFFSET],
+      [1, STANDARD_DELEGATE_OFFSET],
+    ];
+    for (const [ownerTag, delegateOffset] of layouts) {
+      const accounts = await connection.getProgramAccounts(CLOAKED_PROGRAM_ID, {
+        dataSlice: { offset: 0, length: 0 },
+        filters: [
+          { memcmp: { offset: 0, bytes: bs58.encode(AGENT_STATE_DISCRIMINATOR) } },
+          { memcmp: { offset: OWNER_TAG_OFFSET, bytes: bs58.encode([ownerTag]) } },
+          { memcmp: { offset: delegateOffset, bytes: delegate.toBase58() } },
+        ],
+      });
+      if (accounts.length > 0) {
+        return accounts[0].pubkey;
+      }
+    }
+    return null;
+  }
+
   /**
    * Derive Vault PDA from CloakedAgentState PDA
    */
@@ -368,17 +435,27 @@ export class CloakedAgent {
       ? Math.floor(options.expiresAt.getTime() / 1000)
       : 0;
 
-    // Derive PDAs
-    const agentStatePda = CloakedAgent.deriveAgentStatePda(delegate.publicKey);
+    // Derive PDAs (state is seeded by the payer and a creation nonce)
+    const creationNonce = options.creationNonce !== undefined
+      ? new BN(options.creationNonce)
+      : CloakedAgent.randomCreationNonce();
+    const agentStatePda = CloakedAgent.deriveAgentStatePda(owner.publicKey, creationNonce);
     const vaultPda = CloakedAgent.deriveVaultPda(agentStatePda);
 
     // Call create_cloaked_agent instruction
     const signature = await program.methods
       .createCloakedAgent(
+        creationNonce,
         new BN(options.maxPerTx ?? 0),
         new BN(options.dailyLimit ?? 0),
         new BN(options.totalLimit ?? 0),
-        new BN(expiresAt)
+        new BN(expiresAt),
+        new BN(0),
+        null,
+        null,
+        new BN(0),
+        null,
+        false
       )
       .accounts({
         cloakedAgentState: agentStatePda,
@@ -403,7 +480,7 @@ export class CloakedAgent {
         .rpc();
     }
 
-    const agent = new CloakedAgent(agentKey, rpcUrl);
+    const agent = new CloakedAgent(agentKey, rpcUrl, agentStatePda);
     return { agent, agentKey, signature };
   }
 
@@ -427,18 +504,19 @@ export class CloakedAgent {
     );
     const program = new Program(IDL as any, provider);
 
-    const agentStatePda = this.agentStatePda;
+    const agentStatePda = await this.resolveAgentStatePda();
     const vaultPda = this.vaultPda;
 
     const state = await (program.account as any).cloakedAgentState.fetch(agentStatePda);
     const balance = await this.connection.getBalance(vaultPda);
 
     const now = Math.floor(Date.now() / 1000);
-    const currentDay = Math.floor(now / SECONDS_PER_DAY);
-    const lastDay = (state.lastDay as BN).toNumber();
+    const spendingWindow = state.spendingWindow;
+    const windowEnd = (spendingWindow.windowStart as BN).toNumber() + (spendingWindow.windowSeconds as BN).toNumber();
 
-    const dailySpent = currentDay > lastDay ? 0 : (state.dailySpent as BN).toNumber();
-    const dailyLimit = (state.dailyLimit as BN).toNumber();
+    // The window restarts on the next spend once it has elapsed
+    const dailySpent = now >= windowEnd ? 0 : (spendingWindow.spent as BN).toNumber();
+    const dailyLimit = (spendingWindow.limit as BN).toNumber();
     const dailyRemaining = dailyLimit === 0 ? Number.MAX_SAFE_INTEGER : Math.max(0, dailyLimit - dailySpent);
 
     const totalSpent = (state.totalSpent as BN).toNumber();
@@ -514,7 +592,7 @@ export class CloakedAgent {
       throw new Error("Cannot spend in owner mode - requires Agent Key");
     }
 
-    const agentStatePda = this.agentStatePda;
+    const agentStatePda = await this.resolveAgentStatePda();
     const vaultPda = this.vaultPda;
 
     // If feePayer provided, user pays directly (no relayer)
@@ -636,7 +714,7 @@ export class CloakedAgent {
     );
     const program = new Program(IDL as any, provider);
 
-    const agentStatePda = this.agentStatePda;
+    const agentStatePda = await this.resolveAgentStatePda();
     const vaultPda = this.vaultPda;
 
     const signature = await program.methods
@@ -655,9 +733,10 @@ export class CloakedAgent {
   /**
    * Freeze agent (owner only) - emergency stop
    * @param owner - Owner signer (wallet adapter or wrapped Keypair)
+   * @param reason - Optional 32-byte freeze reason recorded on-chain (zeros = none)
    * @returns Transaction signature
    */
-  async freeze(owner: Signer): Promise<string> {
+  async freeze(owner: Signer, reason: number[] = new Array(32).fill(0)): Promise<string> {
     const provider = new AnchorProvider(
       this.connection,
       owner as Wallet,
@@ -665,10 +744,10 @@ export class CloakedAgent {
     );
     const program = new Program(IDL as any, provider);
 
-    const agentStatePda = this.agentStatePda;
+    const agentStatePda = await this.resolveAgentStatePda();
 
     const signature = await program.methods
-      .freeze()
+      .freeze(reason)
       .accounts({
         cloakedAgentState: agentStatePda,
         owner: owner.publicKey,
@@ -691,7 +770,7 @@ export class CloakedAgent {
     );
     const program = new Program(IDL as any, provider);
 
-    const agentStatePda = this.agentStatePda;
+    const agentStatePda = await this.resolveAgentStatePda();
 
     const signature = await program.methods
       .unfreeze()
@@ -718,7 +797,7 @@ export class CloakedAgent {
     );
     const program = new Program(IDL as any, provider);
 
-    const agentStatePda = this.agentStatePda;
+    const agentStatePda = await this.resolveAgentStatePda();
 
     const maxPerTx = options.maxPerTx !== undefined ? new BN(options.maxPerTx) : null;
     const dailyLimit = options.dailyLimit !== undefined ? new BN(options.dailyLimit) : null;
@@ -728,7 +807,22 @@ export class CloakedAgent {
       : null;
 
     const signature = await program.methods
-      .updateConstraints(maxPerTx, dailyLimit, totalLimit, expiresAt)
+      .updateConstraints(
+        maxPerTx,
+        null,
+        dailyLimit,
+        totalLimit,
+        expiresAt,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null,
+        null
+      )
       .accounts({
         cloakedAgentState: agentStatePda,
         owner: owner.publicKey,
@@ -751,7 +845,7 @@ export class CloakedAgent {
     );
     const program = new Program(IDL as any, provider);
 
-    const agentStatePda = this.agentStatePda;
+    const agentStatePda = await this.resolveAgentStatePda();
     const vaultPda = this.vaultPda;
 
     const signature = await program.methods
@@ -760,6 +854,7 @@ export class CloakedAgent {
         cloakedAgentState: agentStatePda,
         vault: vaultPda,
         owner: owner.publicKey,
+        vaultRecipient: owner.publicKey,
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -784,7 +879,7 @@ export class CloakedAgent {
     );
     const program = new Program(IDL as any, provider);
 
-    const agentStatePda = this.agentStatePda;
+    const agentStatePda = await this.resolveAgentStatePda();
     const vaultPda = this.vaultPda;
 
     const signature = await program.methods
@@ -844,18 +939,28 @@ export class CloakedAgent {
       ? Math.floor(options.expiresAt.getTime() / 1000)
       : 0;
 
-    // Derive PDAs
-    const agentStatePda = CloakedAgent.deriveAgentStatePda(delegate.publicKey);
+    // Derive PDAs (state is seeded by the payer and a creation nonce)
+    const creationNonce = options.creationNonce !== undefined
+      ? new BN(options.creationNonce)
+      : CloakedAgent.randomCreationNonce();
+    const agentStatePda = CloakedAgent.deriveAgentStatePda(payer.publicKey, creationNonce);
     const vaultPda = CloakedAgent.deriveVaultPda(agentStatePda);
 
     // Call create_cloaked_agent_private instruction
     const signature = await program.methods
       .createCloakedAgentPrivate(
+        creationNonce,
         Array.from(commitmentBytes),
         new BN(options.maxPerTx ?? 0),
         new BN(options.dailyLimit ?? 0),
         new BN(options.totalLimit ?? 0),
-        new BN(expiresAt)
+        new BN(expiresAt),
+        new BN(0),
+        null,
+        null,
+        false,
+        { zk: {} },
+        COMMITMENT_VERSION
       )
       .accounts({
         cloakedAgentState: agentStatePda,
@@ -879,7 +984,7 @@ export class CloakedAgent {
         .rpc();
     }
 
-    const agent = new CloakedAgent(agentKey, rpcUrl);
+    const agent = new CloakedAgent(agentKey, rpcUrl, agentStatePda);
     agent._ownerCommitment = commitmentBytes;
     agent._agentSecret = agentSecret;
     agent._nonce = nonce;
@@ -936,7 +1041,7 @@ export class CloakedAgent {
     const commitmentBytes = commitmentToBytes(commitment);
 
     // Create agent instance with private mode
-    const agent = new CloakedAgent(result.agentKey, rpcUrl);
+    const agent = new CloakedAgent(result.agentKey, rpcUrl, result.agentStatePda);
     agent._ownerCommitment = commitmentBytes;
     agent._agentSecret = agentSecret;
     agent._nonce = nonce;
@@ -1006,18 +1111,28 @@ export class CloakedAgent {
       ? Math.floor(options.expiresAt.getTime() / 1000)
       : 0;
 
-    // Derive PDAs
-    const agentStatePda = CloakedAgent.deriveAgentStatePda(delegate.publicKey);
+    // Derive PDAs (state is seeded by the payer and a creation nonce)
+    const creationNonce = options.creationNonce !== undefined
+      ? new BN(options.creationNonce)
+      : CloakedAgent.randomCreationNonce();
+    const agentStatePda = CloakedAgent.deriveAgentStatePda(relayerKeypair.publicKey, creationNonce);
     const vaultPda = CloakedAgent.deriveVaultPda(agentStatePda);
 
     // Call create_cloaked_agent_private instruction
     const signature = await program.methods
       .createCloakedAgentPrivate(
+        creationNonce,
         Array.from(commitmentBytes),
         new BN(options.maxPerTx ?? 0),
         new BN(options.dailyLimit ?? 0),
         new BN(options.totalLimit ?? 0),
-        new BN(expiresAt)
+        new BN(expiresAt),
+        new BN(0),
+        null,
+        null,
+        false,
+        { zk: {} },
+        COMMITMENT_VERSION
       )
       .accounts({
         cloakedAgentState: agentStatePda,
@@ -1028,7 +1143,7 @@ export class CloakedAgent {
       })
       .rpc();
 
-    const agent = new CloakedAgent(agentKey, rpcUrl);
+    const agent = new CloakedAgent(agentKey, rpcUrl, agentStatePda);
     agent._ownerCommitment = commitmentBytes;
     agent._agentSecret = agentSecret;
     agent._nonce = nonce;
@@ -1042,29 +1157,54 @@ export class CloakedAgent {
   }
 
   /**
-   * Freeze agent using ZK proof (private mode only)
-   * Uses relayer to submit transaction - vault pays for fees
-   * @param apiUrl - Optional API URL for relayer
-   * @returns Transaction signature
+   * Generate an ownership proof bound to the agent's current proof_nonce
+   * and the given action (private mode only)
    */
-  async freezePrivate(apiUrl?: string): Promise<string> {
+  private async proveOwnership(
+    actionId: number,
+    actionData: Uint8Array,
+    publicMethod: string
+  ): Promise<{ proofBytes: number[]; witnessBytes: number[] }> {
     if (!this._agentSecret || !this._ownerCommitment) {
-      throw new Error("Not in private mode - use freeze() with owner signer instead");
+      throw new Error(`Not in private mode - use ${publicMethod}() with owner signer instead`);
     }
 
     if (!isProverReady()) {
       throw new Error("ZK prover not initialized. Call initProver() first.");
     }
 
+    const agentStatePda = await this.resolveAgentStatePda();
+    const accountInfo = await this.connection.getAccountInfo(agentStatePda);
+    if (!accountInfo) {
+      throw new Error("Agent state account not found");
+    }
+    const state = new BorshAccountsCoder(IDL as any).decode("CloakedAgentState", accountInfo.data);
+
     const commitment = bytesToCommitment(this._ownerCommitment);
-    const proof = await generateOwnershipProof(this._agentSecret, commitment);
-    const proofArgs = proofToInstructionArgs(proof);
+    const proof = await generateOwnershipProof(this._agentSecret, commitment, {
+      proofNonce: BigInt((state.proofNonce as BN).toString()),
+      actionId,
+      actionData,
+    });
+    return proofToInstructionArgs(proof);
+  }
+
+  /**
+   * Freeze agent using ZK proof (private mode only)
+   * Uses relayer to submit transaction - vault pays for fees
+   * @param apiUrl - Optional API URL for relayer
+   * @param reason - 32-byte freeze reason recorded on the agent
+   * @returns Transaction signature
+   */
+  async freezePrivate(apiUrl?: string, reason: number[] = new Array(32).fill(0)): Promise<string> {
+    const proofArgs = await this.proveOwnership(ACTION_FREEZE, EMPTY_ACTION_DATA, "freeze");
 
     const signature = await freezePrivateViaRelayer(
       {
-        agentStatePda: this.agentStatePda.toBase58(),
-        proofBytes: Array.from(proofArgs.proofBytes),
-        witnessBytes: Array.from(proofArgs.witnessBytes),
+        agentStatePda: (await this.resolveAgentStatePda()).toBase58(),
+        proofBytes: proofArgs.proofBytes,
+        witnessBytes: proofArgs.witnessBytes,
+        reason,
       },
       apiUrl
     );
@@ -1079,23 +1219,13 @@ export class CloakedAgent {
    * @returns Transaction signature
    */
   async unfreezePrivate(apiUrl?: string): Promise<string> {
-    if (!this._agentSecret || !this._ownerCommitment) {
-      throw new Error("Not in private mode - use unfreeze() with owner signer instead");
-    }
-
-    if (!isProverReady()) {
-      throw new Error("ZK prover not initialized. Call initProver() first.");
-    }
-
-    const commitment = bytesToCommitment(this._ownerCommitment);
-    const proof = await generateOwnershipProof(this._agentSecret, commitment);
-    const proofArgs = proofToInstructionArgs(proof);
+    const proofArgs = await this.proveOwnership(ACTION_UNFREEZE, EMPTY_ACTION_DATA, "unfreeze");
 
     const signature = await unfreezePrivateViaRelayer(
       {
-        agentStatePda: this.agentStatePda.toBase58(),
-        proofBytes: Array.from(proofArgs.proofBytes),
-        witnessBytes: Array.from(proofArgs.witnessBytes),
+        agentStatePda: (await this.resolveAgentStatePda()).toBase58(),
+        proofBytes: proofArgs.proofBytes,
+        witnessBytes: proofArgs.witnessBytes,
       },
       apiUrl
     );
@@ -1111,29 +1241,47 @@ export class CloakedAgent {
    * @returns Transaction signature
    */
   async updateConstraintsPrivate(options: ConstraintOptions, apiUrl?: string): Promise<string> {
-    if (!this._agentSecret || !this._ownerCommitment) {
-      throw new Error("Not in private mode - use updateConstraints() with owner signer instead");
-    }
-
-    if (!isProverReady()) {
-      throw new Error("ZK prover not initialized. Call initProver() first.");
-    }
+    const maxPerTx = options.maxPerTx !== undefined ? options.maxPerTx : null;
+    const dailyLimit = options.dailyLimit !== undefined ? options.dailyLimit : null;
+    const totalLimit = options.totalLimit !== undefined ? options.totalLimit : null;
+    const expiresAt = options.expiresAt !== undefined
+      ? (options.expiresAt ? Math.floor(options.expiresAt.getTime() / 1000) : 0)
+      : null;
 
-    const commitment = bytesToCommitment(this._ownerCommitment);
-    const proof = await generateOwnershipProof(this._agentSecret, commitment);
-    const proofArgs = proofToInstructionArgs(proof);
+    // Must match the arguments the relayer passes to update_constraints_private
+    const toBigInt = (value: number | null) => (value !== null ? BigInt(value) : null);
+    const actionData = await constraintsActionData({
+      maxPerTx: toBigInt(maxPerTx),
+      windowSeconds: null,
+      windowLimit: toBigInt(dailyLimit),
+      totalLimit: toBigInt(totalLimit),
+      expiresAt: toBigInt(expiresAt),
+      maxVaultBalance: null,
+      minDeposit: null,
+      minSpendAmount: null,
+      blockDepositsWhenInactive: null,
+      allowDelegateSessions: null,
+      allowedStartSecondOfDay: null,
+      allowedEndSecondOfDay: null,
+      allowedDaysBitmask: null,
+      rolloverCap: null,
+      maxPrivateOpsPerDay: null,
+    });
+    const proofArgs = await this.proveOwnership(
+      ACTION_UPDATE_CONSTRAINTS,
+      actionData,
+      "updateConstraints"
+    );
 
     const signature = await updateConstraintsPrivateViaRelayer(
       {
-        agentStatePda: this.agentStatePda.toBase58(),
-        proofBytes: Array.from(proofArgs.proofBytes),
-        witnessBytes: Array.from(proofArgs.witnessBytes),
-        maxPerTx: options.maxPerTx !== undefined ? options.maxPerTx : null,
-        dailyLimit: options.dailyLimit !== undefined ? options.dailyLimit : null,
-        totalLimit: options.totalLimit !== undefined ? options.totalLimit : null,
-        expiresAt: options.expiresAt !== undefined
-          ? (options.expiresAt ? Math.floor(options.expiresAt.getTime() / 1000) : 0)
-          : null,
+        agentStatePda: (await this.resolveAgentStatePda()).toBase58(),
+        proofBytes: proofArgs.proofBytes,
+        witnessBytes: proofArgs.witnessBytes,
+        maxPerTx,
+        dailyLimit,
+        totalLimit,
+        expiresAt,
       },
       apiUrl
     );
@@ -1149,23 +1297,16 @@ export class CloakedAgent {
    * @returns Transaction signature
    */
   async closePrivate(destination: PublicKey, apiUrl?: string): Promise<string> {
-    if (!this._agentSecret || !this._ownerCommitment) {
-      throw new Error("Not in private mode - use close() with owner signer instead");
-    }
-
-    if (!isProverReady()) {
-      throw new Error("ZK prover not initialized. Call initProver() first.");
-    }
-
-    const commitment = bytesToCommitment(this._ownerCommitment);
-    const proof = await generateOwnershipProof(this._agentSecret, commitment);
-    const proofArgs = proofToInstructionArgs(proof);
+    // The relayer receives the account rent as fee_recipient, which the proof commits to
+    const feeRecipient = await getRelayerPublicKey(apiUrl);
+    const actionData = await closeActionData(destination, feeRecipient);
+    const proofArgs = await this.proveOwnership(ACTION_CLOSE, actionData, "close");
 
     const signature = await closePrivateViaRelayer(
       {
-        agentStatePda: this.agentStatePda.toBase58(),
-        proofBytes: Array.from(proofArgs.proofBytes),
-        witnessBytes: Array.from(proofArgs.witnessBytes),
+        agentStatePda: (await this.resolveAgentStatePda()).toBase58(),
+        proofBytes: proofArgs.proofBytes,
+        witnessBytes: proofArgs.witnessBytes,
         destination: destination.toBase58(),
       },
       apiUrl
@@ -1180,28 +1321,26 @@ export class CloakedAgent {
    * @param amount - Amount in lamports to withdraw
    * @param destination - Destination for funds
    * @param apiUrl - Optional API URL for relayer
+   * @param bypassExpiry - Withdraw even if the agent has expired
    * @returns Transaction signature
    */
-  async withdrawPrivate(amount: number, destination: PublicKey, apiUrl?: string): Promise<string> {
-    if (!this._agentSecret || !this._ownerCommitment) {
-      throw new Error("Not in private mode - use withdraw() with owner signer instead");
-    }
-
-    if (!isProverReady()) {
-      throw new Error("ZK prover not initialized. Call initProver() first.");
-    }
-
-    const commitment = bytesToCommitment(this._ownerCommitment);
-    const proof = await generateOwnershipProof(this._agentSecret, commitment);
-    const proofArgs = proofToInstructionArgs(proof);
+  async withdrawPrivate(
+    amount: number,
+    destination: PublicKey,
+    apiUrl?: string,
+    bypassExpiry: boolean = false
+  ): Promise<string> {
+    const actionData = await withdrawActionData(destination, amount, bypassExpiry);
+    const proofArgs = await this.proveOwnership(ACTION_WITHDRAW, actionData, "withdraw");
 
     const signature = await withdrawPrivateViaRelayer(
       {
-        agentStatePda: this.agentStatePda.toBase58(),
-        proofBytes: Array.from(proofArgs.proofBytes),
-        witnessBytes: Array.from(proofArgs.witnessBytes),
+        agentStatePda: (await this.resolveAgentStatePda()).toBase58(),
+        proofBytes: proofArgs.proofBytes,
+        witnessBytes: proofArgs.witnessBytes,
         amount,
         destination: destination.toBase58(),
+        bypassExpiry,
       },
       apiUrl
     );
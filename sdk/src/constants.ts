@@ -1,4 +1,5 @@
 import { PublicKey } from "@solana/web3.js";
+import IDL from "./idl.json";
 
 // Cloaked Program ID (deployed on devnet)
 export const CLOAKED_PROGRAM_ID = new PublicKey(
@@ -9,3 +10,18 @@ export const CLOAKED_PROGRAM_ID = new PublicKey(
 export const ZK_VERIFIER_PROGRAM_ID = new PublicKey(
   "G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F"
 );
+
+// Owner commitment version the program accepts by default (Poseidon)
+export const COMMITMENT_VERSION = 1;
+
+// Anchor discriminator of CloakedAgentState accounts (for getProgramAccounts filters)
+export const AGENT_STATE_DISCRIMINATOR = Buffer.from(
+  IDL.accounts.find((account) => account.name === "CloakedAgentState")!.discriminator
+);
+
+// CloakedAgentState layout: discriminator (8) + owner Option<Pubkey> + owner_commitment (32) + delegate (32)
+// owner is None (1 byte) for private agents and Some (33 bytes) for standard agents
+export const OWNER_TAG_OFFSET = 8;
+export const PRIVATE_COMMITMENT_OFFSET = 9;
+export const PRIVATE_DELEGATE_OFFSET = 41;
+export const STANDARD_DELEGATE_OFFSET = 73;
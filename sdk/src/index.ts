@@ -19,11 +19,29 @@ export {
   type CreatePrivateViaRelayerResult,
   type RelayerStatus,
   type PrivateOperationParams,
+  type FreezePrivateParams,
   type UpdateConstraintsPrivateParams,
   type WithdrawPrivateParams,
   type ClosePrivateParams,
 } from "./relayer";
 
+// Notes (unlinkable vault funding)
+export {
+  NOTE_DENOMINATION,
+  NOTE_TREE_DEPTH,
+  deriveNoteSetPda,
+  deriveNoteNullifierPda,
+  generateNote,
+  noteFromSecrets,
+  noteMerklePath,
+  depositNote,
+  prepareNoteClaim,
+  claimNote,
+  type Note,
+  type NoteClaim,
+  type ClaimNoteParams,
+} from "./notes";
+
 // ZK Privacy module
 export {
   // Poseidon hash
@@ -43,13 +61,27 @@ export {
   isProverReady,
   generateOwnershipProof,
   proofToInstructionArgs,
+  // Action binding
+  EMPTY_ACTION_DATA,
+  actionDataHash,
+  withdrawActionData,
+  withdrawAllActionData,
+  closeActionData,
+  claimNoteActionData,
+  constraintsActionData,
   // Discovery
   findAgentByCommitment,
   discoverPrivateAgents,
   getNextPrivateNonce,
   agentExistsForCommitment,
 } from "./zk";
-export type { PrivateAgentSecrets, OwnershipProof, DiscoveredPrivateAgent } from "./zk";
+export type {
+  PrivateAgentSecrets,
+  OwnershipProof,
+  DiscoveredPrivateAgent,
+  ProofBinding,
+  ConstraintParams,
+} from "./zk";
 
 // MCP types (for programmatic use)
 export type {
@@ -0,0 +1,266 @@
+/**
+ * Note deposits and claims
+ *
+ * deposit_note locks NOTE_DENOMINATION in a NoteSet under the hash of a secret
+ * note. Whoever keeps the note can later credit any private agent's vault with
+ * claim_note_private, without revealing which deposit it was.
+ *
+ * Mirrors the program and circuits/note_claim:
+ * - commitment = H(nullifierSecret || secret)
+ * - nullifier = H(nullifierSecret)
+ * - tree node = H(left || right)
+ * - action_data = H(agent || nullifier)
+ * where H is sha256 with byte 0 cleared (actionDataHash)
+ */
+
+import { Connection, PublicKey, SystemProgram } from "@solana/web3.js";
+import { Program, AnchorProvider, Wallet } from "@coral-xyz/anchor";
+import nacl from "tweetnacl";
+import { CLOAKED_PROGRAM_ID } from "./constants";
+import IDL from "./idl.json";
+import { Signer } from "./signer";
+import { actionDataHash, claimNoteActionData, toFieldHex, ACTION_CLAIM_NOTE } from "./zk/actions";
+
+/** Lamports locked by every note (0.1 SOL) */
+export const NOTE_DENOMINATION = 100_000_000;
+/** Depth of a NoteSet's Merkle tree; each set holds 2^depth notes */
+export const NOTE_TREE_DEPTH = 8;
+
+/** Secret note - keep it private until it is claimed */
+export interface Note {
+  secret: Uint8Array;
+  nullifierSecret: Uint8Array;
+  /** Leaf deposited into the NoteSet */
+  commitment: Uint8Array;
+  /** Recorded on-chain when the note is claimed */
+  nullifier: Uint8Array;
+}
+
+/** Everything needed to prove and submit a claim */
+export interface NoteClaim {
+  /** NoteSet root the claim is proven under */
+  root: Uint8Array;
+  /** Note nullifier passed to claim_note_private */
+  nullifier: Uint8Array;
+  /** Inputs for the note_claim circuit (nargo Prover.toml / noir_js) */
+  circuitInputs: Record<string, string | string[] | string[][]>;
+}
+
+/** Parameters for claim_note_private */
+export interface ClaimNoteParams {
+  agentStatePda: PublicKey;
+  noteSetIndex: number;
+  root: Uint8Array;
+  nullifier: Uint8Array;
+  /** Groth16 proof of the note_claim circuit */
+  proofBytes: Uint8Array;
+  /** Public witness of the note_claim circuit */
+  witnessBytes: Uint8Array;
+  /** Receives the private operation fee (defaults to the payer) */
+  feeRecipient?: PublicKey;
+}
+
+/**
+ * Derive a NoteSet PDA
+ * @param index - NoteSet index (sets are filled in order)
+ */
+export function deriveNoteSetPda(index: number): PublicKey {
+  const indexBytes = Buffer.alloc(4);
+  indexBytes.writeUInt32LE(index);
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("note_set"), indexBytes],
+    CLOAKED_PROGRAM_ID
+  );
+  return pda;
+}
+
+/**
+ * Derive the NoteNullifier PDA a claim creates
+ */
+export function deriveNoteNullifierPda(nullifier: Uint8Array): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("note_nullifier"), Buffer.from(nullifier)],
+    CLOAKED_PROGRAM_ID
+  );
+  return pda;
+}
+
+/**
+ * Rebuild a note from its secrets
+ */
+export async function noteFromSecrets(
+  secret: Uint8Array,
+  nullifierSecret: Uint8Array
+): Promise<Note> {
+  return {
+    secret,
+    nullifierSecret,
+    commitment: await actionDataHash(nullifierSecret, secret),
+    nullifier: await actionDataHash(nullifierSecret),
+  };
+}
+
+/**
+ * Generate a fresh random note
+ */
+export function generateNote(): Promise<Note> {
+  return noteFromSecrets(nacl.randomBytes(32), nacl.randomBytes(32));
+}
+
+/**
+ * Root and sibling path of `leafIndex` in a tree holding `notes` (NoteSet::insert)
+ */
+export async function noteMerklePath(
+  notes: Uint8Array[],
+  leafIndex: number
+): Promise<{ root: Uint8Array; siblings: Uint8Array[] }> {
+  if (leafIndex < 0 || leafIndex >= notes.length) {
+    throw new Error(`Leaf ${leafIndex} not in a set of ${notes.length} notes`);
+  }
+
+  let level: Uint8Array[] = notes.slice();
+  let zero: Uint8Array = new Uint8Array(32);
+  let index = leafIndex;
+  const siblings: Uint8Array[] = [];
+
+  for (let depth = 0; depth < NOTE_TREE_DEPTH; depth++) {
+    const siblingIndex = index % 2 === 0 ? index + 1 : index - 1;
+    siblings.push(level[siblingIndex] ?? zero);
+
+    const parents: Uint8Array[] = [];
+    for (let i = 0; i < level.length; i += 2) {
+      parents.push(await actionDataHash(level[i], level[i + 1] ?? zero));
+    }
+    level = parents;
+    zero = await actionDataHash(zero, zero);
+    index = Math.floor(index / 2);
+  }
+
+  return { root: level[0], siblings };
+}
+
+function getProgram(connection: Connection, signer: Signer): Program {
+  const provider = new AnchorProvider(connection, signer as Wallet, { commitment: "confirmed" });
+  return new Program(IDL as any, provider);
+}
+
+/**
+ * Deposit a note (locks NOTE_DENOMINATION from the depositor)
+ * @param connection - Solana connection
+ * @param depositor - Pays the denomination
+ * @param note - Note to deposit; keep it to claim later
+ * @param noteSetIndex - NoteSet to deposit into
+ * @returns Transaction signature
+ */
+export async function depositNote(
+  connection: Connection,
+  depositor: Signer,
+  note: Note,
+  noteSetIndex: number = 0
+): Promise<string> {
+  const program = getProgram(connection, depositor);
+
+  return program.methods
+    .depositNote(Array.from(note.commitment))
+    .accounts({
+      noteSet: deriveNoteSetPda(noteSetIndex),
+      depositor: depositor.publicKey,
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc();
+}
+
+/**
+ * Look up a deposited note and build the inputs of its claim proof
+ * @param connection - Solana connection
+ * @param note - Note to claim
+ * @param agentStatePda - Private agent whose vault the claim credits
+ * @param noteSetIndex - NoteSet the note was deposited into
+ */
+export async function prepareNoteClaim(
+  connection: Connection,
+  note: Note,
+  agentStatePda: PublicKey,
+  noteSetIndex: number = 0
+): Promise<NoteClaim> {
+  const noteSetPda = deriveNoteSetPda(noteSetIndex);
+  const accountInfo = await connection.getAccountInfo(noteSetPda);
+  if (!accountInfo) {
+    throw new Error(`NoteSet ${noteSetIndex} not found`);
+  }
+  const coder = new Program(IDL as any, { connection } as AnchorProvider).coder;
+  const noteSet = coder.accounts.decode("NoteSet", accountInfo.data);
+  const notes = (noteSet.notes as number[][]).map((leaf) => Uint8Array.from(leaf));
+
+  const commitmentHex = toFieldHex(note.commitment);
+  const leafIndex = notes.findIndex((leaf) => toFieldHex(leaf) === commitmentHex);
+  if (leafIndex < 0) {
+    throw new Error("Note not found in the NoteSet");
+  }
+
+  const { root, siblings } = await noteMerklePath(notes, leafIndex);
+  const actionData = await claimNoteActionData(agentStatePda, note.nullifier);
+  const bytes = (value: Uint8Array) => Array.from(value, (b) => b.toString());
+
+  return {
+    root,
+    nullifier: note.nullifier,
+    circuitInputs: {
+      secret: bytes(note.secret),
+      nullifier_secret: bytes(note.nullifierSecret),
+      leaf_index: leafIndex.toString(),
+      siblings: siblings.map(bytes),
+      agent: bytes(agentStatePda.toBytes()),
+      root: toFieldHex(root),
+      proof_nonce: "0",
+      action_id: ACTION_CLAIM_NOTE.toString(),
+      action_data: toFieldHex(actionData),
+    },
+  };
+}
+
+/**
+ * Claim a note into a private agent's vault
+ * The payer relays the transaction and funds the NoteNullifier; it should not be
+ * linkable to the depositor
+ * @param connection - Solana connection
+ * @param payer - Relays the transaction
+ * @param params - Claim proof and accounts
+ * @returns Transaction signature
+ */
+export async function claimNote(
+  connection: Connection,
+  payer: Signer,
+  params: ClaimNoteParams
+): Promise<string> {
+  const program = getProgram(connection, payer);
+  const [vaultPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("vault"), params.agentStatePda.toBuffer()],
+    CLOAKED_PROGRAM_ID
+  );
+  const [programConfigPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("program_config")],
+    CLOAKED_PROGRAM_ID
+  );
+  const programConfig = await (program.account as any).programConfig.fetch(programConfigPda);
+
+  return program.methods
+    .claimNotePrivate(
+      Buffer.from(params.proofBytes),
+      Buffer.from(params.witnessBytes),
+      Array.from(params.root),
+      Array.from(params.nullifier)
+    )
+    .accounts({
+      cloakedAgentState: params.agentStatePda,
+      vault: vaultPda,
+      noteSet: deriveNoteSetPda(params.noteSetIndex),
+      noteNullifierAccount: deriveNoteNullifierPda(params.nullifier),
+      programConfig: programConfigPda,
+      noteVerifier: programConfig.noteVerifier,
+      payer: payer.publicKey,
+      feeRecipient: params.feeRecipient ?? payer.publicKey,
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc();
+}
@@ -186,6 +186,12 @@ export interface PrivateOperationParams {
   witnessBytes: number[];
 }
 
+/** Freeze private parameters */
+export interface FreezePrivateParams extends PrivateOperationParams {
+  /** 32-byte freeze reason recorded on the agent */
+  reason: number[];
+}
+
 /** Update constraints private parameters */
 export interface UpdateConstraintsPrivateParams extends PrivateOperationParams {
   maxPerTx: number | null;
@@ -198,6 +204,7 @@ export interface UpdateConstraintsPrivateParams extends PrivateOperationParams {
 export interface WithdrawPrivateParams extends PrivateOperationParams {
   amount: number;
   destination: string;
+  bypassExpiry: boolean;
 }
 
 /** Close private parameters */
@@ -209,7 +216,7 @@ export interface ClosePrivateParams extends PrivateOperationParams {
  * Freeze a private agent via relayer
  */
 export async function freezePrivateViaRelayer(
-  params: PrivateOperationParams,
+  params: FreezePrivateParams,
   apiUrl?: string
 ): Promise<string> {
   return postRelayerOperation("freeze-private", params, apiUrl);
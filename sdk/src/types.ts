@@ -40,6 +40,8 @@ export interface CreateAgentOptions {
   totalLimit?: number;
   expiresAt?: Date | null;
   initialDeposit?: number;
+  /** Optional - seeds the agent state PDA together with the payer (random if not provided) */
+  creationNonce?: number;
 }
 
 /** Options for updating constraints */
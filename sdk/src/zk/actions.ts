@@ -0,0 +1,164 @@
+/**
+ * Action binding for ownership proofs
+ *
+ * Every ownership proof is bound to the agent's current proof_nonce, the
+ * action it authorizes and a hash of that action's parameters, so a relayer
+ * cannot replay it or alter what it signs off on. Mirrors the program's
+ * ACTION_* ids and *_action_data helpers (witness layout v4).
+ */
+
+import { PublicKey } from "@solana/web3.js";
+
+export const ACTION_FREEZE = 1;
+export const ACTION_UNFREEZE = 2;
+export const ACTION_PANIC = 3;
+export const ACTION_UPDATE_CONSTRAINTS = 4;
+export const ACTION_LOCK_CONSTRAINTS = 5;
+export const ACTION_CLOSE = 6;
+export const ACTION_WITHDRAW = 7;
+export const ACTION_ROTATE_COMMITMENT = 8;
+export const ACTION_SET_RECOVERY = 9;
+export const ACTION_INITIATE_RECOVERY = 10;
+export const ACTION_CANCEL_RECOVERY = 11;
+export const ACTION_RECOVER = 12;
+export const ACTION_UPDATE_DELEGATE = 13;
+export const ACTION_WITHDRAW_ALL = 14;
+export const ACTION_CLAIM_NOTE = 15;
+export const ACTION_MIGRATE_PROOF_VERSION = 16;
+export const ACTION_SET_DEPOSITS_LOCKED = 17;
+
+/** Public inputs an ownership proof is bound to, besides the commitment */
+export interface ProofBinding {
+  /** Agent's current proof_nonce */
+  proofNonce: bigint;
+  /** One of the ACTION_* ids */
+  actionId: number;
+  /** 32-byte action parameter hash (all zeros for actions without parameters) */
+  actionData: Uint8Array;
+}
+
+/** Constraint changes as update_constraints_private takes them (null = no change) */
+export interface ConstraintParams {
+  maxPerTx: bigint | null;
+  windowSeconds: bigint | null;
+  windowLimit: bigint | null;
+  totalLimit: bigint | null;
+  expiresAt: bigint | null;
+  maxVaultBalance: bigint | null;
+  minDeposit: bigint | null;
+  minSpendAmount: bigint | null;
+  blockDepositsWhenInactive: boolean | null;
+  allowDelegateSessions: boolean | null;
+  allowedStartSecondOfDay: number | null;
+  allowedEndSecondOfDay: number | null;
+  allowedDaysBitmask: number | null;
+  rolloverCap: bigint | null;
+  maxPrivateOpsPerDay: number | null;
+}
+
+/** action_data for actions without parameters */
+export const EMPTY_ACTION_DATA = new Uint8Array(32);
+
+/**
+ * sha256 of the concatenated parts with byte 0 cleared so the value fits
+ * in a BN254 field element
+ */
+export async function actionDataHash(...parts: Uint8Array[]): Promise<Uint8Array> {
+  const length = parts.reduce((sum, part) => sum + part.length, 0);
+  const buffer = new Uint8Array(length);
+  let offset = 0;
+  for (const part of parts) {
+    buffer.set(part, offset);
+    offset += part.length;
+  }
+
+  const digest = new Uint8Array(await crypto.subtle.digest("SHA-256", buffer));
+  digest[0] = 0;
+  return digest;
+}
+
+function u64Le(value: bigint | number): Uint8Array {
+  const bytes = new Uint8Array(8);
+  new DataView(bytes.buffer).setBigUint64(0, BigInt(value), true);
+  return bytes;
+}
+
+/** action_data for withdraw_private */
+export function withdrawActionData(
+  destination: PublicKey,
+  amount: bigint | number,
+  bypassExpiry: boolean
+): Promise<Uint8Array> {
+  return actionDataHash(destination.toBytes(), u64Le(amount), Uint8Array.of(bypassExpiry ? 1 : 0));
+}
+
+/** action_data for withdraw_all_private */
+export function withdrawAllActionData(
+  destination: PublicKey,
+  bypassExpiry: boolean
+): Promise<Uint8Array> {
+  return actionDataHash(destination.toBytes(), Uint8Array.of(bypassExpiry ? 1 : 0));
+}
+
+/** action_data for close_cloaked_agent_private */
+export function closeActionData(
+  destination: PublicKey,
+  feeRecipient: PublicKey
+): Promise<Uint8Array> {
+  return actionDataHash(destination.toBytes(), feeRecipient.toBytes());
+}
+
+/** action_data for claim_note_private */
+export function claimNoteActionData(
+  agent: PublicKey,
+  noteNullifier: Uint8Array
+): Promise<Uint8Array> {
+  return actionDataHash(agent.toBytes(), noteNullifier);
+}
+
+/** action_data for update_constraints_private: hash of the borsh-encoded params */
+export function constraintsActionData(params: ConstraintParams): Promise<Uint8Array> {
+  const out: number[] = [];
+  const option = (value: unknown, encode: () => Uint8Array) => {
+    if (value === null) {
+      out.push(0);
+    } else {
+      out.push(1, ...encode());
+    }
+  };
+  const uint = (value: bigint | number, size: number) => {
+    const bytes = new Uint8Array(size);
+    let v = BigInt(value);
+    for (let i = 0; i < size; i++) {
+      bytes[i] = Number(v & 0xffn);
+      v >>= 8n;
+    }
+    return bytes;
+  };
+
+  option(params.maxPerTx, () => uint(params.maxPerTx!, 8));
+  option(params.windowSeconds, () => uint(params.windowSeconds!, 8));
+  option(params.windowLimit, () => uint(params.windowLimit!, 8));
+  option(params.totalLimit, () => uint(params.totalLimit!, 8));
+  option(params.expiresAt, () => uint(BigInt.asUintN(64, params.expiresAt!), 8));
+  option(params.maxVaultBalance, () => uint(params.maxVaultBalance!, 8));
+  option(params.minDeposit, () => uint(params.minDeposit!, 8));
+  option(params.minSpendAmount, () => uint(params.minSpendAmount!, 8));
+  option(params.blockDepositsWhenInactive, () => Uint8Array.of(params.blockDepositsWhenInactive ? 1 : 0));
+  option(params.allowDelegateSessions, () => Uint8Array.of(params.allowDelegateSessions ? 1 : 0));
+  option(params.allowedStartSecondOfDay, () => uint(params.allowedStartSecondOfDay!, 4));
+  option(params.allowedEndSecondOfDay, () => uint(params.allowedEndSecondOfDay!, 4));
+  option(params.allowedDaysBitmask, () => uint(params.allowedDaysBitmask!, 1));
+  option(params.rolloverCap, () => uint(params.rolloverCap!, 8));
+  option(params.maxPrivateOpsPerDay, () => uint(params.maxPrivateOpsPerDay!, 2));
+
+  return actionDataHash(Uint8Array.from(out));
+}
+
+/** Format a field value as the 0x-prefixed 32-byte hex noir_js expects */
+export function toFieldHex(value: bigint | Uint8Array): string {
+  const hex = typeof value === "bigint"
+    ? value.toString(16)
+    : Array.from(value).map(b => b.toString(16).padStart(2, "0")).join("");
+  return "0x" + hex.padStart(64, "0");
+}
@@ -13,12 +13,13 @@
 import { Barretenberg, UltraHonkBackend } from "@aztec/bb.js";
 import { Noir, type CompiledCircuit } from "@noir-lang/noir_js";
 import circuit from "./ownership_proof.json";
+import { EMPTY_ACTION_DATA, toFieldHex, type ProofBinding } from "./actions";
 
 /** Browser proof result from UltraHonk */
 export interface BrowserProof {
   /** Raw proof bytes */
   proof: Uint8Array;
-  /** Public inputs (commitment) */
+  /** Public inputs (commitment, proof_nonce, action_id, action_data) */
   publicInputs: string[];
 }
 
@@ -81,11 +82,13 @@ export function isBrowserProverAvailable(): boolean {
  *
  * @param agentSecret - The private agent secret (bigint)
  * @param commitment - The public commitment (bigint)
+ * @param binding - Proof nonce and action the proof authorizes
  * @returns Browser proof with proof bytes and public inputs
  */
 export async function generateOwnershipProofBrowser(
   agentSecret: bigint,
-  commitment: bigint
+  commitment: bigint,
+  binding: ProofBinding
 ): Promise<BrowserProof> {
   // Lazy initialize
   if (!cachedBackend || !cachedNoir) {
@@ -96,7 +99,7 @@ export async function generateOwnershipProofBrowser(
   const noir = cachedNoir!;
 
   // Format commitment as hex string with 0x prefix
-  const commitmentHex = "0x" + commitment.toString(16).padStart(64, "0");
+  const commitmentHex = toFieldHex(commitment);
 
   // Generate witness - this is where the circuit assertion happens
   let witness;
@@ -104,6 +107,9 @@ export async function generateOwnershipProofBrowser(
     const result = await noir.execute({
       agent_secret: agentSecret.toString(),
       commitment: commitmentHex,
+      proof_nonce: toFieldHex(binding.proofNonce),
+      action_id: toFieldHex(BigInt(binding.actionId)),
+      action_data: toFieldHex(binding.actionData),
     });
     witness = result.witness;
   } catch (error) {
@@ -178,7 +184,7 @@ function proofBytesToFields(proof: Uint8Array): string[] {
 export async function generateRecursiveArtifacts(
   proof: Uint8Array,
   // eslint-disable-next-line @typescript-eslint/no-unused-vars
-  numPublicInputs: number = 1
+  numPublicInputs: number = 4
 ): Promise<RecursiveArtifacts> {
   if (!cachedBackend) {
     await initBrowserProver();
@@ -253,6 +259,9 @@ export async function verifyPoseidonCompatibility(): Promise<boolean> {
     await noir.execute({
       agent_secret: testSecret.toString(),
       commitment: commitmentHex,
+      proof_nonce: toFieldHex(0n),
+      action_id: toFieldHex(1n),
+      action_data: toFieldHex(EMPTY_ACTION_DATA),
     });
     return true;
   } catch (error) {
@@ -12,7 +12,14 @@ import {
   bytesToCommitment,
   MAX_AGENTS,
 } from "./secrets";
-import { CLOAKED_PROGRAM_ID } from "../constants";
+import {
+  CLOAKED_PROGRAM_ID,
+  AGENT_STATE_DISCRIMINATOR,
+  OWNER_TAG_OFFSET,
+  PRIVATE_COMMITMENT_OFFSET,
+} from "../constants";
+import { BorshAccountsCoder } from "@coral-xyz/anchor";
+import IDL from "../idl.json";
 import bs58 from "bs58";
 
 /** Discovered private agent */
@@ -33,28 +40,10 @@ export interface DiscoveredPrivateAgent {
   commitment: bigint;
 }
 
-// CloakedAgentState layout offsets for PRIVATE MODE (owner = None)
-// In Borsh, Option<Pubkey> when None is just 1 byte (discriminant only)
-//
-// Layout (171 bytes total):
-// - discriminator: 8 bytes
-// - owner discriminant: 1 byte (0 = None for private mode)
-// - owner_commitment: 32 bytes (offset 9)
-// - delegate: 32 bytes (offset 41)
-// - max_per_tx: 8 bytes (offset 73)
-// - daily_limit: 8 bytes (offset 81)
-// - total_limit: 8 bytes (offset 89)
-// - expires_at: 8 bytes (offset 97)
-// - frozen: 1 byte (offset 105)
-// - total_spent: 8 bytes (offset 106)
-// - daily_spent: 8 bytes (offset 114)
-// - last_day: 8 bytes (offset 122)
-// - bump: 1 byte (offset 130)
-// - created_at: 8 bytes (offset 131)
-const CLOAKED_AGENT_STATE_SIZE = 171;
-const COMMITMENT_OFFSET = 9; // After discriminator + owner discriminant (1 byte for None)
-const DELEGATE_OFFSET = 9 + 32; // = 41
-const FROZEN_OFFSET = 9 + 32 + 32 + 8 + 8 + 8 + 8; // = 105
+// Agent state accounts are decoded with the IDL coder; only the filter
+// offsets are hard-coded. Private agents have owner = None, so the Borsh
+// Option tag is a single 0 byte and owner_commitment follows at offset 9.
+const accountsCoder = new BorshAccountsCoder(IDL as any);
 
 /**
  * Find an agent by its owner commitment
@@ -74,13 +63,14 @@ export async function findAgentByCommitment(
 } | null> {
   const commitmentBytes = commitmentToBytes(commitment);
 
-  // Query program accounts with commitment filter
+  // Query private agent state accounts with commitment filter
   const accounts = await connection.getProgramAccounts(CLOAKED_PROGRAM_ID, {
     filters: [
-      { dataSize: CLOAKED_AGENT_STATE_SIZE },
+      { memcmp: { offset: 0, bytes: bs58.encode(AGENT_STATE_DISCRIMINATOR) } },
+      { memcmp: { offset: OWNER_TAG_OFFSET, bytes: bs58.encode([0]) } },
       {
         memcmp: {
-          offset: COMMITMENT_OFFSET,
+          offset: PRIVATE_COMMITMENT_OFFSET,
           bytes: bs58.encode(commitmentBytes),
         },
       },
@@ -91,14 +81,9 @@ export async function findAgentByCommitment(
 
   // Parse first matching account
   const { pubkey, account } = accounts[0];
-  const data = account.data;
-
-  // Extract delegate (32 bytes at offset 41)
-  const delegateBytes = data.slice(DELEGATE_OFFSET, DELEGATE_OFFSET + 32);
-  const delegate = new PublicKey(delegateBytes);
-
-  // Extract frozen (1 byte at offset 105)
-  const frozen = data[FROZEN_OFFSET] === 1;
+  const state = accountsCoder.decode("CloakedAgentState", account.data);
+  const delegate = state.delegate as PublicKey;
+  const frozen = state.frozen as boolean;
 
   // Get vault balance
   const [vaultPda] = PublicKey.findProgramAddressSync(
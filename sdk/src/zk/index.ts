@@ -30,6 +30,37 @@ export {
   type OwnershipProof,
 } from "./prover";
 
+// Action binding
+export {
+  ACTION_FREEZE,
+  ACTION_UNFREEZE,
+  ACTION_PANIC,
+  ACTION_UPDATE_CONSTRAINTS,
+  ACTION_LOCK_CONSTRAINTS,
+  ACTION_CLOSE,
+  ACTION_WITHDRAW,
+  ACTION_ROTATE_COMMITMENT,
+  ACTION_SET_RECOVERY,
+  ACTION_INITIATE_RECOVERY,
+  ACTION_CANCEL_RECOVERY,
+  ACTION_RECOVER,
+  ACTION_UPDATE_DELEGATE,
+  ACTION_WITHDRAW_ALL,
+  ACTION_CLAIM_NOTE,
+  ACTION_MIGRATE_PROOF_VERSION,
+  ACTION_SET_DEPOSITS_LOCKED,
+  EMPTY_ACTION_DATA,
+  actionDataHash,
+  withdrawActionData,
+  withdrawAllActionData,
+  closeActionData,
+  claimNoteActionData,
+  constraintsActionData,
+  toFieldHex,
+  type ProofBinding,
+  type ConstraintParams,
+} from "./actions";
+
 // Browser-based proving
 export {
   initBrowserProver,
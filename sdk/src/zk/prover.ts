@@ -12,6 +12,7 @@ import {
   verifyPoseidonCompatibility,
   type BrowserProof,
 } from "./browser-prover";
+import type { ProofBinding } from "./actions";
 
 /** Ownership proof components for Solana program */
 export interface OwnershipProof {
@@ -128,11 +129,13 @@ async function browserProofToOwnershipProof(
  *
  * @param agentSecret - The private agent secret
  * @param commitment - The public commitment (must match on-chain)
+ * @param binding - Agent's proof_nonce and the action the proof authorizes
  * @returns Proof components for Solana program
  */
 export async function generateOwnershipProof(
   agentSecret: bigint,
-  commitment: bigint
+  commitment: bigint,
+  binding: ProofBinding
 ): Promise<OwnershipProof> {
   if (!isBrowserProverAvailable()) {
     throw new Error(
@@ -142,7 +145,7 @@ export async function generateOwnershipProof(
   }
 
   // Generate UltraHonk proof in browser (secret stays local)
-  const browserProof = await generateOwnershipProofBrowser(agentSecret, commitment);
+  const browserProof = await generateOwnershipProofBrowser(agentSecret, commitment, binding);
 
   // Send proof to backend for attestation (secret NOT transmitted)
   return browserProofToOwnershipProof(browserProof);
@@ -4,6 +4,10 @@ import {
   generateOwnershipProofBrowser,
 } from "../src/zk/browser-prover";
 import { poseidonHash, initPoseidonSync } from "../src/zk/poseidon";
+import { ACTION_FREEZE, EMPTY_ACTION_DATA, type ProofBinding } from "../src/zk/actions";
+
+// Proofs in these tests authorize a freeze at proof_nonce 0
+const binding: ProofBinding = { proofNonce: 0n, actionId: ACTION_FREEZE, actionData: EMPTY_ACTION_DATA };
 
 const BACKEND_URL = process.env.BACKEND_URL || "http://localhost:3645";
 
@@ -80,7 +84,7 @@ describe("Attestation Prover", function () {
 
     console.log("\n  Step 1: Generate browser UltraHonk proof...");
     const startBrowser = Date.now();
-    const browserProof = await generateOwnershipProofBrowser(agentSecret, commitment);
+    const browserProof = await generateOwnershipProofBrowser(agentSecret, commitment, binding);
     const browserTime = Date.now() - startBrowser;
     console.log(`    Browser proof generated in ${browserTime}ms`);
     console.log(`    Proof size: ${browserProof.proof.length} bytes`);
@@ -6,6 +6,10 @@ import {
   isBrowserProverAvailable,
 } from "../src/zk/browser-prover";
 import { poseidonHash, initPoseidonSync } from "../src/zk/poseidon";
+import { ACTION_FREEZE, EMPTY_ACTION_DATA, type ProofBinding } from "../src/zk/actions";
+
+// Proofs in these tests authorize a freeze at proof_nonce 0
+const binding: ProofBinding = { proofNonce: 0n, actionId: ACTION_FREEZE, actionData: EMPTY_ACTION_DATA };
 
 describe("Browser Prover", function () {
   // Proof generation can be slow
@@ -40,7 +44,7 @@ describe("Browser Prover", function () {
       const commitment = poseidonHash([agentSecret]);
 
       // Generate proof
-      const proof = await generateOwnershipProofBrowser(agentSecret, commitment);
+      const proof = await generateOwnershipProofBrowser(agentSecret, commitment, binding);
 
       // Verify proof structure
       expect(proof).to.have.property("proof");
@@ -48,6 +52,7 @@ describe("Browser Prover", function () {
       expect(proof.proof).to.be.instanceOf(Uint8Array);
       expect(proof.proof.length).to.be.greaterThan(0);
       expect(proof.publicInputs).to.be.an("array");
+      expect(proof.publicInputs).to.have.length(4);
 
       console.log(`  Proof size: ${proof.proof.length} bytes`);
       console.log(`  Public inputs: ${proof.publicInputs.length}`);
@@ -57,7 +62,7 @@ describe("Browser Prover", function () {
       const agentSecret = BigInt("98765432109876543210");
       const commitment = poseidonHash([agentSecret]);
 
-      const proof = await generateOwnershipProofBrowser(agentSecret, commitment);
+      const proof = await generateOwnershipProofBrowser(agentSecret, commitment, binding);
       const isValid = await verifyProofBrowser(proof.proof, proof.publicInputs);
 
       expect(isValid).to.be.true;
@@ -67,7 +72,7 @@ describe("Browser Prover", function () {
       const agentSecret = BigInt("11111111111111111111");
       const commitment = poseidonHash([agentSecret]);
 
-      const proof = await generateOwnershipProofBrowser(agentSecret, commitment);
+      const proof = await generateOwnershipProofBrowser(agentSecret, commitment, binding);
 
       // Modify public inputs
       const wrongInputs = ["0x0000000000000000000000000000000000000000000000000000000000000001"];
@@ -75,5 +80,19 @@ describe("Browser Prover", function () {
       const isValid = await verifyProofBrowser(proof.proof, wrongInputs);
       expect(isValid).to.be.false;
     });
+
+    it("should fail verification when the action is swapped", async function () {
+      const agentSecret = BigInt("22222222222222222222");
+      const commitment = poseidonHash([agentSecret]);
+
+      const proof = await generateOwnershipProofBrowser(agentSecret, commitment, binding);
+
+      // Same commitment and nonce, but claims to authorize unfreeze (2)
+      const swapped = [...proof.publicInputs];
+      swapped[2] = "0x" + "2".padStart(64, "0");
+
+      const isValid = await verifyProofBrowser(proof.proof, swapped);
+      expect(isValid).to.be.false;
+    });
   });
 });
@@ -8,11 +8,11 @@ const RPC_URL = "https://api.devnet.solana.com";
 
 describe("Devnet Integration", () => {
   describe("CloakedAgent basic operations", () => {
-    it("generates a new agent with valid PDAs", () => {
+    it("generates a new agent with an unresolved state address", () => {
       const { agent, agentKey } = CloakedAgent.generate(RPC_URL);
       expect(agent.publicKey.toBase58()).to.have.length(44);
-      expect(agent.vaultPda.toBase58()).to.have.length(44);
-      expect(agent.agentStatePda.toBase58()).to.have.length(44);
+      // A generated agent has no on-chain state yet, so its address is unresolved
+      expect(() => agent.agentStatePda).to.throw();
     });
 
     it("destroy() clears agent state", () => {
@@ -5,6 +5,10 @@ import {
   generateRecursiveArtifacts,
 } from "../src/zk/browser-prover";
 import { poseidonHash, initPoseidonSync } from "../src/zk/poseidon";
+import { ACTION_FREEZE, EMPTY_ACTION_DATA, type ProofBinding } from "../src/zk/actions";
+
+// Proofs in these tests authorize a freeze at proof_nonce 0
+const binding: ProofBinding = { proofNonce: 0n, actionId: ACTION_FREEZE, actionData: EMPTY_ACTION_DATA };
 
 const BACKEND_URL = process.env.BACKEND_URL || "http://localhost:3645";
 
@@ -82,14 +86,14 @@ describe("E2E Recursive Proving", function () {
 
     console.log("\n  Step 1: Generate browser UltraHonk proof...");
     const startBrowser = Date.now();
-    const browserProof = await generateOwnershipProofBrowser(agentSecret, commitment);
+    const browserProof = await generateOwnershipProofBrowser(agentSecret, commitment, binding);
     const browserTime = Date.now() - startBrowser;
     console.log(`    Browser proof generated in ${browserTime}ms`);
     console.log(`    Proof size: ${browserProof.proof.length} bytes (${browserProof.proof.length / 32} fields)`);
 
     // Step 2: Extract recursive artifacts
     console.log("\n  Step 2: Extract recursive artifacts...");
-    const artifacts = await generateRecursiveArtifacts(browserProof.proof, 1);
+    const artifacts = await generateRecursiveArtifacts(browserProof.proof, 4);
     console.log(`    proofAsFields: ${artifacts.proofAsFields.length} fields`);
     console.log(`    vkAsFields: ${artifacts.vkAsFields.length} fields`);
     console.log(`    vkHash: ${artifacts.vkHash.substring(0, 20)}...`);
@@ -111,6 +115,9 @@ describe("E2E Recursive Proving", function () {
         proof: artifacts.proofAsFields,
         vkHash: artifacts.vkHash,
         commitment: commitmentHex,
+        proofNonce: browserProof.publicInputs[1],
+        actionId: browserProof.publicInputs[2],
+        actionData: browserProof.publicInputs[3],
       }),
     });
 
@@ -5,6 +5,10 @@ import {
   generateRecursiveArtifacts,
 } from "../src/zk/browser-prover";
 import { poseidonHash, initPoseidonSync } from "../src/zk/poseidon";
+import { ACTION_FREEZE, EMPTY_ACTION_DATA, type ProofBinding } from "../src/zk/actions";
+
+// Proofs in these tests authorize a freeze at proof_nonce 0
+const binding: ProofBinding = { proofNonce: 0n, actionId: ACTION_FREEZE, actionData: EMPTY_ACTION_DATA };
 
 describe("Recursive Artifacts", function () {
   this.timeout(120000);
@@ -20,13 +24,13 @@ describe("Recursive Artifacts", function () {
     const commitment = poseidonHash([agentSecret]);
 
     console.log("  Generating browser proof...");
-    const browserProof = await generateOwnershipProofBrowser(agentSecret, commitment);
+    const browserProof = await generateOwnershipProofBrowser(agentSecret, commitment, binding);
     console.log(`  Browser proof size: ${browserProof.proof.length} bytes`);
     console.log(`  Public inputs: ${browserProof.publicInputs.length}`);
 
     // Generate recursive artifacts
     console.log("  Generating recursive artifacts...");
-    const artifacts = await generateRecursiveArtifacts(browserProof.proof, 1);
+    const artifacts = await generateRecursiveArtifacts(browserProof.proof, 4);
 
     // Log results for debugging
     console.log(`  proofAsFields length: ${artifacts.proofAsFields.length}`);
@@ -62,8 +66,8 @@ describe("Recursive Artifacts", function () {
 
     for (const secret of secrets) {
       const commitment = poseidonHash([secret]);
-      const browserProof = await generateOwnershipProofBrowser(secret, commitment);
-      const artifacts = await generateRecursiveArtifacts(browserProof.proof, 1);
+      const browserProof = await generateOwnershipProofBrowser(secret, commitment, binding);
+      const artifacts = await generateRecursiveArtifacts(browserProof.proof, 4);
 
       sizes.push({
         proof: artifacts.proofAsFields.length,
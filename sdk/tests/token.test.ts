@@ -1,5 +1,6 @@
 import { expect } from "chai";
 import { Keypair, PublicKey, Connection, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import { BN } from "@coral-xyz/anchor";
 import bs58 from "bs58";
 import { CloakedAgent, CLOAKED_PROGRAM_ID } from "../src";
 
@@ -101,21 +102,30 @@ describe("CloakedAgent", () => {
       expect(derivedPda.toBase58()).to.equal(expectedPda.toBase58());
     });
 
-    it("deriveAgentStatePda() returns correct PDA for delegate", () => {
-      const delegate = Keypair.generate().publicKey;
+    it("deriveAgentStatePda() returns correct PDA for creator and nonce", () => {
+      const creator = Keypair.generate().publicKey;
+      const nonce = new BN(7);
 
       const [expectedPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegate.toBuffer()],
+        [Buffer.from("cloaked_agent_state"), creator.toBuffer(), nonce.toArrayLike(Buffer, "le", 8)],
         CLOAKED_PROGRAM_ID
       );
 
-      const derivedPda = CloakedAgent.deriveAgentStatePda(delegate);
+      const derivedPda = CloakedAgent.deriveAgentStatePda(creator, nonce);
       expect(derivedPda.toBase58()).to.equal(expectedPda.toBase58());
     });
 
+    it("deriveAgentStatePda() differs per creation nonce", () => {
+      const creator = Keypair.generate().publicKey;
+
+      const first = CloakedAgent.deriveAgentStatePda(creator, new BN(0));
+      const second = CloakedAgent.deriveAgentStatePda(creator, new BN(1));
+      expect(first.toBase58()).to.not.equal(second.toBase58());
+    });
+
     it("deriveVaultPda() returns correct PDA for agent state", () => {
-      const delegate = Keypair.generate().publicKey;
-      const agentStatePda = CloakedAgent.deriveAgentStatePda(delegate);
+      const creator = Keypair.generate().publicKey;
+      const agentStatePda = CloakedAgent.deriveAgentStatePda(creator, new BN(0));
 
       const [expectedVault] = PublicKey.findProgramAddressSync(
         [Buffer.from("vault"), agentStatePda.toBuffer()],
@@ -128,20 +138,22 @@ describe("CloakedAgent", () => {
   });
 
   describe("agent state PDAs", () => {
-    it("agentStatePda is derived from delegate public key", () => {
-      const agent = new CloakedAgent(agentKey, TEST_RPC_URL);
-
-      const [expectedPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), testKeypair.publicKey.toBuffer()],
-        CLOAKED_PROGRAM_ID
-      );
+    it("agentStatePda is the address passed to the constructor", () => {
+      const expectedPda = CloakedAgent.deriveAgentStatePda(Keypair.generate().publicKey, new BN(0));
+      const agent = new CloakedAgent(agentKey, TEST_RPC_URL, expectedPda);
 
       expect(agent.agentStatePda.toBase58()).to.equal(expectedPda.toBase58());
     });
 
-    it("vaultPda is derived from agentStatePda", () => {
+    it("agentStatePda throws until resolved", () => {
       const agent = new CloakedAgent(agentKey, TEST_RPC_URL);
-      const agentStatePda = agent.agentStatePda;
+
+      expect(() => agent.agentStatePda).to.throw(/resolveAgentStatePda/);
+    });
+
+    it("vaultPda is derived from agentStatePda", () => {
+      const agentStatePda = CloakedAgent.deriveAgentStatePda(Keypair.generate().publicKey, new BN(0));
+      const agent = new CloakedAgent(agentKey, TEST_RPC_URL, agentStatePda);
 
       const [expectedVault] = PublicKey.findProgramAddressSync(
         [Buffer.from("vault"), agentStatePda.toBuffer()],
@@ -152,8 +164,9 @@ describe("CloakedAgent", () => {
     });
 
     it("vaultPda derivation is deterministic", () => {
-      const agent1 = new CloakedAgent(agentKey, TEST_RPC_URL);
-      const agent2 = new CloakedAgent(agentKey, TEST_RPC_URL);
+      const agentStatePda = CloakedAgent.deriveAgentStatePda(Keypair.generate().publicKey, new BN(0));
+      const agent1 = new CloakedAgent(agentKey, TEST_RPC_URL, agentStatePda);
+      const agent2 = new CloakedAgent(agentKey, TEST_RPC_URL, agentStatePda);
 
       expect(agent1.vaultPda.toBase58()).to.equal(agent2.vaultPda.toBase58());
     });
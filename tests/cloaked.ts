@@ -6,8 +6,11 @@ import {
   LAMPORTS_PER_SOL,
   PublicKey,
   SystemProgram,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  Ed25519Program,
 } from "@solana/web3.js";
 import { expect } from "chai";
+import { createHash } from "crypto";
 
 describe("cloaked", () => {
   const provider = anchor.AnchorProvider.env();
@@ -15,6 +18,13 @@ describe("cloaked", () => {
 
   const program = anchor.workspace.Cloaked as Program<Cloaked>;
 
+  // Nullifier PDA a private instruction creates for its proof
+  const nullifierPda = (proof: Buffer) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("nullifier"), createHash("sha256").update(proof).digest()],
+      program.programId
+    )[0];
+
   describe("create_cloaked_agent instruction", () => {
     let owner: Keypair;
     let delegateKeypair: Keypair;
@@ -34,7 +44,7 @@ describe("cloaked", () => {
 
       // Derive PDAs
       [agentStatePda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegateKeypair.publicKey.toBuffer()],
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       [vaultPda] = PublicKey.findProgramAddressSync(
@@ -51,10 +61,17 @@ describe("cloaked", () => {
 
       await program.methods
         .createCloakedAgent(
+          new anchor.BN(0),
           maxPerTx,
           dailyLimit,
           totalLimit,
-          expiresAt
+          expiresAt,
+          new anchor.BN(0),
+          null,
+          null,
+          new anchor.BN(0),
+          null,
+          false
         )
         .accounts({
           cloakedAgentState: agentStatePda,
@@ -72,11 +89,12 @@ describe("cloaked", () => {
       expect(agentState.owner.toBase58()).to.equal(owner.publicKey.toBase58());
       expect(agentState.delegate.toBase58()).to.equal(delegateKeypair.publicKey.toBase58());
       expect(agentState.maxPerTx.toNumber()).to.equal(maxPerTx.toNumber());
-      expect(agentState.dailyLimit.toNumber()).to.equal(dailyLimit.toNumber());
+      expect(agentState.spendingWindow.limit.toNumber()).to.equal(dailyLimit.toNumber());
+      expect(agentState.spendingWindow.windowSeconds.toNumber()).to.equal(86_400);
       expect(agentState.totalLimit.toNumber()).to.equal(totalLimit.toNumber());
       expect(agentState.frozen).to.equal(false);
       expect(agentState.totalSpent.toNumber()).to.equal(0);
-      expect(agentState.dailySpent.toNumber()).to.equal(0);
+      expect(agentState.spendingWindow.spent.toNumber()).to.equal(0);
     });
 
     it("deposits SOL to agent vault", async () => {
@@ -86,7 +104,14 @@ describe("cloaked", () => {
           new anchor.BN(0),
           new anchor.BN(0),
           new anchor.BN(0),
-          new anchor.BN(0)
+          new anchor.BN(0),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          null,
+          null,
+          new anchor.BN(0),
+          null,
+          false
         )
         .accounts({
           cloakedAgentState: agentStatePda,
@@ -148,7 +173,7 @@ describe("cloaked", () => {
 
       // Derive PDAs
       [agentStatePda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegateKeypair.publicKey.toBuffer()],
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       [vaultPda] = PublicKey.findProgramAddressSync(
@@ -159,10 +184,17 @@ describe("cloaked", () => {
       // Create agent with constraints
       await program.methods
         .createCloakedAgent(
+          new anchor.BN(0),                       // creation_nonce
           new anchor.BN(0.1 * LAMPORTS_PER_SOL),  // max 0.1 SOL per tx
           new anchor.BN(0.5 * LAMPORTS_PER_SOL),  // max 0.5 SOL per day
           new anchor.BN(2 * LAMPORTS_PER_SOL),    // max 2 SOL total
-          new anchor.BN(0)                         // never expires
+          new anchor.BN(0),                        // never expires
+          new anchor.BN(0),
+          null,
+          null,
+          new anchor.BN(0),
+          null,
+          false
         )
         .accounts({
           cloakedAgentState: agentStatePda,
@@ -211,7 +243,8 @@ describe("cloaked", () => {
       // Verify tracking updated
       const state = await program.account.cloakedAgentState.fetch(agentStatePda);
       expect(state.totalSpent.toNumber()).to.equal(spendAmount.toNumber());
-      expect(state.dailySpent.toNumber()).to.equal(spendAmount.toNumber());
+      expect(state.spendingWindow.spent.toNumber()).to.equal(spendAmount.toNumber());
+      expect(state.spendNonce.toNumber()).to.equal(1);
     });
 
     it("fails when amount exceeds max_per_tx", async () => {
@@ -288,7 +321,7 @@ describe("cloaked", () => {
       await provider.connection.confirmTransaction(sig);
 
       [agentStatePda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegateKeypair.publicKey.toBuffer()],
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       [vaultPda] = PublicKey.findProgramAddressSync(
@@ -297,7 +330,7 @@ describe("cloaked", () => {
       );
 
       await program.methods
-        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0))
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
         .accounts({
           cloakedAgentState: agentStatePda,
           vault: vaultPda,
@@ -311,8 +344,9 @@ describe("cloaked", () => {
     });
 
     it("owner can freeze agent", async () => {
+      const reason = Array.from(Buffer.from("INC-1042".padEnd(32, "\0")));
       await program.methods
-        .freeze()
+        .freeze(reason)
         .accounts({
           cloakedAgentState: agentStatePda,
           owner: owner.publicKey,
@@ -322,12 +356,22 @@ describe("cloaked", () => {
 
       const state = await program.account.cloakedAgentState.fetch(agentStatePda);
       expect(state.frozen).to.equal(true);
+      expect(state.freezeReason).to.deep.equal(reason);
+
+      await program.methods
+        .unfreeze()
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      const unfrozen = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(unfrozen.freezeReason).to.deep.equal(Array(32).fill(0));
     });
 
     it("owner can unfreeze agent", async () => {
       // First freeze
       await program.methods
-        .freeze()
+        .freeze(Array(32).fill(0))
         .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
         .signers([owner])
         .rpc();
@@ -350,7 +394,7 @@ describe("cloaked", () => {
 
       try {
         await program.methods
-          .freeze()
+          .freeze(Array(32).fill(0))
           .accounts({ cloakedAgentState: agentStatePda, owner: nonOwner.publicKey })
           .signers([nonOwner])
           .rpc();
@@ -362,6 +406,29 @@ describe("cloaked", () => {
         );
       }
     });
+
+    it("delegate can freeze itself but not unfreeze", async () => {
+      await program.methods
+        .freezeByDelegate(Array(32).fill(0))
+        .accounts({ cloakedAgentState: agentStatePda, delegate: delegateKeypair.publicKey })
+        .signers([delegateKeypair])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.frozen).to.equal(true);
+      expect(state.frozenByDelegate).to.equal(true);
+
+      try {
+        await program.methods
+          .unfreeze()
+          .accounts({ cloakedAgentState: agentStatePda, owner: delegateKeypair.publicKey })
+          .signers([delegateKeypair])
+          .rpc();
+        expect.fail("Should fail");
+      } catch (error: any) {
+        expect(error.toString()).to.include("NotOwner");
+      }
+    });
   });
 
   describe("update_constraints instruction", () => {
@@ -378,7 +445,7 @@ describe("cloaked", () => {
       await provider.connection.confirmTransaction(sig);
 
       [agentStatePda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegateKeypair.publicKey.toBuffer()],
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       [vaultPda] = PublicKey.findProgramAddressSync(
@@ -388,10 +455,17 @@ describe("cloaked", () => {
 
       await program.methods
         .createCloakedAgent(
+          new anchor.BN(0),
           new anchor.BN(1000),
           new anchor.BN(10000),
           new anchor.BN(100000),
-          new anchor.BN(0)
+          new anchor.BN(0),
+          new anchor.BN(0),
+          null,
+          null,
+          new anchor.BN(0),
+          null,
+          false
         )
         .accounts({
           cloakedAgentState: agentStatePda,
@@ -410,7 +484,7 @@ describe("cloaked", () => {
       const newDailyLimit = new anchor.BN(20000);
 
       await program.methods
-        .updateConstraints(newMaxPerTx, newDailyLimit, null, null)
+        .updateConstraints(newMaxPerTx, null, newDailyLimit, null, null, null, null, null, null, null, null, null, null, null)
         .accounts({
           cloakedAgentState: agentStatePda,
           owner: owner.publicKey,
@@ -420,7 +494,7 @@ describe("cloaked", () => {
 
       const state = await program.account.cloakedAgentState.fetch(agentStatePda);
       expect(state.maxPerTx.toNumber()).to.equal(2000);
-      expect(state.dailyLimit.toNumber()).to.equal(20000);
+      expect(state.spendingWindow.limit.toNumber()).to.equal(20000);
       expect(state.totalLimit.toNumber()).to.equal(100000); // unchanged
     });
   });
@@ -439,7 +513,7 @@ describe("cloaked", () => {
       await provider.connection.confirmTransaction(sig);
 
       [agentStatePda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegateKeypair.publicKey.toBuffer()],
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       [vaultPda] = PublicKey.findProgramAddressSync(
@@ -448,7 +522,7 @@ describe("cloaked", () => {
       );
 
       await program.methods
-        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0))
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
         .accounts({
           cloakedAgentState: agentStatePda,
           vault: vaultPda,
@@ -482,6 +556,7 @@ describe("cloaked", () => {
           cloakedAgentState: agentStatePda,
           vault: vaultPda,
           owner: owner.publicKey,
+          vaultRecipient: owner.publicKey,
           systemProgram: SystemProgram.programId,
         })
         .signers([owner])
@@ -496,6 +571,26 @@ describe("cloaked", () => {
       const accountInfo = await provider.connection.getAccountInfo(agentStatePda);
       expect(accountInfo).to.be.null;
     });
+
+    it("sends the vault balance to a separate vault recipient", async () => {
+      const coldWallet = Keypair.generate();
+      const vaultBalance = await provider.connection.getBalance(vaultPda);
+
+      await program.methods
+        .closeCloakedAgent()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          vaultRecipient: coldWallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      expect(await provider.connection.getBalance(coldWallet.publicKey)).to.equal(vaultBalance);
+      expect(await provider.connection.getAccountInfo(agentStatePda)).to.be.null;
+    });
   });
 
   describe("withdraw instruction (owner only)", () => {
@@ -514,7 +609,7 @@ describe("cloaked", () => {
       await provider.connection.confirmTransaction(sig);
 
       [agentStatePda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegateKeypair.publicKey.toBuffer()],
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       [vaultPda] = PublicKey.findProgramAddressSync(
@@ -524,10 +619,17 @@ describe("cloaked", () => {
 
       await program.methods
         .createCloakedAgent(
+          new anchor.BN(0),                        // creation_nonce
           new anchor.BN(0.01 * LAMPORTS_PER_SOL),  // small max per tx
           new anchor.BN(0.1 * LAMPORTS_PER_SOL),   // small daily limit
           new anchor.BN(0.5 * LAMPORTS_PER_SOL),   // small total limit
-          new anchor.BN(0)
+          new anchor.BN(0),
+          new anchor.BN(0),
+          null,
+          null,
+          new anchor.BN(0),
+          null,
+          false
         )
         .accounts({
           cloakedAgentState: agentStatePda,
@@ -597,7 +699,7 @@ describe("cloaked", () => {
     it("owner can withdraw even when agent is frozen", async () => {
       // Freeze the agent
       await program.methods
-        .freeze()
+        .freeze(Array(32).fill(0))
         .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
         .signers([owner])
         .rpc();
@@ -763,7 +865,7 @@ describe("cloaked", () => {
       await provider.connection.confirmTransaction(sig2);
 
       [agentStatePda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("cloaked_agent_state"), delegateKeypair.publicKey.toBuffer()],
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       [vaultPda] = PublicKey.findProgramAddressSync(
@@ -776,10 +878,17 @@ describe("cloaked", () => {
       // Create with 0.1 SOL daily limit
       await program.methods
         .createCloakedAgent(
+          new anchor.BN(0),                        // creation_nonce
           new anchor.BN(0),                        // unlimited per tx
           new anchor.BN(0.1 * LAMPORTS_PER_SOL),   // 0.1 daily
           new anchor.BN(0),                        // unlimited total
-          new anchor.BN(0)
+          new anchor.BN(0),
+          new anchor.BN(0),
+          null,
+          null,
+          new anchor.BN(0),
+          null,
+          false
         )
         .accounts({
           cloakedAgentState: agentStatePda,
@@ -832,15 +941,15 @@ describe("cloaked", () => {
           .signers([delegateKeypair, feePayer])
           .rpc();
 
-        expect.fail("Should have failed with ExceedsDailyLimit");
+        expect.fail("Should have failed with ExceedsWindowLimit");
       } catch (error: any) {
-        expect(error.message).to.include("ExceedsDailyLimit");
+        expect(error.message).to.include("ExceedsWindowLimit");
       }
     });
 
     it("blocks spending when frozen", async () => {
       await program.methods
-        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0))
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
         .accounts({
           cloakedAgentState: agentStatePda,
           vault: vaultPda,
@@ -865,7 +974,7 @@ describe("cloaked", () => {
 
       // Freeze
       await program.methods
-        .freeze()
+        .freeze(Array(32).fill(0))
         .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
         .signers([owner])
         .rpc();
@@ -894,7 +1003,7 @@ describe("cloaked", () => {
     it("unlimited constraints work (value 0)", async () => {
       // All limits set to 0 = unlimited
       await program.methods
-        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0))
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
         .accounts({
           cloakedAgentState: agentStatePda,
           vault: vaultPda,
@@ -935,4 +1044,5977 @@ describe("cloaked", () => {
       expect(state.totalSpent.toNumber()).to.equal(1.5 * LAMPORTS_PER_SOL);
     });
   });
+
+  describe("panic instruction", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    let safeWallet: Keypair;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+      safeWallet = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 3 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("owner can freeze and drain vault in one instruction", async () => {
+      await program.methods
+        .panic()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          destination: safeWallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.frozen).to.equal(true);
+      expect(Buffer.from(state.freezeReason).subarray(0, 5).toString()).to.equal("Panic");
+      expect(await provider.connection.getBalance(vaultPda)).to.equal(0);
+      expect(await provider.connection.getBalance(safeWallet.publicKey)).to.equal(1 * LAMPORTS_PER_SOL);
+    });
+
+    it("non-owner cannot panic", async () => {
+      const nonOwner = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(nonOwner.publicKey, 0.1 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      try {
+        await program.methods
+          .panic()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: nonOwner.publicKey,
+            destination: nonOwner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([nonOwner])
+          .rpc();
+        expect.fail("Should fail");
+      } catch (error: any) {
+        expect(error.message).to.include("NotOwner");
+      }
+    });
+  });
+
+  describe("constraint validation", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+    });
+
+    it("rejects max_per_tx above daily_limit", async () => {
+      try {
+        await program.methods
+          .createCloakedAgent(
+            new anchor.BN(0),
+            new anchor.BN(1 * LAMPORTS_PER_SOL),
+            new anchor.BN(0.1 * LAMPORTS_PER_SOL),
+            new anchor.BN(0),
+            new anchor.BN(0),
+            new anchor.BN(0),
+            null,
+            null,
+            new anchor.BN(0),
+            null,
+            false
+          )
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: owner.publicKey,
+            delegate: delegateKeypair.publicKey,
+            payer: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with InconsistentLimits");
+      } catch (error: any) {
+        expect(error.message).to.include("InconsistentLimits");
+      }
+    });
+
+    it("ignores unlimited (zero) values when comparing limits", async () => {
+      await program.methods
+        .createCloakedAgent(
+          new anchor.BN(0),
+          new anchor.BN(0.5 * LAMPORTS_PER_SOL),
+          new anchor.BN(0),
+          new anchor.BN(1 * LAMPORTS_PER_SOL),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          null,
+          null,
+          new anchor.BN(0),
+          null,
+          false
+        )
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      try {
+        await program.methods
+          .updateConstraints(null, null, null, new anchor.BN(0.1 * LAMPORTS_PER_SOL), null, null, null, null, null, null, null, null, null, null)
+          .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with InconsistentLimits");
+      } catch (error: any) {
+        expect(error.message).to.include("InconsistentLimits");
+      }
+    });
+  });
+
+  describe("spending window", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0.1 * LAMPORTS_PER_SOL), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("owner can switch to an hourly window", async () => {
+      await program.methods
+        .updateConstraints(null, new anchor.BN(3_600), null, null, null, null, null, null, null, null, null, null, null, null)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.spendingWindow.windowSeconds.toNumber()).to.equal(3_600);
+      expect(state.spendingWindow.limit.toNumber()).to.equal(0.1 * LAMPORTS_PER_SOL);
+    });
+
+    it("rejects a zero-length window", async () => {
+      try {
+        await program.methods
+          .updateConstraints(null, new anchor.BN(0), null, null, null, null, null, null, null, null, null, null, null, null)
+          .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with InvalidSpendingWindow");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidSpendingWindow");
+      }
+    });
+  });
+
+  describe("destination whitelist", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    let destination: Keypair;
+    let whitelistPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+      destination = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      [whitelistPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("whitelist"), agentStatePda.toBuffer(), destination.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .addWhitelistEntry(destination.publicKey)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          whitelistEntry: whitelistPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("tracks whitelist entries on the agent", async () => {
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.whitelistEntryCount).to.equal(1);
+
+      await program.methods
+        .removeWhitelistEntry(destination.publicKey)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          whitelistEntry: whitelistPda,
+          owner: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      const after = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(after.whitelistEntryCount).to.equal(0);
+    });
+
+    it("close fails while whitelist entries would be orphaned", async () => {
+      try {
+        await program.methods
+          .closeCloakedAgent()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: owner.publicKey,
+            vaultRecipient: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with HasOrphanedPDAs");
+      } catch (error: any) {
+        expect(error.message).to.include("HasOrphanedPDAs");
+      }
+    });
+
+    it("close reclaims whitelist entries passed in remaining accounts", async () => {
+      await program.methods
+        .closeCloakedAgent()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          vaultRecipient: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts([{ pubkey: whitelistPda, isSigner: false, isWritable: true }])
+        .signers([owner])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(whitelistPda)).to.be.null;
+      expect(await provider.connection.getAccountInfo(agentStatePda)).to.be.null;
+    });
+  });
+
+  describe("additional delegates", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let worker: Keypair;
+    let feePayer: Keypair;
+    let destination: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    let delegateEntryPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+      worker = Keypair.generate();
+      feePayer = Keypair.generate();
+      destination = Keypair.generate();
+
+      const sig1 = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig1);
+      const sig2 = await provider.connection.requestAirdrop(feePayer.publicKey, 1 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig2);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      [delegateEntryPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("delegate"), agentStatePda.toBuffer(), worker.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .addDelegate(worker.publicKey, new anchor.BN(0.1 * LAMPORTS_PER_SOL), new anchor.BN(0.15 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          delegateEntry: delegateEntryPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spendAsWorker = (amount: number) =>
+      program.methods
+        .spendAsDelegate(new anchor.BN(amount))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          delegateEntry: delegateEntryPda,
+          vault: vaultPda,
+          delegate: worker.publicKey,
+          feePayer: feePayer.publicKey,
+          destination: destination.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([worker, feePayer])
+        .rpc();
+
+    it("worker spends within its own limits and agent totals update", async () => {
+      await spendAsWorker(0.1 * LAMPORTS_PER_SOL);
+
+      const entry = await program.account.delegateEntry.fetch(delegateEntryPda);
+      expect(entry.spendingWindow.spent.toNumber()).to.equal(0.1 * LAMPORTS_PER_SOL);
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalSpent.toNumber()).to.equal(0.1 * LAMPORTS_PER_SOL);
+    });
+
+    it("enforces per-delegate limits", async () => {
+      try {
+        await spendAsWorker(0.2 * LAMPORTS_PER_SOL);
+        expect.fail("Should have failed with ExceedsPerTxLimit");
+      } catch (error: any) {
+        expect(error.message).to.include("ExceedsPerTxLimit");
+      }
+
+      await spendAsWorker(0.1 * LAMPORTS_PER_SOL);
+      try {
+        await spendAsWorker(0.1 * LAMPORTS_PER_SOL);
+        expect.fail("Should have failed with ExceedsWindowLimit");
+      } catch (error: any) {
+        expect(error.message).to.include("ExceedsWindowLimit");
+      }
+    });
+
+    it("removed delegate can no longer spend", async () => {
+      await program.methods
+        .removeDelegate(worker.publicKey)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          delegateEntry: delegateEntryPda,
+          owner: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(delegateEntryPda)).to.be.null;
+      try {
+        await spendAsWorker(0.05 * LAMPORTS_PER_SOL);
+        expect.fail("Should have failed");
+      } catch (error: any) {
+        expect(error.message).to.include("AccountNotInitialized");
+      }
+    });
+  });
+
+  describe("agent address derivation", () => {
+    it("lets two owners share the same delegate key", async () => {
+      const delegateKeypair = Keypair.generate();
+      const owners = [Keypair.generate(), Keypair.generate()];
+
+      for (const owner of owners) {
+        const sig = await provider.connection.requestAirdrop(owner.publicKey, 1 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+
+        const [agentStatePda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+          program.programId
+        );
+        const [vaultPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("vault"), agentStatePda.toBuffer()],
+          program.programId
+        );
+
+        await program.methods
+          .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: owner.publicKey,
+            delegate: delegateKeypair.publicKey,
+            payer: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+
+        const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+        expect(state.delegate.toBase58()).to.equal(delegateKeypair.publicKey.toBase58());
+        expect(state.createdBy.toBase58()).to.equal(owner.publicKey.toBase58());
+      }
+    });
+  });
+
+  describe("session keys", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let sessionKey: Keypair;
+    let feePayer: Keypair;
+    let destination: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    let sessionPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+      sessionKey = Keypair.generate();
+      feePayer = Keypair.generate();
+      destination = Keypair.generate();
+
+      const sig1 = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig1);
+      const sig2 = await provider.connection.requestAirdrop(feePayer.publicKey, 1 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig2);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      [sessionPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("session"), agentStatePda.toBuffer(), sessionKey.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const expiresAt = Math.floor(Date.now() / 1000) + 3_600;
+      await program.methods
+        .createSession(sessionKey.publicKey, new anchor.BN(expiresAt), new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          session: sessionPda,
+          authority: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spendWithSession = (amount: number) =>
+      program.methods
+        .spendWithSession(new anchor.BN(amount))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          session: sessionPda,
+          vault: vaultPda,
+          sessionKey: sessionKey.publicKey,
+          feePayer: feePayer.publicKey,
+          destination: destination.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([sessionKey, feePayer])
+        .rpc();
+
+    it("session spend decrements session budget and agent counters", async () => {
+      await spendWithSession(0.06 * LAMPORTS_PER_SOL);
+
+      const session = await program.account.session.fetch(sessionPda);
+      expect(session.budget.toNumber()).to.equal(0.04 * LAMPORTS_PER_SOL);
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalSpent.toNumber()).to.equal(0.06 * LAMPORTS_PER_SOL);
+
+      try {
+        await spendWithSession(0.06 * LAMPORTS_PER_SOL);
+        expect.fail("Should have failed with SessionBudgetExhausted");
+      } catch (error: any) {
+        expect(error.message).to.include("SessionBudgetExhausted");
+      }
+    });
+
+    it("delegate cannot create sessions unless allowed", async () => {
+      const other = Keypair.generate();
+      const [otherSessionPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("session"), agentStatePda.toBuffer(), other.publicKey.toBuffer()],
+        program.programId
+      );
+      const sig = await provider.connection.requestAirdrop(delegateKeypair.publicKey, 1 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      try {
+        await program.methods
+          .createSession(other.publicKey, new anchor.BN(Math.floor(Date.now() / 1000) + 3_600), new anchor.BN(1))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            session: otherSessionPda,
+            authority: delegateKeypair.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([delegateKeypair])
+          .rpc();
+        expect.fail("Should have failed with NotOwner");
+      } catch (error: any) {
+        expect(error.message).to.include("NotOwner");
+      }
+    });
+
+    it("revoked session can no longer spend", async () => {
+      await program.methods
+        .revokeSession()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          session: sessionPda,
+          revoker: owner.publicKey,
+          sessionAuthority: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(sessionPda)).to.be.null;
+      try {
+        await spendWithSession(0.01 * LAMPORTS_PER_SOL);
+        expect.fail("Should have failed");
+      } catch (error: any) {
+        expect(error.message).to.include("AccountNotInitialized");
+      }
+    });
+  });
+
+  describe("sub-agents", () => {
+    let owner: Keypair;
+    let parentDelegate: Keypair;
+    let childDelegate: Keypair;
+    let feePayer: Keypair;
+    let destination: Keypair;
+    let parentPda: PublicKey;
+    let parentVaultPda: PublicKey;
+    let childPda: PublicKey;
+    let childVaultPda: PublicKey;
+
+    const agentPda = (payer: PublicKey, nonce: number) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), payer.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+    const vaultFor = (agent: PublicKey) =>
+      PublicKey.findProgramAddressSync([Buffer.from("vault"), agent.toBuffer()], program.programId)[0];
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      parentDelegate = Keypair.generate();
+      childDelegate = Keypair.generate();
+      feePayer = Keypair.generate();
+      destination = Keypair.generate();
+
+      const sig1 = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig1);
+      const sig2 = await provider.connection.requestAirdrop(feePayer.publicKey, 1 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig2);
+
+      parentPda = agentPda(owner.publicKey, 0);
+      parentVaultPda = vaultFor(parentPda);
+      childPda = agentPda(owner.publicKey, 1);
+      childVaultPda = vaultFor(childPda);
+
+      // Parent: 0.1 SOL window limit
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0.1 * LAMPORTS_PER_SOL), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: parentPda,
+          vault: parentVaultPda,
+          owner: owner.publicKey,
+          delegate: parentDelegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(1), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: childPda,
+          vault: childVaultPda,
+          owner: owner.publicKey,
+          delegate: childDelegate.publicKey,
+          payer: owner.publicKey,
+          parentState: parentPda,
+          parentDelegate: parentDelegate.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner, parentDelegate])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: childPda,
+          vault: childVaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spendFromChild = (amount: number) =>
+      program.methods
+        .spend(new anchor.BN(amount))
+        .accounts({
+          cloakedAgentState: childPda,
+          vault: childVaultPda,
+          delegate: childDelegate.publicKey,
+          feePayer: feePayer.publicKey,
+          destination: destination.publicKey,
+          parentState: parentPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([childDelegate, feePayer])
+        .rpc();
+
+    it("child spends roll up into the parent's limits", async () => {
+      const child = await program.account.cloakedAgentState.fetch(childPda);
+      expect(child.parent?.toBase58()).to.equal(parentPda.toBase58());
+
+      await spendFromChild(0.08 * LAMPORTS_PER_SOL);
+      const parent = await program.account.cloakedAgentState.fetch(parentPda);
+      expect(parent.childCount).to.equal(1);
+      expect(parent.spendingWindow.spent.toNumber()).to.equal(0.08 * LAMPORTS_PER_SOL);
+
+      try {
+        await spendFromChild(0.05 * LAMPORTS_PER_SOL);
+        expect.fail("Should have failed with ExceedsWindowLimit");
+      } catch (error: any) {
+        expect(error.message).to.include("ExceedsWindowLimit");
+      }
+    });
+
+    it("parent cannot close while sub-agents exist", async () => {
+      try {
+        await program.methods
+          .closeCloakedAgent()
+          .accounts({
+            cloakedAgentState: parentPda,
+            vault: parentVaultPda,
+            owner: owner.publicKey,
+            vaultRecipient: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with HasChildAgents");
+      } catch (error: any) {
+        expect(error.message).to.include("HasChildAgents");
+      }
+
+      await program.methods
+        .closeCloakedAgent()
+        .accounts({
+          cloakedAgentState: childPda,
+          vault: childVaultPda,
+          owner: owner.publicKey,
+          vaultRecipient: owner.publicKey,
+          parentState: parentPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const parent = await program.account.cloakedAgentState.fetch(parentPda);
+      expect(parent.childCount).to.equal(0);
+    });
+  });
+
+  describe("vault balance snapshot", () => {
+    it("records the vault balance after a deposit", async () => {
+      const owner = Keypair.generate();
+      const delegateKeypair = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      const vaultBalance = await provider.connection.getBalance(vaultPda);
+      expect(state.vaultBalanceSnapshot.toNumber()).to.equal(vaultBalance);
+      expect(state.snapshotTimestamp.toNumber()).to.be.greaterThan(0);
+    });
+  });
+
+  describe("withdraw destination lock", () => {
+    let owner: Keypair;
+    let coldWallet: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      coldWallet = Keypair.generate();
+      const delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), coldWallet.publicKey, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const withdrawTo = (destination: PublicKey) =>
+      program.methods
+        .withdraw(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+    it("only allows withdrawals to the locked destination", async () => {
+      try {
+        await withdrawTo(Keypair.generate().publicKey);
+        expect.fail("Should have failed with DestinationLocked");
+      } catch (error: any) {
+        expect(error.message).to.include("DestinationLocked");
+      }
+
+      await withdrawTo(coldWallet.publicKey);
+      const coldBalance = await provider.connection.getBalance(coldWallet.publicKey);
+      expect(coldBalance).to.equal(0.1 * LAMPORTS_PER_SOL);
+    });
+
+    it("queues lock changes behind the timelock", async () => {
+      const newCold = Keypair.generate().publicKey;
+      await program.methods
+        .proposeDestinationLock(newCold)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.withdrawDestinationLock?.toBase58()).to.equal(coldWallet.publicKey.toBase58());
+      expect(state.pendingDestinationLock?.toBase58()).to.equal(newCold.toBase58());
+
+      try {
+        await program.methods
+          .applyDestinationLock()
+          .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with DestinationLockTimelocked");
+      } catch (error: any) {
+        expect(error.message).to.include("DestinationLockTimelocked");
+      }
+    });
+  });
+
+  describe("timelocked withdrawals", () => {
+    let owner: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    const timelockPda = (nonce: number) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("timelock"), agentStatePda.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    const propose = (nonce: number) =>
+      program.methods
+        .proposeTimelockedWithdrawal(owner.publicKey, new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          timelockRecord: timelockPda(nonce),
+          vault: vaultPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      const delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("caps pending withdrawals at max_concurrent_timelocks", async () => {
+      await program.methods
+        .setMaxConcurrentTimelocks(2)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      await propose(0);
+      await propose(1);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.pendingTimelocks).to.equal(2);
+      expect(state.reservedLamports.toNumber()).to.equal(0.2 * LAMPORTS_PER_SOL);
+
+      try {
+        await propose(2);
+        expect.fail("Should have failed with TooManyPendingTimelocks");
+      } catch (error: any) {
+        expect(error.message).to.include("TooManyPendingTimelocks");
+      }
+    });
+
+    it("rejects a cap above MAX_CONCURRENT_TIMELOCKS", async () => {
+      try {
+        await program.methods
+          .setMaxConcurrentTimelocks(33)
+          .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with InvalidTimelockCap");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidTimelockCap");
+      }
+    });
+
+    it("cannot execute before the eta", async () => {
+      await propose(0);
+
+      try {
+        await program.methods
+          .executeTimelockedWithdrawal()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            timelockRecord: timelockPda(0),
+            vault: vaultPda,
+            owner: owner.publicKey,
+            destination: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with WithdrawalTimelocked");
+      } catch (error: any) {
+        expect(error.message).to.include("WithdrawalTimelocked");
+      }
+    });
+
+    it("blocks close until pending withdrawals are cancelled", async () => {
+      await propose(0);
+
+      const close = () =>
+        program.methods
+          .closeCloakedAgent()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: owner.publicKey,
+            vaultRecipient: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+
+      try {
+        await close();
+        expect.fail("Should have failed with HasPendingTimelocks");
+      } catch (error: any) {
+        expect(error.message).to.include("HasPendingTimelocks");
+      }
+
+      await program.methods
+        .cancelTimelockedWithdrawal()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          timelockRecord: timelockPda(0),
+          owner: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.pendingTimelocks).to.equal(0);
+      expect(state.reservedLamports.toNumber()).to.equal(0);
+
+      await close();
+      const closed = await provider.connection.getAccountInfo(agentStatePda);
+      expect(closed).to.be.null;
+    });
+  });
+
+  describe("multisig owners", () => {
+    let payer: Keypair;
+    let owners: Keypair[];
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      payer = Keypair.generate();
+      owners = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+      const delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(payer.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), payer.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      // 2-of-3
+      await program.methods
+        .createCloakedAgentMultisig(
+          new anchor.BN(0),
+          owners.map((o) => o.publicKey),
+          2,
+          new anchor.BN(0),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegateKeypair.publicKey,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+    });
+
+    it("stores the owner set and threshold", async () => {
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.owner).to.be.null;
+      expect(state.multisigOwnerCount).to.equal(3);
+      expect(state.multisigThreshold).to.equal(2);
+      expect(state.multisigOwners[1].toBase58()).to.equal(owners[1].publicKey.toBase58());
+    });
+
+    it("rejects a single owner signature below threshold", async () => {
+      try {
+        await program.methods
+          .freeze(Array(32).fill(0))
+          .accounts({ cloakedAgentState: agentStatePda, owner: owners[0].publicKey })
+          .signers([owners[0]])
+          .rpc();
+        expect.fail("Should have failed with MultisigThresholdNotMet");
+      } catch (error: any) {
+        expect(error.message).to.include("MultisigThresholdNotMet");
+      }
+    });
+
+    it("freezes once threshold owners sign", async () => {
+      await program.methods
+        .freeze(Array(32).fill(0))
+        .accounts({ cloakedAgentState: agentStatePda, owner: owners[0].publicKey })
+        .remainingAccounts([{ pubkey: owners[2].publicKey, isSigner: true, isWritable: false }])
+        .signers([owners[0], owners[2]])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.frozen).to.be.true;
+    });
+
+    it("panics once threshold owners sign", async () => {
+      const destination = Keypair.generate().publicKey;
+      const panic = (signers: Keypair[]) =>
+        program.methods
+          .panic()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: signers[0].publicKey,
+            destination,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts(
+            signers.slice(1).map((o) => ({ pubkey: o.publicKey, isSigner: true, isWritable: false }))
+          )
+          .signers(signers)
+          .rpc();
+
+      try {
+        await panic([owners[1]]);
+        expect.fail("Should have failed with MultisigThresholdNotMet");
+      } catch (error: any) {
+        expect(error.message).to.include("MultisigThresholdNotMet");
+      }
+
+      await panic([owners[1], owners[2]]);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.frozen).to.be.true;
+    });
+
+    it("requires threshold approval to change the owner set", async () => {
+      try {
+        await program.methods
+          .updateMultisig([owners[0].publicKey], 1)
+          .accounts({ cloakedAgentState: agentStatePda, owner: owners[0].publicKey })
+          .signers([owners[0]])
+          .rpc();
+        expect.fail("Should have failed with MultisigThresholdNotMet");
+      } catch (error: any) {
+        expect(error.message).to.include("MultisigThresholdNotMet");
+      }
+
+      await program.methods
+        .updateMultisig([owners[0].publicKey], 1)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owners[0].publicKey })
+        .remainingAccounts([{ pubkey: owners[1].publicKey, isSigner: true, isWritable: false }])
+        .signers([owners[0], owners[1]])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.multisigOwnerCount).to.equal(1);
+      expect(state.multisigThreshold).to.equal(1);
+    });
+  });
+
+  describe("secp256k1 delegate", () => {
+    let owner: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      const delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spendWithSecp = (nonce: number) =>
+      program.methods
+        .spendWithSecp(
+          new anchor.BN(1000),
+          new anchor.BN(nonce),
+          new anchor.BN(Math.floor(Date.now() / 1000) + 600),
+          Array(64).fill(1),
+          0
+        )
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          feePayer: owner.publicKey,
+          destination: Keypair.generate().publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+    it("rejects secp spends until an Ethereum address is set", async () => {
+      try {
+        await spendWithSecp(1);
+        expect.fail("Should have failed with EthDelegateNotSet");
+      } catch (error: any) {
+        expect(error.message).to.include("EthDelegateNotSet");
+      }
+    });
+
+    it("rejects signatures from other keys", async () => {
+      const ethAddress = Array(20).fill(7);
+      await program.methods
+        .setDelegateEthAddress(ethAddress)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.delegateEthAddress).to.deep.equal(ethAddress);
+
+      try {
+        await spendWithSecp(1);
+        expect.fail("Should have failed with InvalidSecpSignature");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidSecpSignature");
+      }
+    });
+  });
+
+  describe("delegate instruction whitelist", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("allows every instruction by default", async () => {
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.instructionWhitelist).to.equal(0xffff);
+    });
+
+    it("blocks spend when its bit is cleared", async () => {
+      await program.methods
+        .setInstructionWhitelist(0xfffe)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      try {
+        await program.methods
+          .spend(new anchor.BN(1000))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: delegateKeypair.publicKey,
+            feePayer: owner.publicKey,
+            destination: Keypair.generate().publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([delegateKeypair, owner])
+          .rpc();
+        expect.fail("Should have failed with InstructionNotAllowed");
+      } catch (error: any) {
+        expect(error.message).to.include("InstructionNotAllowed");
+      }
+    });
+  });
+
+  describe("batch freeze", () => {
+    it("freezes several agents of the same owner atomically", async () => {
+      const owner = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const agents: PublicKey[] = [];
+      for (const nonce of [0, 1]) {
+        const [agentStatePda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, "le", 8)],
+          program.programId
+        );
+        const [vaultPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("vault"), agentStatePda.toBuffer()],
+          program.programId
+        );
+
+        await program.methods
+          .createCloakedAgent(new anchor.BN(nonce), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: owner.publicKey,
+            delegate: Keypair.generate().publicKey,
+            payer: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+        agents.push(agentStatePda);
+      }
+
+      const reason = Array.from(Buffer.from("INC-1234".padEnd(32, "\0")));
+      await program.methods
+        .batchFreeze(agents, reason)
+        .accounts({ owner: owner.publicKey })
+        .remainingAccounts(agents.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+        .signers([owner])
+        .rpc();
+
+      for (const agent of agents) {
+        const state = await program.account.cloakedAgentState.fetch(agent);
+        expect(state.frozen).to.be.true;
+        expect(state.freezeReason).to.deep.equal(reason);
+      }
+    });
+  });
+
+  describe("passkey delegate", () => {
+    let owner: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spendWithPasskey = () =>
+      program.methods
+        .spendWithPasskey(new anchor.BN(1000), new anchor.BN(1), new anchor.BN(Math.floor(Date.now() / 1000) + 600))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          feePayer: owner.publicKey,
+          destination: Keypair.generate().publicKey,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+    it("rejects passkey spends until a passkey is set", async () => {
+      try {
+        await spendWithPasskey();
+        expect.fail("Should have failed with PasskeyDelegateNotSet");
+      } catch (error: any) {
+        expect(error.message).to.include("PasskeyDelegateNotSet");
+      }
+    });
+
+    it("requires a preceding secp256r1 verify instruction", async () => {
+      const passkey = [0x02, ...Array(32).fill(9)];
+      await program.methods
+        .setDelegatePasskey(passkey)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      try {
+        await spendWithPasskey();
+        expect.fail("Should have failed with MissingSignatureInstruction");
+      } catch (error: any) {
+        expect(error.message).to.include("MissingSignatureInstruction");
+      }
+    });
+  });
+
+  describe("signed spend intents", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const signedSpend = (destination: PublicKey, nonce: number) => {
+      const intent = {
+        agent: agentStatePda,
+        amount: new anchor.BN(0.1 * LAMPORTS_PER_SOL),
+        destination,
+        nonce: new anchor.BN(nonce),
+        expiresAt: new anchor.BN(Math.floor(Date.now() / 1000) + 600),
+      };
+      const message = Buffer.concat([
+        Buffer.from("cloaked:spend_signed"),
+        intent.agent.toBuffer(),
+        intent.amount.toArrayLike(Buffer, "le", 8),
+        intent.destination.toBuffer(),
+        intent.nonce.toArrayLike(Buffer, "le", 8),
+        intent.expiresAt.toArrayLike(Buffer, "le", 8),
+      ]);
+      const verifyIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: delegateKeypair.secretKey,
+        message,
+      });
+      const signature = Array.from(verifyIx.data.subarray(48, 112));
+
+      // The relayer (owner here) lands the transaction; the delegate never signs it
+      return program.methods
+        .spendSigned(intent, signature)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          feePayer: owner.publicKey,
+          destination,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([verifyIx])
+        .signers([owner])
+        .rpc();
+    };
+
+    it("spends from a delegate-signed intent and rejects replays", async () => {
+      const destination = Keypair.generate().publicKey;
+      await signedSpend(destination, 1);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.delegateIntentNonce.toNumber()).to.equal(1);
+
+      try {
+        await signedSpend(destination, 1);
+        expect.fail("Should have failed with InvalidSpendIntent");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidSpendIntent");
+      }
+    });
+  });
+
+  describe("emergency rescue", () => {
+    let owner: Keypair;
+    let rescueAddress: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    const createWithRescue = (rescueDelay: number) =>
+      program.methods
+        .createCloakedAgent(
+          new anchor.BN(0),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          new anchor.BN(0),
+          null,
+          rescueAddress.publicKey,
+          new anchor.BN(rescueDelay),
+          null,
+          false
+        )
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      rescueAddress = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+    });
+
+    it("rejects rescue delays shorter than 30 days", async () => {
+      try {
+        await createWithRescue(86400);
+        expect.fail("Should have failed with InvalidRescueConfig");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidRescueConfig");
+      }
+    });
+
+    it("blocks execute_rescue during the delay and lets the owner push it back", async () => {
+      await createWithRescue(365 * 86400);
+
+      try {
+        await program.methods
+          .executeRescue()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            rescueDestination: rescueAddress.publicKey,
+            caller: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with RescueNotAvailable");
+      } catch (error: any) {
+        expect(error.message).to.include("RescueNotAvailable");
+      }
+
+      const before = await program.account.cloakedAgentState.fetch(agentStatePda);
+      await program.methods
+        .cancelRescue()
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+      const after = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(after.rescueAvailableAt.toNumber()).to.be.at.least(before.rescueAvailableAt.toNumber());
+    });
+  });
+
+  describe("time-of-day spend window", () => {
+    let owner: Keypair;
+    let delegateKeypair: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegateKeypair = Keypair.generate();
+
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("rejects spends outside the allowed hours", async () => {
+      // Window opens one hour from now and closes an hour later (wraps midnight if needed)
+      const secondOfDay = Math.floor(Date.now() / 1000) % 86400;
+      const start = (secondOfDay + 3600) % 86400;
+      const end = (secondOfDay + 7200) % 86400;
+
+      await program.methods
+        .updateConstraints(null, null, null, null, null, null, null, null, null, null, start, end, null, null)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      try {
+        await program.methods
+          .spend(new anchor.BN(1000))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: delegateKeypair.publicKey,
+            feePayer: owner.publicKey,
+            destination: Keypair.generate().publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([delegateKeypair, owner])
+          .rpc();
+        expect.fail("Should have failed with OutsideSpendWindow");
+      } catch (error: any) {
+        expect(error.message).to.include("OutsideSpendWindow");
+      }
+    });
+
+    it("rejects spends on disallowed weekdays", async () => {
+      // Every day except today (bit 0 = Sunday, matching getUTCDay)
+      const today = new Date().getUTCDay();
+      const mask = 0x7f & ~(1 << today);
+
+      await program.methods
+        .updateConstraints(null, null, null, null, null, null, null, null, null, null, null, null, mask, null)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      try {
+        await program.methods
+          .spend(new anchor.BN(1000))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: delegateKeypair.publicKey,
+            feePayer: owner.publicKey,
+            destination: Keypair.generate().publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([delegateKeypair, owner])
+          .rpc();
+        expect.fail("Should have failed with DayNotAllowed");
+      } catch (error: any) {
+        expect(error.message).to.include("DayNotAllowed");
+      }
+    });
+  });
+
+  describe("constraint lock", () => {
+    it("rejects constraint updates once locked", async () => {
+      const owner = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .lockConstraints()
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.constraintsLocked).to.be.true;
+
+      try {
+        await program.methods
+          .updateConstraints(new anchor.BN(1), null, null, null, null, null, null, null, null, null, null, null, null, null)
+          .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with ConstraintsLocked");
+      } catch (error: any) {
+        expect(error.message).to.include("ConstraintsLocked");
+      }
+    });
+  });
+
+  describe("topup and spend", () => {
+    it("funds an empty vault and spends in one instruction", async () => {
+      const owner = Keypair.generate();
+      const delegateKeypair = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const destination = Keypair.generate().publicKey;
+      await program.methods
+        .topupAndSpend(new anchor.BN(0.2 * LAMPORTS_PER_SOL), new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          feePayer: owner.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner, delegateKeypair])
+        .rpc();
+
+      const destinationBalance = await provider.connection.getBalance(destination);
+      expect(destinationBalance).to.equal(0.1 * LAMPORTS_PER_SOL);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalDeposited.toNumber()).to.equal(0.2 * LAMPORTS_PER_SOL);
+      expect(state.totalSpent.toNumber()).to.equal(0.1 * LAMPORTS_PER_SOL);
+    });
+  });
+
+  describe("low balance circuit breaker", () => {
+    it("freezes the agent when a spend drops the vault below the threshold", async () => {
+      const owner = Keypair.generate();
+      const delegateKeypair = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegateKeypair.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .setConditionalFreezeThreshold(new anchor.BN(0.45 * LAMPORTS_PER_SOL))
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .spend(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegateKeypair.publicKey,
+          feePayer: owner.publicKey,
+          destination: Keypair.generate().publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegateKeypair, owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.frozen).to.be.true;
+      expect(Buffer.from(state.freezeReason).subarray(0, 10).toString()).to.equal("LowBalance");
+    });
+  });
+
+  describe("custom budget period", () => {
+    it("creates an agent with a weekly spending window", async () => {
+      const owner = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      const week = 7 * 86400;
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), new anchor.BN(week), false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.spendingWindow.windowSeconds.toNumber()).to.equal(week);
+    });
+  });
+
+  describe("recovery delegate", () => {
+    let owner: Keypair;
+    let recoveryDelegate: Keypair;
+    let agentStatePda: PublicKey;
+    let recoveryProposalPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      recoveryDelegate = Keypair.generate();
+
+      for (const kp of [owner, recoveryDelegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      [recoveryProposalPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("recovery"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .setRecoveryDelegate(recoveryDelegate.publicKey)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("timelocks recovery and lets the owner cancel it", async () => {
+      const newOwner = Keypair.generate().publicKey;
+      await program.methods
+        .proposeRecovery(newOwner)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          recoveryProposal: recoveryProposalPda,
+          recoveryDelegate: recoveryDelegate.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([recoveryDelegate])
+        .rpc();
+
+      try {
+        await program.methods
+          .executeRecovery(newOwner)
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            recoveryProposal: recoveryProposalPda,
+            recoveryDelegate: recoveryDelegate.publicKey,
+          })
+          .signers([recoveryDelegate])
+          .rpc();
+        expect.fail("Should have failed with RecoveryTimelocked");
+      } catch (error: any) {
+        expect(error.message).to.include("RecoveryTimelocked");
+      }
+
+      await program.methods
+        .cancelRecovery()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          recoveryProposal: recoveryProposalPda,
+          owner: owner.publicKey,
+          proposer: recoveryDelegate.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      const proposal = await provider.connection.getAccountInfo(recoveryProposalPda);
+      expect(proposal).to.be.null;
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.owner?.toBase58()).to.equal(owner.publicKey.toBase58());
+    });
+  });
+
+  describe("surplus sweep", () => {
+    it("sweeps vault balance above the target and pays the cranker", async () => {
+      const owner = Keypair.generate();
+      const cranker = Keypair.generate();
+      const treasury = Keypair.generate();
+
+      for (const kp of [owner, cranker]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const target = new anchor.BN(0.25 * LAMPORTS_PER_SOL);
+      await program.methods
+        .setSweepConfig(target, treasury.publicKey)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .sweepSurplus()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          sweepDestination: treasury.publicKey,
+          cranker: cranker.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([cranker])
+        .rpc();
+
+      const vaultBalance = await provider.connection.getBalance(vaultPda);
+      expect(vaultBalance).to.equal(target.toNumber());
+      const treasuryBalance = await provider.connection.getBalance(treasury.publicKey);
+      expect(treasuryBalance).to.equal(0.75 * LAMPORTS_PER_SOL - 5_000);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalSpent.toNumber()).to.equal(0);
+
+      try {
+        await program.methods
+          .sweepSurplus()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            sweepDestination: treasury.publicKey,
+            cranker: cranker.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([cranker])
+          .rpc();
+        expect.fail("Should have failed with NoSurplusToSweep");
+      } catch (error: any) {
+        expect(error.message).to.include("NoSurplusToSweep");
+      }
+    });
+  });
+
+  describe("payment schedules", () => {
+    it("pays a due schedule once per period and can be cancelled", async () => {
+      const owner = Keypair.generate();
+      const cranker = Keypair.generate();
+      const payee = Keypair.generate();
+
+      for (const kp of [owner, cranker]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      const [schedulePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("schedule"), agentStatePda.toBuffer(), payee.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const amount = new anchor.BN(0.1 * LAMPORTS_PER_SOL);
+      await program.methods
+        .createSchedule(payee.publicKey, amount, new anchor.BN(30 * 86400), new anchor.BN(0), false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          paymentSchedule: schedulePda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const executeAccounts = {
+        cloakedAgentState: agentStatePda,
+        paymentSchedule: schedulePda,
+        vault: vaultPda,
+        cranker: cranker.publicKey,
+        destination: payee.publicKey,
+        commissionRecipient: null,
+        whitelistEntry: null,
+        parentState: null,
+        systemProgram: SystemProgram.programId,
+      };
+
+      await program.methods
+        .executeSchedule()
+        .accounts(executeAccounts)
+        .signers([cranker])
+        .rpc();
+
+      expect(await provider.connection.getBalance(payee.publicKey)).to.equal(amount.toNumber());
+      const schedule = await program.account.paymentSchedule.fetch(schedulePda);
+      expect(schedule.nextDue.toNumber()).to.be.greaterThan(Math.floor(Date.now() / 1000));
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalSpent.toNumber()).to.equal(amount.toNumber());
+
+      try {
+        await program.methods
+          .executeSchedule()
+          .accounts(executeAccounts)
+          .signers([cranker])
+          .rpc();
+        expect.fail("Should have failed with ScheduleNotDue");
+      } catch (error: any) {
+        expect(error.message).to.include("ScheduleNotDue");
+      }
+
+      await program.methods
+        .cancelSchedule(payee.publicKey)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          paymentSchedule: schedulePda,
+          owner: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(schedulePda)).to.be.null;
+    });
+  });
+
+  describe("payment vouchers", () => {
+    it("lets the delegate redeem a voucher above max_per_tx exactly once", async () => {
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+      const payee = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 3 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      const [voucherPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("voucher"), agentStatePda.toBuffer(), payee.publicKey.toBuffer()],
+        program.programId
+      );
+
+      // max_per_tx of 0.1 SOL
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0.1 * LAMPORTS_PER_SOL), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(2 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const amount = new anchor.BN(1 * LAMPORTS_PER_SOL);
+      const expiresAt = new anchor.BN(Math.floor(Date.now() / 1000) + 3600);
+      await program.methods
+        .createVoucher(payee.publicKey, amount, expiresAt, true)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          voucher: voucherPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const redeemAccounts = {
+        cloakedAgentState: agentStatePda,
+        voucher: voucherPda,
+        vault: vaultPda,
+        delegate: delegate.publicKey,
+        feePayer: delegate.publicKey,
+        destination: payee.publicKey,
+        owner: owner.publicKey,
+        systemProgram: SystemProgram.programId,
+      };
+
+      await program.methods
+        .redeemVoucher()
+        .accounts(redeemAccounts)
+        .signers([delegate])
+        .rpc();
+
+      expect(await provider.connection.getBalance(payee.publicKey)).to.equal(amount.toNumber());
+      expect(await provider.connection.getAccountInfo(voucherPda)).to.be.null;
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalSpent.toNumber()).to.equal(amount.toNumber());
+
+      try {
+        await program.methods
+          .redeemVoucher()
+          .accounts(redeemAccounts)
+          .signers([delegate])
+          .rpc();
+        expect.fail("Should have failed: voucher already consumed");
+      } catch (error: any) {
+        expect(error.message).to.include("AccountNotInitialized");
+      }
+    });
+  });
+
+  describe("oracle-gated conditional spend", () => {
+    it("rejects a price feed not owned by the Pyth receiver", async () => {
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // A system-owned account posing as a price feed
+      const fakeFeed = owner.publicKey;
+      try {
+        await program.methods
+          .conditionalSpend(new anchor.BN(0.1 * LAMPORTS_PER_SOL), fakeFeed, new anchor.BN(0), { above: {} })
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: delegate.publicKey,
+            feePayer: delegate.publicKey,
+            destination: Keypair.generate().publicKey,
+            commissionRecipient: null,
+            whitelistEntry: null,
+            parentState: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts([{ pubkey: fakeFeed, isSigner: false, isWritable: false }])
+          .signers([delegate])
+          .rpc();
+        expect.fail("Should have failed with InvalidPriceFeed");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidPriceFeed");
+      }
+    });
+  });
+
+  describe("escrowed spends", () => {
+    it("holds an escrowed spend and lets the owner revoke it", async () => {
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+      const payee = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      // First spend: spend_nonce is 0
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("escrow"), agentStatePda.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const amount = new anchor.BN(0.3 * LAMPORTS_PER_SOL);
+      const releaseAfter = new anchor.BN(Math.floor(Date.now() / 1000) + 3600);
+      await program.methods
+        .spendEscrowed(amount, payee.publicKey, releaseAfter)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          escrow: escrowPda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          commissionRecipient: null,
+          whitelistEntry: null,
+          parentState: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+
+      let state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalSpent.toNumber()).to.equal(amount.toNumber());
+
+      try {
+        await program.methods
+          .releaseEscrow()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            escrow: escrowPda,
+            destination: payee.publicKey,
+            rentPayer: delegate.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have failed with EscrowLocked");
+      } catch (error: any) {
+        expect(error.message).to.include("EscrowLocked");
+      }
+
+      const vaultBefore = await provider.connection.getBalance(vaultPda);
+      await program.methods
+        .revokeEscrow()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          escrow: escrowPda,
+          vault: vaultPda,
+          parentState: null,
+          owner: owner.publicKey,
+          rentPayer: delegate.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      expect(await provider.connection.getBalance(vaultPda)).to.equal(vaultBefore + amount.toNumber());
+      expect(await provider.connection.getAccountInfo(escrowPda)).to.be.null;
+      state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalSpent.toNumber()).to.equal(0);
+    });
+  });
+
+  describe("deposit with program authority", () => {
+    it("accepts a signing depositor and rejects an unsigned one", async () => {
+      const owner = Keypair.generate();
+      const authority = Keypair.generate();
+
+      for (const kp of [owner, authority]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // A keypair stands in for a CPI-signing PDA: both arrive with is_signer set
+      const amount = new anchor.BN(0.5 * LAMPORTS_PER_SOL);
+      await program.methods
+        .depositWithProgramAuthority(amount)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: authority.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalDeposited.toNumber()).to.equal(amount.toNumber());
+
+      // Without the depositor's signature the system transfer fails
+      try {
+        await program.methods
+          .depositWithProgramAuthority(amount)
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            depositor: authority.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have failed without the depositor signature");
+      } catch (error: any) {
+        expect(error.message).to.include("signature");
+      }
+    });
+  });
+
+  describe("vault staking", () => {
+    it("stakes vault SOL only to allowlisted validators", async () => {
+      const owner = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 5 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(3 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // The local test validator's own vote account
+      const { current } = await provider.connection.getVoteAccounts();
+      const voteAccount = new PublicKey(current[0].votePubkey);
+      const [validatorEntryPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("validator"), agentStatePda.toBuffer(), voteAccount.toBuffer()],
+        program.programId
+      );
+
+      const stakeAccount = Keypair.generate();
+      const [stakeRecordPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("stake"), agentStatePda.toBuffer(), stakeAccount.publicKey.toBuffer()],
+        program.programId
+      );
+      const amount = new anchor.BN(1 * LAMPORTS_PER_SOL);
+      const stakeAccounts = {
+        cloakedAgentState: agentStatePda,
+        vault: vaultPda,
+        validatorEntry: validatorEntryPda,
+        stakeRecord: stakeRecordPda,
+        stakeAccount: stakeAccount.publicKey,
+        voteAccount,
+        owner: owner.publicKey,
+        clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        stakeHistory: anchor.web3.SYSVAR_STAKE_HISTORY_PUBKEY,
+        stakeConfig: anchor.web3.STAKE_CONFIG_ID,
+        stakeProgram: anchor.web3.StakeProgram.programId,
+        systemProgram: SystemProgram.programId,
+      };
+
+      try {
+        await program.methods
+          .stakeFromVault(amount, voteAccount)
+          .accounts(stakeAccounts)
+          .signers([owner, stakeAccount])
+          .rpc();
+        expect.fail("Should have failed: validator not allowlisted");
+      } catch (error: any) {
+        expect(error.message).to.include("AccountNotInitialized");
+      }
+
+      await program.methods
+        .addValidator(voteAccount)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          validatorEntry: validatorEntryPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const vaultBefore = await provider.connection.getBalance(vaultPda);
+      await program.methods
+        .stakeFromVault(amount, voteAccount)
+        .accounts(stakeAccounts)
+        .signers([owner, stakeAccount])
+        .rpc();
+
+      expect(await provider.connection.getBalance(vaultPda)).to.equal(vaultBefore - amount.toNumber());
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.stakedAmount.toNumber()).to.equal(amount.toNumber());
+    });
+  });
+
+  describe("destination stats", () => {
+    it("records spends to a destination and lets the owner close the stats", async () => {
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+      const payee = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      const [destStatsPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("dest_stats"), agentStatePda.toBuffer(), payee.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const amount = new anchor.BN(0.05 * LAMPORTS_PER_SOL);
+      for (let i = 0; i < 2; i++) {
+        await program.methods
+          .spend(amount)
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: delegate.publicKey,
+            feePayer: delegate.publicKey,
+            destination: payee.publicKey,
+            commissionRecipient: null,
+            whitelistEntry: null,
+            parentState: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts([{ pubkey: destStatsPda, isSigner: false, isWritable: true }])
+          .signers([delegate])
+          .rpc();
+      }
+
+      const stats = await program.account.destinationStats.fetch(destStatsPda);
+      expect(stats.totalSent.toNumber()).to.equal(2 * amount.toNumber());
+      expect(stats.sentCount.toNumber()).to.equal(2);
+
+      await program.methods
+        .closeDestStats(payee.publicKey)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          destStats: destStatsPda,
+          owner: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(destStatsPda)).to.be.null;
+    });
+  });
+
+  describe("spend via allowlisted program", () => {
+    // The system program stands in for a DEX: its transfer lets the CPI target move
+    // vault lamports, so over-withdrawal is directly observable
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    let allowedProgramPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      [allowedProgramPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("allowed_program"), agentStatePda.toBuffer(), SystemProgram.programId.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .addAllowedProgram(SystemProgram.programId)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          allowedProgram: allowedProgramPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spendViaSystemTransfer = (amount: number, lamports: number, recipient: PublicKey) =>
+      program.methods
+        .spendViaProgram(
+          new anchor.BN(amount),
+          SystemProgram.programId,
+          SystemProgram.transfer({ fromPubkey: vaultPda, toPubkey: recipient, lamports }).data
+        )
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          allowedProgram: allowedProgramPda,
+          targetProgram: SystemProgram.programId,
+          delegate: delegate.publicKey,
+          parentState: null,
+        })
+        .remainingAccounts([
+          { pubkey: vaultPda, isSigner: false, isWritable: true },
+          { pubkey: recipient, isSigner: false, isWritable: true },
+        ])
+        .signers([delegate])
+        .rpc();
+
+    it("allows the CPI to move up to the charged amount", async () => {
+      const recipient = Keypair.generate().publicKey;
+      const amount = 0.1 * LAMPORTS_PER_SOL;
+
+      await spendViaSystemTransfer(amount, amount, recipient);
+
+      expect(await provider.connection.getBalance(recipient)).to.equal(amount);
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.totalSpent.toNumber()).to.equal(amount);
+    });
+
+    it("rejects a CPI that takes more than the charged amount", async () => {
+      const amount = 0.1 * LAMPORTS_PER_SOL;
+      try {
+        await spendViaSystemTransfer(amount, amount + 1, Keypair.generate().publicKey);
+        expect.fail("Should have failed with ProgramSpendExceeded");
+      } catch (error: any) {
+        expect(error.message).to.include("ProgramSpendExceeded");
+      }
+    });
+  });
+
+  describe("program delegates", () => {
+    let owner: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+    });
+
+    it("rejects an executable delegate in create_cloaked_agent", async () => {
+      try {
+        await program.methods
+          .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: owner.publicKey,
+            delegate: SystemProgram.programId,
+            payer: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with DelegateIsProgram");
+      } catch (error: any) {
+        expect(error.message).to.include("DelegateIsProgram");
+      }
+    });
+
+    it("creates an agent flagged with a program delegate", async () => {
+      await program.methods
+        .createCloakedAgentWithProgramDelegate(new anchor.BN(0), SystemProgram.programId, new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.delegate.toBase58()).to.equal(SystemProgram.programId.toBase58());
+      expect(state.programDelegate).to.be.true;
+    });
+  });
+
+  describe("global stats", () => {
+    const [globalStatsPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("global_stats")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+
+    before(async () => {
+      // Singleton: created once per validator by the upgrade authority (provider wallet)
+      if (await provider.connection.getAccountInfo(globalStatsPda)) return;
+      await program.methods
+        .initializeGlobalStats()
+        .accounts({
+          globalStats: globalStatsPda,
+          program: program.programId,
+          programData: programDataPda,
+          admin: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    it("rejects initialization by anyone but the upgrade authority", async () => {
+      const outsider = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(outsider.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      try {
+        await program.methods
+          .initializeGlobalStats()
+          .accounts({
+            globalStats: globalStatsPda,
+            program: program.programId,
+            programData: programDataPda,
+            admin: outsider.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([outsider])
+          .rpc();
+        expect.fail("Should have failed");
+      } catch (error: any) {
+        // Either the PDA already exists or the signer is not the upgrade authority
+        expect(error.message).to.match(/NotAdmin|already in use/);
+      }
+    });
+
+    it("counts created agents and deposits when passed", async () => {
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      const before = await program.account.globalStats.fetch(globalStatsPda);
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          globalStats: globalStatsPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const amount = 0.5 * LAMPORTS_PER_SOL;
+      await program.methods
+        .deposit(new anchor.BN(amount))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          globalStats: globalStatsPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const after = await program.account.globalStats.fetch(globalStatsPda);
+      expect(after.totalAgentsCreated.toNumber()).to.equal(before.totalAgentsCreated.toNumber() + 1);
+      expect(after.totalLamportsDeposited.toNumber()).to.equal(before.totalLamportsDeposited.toNumber() + amount);
+    });
+  });
+
+  describe("delegate registry", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let registryPda: PublicKey;
+
+    const agentPdas = (nonce: number) => {
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      return { agentStatePda, vaultPda };
+    };
+
+    const createAgent = (nonce: number, delegateRegistry: PublicKey | null) => {
+      const { agentStatePda, vaultPda } = agentPdas(nonce);
+      return program.methods
+        .createCloakedAgent(new anchor.BN(nonce), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          delegateRegistry,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    };
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [registryPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("delegate_registry"), delegate.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createDelegateRegistry()
+        .accounts({
+          delegateRegistry: registryPda,
+          delegate: delegate.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+    });
+
+    it("lists agents created with the registry and drops them on close", async () => {
+      await createAgent(0, registryPda);
+      const { agentStatePda, vaultPda } = agentPdas(0);
+
+      let registry = await program.account.delegateRegistry.fetch(registryPda);
+      expect(registry.agents.map((a) => a.toBase58())).to.deep.equal([agentStatePda.toBase58()]);
+
+      await program.methods
+        .closeCloakedAgent()
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          vaultRecipient: owner.publicKey,
+          delegateRegistry: registryPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      registry = await program.account.delegateRegistry.fetch(registryPda);
+      expect(registry.agents).to.be.empty;
+    });
+
+    it("lets the delegate register an existing agent once", async () => {
+      await createAgent(1, null);
+      const { agentStatePda } = agentPdas(1);
+
+      const addToRegistry = () =>
+        program.methods
+          .addToDelegateRegistry(agentStatePda)
+          .accounts({
+            delegateRegistry: registryPda,
+            cloakedAgentState: agentStatePda,
+            delegate: delegate.publicKey,
+          })
+          .signers([delegate])
+          .rpc();
+
+      await addToRegistry();
+      const registry = await program.account.delegateRegistry.fetch(registryPda);
+      expect(registry.agents.map((a) => a.toBase58())).to.deep.equal([agentStatePda.toBase58()]);
+
+      try {
+        await addToRegistry();
+        expect.fail("Should have failed with AgentAlreadyRegistered");
+      } catch (error: any) {
+        expect(error.message).to.include("AgentAlreadyRegistered");
+      }
+    });
+  });
+
+  describe("2FA for high-value spends", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let secondFactor: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    const threshold = 0.1 * LAMPORTS_PER_SOL;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+      secondFactor = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .setTwoFa(secondFactor.publicKey, new anchor.BN(threshold))
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("requires the second factor at or above the threshold", async () => {
+      const destination = Keypair.generate().publicKey;
+
+      // Below the threshold a plain spend is fine
+      await program.methods
+        .spend(new anchor.BN(threshold - 1))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+
+      try {
+        await program.methods
+          .spend(new anchor.BN(threshold))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: delegate.publicKey,
+            feePayer: delegate.publicKey,
+            destination,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([delegate])
+          .rpc();
+        expect.fail("Should have failed with Invalid2FA");
+      } catch (error: any) {
+        expect(error.message).to.include("Invalid2FA");
+      }
+
+      await program.methods
+        .spendWith2fa(new anchor.BN(threshold))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          secondFactor: secondFactor.publicKey,
+          feePayer: delegate.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate, secondFactor])
+        .rpc();
+
+      expect(await provider.connection.getBalance(destination)).to.equal(2 * threshold - 1);
+    });
+
+    it("needs the current 2FA key to remove 2FA", async () => {
+      try {
+        await program.methods
+          .removeTwoFa()
+          .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey, secondFactor: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with Invalid2FA");
+      } catch (error: any) {
+        expect(error.message).to.include("Invalid2FA");
+      }
+
+      await program.methods
+        .removeTwoFa()
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey, secondFactor: secondFactor.publicKey })
+        .signers([owner, secondFactor])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.twoFaKey).to.be.null;
+    });
+  });
+
+  describe("program config", () => {
+    const [programConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("program_config")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    const ZK_VERIFIER_PROGRAM_ID = new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F");
+
+    before(async () => {
+      // Singleton: created once per validator by the upgrade authority (provider wallet)
+      if (await provider.connection.getAccountInfo(programConfigPda)) return;
+      await program.methods
+        .initializeProgramConfig(ZK_VERIFIER_PROGRAM_ID)
+        .accounts({
+          programConfig: programConfigPda,
+          program: program.programId,
+          programData: programDataPda,
+          admin: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    it("timelocks verifier changes and keeps the current verifier meanwhile", async () => {
+      const newVerifier = Keypair.generate().publicKey;
+
+      await program.methods
+        .setVerifier(newVerifier)
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+
+      const config = await program.account.programConfig.fetch(programConfigPda);
+      expect(config.pendingVerifier.toBase58()).to.equal(newVerifier.toBase58());
+      expect(config.verifierProgram.toBase58()).to.not.equal(newVerifier.toBase58());
+
+      try {
+        await program.methods
+          .applyVerifier()
+          .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+          .rpc();
+        expect.fail("Should have failed with VerifierChangeTimelocked");
+      } catch (error: any) {
+        expect(error.message).to.include("VerifierChangeTimelocked");
+      }
+    });
+
+    it("rejects verifier changes from anyone but the admin", async () => {
+      const outsider = Keypair.generate();
+      try {
+        await program.methods
+          .setVerifier(Keypair.generate().publicKey)
+          .accounts({ programConfig: programConfigPda, admin: outsider.publicKey })
+          .signers([outsider])
+          .rpc();
+        expect.fail("Should have failed with NotAdmin");
+      } catch (error: any) {
+        expect(error.message).to.include("NotAdmin");
+      }
+    });
+  });
+
+  describe("delegate reserve", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .setMinimumVaultBalanceForDelegation(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spend = (amount: number) =>
+      program.methods
+        .spend(new anchor.BN(amount))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination: Keypair.generate().publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+
+    it("allows spends that leave the reserve in place", async () => {
+      await spend(0.4 * LAMPORTS_PER_SOL);
+      expect(await provider.connection.getBalance(vaultPda)).to.be.at.least(0.5 * LAMPORTS_PER_SOL);
+    });
+
+    it("rejects spends that would dip into the reserve", async () => {
+      try {
+        await spend(0.6 * LAMPORTS_PER_SOL);
+        expect.fail("Should have failed with WouldViolateDelegateReserve");
+      } catch (error: any) {
+        expect(error.message).to.include("WouldViolateDelegateReserve");
+      }
+    });
+  });
+
+  describe("post-spend hook", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    const hookProgram = new PublicKey("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spend = () =>
+      program.methods
+        .spend(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination: Keypair.generate().publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+
+    it("only lets the owner set the hook", async () => {
+      try {
+        await program.methods
+          .setSpendHook(hookProgram)
+          .accounts({ cloakedAgentState: agentStatePda, owner: delegate.publicKey })
+          .signers([delegate])
+          .rpc();
+        expect.fail("Should have failed with NotOwner");
+      } catch (error: any) {
+        expect(error.message).to.include("NotOwner");
+      }
+    });
+
+    it("requires the hook program in remaining accounts once set", async () => {
+      await program.methods
+        .setSpendHook(hookProgram)
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.postSpendHook.toBase58()).to.equal(hookProgram.toBase58());
+
+      try {
+        await spend();
+        expect.fail("Should have failed with InvalidSpendHook");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidSpendHook");
+      }
+
+      await program.methods
+        .clearSpendHook()
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+      await spend();
+    });
+  });
+
+  describe("verifier registry", () => {
+    const [programConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("program_config")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    const [verifierRegistryPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("verifier_registry")],
+      program.programId
+    );
+    const ZK_VERIFIER_PROGRAM_ID = new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F");
+    const admin = () => ({
+      verifierRegistry: verifierRegistryPda,
+      programConfig: programConfigPda,
+      admin: provider.wallet.publicKey,
+    });
+
+    before(async () => {
+      // Singletons: created once per validator by the upgrade authority (provider wallet)
+      if (!(await provider.connection.getAccountInfo(programConfigPda))) {
+        await program.methods
+          .initializeProgramConfig(ZK_VERIFIER_PROGRAM_ID)
+          .accounts({
+            programConfig: programConfigPda,
+            program: program.programId,
+            programData: programDataPda,
+            admin: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+      if (!(await provider.connection.getAccountInfo(verifierRegistryPda))) {
+        await program.methods
+          .initializeVerifierRegistry()
+          .accounts({ ...admin(), systemProgram: SystemProgram.programId })
+          .rpc();
+      }
+    });
+
+    it("adds one entry per witness version", async () => {
+      await program.methods
+        .addVerifierEntry(ZK_VERIFIER_PROGRAM_ID, 5, 388, 1)
+        .accounts(admin())
+        .rpc();
+
+      const registry = await program.account.verifierRegistry.fetch(verifierRegistryPda);
+      const entry = registry.entries.find((e: any) => e.witnessVersion === 5);
+      expect(entry.proofSize).to.equal(388);
+      expect(entry.removableAt.toNumber()).to.equal(0);
+
+      try {
+        await program.methods
+          .addVerifierEntry(ZK_VERIFIER_PROGRAM_ID, 5, 324, 1)
+          .accounts(admin())
+          .rpc();
+        expect.fail("Should have failed with VerifierVersionExists");
+      } catch (error: any) {
+        expect(error.message).to.include("VerifierVersionExists");
+      }
+    });
+
+    it("timelocks entry removal", async () => {
+      await program.methods
+        .scheduleVerifierRemoval(5)
+        .accounts(admin())
+        .rpc();
+
+      const registry = await program.account.verifierRegistry.fetch(verifierRegistryPda);
+      const entry = registry.entries.find((e: any) => e.witnessVersion === 5);
+      expect(entry.removableAt.toNumber()).to.be.greaterThan(Math.floor(Date.now() / 1000));
+
+      try {
+        await program.methods
+          .removeVerifierEntry(5)
+          .accounts(admin())
+          .rpc();
+        expect.fail("Should have failed with VerifierChangeTimelocked");
+      } catch (error: any) {
+        expect(error.message).to.include("VerifierChangeTimelocked");
+      }
+    });
+
+    it("rejects registry changes from anyone but the admin", async () => {
+      const outsider = Keypair.generate();
+      try {
+        await program.methods
+          .addVerifierEntry(Keypair.generate().publicKey, 6, 324, 1)
+          .accounts({ ...admin(), admin: outsider.publicKey })
+          .signers([outsider])
+          .rpc();
+        expect.fail("Should have failed with NotAdmin");
+      } catch (error: any) {
+        expect(error.message).to.include("NotAdmin");
+      }
+    });
+  });
+
+  describe("proof buffer", () => {
+    let submitter: Keypair;
+    let agentStatePda: PublicKey;
+    let proofBufferPda: PublicKey;
+
+    beforeEach(async () => {
+      submitter = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(submitter.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), submitter.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      [proofBufferPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("proof_buffer"), submitter.publicKey.toBuffer(), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgentPrivate(new anchor.BN(0), Array(32).fill(7), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, false, { zk: {} }, 1)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: Keypair.generate().publicKey,
+          payer: submitter.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([submitter])
+        .rpc();
+
+      // freeze_private: 324-byte proof + 140-byte v4 witness
+      await program.methods
+        .initProofBuffer(1, 324, 140)
+        .accounts({
+          proofBuffer: proofBufferPda,
+          cloakedAgentState: agentStatePda,
+          submitter: submitter.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([submitter])
+        .rpc();
+    });
+
+    it("stages chunks for the recorded agent and action", async () => {
+      await program.methods
+        .writeProofChunk(0, Buffer.alloc(300, 1))
+        .accounts({ proofBuffer: proofBufferPda, submitter: submitter.publicKey })
+        .signers([submitter])
+        .rpc();
+      await program.methods
+        .writeProofChunk(300, Buffer.alloc(164, 2))
+        .accounts({ proofBuffer: proofBufferPda, submitter: submitter.publicKey })
+        .signers([submitter])
+        .rpc();
+
+      const buffer = await program.account.proofBuffer.fetch(proofBufferPda);
+      expect(buffer.agent.toBase58()).to.equal(agentStatePda.toBase58());
+      expect(buffer.actionId).to.equal(1);
+      expect(buffer.proofLen).to.equal(324);
+      expect(buffer.data.length).to.equal(464);
+      expect(buffer.data[299]).to.equal(1);
+      expect(buffer.data[463]).to.equal(2);
+    });
+
+    it("rejects writes past the end", async () => {
+      try {
+        await program.methods
+          .writeProofChunk(400, Buffer.alloc(100))
+          .accounts({ proofBuffer: proofBufferPda, submitter: submitter.publicKey })
+          .signers([submitter])
+          .rpc();
+        expect.fail("Should have failed with ProofBufferOverflow");
+      } catch (error: any) {
+        expect(error.message).to.include("ProofBufferOverflow");
+      }
+    });
+
+    it("returns the rent to the submitter on close", async () => {
+      const before = await provider.connection.getBalance(submitter.publicKey);
+      await program.methods
+        .closeProofBuffer()
+        .accounts({ proofBuffer: proofBufferPda, submitter: submitter.publicKey })
+        .signers([submitter])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(proofBufferPda)).to.be.null;
+      expect(await provider.connection.getBalance(submitter.publicKey)).to.be.greaterThan(before);
+    });
+  });
+
+  describe("agent notes", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const notes = (text: string) => Array.from(Buffer.concat([Buffer.from(text), Buffer.alloc(32)]).subarray(0, 32));
+
+    it("keeps owner and delegate notes independent", async () => {
+      await program.methods
+        .setOwnerNotes(notes("invoice bot"))
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .setDelegateNotes(notes("worker-7"))
+        .accounts({ cloakedAgentState: agentStatePda, delegate: delegate.publicKey })
+        .signers([delegate])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.ownerNotes).to.deep.equal(notes("invoice bot"));
+      expect(state.delegateNotes).to.deep.equal(notes("worker-7"));
+    });
+
+    it("rejects notes from the other role", async () => {
+      try {
+        await program.methods
+          .setOwnerNotes(notes("hijack"))
+          .accounts({ cloakedAgentState: agentStatePda, owner: delegate.publicKey })
+          .signers([delegate])
+          .rpc();
+        expect.fail("Should have failed with NotOwner");
+      } catch (error: any) {
+        expect(error.message).to.include("NotOwner");
+      }
+
+      try {
+        await program.methods
+          .setDelegateNotes(notes("hijack"))
+          .accounts({ cloakedAgentState: agentStatePda, delegate: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with has_one constraint");
+      } catch (error: any) {
+        expect(error.message).to.include("ConstraintHasOne");
+      }
+    });
+  });
+
+  describe("commitment rotation", () => {
+    const commitment = Array(32).fill(7);
+    let payer: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      payer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(payer.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), payer.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgentPrivate(new anchor.BN(0), commitment, new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, false, { zk: {} }, 1)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: Keypair.generate().publicKey,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+    });
+
+    const rotate = (newCommitment: number[]) =>
+      program.methods
+        .rotateCommitmentPrivate(Buffer.alloc(324), Buffer.alloc(140), 4, newCommitment)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          feeRecipient: payer.publicKey,
+          zkVerifier: new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F"),
+          nullifier: nullifierPda(Buffer.alloc(324)),
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+    it("rejects an all-zero commitment", async () => {
+      try {
+        await rotate(Array(32).fill(0));
+        expect.fail("Should have failed with InvalidCommitment");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidCommitment");
+      }
+    });
+
+    it("rejects rotating to the current commitment", async () => {
+      try {
+        await rotate(commitment);
+        expect.fail("Should have failed with CommitmentUnchanged");
+      } catch (error: any) {
+        expect(error.message).to.include("CommitmentUnchanged");
+      }
+    });
+  });
+
+  describe("sliding window limit", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .setSlidingWindow(new anchor.BN(3600), new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+    });
+
+    const spend = (amount: number) =>
+      program.methods
+        .spend(new anchor.BN(amount))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination: Keypair.generate().publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+
+    it("sums recent spends against the limit", async () => {
+      await spend(0.3 * LAMPORTS_PER_SOL);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.recentSpendAmounts[0].toNumber()).to.equal(0.3 * LAMPORTS_PER_SOL);
+      expect(state.ringHead).to.equal(1);
+
+      try {
+        await spend(0.3 * LAMPORTS_PER_SOL);
+        expect.fail("Should have failed with ExceedsSlidingWindowLimit");
+      } catch (error: any) {
+        expect(error.message).to.include("ExceedsSlidingWindowLimit");
+      }
+      await spend(0.2 * LAMPORTS_PER_SOL);
+    });
+
+    it("rejects a limit without a window length", async () => {
+      try {
+        await program.methods
+          .setSlidingWindow(new anchor.BN(0), new anchor.BN(1))
+          .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+          .signers([owner])
+          .rpc();
+        expect.fail("Should have failed with InvalidSpendingWindow");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidSpendingWindow");
+      }
+    });
+  });
+
+  describe("recovery commitment", () => {
+    const ownerCommitment = Array(32).fill(7);
+    const recoveryCommitment = Array(32).fill(9);
+    let payer: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    const createPrivate = (nonce: number, recovery: number[] | null) => {
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), payer.publicKey.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      return program.methods
+        .createCloakedAgentPrivate(new anchor.BN(nonce), ownerCommitment, new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, recovery, false, { zk: {} }, 1)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: Keypair.generate().publicKey,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+    };
+
+    const recoveryAccounts = () => ({
+      cloakedAgentState: agentStatePda,
+      vault: vaultPda,
+      feeRecipient: payer.publicKey,
+      zkVerifier: new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F"),
+      nullifier: nullifierPda(Buffer.alloc(324)),
+      systemProgram: SystemProgram.programId,
+    });
+
+    beforeEach(async () => {
+      payer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(payer.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+    });
+
+    it("stores the recovery commitment at creation", async () => {
+      await createPrivate(0, recoveryCommitment);
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.recoveryCommitment).to.deep.equal(recoveryCommitment);
+      expect(state.recoveryInitiatedAt.toNumber()).to.equal(0);
+    });
+
+    it("rejects a recovery commitment equal to the owner commitment", async () => {
+      try {
+        await createPrivate(0, ownerCommitment);
+        expect.fail("Should have failed with InvalidCommitment");
+      } catch (error: any) {
+        expect(error.message).to.include("InvalidCommitment");
+      }
+    });
+
+    it("cannot initiate recovery without a recovery commitment", async () => {
+      await createPrivate(0, null);
+      try {
+        await program.methods
+          .initiateRecoveryPrivate(Buffer.alloc(324), Buffer.alloc(140), 4)
+          .accounts(recoveryAccounts())
+          .rpc();
+        expect.fail("Should have failed with NoRecoveryCommitment");
+      } catch (error: any) {
+        expect(error.message).to.include("NoRecoveryCommitment");
+      }
+    });
+
+    it("cannot recover or cancel before recovery is initiated", async () => {
+      await createPrivate(0, recoveryCommitment);
+      try {
+        await program.methods
+          .recoverPrivate(Buffer.alloc(324), Buffer.alloc(140), 4, Array(32).fill(5))
+          .accounts(recoveryAccounts())
+          .rpc();
+        expect.fail("Should have failed with RecoveryNotInitiated");
+      } catch (error: any) {
+        expect(error.message).to.include("RecoveryNotInitiated");
+      }
+      try {
+        await program.methods
+          .cancelRecoveryPrivate(Buffer.alloc(324), Buffer.alloc(140), 4)
+          .accounts(recoveryAccounts())
+          .rpc();
+        expect.fail("Should have failed with RecoveryNotInitiated");
+      } catch (error: any) {
+        expect(error.message).to.include("RecoveryNotInitiated");
+      }
+    });
+  });
+
+  describe("update delegate", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const updateDelegate = (newDelegate: PublicKey) =>
+      program.methods
+        .updateDelegate(newDelegate)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          owner: owner.publicKey,
+          newDelegate,
+        })
+        .signers([owner])
+        .rpc();
+
+    const spend = (signer: Keypair) =>
+      program.methods
+        .spend(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: signer.publicKey,
+          feePayer: signer.publicKey,
+          destination: Keypair.generate().publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([signer])
+        .rpc();
+
+    it("hands spending to the new key and locks out the old one", async () => {
+      const replacement = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(replacement.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      await updateDelegate(replacement.publicKey);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.delegate.toBase58()).to.equal(replacement.publicKey.toBase58());
+
+      await spend(replacement);
+      try {
+        await spend(delegate);
+        expect.fail("Old delegate should no longer be able to spend");
+      } catch (error: any) {
+        expect(error.message).to.not.include("Old delegate should");
+      }
+    });
+
+    it("rejects the owner and executable programs as delegate", async () => {
+      try {
+        await updateDelegate(owner.publicKey);
+        expect.fail("Should have failed with DelegateIsOwner");
+      } catch (error: any) {
+        expect(error.message).to.include("DelegateIsOwner");
+      }
+
+      try {
+        await updateDelegate(SystemProgram.programId);
+        expect.fail("Should have failed with DelegateIsProgram");
+      } catch (error: any) {
+        expect(error.message).to.include("DelegateIsProgram");
+      }
+    });
+
+    it("only lets the owner replace the delegate", async () => {
+      try {
+        await program.methods
+          .updateDelegate(delegate.publicKey)
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            owner: delegate.publicKey,
+            newDelegate: delegate.publicKey,
+          })
+          .signers([delegate])
+          .rpc();
+        expect.fail("Should have failed with NotOwner");
+      } catch (error: any) {
+        expect(error.message).to.include("NotOwner");
+      }
+    });
+  });
+
+  describe("nullifiers", () => {
+    it("derives one nullifier address per proof", () => {
+      const proof = Buffer.alloc(324, 1);
+      expect(nullifierPda(proof).toBase58()).to.equal(nullifierPda(Buffer.from(proof)).toBase58());
+      expect(nullifierPda(proof).toBase58()).to.not.equal(nullifierPda(Buffer.alloc(324, 2)).toBase58());
+    });
+
+    it("cannot close a nullifier that was never created", async () => {
+      try {
+        await program.methods
+          .closeNullifier()
+          .accounts({
+            nullifier: nullifierPda(Buffer.alloc(324, 3)),
+            vault: Keypair.generate().publicKey,
+          })
+          .rpc();
+        expect.fail("Should have failed with AccountNotInitialized");
+      } catch (error: any) {
+        expect(error.message).to.include("AccountNotInitialized");
+      }
+    });
+  });
+
+  describe("relayer registry", () => {
+    const [programConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("program_config")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    const [relayerRegistryPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("relayer_registry")],
+      program.programId
+    );
+    const admin = () => ({
+      relayerRegistry: relayerRegistryPda,
+      programConfig: programConfigPda,
+      admin: provider.wallet.publicKey,
+    });
+    const relayer = Keypair.generate().publicKey;
+
+    before(async () => {
+      // Singletons: created once per validator by the upgrade authority (provider wallet)
+      if (!(await provider.connection.getAccountInfo(programConfigPda))) {
+        await program.methods
+          .initializeProgramConfig(new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F"))
+          .accounts({
+            programConfig: programConfigPda,
+            program: program.programId,
+            programData: programDataPda,
+            admin: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+      if (!(await provider.connection.getAccountInfo(relayerRegistryPda))) {
+        await program.methods
+          .initializeRelayerRegistry()
+          .accounts({ ...admin(), systemProgram: SystemProgram.programId })
+          .rpc();
+      }
+    });
+
+    it("lets the admin approve and revoke relayers", async () => {
+      await program.methods.addRelayer(relayer).accounts(admin()).rpc();
+      let registry = await program.account.relayerRegistry.fetch(relayerRegistryPda);
+      expect(registry.relayers.map((r: PublicKey) => r.toBase58())).to.include(relayer.toBase58());
+
+      try {
+        await program.methods.addRelayer(relayer).accounts(admin()).rpc();
+        expect.fail("Should have failed with RelayerAlreadyRegistered");
+      } catch (error: any) {
+        expect(error.message).to.include("RelayerAlreadyRegistered");
+      }
+
+      await program.methods.removeRelayer(relayer).accounts(admin()).rpc();
+      registry = await program.account.relayerRegistry.fetch(relayerRegistryPda);
+      expect(registry.relayers.map((r: PublicKey) => r.toBase58())).to.not.include(relayer.toBase58());
+    });
+
+    it("rejects a non-admin signer", async () => {
+      const outsider = Keypair.generate();
+      try {
+        await program.methods
+          .addRelayer(outsider.publicKey)
+          .accounts({ ...admin(), admin: outsider.publicKey })
+          .signers([outsider])
+          .rpc();
+        expect.fail("Should have failed with NotAdmin");
+      } catch (error: any) {
+        expect(error.message).to.include("NotAdmin");
+      }
+    });
+
+    it("records the opt-in on private agents", async () => {
+      const payer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(payer.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), payer.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgentPrivate(new anchor.BN(0), Array(32).fill(7), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, true, { zk: {} }, 1)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: Keypair.generate().publicKey,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.requireRegisteredRelayer).to.equal(true);
+    });
+  });
+
+  describe("auto close on zero", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    const createAgent = async (autoClose: boolean) => {
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, autoClose)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    };
+
+    const spend = (amount: number, destination: PublicKey) =>
+      program.methods
+        .spend(new anchor.BN(amount))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+    });
+
+    it("closes the agent when a spend empties the vault", async () => {
+      await createAgent(true);
+      const stateRent = await provider.connection.getBalance(agentStatePda);
+      const destination = Keypair.generate().publicKey;
+
+      await spend(1 * LAMPORTS_PER_SOL, destination);
+
+      expect(await provider.connection.getAccountInfo(agentStatePda)).to.be.null;
+      expect(await provider.connection.getBalance(destination)).to.equal(1 * LAMPORTS_PER_SOL + stateRent);
+    });
+
+    it("stays open after a partial spend", async () => {
+      await createAgent(true);
+      await spend(0.5 * LAMPORTS_PER_SOL, Keypair.generate().publicKey);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.autoCloseOnZero).to.equal(true);
+    });
+
+    it("leaves agents without the flag open at zero balance", async () => {
+      await createAgent(false);
+      await spend(1 * LAMPORTS_PER_SOL, Keypair.generate().publicKey);
+
+      expect(await provider.connection.getAccountInfo(agentStatePda)).to.not.be.null;
+    });
+
+    it("stays open while close would orphan whitelist entries", async () => {
+      await createAgent(true);
+      const destination = Keypair.generate().publicKey;
+      const [whitelistPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("whitelist"), agentStatePda.toBuffer(), destination.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .addWhitelistEntry(destination)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          whitelistEntry: whitelistPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .spend(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination,
+          whitelistEntry: whitelistPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.whitelistEntryCount).to.equal(1);
+    });
+
+    it("drops the auto-closed agent from the delegate registry", async () => {
+      const [registryPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("delegate_registry"), delegate.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .createDelegateRegistry()
+        .accounts({
+          delegateRegistry: registryPda,
+          delegate: delegate.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+      await createAgent(true);
+      await program.methods
+        .addToDelegateRegistry(agentStatePda)
+        .accounts({
+          delegateRegistry: registryPda,
+          cloakedAgentState: agentStatePda,
+          delegate: delegate.publicKey,
+        })
+        .signers([delegate])
+        .rpc();
+
+      await program.methods
+        .spend(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination: Keypair.generate().publicKey,
+          delegateRegistry: registryPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(agentStatePda)).to.be.null;
+      const registry = await program.account.delegateRegistry.fetch(registryPda);
+      expect(registry.agents).to.be.empty;
+    });
+  });
+
+  describe("configurable fees", () => {
+    const [programConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("program_config")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    const admin = () => ({
+      programConfig: programConfigPda,
+      admin: provider.wallet.publicKey,
+    });
+    const setFees = (privateFee: number, spendFee: number) =>
+      program.methods
+        .setFees(new anchor.BN(privateFee), new anchor.BN(spendFee))
+        .accounts(admin())
+        .rpc();
+
+    before(async () => {
+      // Singleton: created once per validator by the upgrade authority (provider wallet)
+      if (!(await provider.connection.getAccountInfo(programConfigPda))) {
+        await program.methods
+          .initializeProgramConfig(new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F"))
+          .accounts({
+            programConfig: programConfigPda,
+            program: program.programId,
+            programData: programDataPda,
+            admin: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+    });
+
+    after(async () => {
+      await setFees(50_000, 10_000);
+    });
+
+    it("rejects fees above the ceiling", async () => {
+      try {
+        await setFees(500_001, 10_000);
+        expect.fail("Should have failed with FeeTooHigh");
+      } catch (error: any) {
+        expect(error.message).to.include("FeeTooHigh");
+      }
+      try {
+        await setFees(50_000, 100_001);
+        expect.fail("Should have failed with FeeTooHigh");
+      } catch (error: any) {
+        expect(error.message).to.include("FeeTooHigh");
+      }
+    });
+
+    it("reimburses spend fee payers with the configured amount", async () => {
+      await setFees(60_000, 20_000);
+      const config = await program.account.programConfig.fetch(programConfigPda);
+      expect(config.privateOperationFee.toNumber()).to.equal(60_000);
+      expect(config.spendFeeReimbursement.toNumber()).to.equal(20_000);
+
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+      const feePayer = Keypair.generate();
+      for (const kp of [owner, delegate, feePayer]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const vaultBefore = await provider.connection.getBalance(vaultPda);
+      await program.methods
+        .spend(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: feePayer.publicKey,
+          destination: Keypair.generate().publicKey,
+          programConfig: programConfigPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate, feePayer])
+        .rpc();
+
+      expect(vaultBefore - (await provider.connection.getBalance(vaultPda))).to.equal(0.1 * LAMPORTS_PER_SOL + 20_000);
+
+      // Every delegate spend path uses the configured amount, not SPEND_FEE_REIMBURSEMENT
+      const vaultBefore2fa = await provider.connection.getBalance(vaultPda);
+      await program.methods
+        .spendWith2fa(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          secondFactor: delegate.publicKey,
+          feePayer: feePayer.publicKey,
+          destination: Keypair.generate().publicKey,
+          programConfig: programConfigPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([delegate, feePayer])
+        .rpc();
+
+      expect(vaultBefore2fa - (await provider.connection.getBalance(vaultPda))).to.equal(0.1 * LAMPORTS_PER_SOL + 20_000);
+    });
+
+    it("rejects a non-admin signer", async () => {
+      const outsider = Keypair.generate();
+      try {
+        await program.methods
+          .setFees(new anchor.BN(0), new anchor.BN(0))
+          .accounts({ programConfig: programConfigPda, admin: outsider.publicKey })
+          .signers([outsider])
+          .rpc();
+        expect.fail("Should have failed with NotAdmin");
+      } catch (error: any) {
+        expect(error.message).to.include("NotAdmin");
+      }
+    });
+  });
+
+  describe("program whitelist", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    let programWlPda: PublicKey;
+
+    const spend = (destination: PublicKey, remaining: PublicKey[]) =>
+      program.methods
+        .spend(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(remaining.map((pubkey) => ({ pubkey, isSigner: false, isWritable: false })))
+        .signers([delegate])
+        .rpc();
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      [programWlPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("program_wl"), agentStatePda.toBuffer(), SystemProgram.programId.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .addProgramWhitelistEntry(SystemProgram.programId)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          programWhitelistEntry: programWlPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("allows destinations owned by a whitelisted program", async () => {
+      const destination = Keypair.generate().publicKey;
+      await spend(destination, [programWlPda]);
+
+      expect(await provider.connection.getBalance(destination)).to.equal(0.1 * LAMPORTS_PER_SOL);
+    });
+
+    it("rejects spends without the program entry", async () => {
+      try {
+        await spend(Keypair.generate().publicKey, []);
+        expect.fail("Should have failed with DestinationNotWhitelisted");
+      } catch (error: any) {
+        expect(error.message).to.include("DestinationNotWhitelisted");
+      }
+    });
+
+    it("rejects destinations owned by another program", async () => {
+      try {
+        await spend(agentStatePda, [programWlPda]);
+        expect.fail("Should have failed with DestinationNotWhitelisted");
+      } catch (error: any) {
+        expect(error.message).to.include("DestinationNotWhitelisted");
+      }
+    });
+
+    it("close fails until program entries are removed", async () => {
+      const close = () =>
+        program.methods
+          .closeCloakedAgent()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: owner.publicKey,
+            vaultRecipient: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+
+      try {
+        await close();
+        expect.fail("Should have failed with HasOrphanedPDAs");
+      } catch (error: any) {
+        expect(error.message).to.include("HasOrphanedPDAs");
+      }
+
+      await program.methods
+        .removeProgramWhitelistEntry(SystemProgram.programId)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          programWhitelistEntry: programWlPda,
+          owner: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+      await close();
+
+      expect(await provider.connection.getAccountInfo(agentStatePda)).to.be.null;
+    });
+  });
+
+  describe("stealth owner", () => {
+    let payer: Keypair;
+    let stealthKey: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+
+    const sha256 = (data: Buffer) => createHash("sha256").update(data).digest();
+
+    // STEALTH_DOMAIN || agent || action_id || proof_nonce || action_data || next_commitment
+    const freezeWithStealthKey = async (key: Keypair, nextCommitment: Buffer) => {
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      const message = Buffer.concat([
+        Buffer.from("cloaked:stealth"),
+        agentStatePda.toBuffer(),
+        Buffer.from([1]), // ACTION_FREEZE
+        state.proofNonce.toArrayLike(Buffer, "le", 8),
+        Buffer.alloc(32),
+        nextCommitment,
+      ]);
+      const verifyIx = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: key.secretKey,
+        message,
+      });
+      const proof = Buffer.concat([key.publicKey.toBuffer(), nextCommitment]);
+
+      return program.methods
+        .freezePrivate(proof, Buffer.alloc(0), 4, Array(32).fill(0))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          feeRecipient: payer.publicKey,
+          zkVerifier: SystemProgram.programId,
+          nullifier: nullifierPda(proof),
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          systemProgram: SystemProgram.programId,
+        })
+        .preInstructions([verifyIx])
+        .signers([payer])
+        .rpc();
+    };
+
+    beforeEach(async () => {
+      payer = Keypair.generate();
+      stealthKey = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(payer.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), payer.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgentPrivate(new anchor.BN(0), Array.from(sha256(stealthKey.publicKey.toBuffer())), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, false, { stealthEd25519: {} }, 1)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: Keypair.generate().publicKey,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+    });
+
+    it("accepts a stealth key signature and rotates the commitment", async () => {
+      const nextKey = Keypair.generate();
+      const nextCommitment = sha256(nextKey.publicKey.toBuffer());
+      await freezeWithStealthKey(stealthKey, nextCommitment);
+
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.frozen).to.equal(true);
+      expect(state.commitmentScheme).to.deep.equal({ stealthEd25519: {} });
+      expect(Buffer.from(state.ownerCommitment)).to.deep.equal(nextCommitment);
+    });
+
+    it("waives the operation fee when the owner relays it", async () => {
+      // payer is both fee_recipient and signer, so only the nullifier rent leaves the vault
+      const before = await provider.connection.getBalance(vaultPda);
+      await freezeWithStealthKey(stealthKey, sha256(Keypair.generate().publicKey.toBuffer()));
+
+      const nullifierRent = await provider.connection.getMinimumBalanceForRentExemption(81);
+      expect(await provider.connection.getBalance(vaultPda)).to.equal(before - nullifierRent);
+    });
+
+    it("rejects a spent stealth key", async () => {
+      await freezeWithStealthKey(stealthKey, sha256(Keypair.generate().publicKey.toBuffer()));
+
+      try {
+        await freezeWithStealthKey(stealthKey, sha256(Keypair.generate().publicKey.toBuffer()));
+        expect.fail("Should have failed with CommitmentMismatch");
+      } catch (error: any) {
+        expect(error.message).to.include("CommitmentMismatch");
+      }
+    });
+  });
+
+  describe("destination blacklist", () => {
+    let owner: Keypair;
+    let delegate: Keypair;
+    let agentStatePda: PublicKey;
+    let vaultPda: PublicKey;
+    let destination: PublicKey;
+    let blacklistPda: PublicKey;
+
+    const spend = (remaining: PublicKey[]) =>
+      program.methods
+        .spend(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: delegate.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(remaining.map((pubkey) => ({ pubkey, isSigner: false, isWritable: false })))
+        .signers([delegate])
+        .rpc();
+
+    beforeEach(async () => {
+      owner = Keypair.generate();
+      delegate = Keypair.generate();
+      destination = Keypair.generate().publicKey;
+
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      [blacklistPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("blacklist"), agentStatePda.toBuffer(), destination.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .addBlacklistEntry(destination)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          blacklistEntry: blacklistPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    it("rejects spends when the entry is passed", async () => {
+      try {
+        await spend([blacklistPda]);
+        expect.fail("Should have failed with DestinationBlacklisted");
+      } catch (error: any) {
+        expect(error.message).to.include("DestinationBlacklisted");
+      }
+    });
+
+    it("only applies when the entry is passed", async () => {
+      await spend([]);
+
+      expect(await provider.connection.getBalance(destination)).to.equal(0.1 * LAMPORTS_PER_SOL);
+    });
+
+    it("tracks blacklist entries on the agent", async () => {
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.blacklistEntryCount).to.equal(1);
+
+      await program.methods
+        .removeBlacklistEntry(destination)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          blacklistEntry: blacklistPda,
+          owner: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+
+      const after = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(after.blacklistEntryCount).to.equal(0);
+      expect(await provider.connection.getAccountInfo(blacklistPda)).to.be.null;
+    });
+  });
+
+  describe("commitment version", () => {
+    it("records the commitment version and rejects unknown ones", async () => {
+      const payer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(payer.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const create = (nonce: number, commitmentVersion: number) => {
+        const [agentStatePda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("cloaked_agent_state"), payer.publicKey.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, "le", 8)],
+          program.programId
+        );
+        const [vaultPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("vault"), agentStatePda.toBuffer()],
+          program.programId
+        );
+        return program.methods
+          .createCloakedAgentPrivate(new anchor.BN(nonce), Array(32).fill(7), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, false, { zk: {} }, commitmentVersion)
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: Keypair.generate().publicKey,
+            payer: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([payer])
+          .rpc()
+          .then(() => agentStatePda);
+      };
+
+      const agentStatePda = await create(0, 1);
+      const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+      expect(state.commitmentVersion).to.equal(1);
+      expect(state.proofFormatVersion).to.equal(1);
+
+      try {
+        await create(1, 9);
+        expect.fail("Should have failed with UnknownCommitmentVersion");
+      } catch (error: any) {
+        expect(error.message).to.include("UnknownCommitmentVersion");
+      }
+    });
+  });
+
+  describe("withdraw all", () => {
+    it("drains the vault down to its rent reserve and keeps the agent open", async () => {
+      const owner = Keypair.generate();
+      const destination = Keypair.generate().publicKey;
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const withdrawAll = () =>
+        program.methods
+          .withdrawAll()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            owner: owner.publicKey,
+            destination,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+
+      await withdrawAll();
+
+      const reserve = await provider.connection.getMinimumBalanceForRentExemption(0);
+      expect(await provider.connection.getBalance(vaultPda)).to.equal(reserve);
+      expect(await provider.connection.getBalance(destination)).to.equal(1 * LAMPORTS_PER_SOL - reserve);
+      expect(await provider.connection.getAccountInfo(agentStatePda)).to.not.be.null;
+
+      try {
+        await withdrawAll();
+        expect.fail("Should have failed with InsufficientBalance");
+      } catch (error: any) {
+        expect(error.message).to.include("InsufficientBalance");
+      }
+    });
+  });
+
+  describe("shielded notes", () => {
+    const [programConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("program_config")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    const [noteSetPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("note_set"), Buffer.from([0, 0, 0, 0])],
+      program.programId
+    );
+    const noteVerifier = Keypair.generate().publicKey;
+
+    before(async () => {
+      if (!(await provider.connection.getAccountInfo(programConfigPda))) {
+        await program.methods
+          .initializeProgramConfig(new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F"))
+          .accounts({
+            programConfig: programConfigPda,
+            program: program.programId,
+            programData: programDataPda,
+            admin: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+      if (!(await provider.connection.getAccountInfo(noteSetPda))) {
+        await program.methods
+          .initializeNoteSet(0)
+          .accounts({
+            noteSet: noteSetPda,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+      await program.methods
+        .setNoteVerifier(noteVerifier)
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+    });
+
+    after(async () => {
+      await program.methods
+        .setNoteVerifier(PublicKey.default)
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+    });
+
+    it("locks the denomination and advances the root", async () => {
+      const before = await program.account.noteSet.fetch(noteSetPda);
+      const balanceBefore = await provider.connection.getBalance(noteSetPda);
+
+      await program.methods
+        .depositNote(Array(32).fill(5))
+        .accounts({
+          noteSet: noteSetPda,
+          depositor: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const after = await program.account.noteSet.fetch(noteSetPda);
+      expect(after.notes.length).to.equal(before.notes.length + 1);
+      expect(after.currentRootIndex).to.not.equal(before.currentRootIndex);
+      expect(await provider.connection.getBalance(noteSetPda)).to.equal(balanceBefore + 0.1 * LAMPORTS_PER_SOL);
+    });
+
+    it("rejects claims against an unknown root", async () => {
+      const payer = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(payer.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), payer.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .createCloakedAgentPrivate(new anchor.BN(0), Array(32).fill(7), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, false, { zk: {} }, 1)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: Keypair.generate().publicKey,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+
+      const noteNullifier = Buffer.alloc(32, 9);
+      const [noteNullifierPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("note_nullifier"), noteNullifier],
+        program.programId
+      );
+      try {
+        await program.methods
+          .claimNotePrivate(Buffer.alloc(324), Buffer.alloc(140), Array(32).fill(1), Array.from(noteNullifier))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            noteSet: noteSetPda,
+            noteNullifierAccount: noteNullifierPda,
+            programConfig: programConfigPda,
+            noteVerifier,
+            payer: payer.publicKey,
+            feeRecipient: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([payer])
+          .rpc();
+        expect.fail("Should have failed with UnknownNoteRoot");
+      } catch (error: any) {
+        expect(error.message).to.include("UnknownNoteRoot");
+      }
+    });
+  });
+
+  describe("relayer stats", () => {
+    const relayer = Keypair.generate();
+    let relayerStatsPda: PublicKey;
+
+    before(async () => {
+      const sig = await provider.connection.requestAirdrop(relayer.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+      [relayerStatsPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("relayer_stats"), relayer.publicKey.toBuffer()],
+        program.programId
+      );
+    });
+
+    it("registers a relayer with empty stats", async () => {
+      await program.methods
+        .registerRelayer()
+        .accounts({
+          relayerStats: relayerStatsPda,
+          relayer: relayer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([relayer])
+        .rpc();
+
+      const stats = await program.account.relayerStats.fetch(relayerStatsPda);
+      expect(stats.relayer.toBase58()).to.equal(relayer.publicKey.toBase58());
+      expect(stats.totalFeesEarned.toNumber()).to.equal(0);
+      expect(stats.totalOpsRelayed.toNumber()).to.equal(0);
+      expect(stats.registeredAt.toNumber()).to.be.greaterThan(0);
+    });
+
+    it("closes the registration and returns the rent", async () => {
+      const balanceBefore = await provider.connection.getBalance(relayer.publicKey);
+      const rent = await provider.connection.getBalance(relayerStatsPda);
+
+      await program.methods
+        .closeRelayerRegistration()
+        .accounts({ relayerStats: relayerStatsPda, relayer: relayer.publicKey })
+        .signers([relayer])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(relayerStatsPda)).to.be.null;
+      // The relayer also paid the transaction fee
+      expect(await provider.connection.getBalance(relayer.publicKey)).to.be.greaterThan(balanceBefore + rent - 10_000);
+    });
+  });
+
+  describe("config admin handoff", () => {
+    const [programConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("program_config")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    const newAdmin = Keypair.generate();
+
+    before(async () => {
+      if (await provider.connection.getAccountInfo(programConfigPda)) return;
+      await program.methods
+        .initializeProgramConfig(new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F"))
+        .accounts({
+          programConfig: programConfigPda,
+          program: program.programId,
+          programData: programDataPda,
+          admin: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    it("keeps the current admin until the proposed one accepts", async () => {
+      await program.methods
+        .proposeAdmin(newAdmin.publicKey)
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+
+      const config = await program.account.programConfig.fetch(programConfigPda);
+      expect(config.pendingAdmin.toBase58()).to.equal(newAdmin.publicKey.toBase58());
+      expect(config.admin.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+    });
+
+    it("rejects acceptance by anyone but the pending admin", async () => {
+      const outsider = Keypair.generate();
+      try {
+        await program.methods
+          .acceptAdmin()
+          .accounts({ programConfig: programConfigPda, newAdmin: outsider.publicKey })
+          .signers([outsider])
+          .rpc();
+        expect.fail("Should have failed with NotPendingAdmin");
+      } catch (error: any) {
+        expect(error.message).to.include("NotPendingAdmin");
+      }
+    });
+
+    it("hands the config over and back", async () => {
+      await program.methods
+        .acceptAdmin()
+        .accounts({ programConfig: programConfigPda, newAdmin: newAdmin.publicKey })
+        .signers([newAdmin])
+        .rpc();
+
+      let config = await program.account.programConfig.fetch(programConfigPda);
+      expect(config.admin.toBase58()).to.equal(newAdmin.publicKey.toBase58());
+      expect(config.pendingAdmin).to.be.null;
+
+      // Restore the provider wallet for the other config tests
+      await program.methods
+        .proposeAdmin(provider.wallet.publicKey)
+        .accounts({ programConfig: programConfigPda, admin: newAdmin.publicKey })
+        .signers([newAdmin])
+        .rpc();
+      await program.methods
+        .acceptAdmin()
+        .accounts({ programConfig: programConfigPda, newAdmin: provider.wallet.publicKey })
+        .rpc();
+
+      config = await program.account.programConfig.fetch(programConfigPda);
+      expect(config.admin.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+    });
+  });
+
+  describe("deposit lock", () => {
+    it("refuses deposits while locked and still allows withdrawals", async () => {
+      const owner = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: Keypair.generate().publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const deposit = () =>
+        program.methods
+          .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            depositor: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc();
+
+      await deposit();
+
+      await program.methods
+        .lockNewDeposits()
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+      expect((await program.account.cloakedAgentState.fetch(agentStatePda)).depositsLocked).to.be.true;
+
+      try {
+        await deposit();
+        expect.fail("Should have failed with DepositsLocked");
+      } catch (error: any) {
+        expect(error.message).to.include("DepositsLocked");
+      }
+
+      const destination = Keypair.generate().publicKey;
+      await program.methods
+        .withdraw(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+      expect(await provider.connection.getBalance(destination)).to.equal(0.1 * LAMPORTS_PER_SOL);
+
+      await program.methods
+        .unlockDeposits()
+        .accounts({ cloakedAgentState: agentStatePda, owner: owner.publicKey })
+        .signers([owner])
+        .rpc();
+      await deposit();
+    });
+  });
+
+  describe("program pause", () => {
+    const [programConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("program_config")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+
+    before(async () => {
+      if (await provider.connection.getAccountInfo(programConfigPda)) return;
+      await program.methods
+        .initializeProgramConfig(new PublicKey("G1fDdFA16d199sf6b8zFhRK1NPZiuhuQCwWWVmGBUG3F"))
+        .accounts({
+          programConfig: programConfigPda,
+          program: program.programId,
+          programData: programDataPda,
+          admin: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    });
+
+    after(async () => {
+      await program.methods
+        .unpauseProgram()
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+    });
+
+    it("blocks spends while paused but lets the owner withdraw", async () => {
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const spend = () =>
+        program.methods
+          .spend(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: delegate.publicKey,
+            feePayer: owner.publicKey,
+            destination: Keypair.generate().publicKey,
+            programConfig: programConfigPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([delegate, owner])
+          .rpc();
+
+      await program.methods
+        .pauseProgram()
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+      expect((await program.account.programConfig.fetch(programConfigPda)).paused).to.be.true;
+
+      try {
+        await spend();
+        expect.fail("Should have failed with ProgramPaused");
+      } catch (error: any) {
+        expect(error.message).to.include("ProgramPaused");
+      }
+
+      const destination = Keypair.generate().publicKey;
+      await program.methods
+        .withdraw(new anchor.BN(0.2 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          destination,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+      expect(await provider.connection.getBalance(destination)).to.equal(0.2 * LAMPORTS_PER_SOL);
+
+      await program.methods
+        .unpauseProgram()
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+      await spend();
+    });
+
+    const pause = () =>
+      program.methods
+        .pauseProgram()
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+
+    const unpause = () =>
+      program.methods
+        .unpauseProgram()
+        .accounts({ programConfig: programConfigPda, admin: provider.wallet.publicKey })
+        .rpc();
+
+    it("rejects a batch of spends in one transaction while paused", async () => {
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(owner.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const destinations = [Keypair.generate(), Keypair.generate(), Keypair.generate()].map((k) => k.publicKey);
+      const spendTo = (destination: PublicKey) =>
+        program.methods.spend(new anchor.BN(0.1 * LAMPORTS_PER_SOL)).accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: delegate.publicKey,
+          feePayer: owner.publicKey,
+          destination,
+          programConfig: programConfigPda,
+          systemProgram: SystemProgram.programId,
+        });
+      const spendBatch = async () =>
+        spendTo(destinations[0])
+          .postInstructions(await Promise.all(destinations.slice(1).map((d) => spendTo(d).instruction())))
+          .signers([delegate, owner])
+          .rpc();
+
+      await pause();
+      try {
+        await spendBatch();
+        expect.fail("Should have failed with ProgramPaused");
+      } catch (error: any) {
+        expect(error.message).to.include("ProgramPaused");
+      }
+      for (const destination of destinations) {
+        expect(await provider.connection.getBalance(destination)).to.equal(0);
+      }
+
+      await unpause();
+      await spendBatch();
+      for (const destination of destinations) {
+        expect(await provider.connection.getBalance(destination)).to.equal(0.1 * LAMPORTS_PER_SOL);
+      }
+    });
+
+    it("blocks fee-charging private operations while paused", async () => {
+      const payer = Keypair.generate();
+      const stealthKey = Keypair.generate();
+      const sig = await provider.connection.requestAirdrop(payer.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(sig);
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), payer.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      const sha256 = (data: Buffer) => createHash("sha256").update(data).digest();
+
+      await program.methods
+        .createCloakedAgentPrivate(new anchor.BN(0), Array.from(sha256(stealthKey.publicKey.toBuffer())), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, false, { stealthEd25519: {} }, 1)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          delegate: Keypair.generate().publicKey,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+      await program.methods
+        .deposit(new anchor.BN(0.5 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+
+      // fee_recipient does not sign, so the private operation fee is charged
+      const relayer = Keypair.generate();
+      const relayerSig = await provider.connection.requestAirdrop(relayer.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(relayerSig);
+
+      const freezePrivate = async () => {
+        const state = await program.account.cloakedAgentState.fetch(agentStatePda);
+        const nextCommitment = sha256(Keypair.generate().publicKey.toBuffer());
+        const verifyIx = Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: stealthKey.secretKey,
+          message: Buffer.concat([
+            Buffer.from("cloaked:stealth"),
+            agentStatePda.toBuffer(),
+            Buffer.from([1]), // ACTION_FREEZE
+            state.proofNonce.toArrayLike(Buffer, "le", 8),
+            Buffer.alloc(32),
+            nextCommitment,
+          ]),
+        });
+        const proof = Buffer.concat([stealthKey.publicKey.toBuffer(), nextCommitment]);
+        return program.methods
+          .freezePrivate(proof, Buffer.alloc(0), 4, Array(32).fill(0))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            feeRecipient: relayer.publicKey,
+            zkVerifier: SystemProgram.programId,
+            nullifier: nullifierPda(proof),
+            programConfig: programConfigPda,
+            instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([verifyIx])
+          .rpc();
+      };
+
+      await pause();
+      const relayerBalanceBefore = await provider.connection.getBalance(relayer.publicKey);
+      try {
+        await freezePrivate();
+        expect.fail("Should have failed with ProgramPaused");
+      } catch (error: any) {
+        expect(error.message).to.include("ProgramPaused");
+      }
+      expect((await program.account.cloakedAgentState.fetch(agentStatePda)).frozen).to.be.false;
+
+      await unpause();
+      await freezePrivate();
+      expect((await program.account.cloakedAgentState.fetch(agentStatePda)).frozen).to.be.true;
+      expect(await provider.connection.getBalance(relayer.publicKey)).to.be.greaterThan(relayerBalanceBefore);
+    });
+
+    it("blocks delegate spend paths while paused", async () => {
+      const owner = Keypair.generate();
+      const delegate = Keypair.generate();
+      const payee = Keypair.generate().publicKey;
+      for (const kp of [owner, delegate]) {
+        const sig = await provider.connection.requestAirdrop(kp.publicKey, 2 * LAMPORTS_PER_SOL);
+        await provider.connection.confirmTransaction(sig);
+      }
+
+      const [agentStatePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cloaked_agent_state"), owner.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), agentStatePda.toBuffer()],
+        program.programId
+      );
+      const [voucherPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("voucher"), agentStatePda.toBuffer(), payee.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createCloakedAgent(new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), new anchor.BN(0), null, null, new anchor.BN(0), null, false)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          owner: owner.publicKey,
+          delegate: delegate.publicKey,
+          payer: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .deposit(new anchor.BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          vault: vaultPda,
+          depositor: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .createVoucher(payee, new anchor.BN(0.1 * LAMPORTS_PER_SOL), new anchor.BN(Math.floor(Date.now() / 1000) + 3600), true)
+        .accounts({
+          cloakedAgentState: agentStatePda,
+          voucher: voucherPda,
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const redeem = () =>
+        program.methods
+          .redeemVoucher()
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            voucher: voucherPda,
+            vault: vaultPda,
+            delegate: delegate.publicKey,
+            feePayer: delegate.publicKey,
+            destination: payee,
+            owner: owner.publicKey,
+            programConfig: programConfigPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([delegate])
+          .rpc();
+      const spendWith2fa = () =>
+        program.methods
+          .spendWith2fa(new anchor.BN(0.1 * LAMPORTS_PER_SOL))
+          .accounts({
+            cloakedAgentState: agentStatePda,
+            vault: vaultPda,
+            delegate: delegate.publicKey,
+            secondFactor: delegate.publicKey,
+            feePayer: delegate.publicKey,
+            destination: payee,
+            programConfig: programConfigPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([delegate])
+          .rpc();
+
+      await pause();
+      for (const attempt of [redeem, spendWith2fa]) {
+        try {
+          await attempt();
+          expect.fail("Should have failed with ProgramPaused");
+        } catch (error: any) {
+          expect(error.message).to.include("ProgramPaused");
+        }
+      }
+      expect(await provider.connection.getBalance(payee)).to.equal(0);
+
+      await unpause();
+      await redeem();
+      await spendWith2fa();
+      expect(await provider.connection.getBalance(payee)).to.equal(0.2 * LAMPORTS_PER_SOL);
+    });
+
+    it("rejects pausing from anyone but the admin", async () => {
+      const outsider = Keypair.generate();
+      try {
+        await program.methods
+          .pauseProgram()
+          .accounts({ programConfig: programConfigPda, admin: outsider.publicKey })
+          .signers([outsider])
+          .rpc();
+        expect.fail("Should have failed with NotAdmin");
+      } catch (error: any) {
+        expect(error.message).to.include("NotAdmin");
+      }
+    });
+  });
 });
@@ -19,6 +19,7 @@ import {
   SystemProgram,
   Connection,
 } from "@solana/web3.js";
+import { createHash } from "crypto";
 import * as fs from "fs";
 import * as path from "path";
 
@@ -35,6 +36,14 @@ const CLOAKED_PROGRAM_ID = new PublicKey(
 // Backend URL
 const BACKEND_URL = "http://localhost:3645";
 
+// Nullifier PDA a private instruction creates for its proof
+function nullifierPda(proofBytes: Buffer): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("nullifier"), createHash("sha256").update(proofBytes).digest()],
+    CLOAKED_PROGRAM_ID
+  )[0];
+}
+
 interface ProofResult {
   proofBytes: number[];
   witnessBytes: number[];
@@ -128,7 +137,7 @@ async function main() {
 
   // Derive PDAs
   const [agentStatePda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("cloaked_agent_state"), delegateKeypair.publicKey.toBuffer()],
+    [Buffer.from("cloaked_agent_state"), walletKeypair.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
     CLOAKED_PROGRAM_ID
   );
   const [vaultPda] = PublicKey.findProgramAddressSync(
@@ -144,11 +153,18 @@ async function main() {
   try {
     const createTx = await program.methods
       .createCloakedAgentPrivate(
+        new anchor.BN(0), // creation_nonce
         commitmentBytes,
         new anchor.BN(0), // max_per_tx (unlimited)
         new anchor.BN(0), // daily_limit (unlimited)
         new anchor.BN(0), // total_limit (unlimited)
-        new anchor.BN(0)  // expires_at (never)
+        new anchor.BN(0), // expires_at (never)
+        new anchor.BN(0), // max_vault_balance (unlimited)
+        null,             // window_seconds (daily)
+        null,             // recovery_commitment (none)
+        false,            // require_registered_relayer (any relayer)
+        { zk: {} },       // commitment_scheme
+        1                 // commitment_version (poseidon)
       )
       .accounts({
         cloakedAgentState: agentStatePda,
@@ -208,13 +224,16 @@ async function main() {
     const freezeTx = await program.methods
       .freezePrivate(
         Buffer.from(proof.proofBytes),
-        Buffer.from(proof.witnessBytes)
+        Buffer.from(proof.witnessBytes),
+        4,
+        Array(32).fill(0)
       )
       .accounts({
         cloakedAgentState: agentStatePda,
         vault: vaultPda,
         feeRecipient: walletKeypair.publicKey,
         zkVerifier: ZK_VERIFIER_PROGRAM_ID,
+        nullifier: nullifierPda(Buffer.from(proof.proofBytes)),
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -249,13 +268,15 @@ async function main() {
     const unfreezeTx = await program.methods
       .unfreezePrivate(
         Buffer.from(unfreezeProof.proofBytes),
-        Buffer.from(unfreezeProof.witnessBytes)
+        Buffer.from(unfreezeProof.witnessBytes),
+        4
       )
       .accounts({
         cloakedAgentState: agentStatePda,
         vault: vaultPda,
         feeRecipient: walletKeypair.publicKey,
         zkVerifier: ZK_VERIFIER_PROGRAM_ID,
+        nullifier: nullifierPda(Buffer.from(unfreezeProof.proofBytes)),
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -282,13 +303,16 @@ async function main() {
     await program.methods
       .freezePrivate(
         Buffer.from(fakeProofBytes),
-        Buffer.from(fakeWitnessBytes)
+        Buffer.from(fakeWitnessBytes),
+        4,
+        Array(32).fill(0)
       )
       .accounts({
         cloakedAgentState: agentStatePda,
         vault: vaultPda,
         feeRecipient: walletKeypair.publicKey,
         zkVerifier: ZK_VERIFIER_PROGRAM_ID,
+        nullifier: nullifierPda(Buffer.from(fakeProofBytes)),
         systemProgram: SystemProgram.programId,
       })
       .rpc();